@@ -0,0 +1,147 @@
+/* automatically generated by rust-bindgen 0.57.0 */
+// From wrapper.h and rtmidi_c.h of RtMidi 4.0.0, with `size_t` as `usize` and no layout tests so
+// that they suit every target.
+
+pub type size_t = usize;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct RtMidiWrapper {
+    pub ptr: *mut ::std::os::raw::c_void,
+    pub data: *mut ::std::os::raw::c_void,
+    pub ok: bool,
+    pub msg: *const ::std::os::raw::c_char,
+}
+pub type RtMidiPtr = *mut RtMidiWrapper;
+pub type RtMidiInPtr = *mut RtMidiWrapper;
+pub type RtMidiOutPtr = *mut RtMidiWrapper;
+pub const RtMidiApi_RTMIDI_API_UNSPECIFIED: RtMidiApi = 0;
+pub const RtMidiApi_RTMIDI_API_MACOSX_CORE: RtMidiApi = 1;
+pub const RtMidiApi_RTMIDI_API_LINUX_ALSA: RtMidiApi = 2;
+pub const RtMidiApi_RTMIDI_API_UNIX_JACK: RtMidiApi = 3;
+pub const RtMidiApi_RTMIDI_API_WINDOWS_MM: RtMidiApi = 4;
+pub const RtMidiApi_RTMIDI_API_RTMIDI_DUMMY: RtMidiApi = 5;
+pub const RtMidiApi_RTMIDI_API_NUM: RtMidiApi = 6;
+pub type RtMidiApi = ::std::os::raw::c_uint;
+pub const RtMidiErrorType_RTMIDI_ERROR_WARNING: RtMidiErrorType = 0;
+pub const RtMidiErrorType_RTMIDI_ERROR_DEBUG_WARNING: RtMidiErrorType = 1;
+pub const RtMidiErrorType_RTMIDI_ERROR_UNSPECIFIED: RtMidiErrorType = 2;
+pub const RtMidiErrorType_RTMIDI_ERROR_NO_DEVICES_FOUND: RtMidiErrorType = 3;
+pub const RtMidiErrorType_RTMIDI_ERROR_INVALID_DEVICE: RtMidiErrorType = 4;
+pub const RtMidiErrorType_RTMIDI_ERROR_MEMORY_ERROR: RtMidiErrorType = 5;
+pub const RtMidiErrorType_RTMIDI_ERROR_INVALID_PARAMETER: RtMidiErrorType = 6;
+pub const RtMidiErrorType_RTMIDI_ERROR_INVALID_USE: RtMidiErrorType = 7;
+pub const RtMidiErrorType_RTMIDI_ERROR_DRIVER_ERROR: RtMidiErrorType = 8;
+pub const RtMidiErrorType_RTMIDI_ERROR_SYSTEM_ERROR: RtMidiErrorType = 9;
+pub const RtMidiErrorType_RTMIDI_ERROR_THREAD_ERROR: RtMidiErrorType = 10;
+pub type RtMidiErrorType = ::std::os::raw::c_uint;
+pub type RtMidiCCallback = ::std::option::Option<
+    unsafe extern "C" fn(
+        timeStamp: f64,
+        message: *const ::std::os::raw::c_uchar,
+        messageSize: size_t,
+        userData: *mut ::std::os::raw::c_void,
+    ),
+>;
+extern "C" {
+    pub fn rtmidi_get_compiled_api(
+        apis: *mut RtMidiApi,
+        apis_size: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rtmidi_api_name(api: RtMidiApi) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn rtmidi_api_display_name(api: RtMidiApi) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn rtmidi_compiled_api_by_name(name: *const ::std::os::raw::c_char) -> RtMidiApi;
+}
+extern "C" {
+    pub fn rtmidi_error(type_: RtMidiErrorType, errorString: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn rtmidi_open_port(
+        device: RtMidiPtr,
+        portNumber: ::std::os::raw::c_uint,
+        portName: *const ::std::os::raw::c_char,
+    );
+}
+extern "C" {
+    pub fn rtmidi_open_virtual_port(device: RtMidiPtr, portName: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn rtmidi_close_port(device: RtMidiPtr);
+}
+extern "C" {
+    pub fn rtmidi_get_port_count(device: RtMidiPtr) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn rtmidi_get_port_name(
+        device: RtMidiPtr,
+        portNumber: ::std::os::raw::c_uint,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn rtmidi_in_create_default() -> RtMidiInPtr;
+}
+extern "C" {
+    pub fn rtmidi_in_create(
+        api: RtMidiApi,
+        clientName: *const ::std::os::raw::c_char,
+        queueSizeLimit: ::std::os::raw::c_uint,
+    ) -> RtMidiInPtr;
+}
+extern "C" {
+    pub fn rtmidi_in_free(device: RtMidiInPtr);
+}
+extern "C" {
+    pub fn rtmidi_in_get_current_api(device: RtMidiPtr) -> RtMidiApi;
+}
+extern "C" {
+    pub fn rtmidi_in_set_callback(
+        device: RtMidiInPtr,
+        callback: RtMidiCCallback,
+        userData: *mut ::std::os::raw::c_void,
+    );
+}
+extern "C" {
+    pub fn rtmidi_in_cancel_callback(device: RtMidiInPtr);
+}
+extern "C" {
+    pub fn rtmidi_in_ignore_types(
+        device: RtMidiInPtr,
+        midiSysex: bool,
+        midiTime: bool,
+        midiSense: bool,
+    );
+}
+extern "C" {
+    pub fn rtmidi_in_get_message(
+        device: RtMidiInPtr,
+        message: *mut ::std::os::raw::c_uchar,
+        size: *mut size_t,
+    ) -> f64;
+}
+extern "C" {
+    pub fn rtmidi_out_create_default() -> RtMidiOutPtr;
+}
+extern "C" {
+    pub fn rtmidi_out_create(
+        api: RtMidiApi,
+        clientName: *const ::std::os::raw::c_char,
+    ) -> RtMidiOutPtr;
+}
+extern "C" {
+    pub fn rtmidi_out_free(device: RtMidiOutPtr);
+}
+extern "C" {
+    pub fn rtmidi_out_get_current_api(device: RtMidiPtr) -> RtMidiApi;
+}
+extern "C" {
+    pub fn rtmidi_out_send_message(
+        device: RtMidiOutPtr,
+        message: *const ::std::os::raw::c_uchar,
+        length: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
@@ -1,29 +1,93 @@
 use std::env;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 fn main() {
-    println!("cargo:rustc-link-lib=rtmidi");
+    // RtMidi can't be installed on docs.rs, so the crate is built against stand-ins for its C API
+    // there (or when asked to with RTMIDI_STUB)
+    println!("cargo:rerun-if-env-changed=RTMIDI_STUB");
+    if env::var_os("DOCS_RS").is_some() || env::var_os("RTMIDI_STUB").is_some() {
+        println!("cargo:rustc-cfg=rtmidi_version=\"v4_0_0\"");
+        println!("cargo:rustc-cfg=rtmidi_stub");
+        return;
+    }
+
+    // Libraries are chosen for the target rather than the host, so cross-compiling links the
+    // right ones
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    if env::var_os("CARGO_FEATURE_JACK").is_some() {
+        // The JACK for Windows installer names the 64-bit library libjack64
+        match (
+            target_os.as_str(),
+            env::var("CARGO_CFG_TARGET_POINTER_WIDTH").as_deref(),
+        ) {
+            ("windows", Ok("64")) => println!("cargo:rustc-link-lib=libjack64"),
+            ("windows", _) => println!("cargo:rustc-link-lib=libjack"),
+            _ => println!("cargo:rustc-link-lib=jack"),
+        }
+    }
+    if env::var_os("CARGO_FEATURE_ALSA").is_some() && target_os == "linux" {
+        println!("cargo:rustc-link-lib=asound");
+    }
+    if env::var_os("CARGO_FEATURE_COREMIDI").is_some() && target_os == "macos" {
+        println!("cargo:rustc-link-lib=framework=CoreMIDI");
+        println!("cargo:rustc-link-lib=framework=CoreFoundation");
+    }
+    // RtMidi's Windows MM API (and the winmm feature) use the Windows Multimedia library, which
+    // a static RtMidi doesn't bring with it
+    if target_os == "windows" {
+        println!("cargo:rustc-link-lib=winmm");
+    }
+    if env::var_os("CARGO_FEATURE_VIRTUALMIDI").is_some() && target_os == "windows" {
+        println!("cargo:rerun-if-env-changed=TEVIRTUALMIDI_LIB_DIR");
+        if let Some(dir) = env::var_os("TEVIRTUALMIDI_LIB_DIR") {
+            println!(
+                "cargo:rustc-link-search=native={}",
+                Path::new(&dir).display()
+            );
+        }
+        // The SDK names its import libraries after the pointer width
+        match env::var("CARGO_CFG_TARGET_POINTER_WIDTH").as_deref() {
+            Ok("64") => println!("cargo:rustc-link-lib=teVirtualMIDI64"),
+            _ => println!("cargo:rustc-link-lib=teVirtualMIDI32"),
+        }
+    }
+    if env::var_os("CARGO_FEATURE_LINK").is_some() {
+        println!("cargo:rerun-if-env-changed=ABL_LINK_LIB_DIR");
+        if let Some(dir) = env::var_os("ABL_LINK_LIB_DIR") {
+            println!(
+                "cargo:rustc-link-search=native={}",
+                Path::new(&dir).display()
+            );
+        }
+        println!("cargo:rustc-link-lib=abl_link");
+        // Link is written in C++, and MSVC links its runtime itself
+        match (target_os.as_str(), target_env.as_str()) {
+            ("macos", _) | ("ios", _) => println!("cargo:rustc-link-lib=c++"),
+            ("windows", "msvc") => {}
+            _ => println!("cargo:rustc-link-lib=stdc++"),
+        }
+    }
+
+    // RtMidiIn and RtMidiOut are built on the crate's own backend for the target instead of
+    // RtMidi when asked to (see src/ffi/native.rs), so RtMidi isn't needed at all
+    let native = match target_os.as_str() {
+        "linux" => env::var_os("CARGO_FEATURE_NATIVE_ALSA").is_some(),
+        "windows" => env::var_os("CARGO_FEATURE_WINMM").is_some(),
+        _ => false,
+    };
+    if native {
+        println!("cargo:rustc-cfg=rtmidi_version=\"v4_0_0\"");
+        println!("cargo:rustc-cfg=rtmidi_native");
+        return;
+    }
     println!("cargo:rerun-if-changed=wrapper.h");
 
-    let (version, include_args) = match pkg_config::Config::new()
-        .statik(false)
-        .atleast_version("3.0.0")
-        .probe("rtmidi")
-    {
-        Err(_) => ("4.0.0".to_string(), vec![]),
-        Ok(library) => (
-            library.version,
-            library
-                .include_paths
-                .iter()
-                .map(|include_path| {
-                    format!(
-                        "-I{}",
-                        include_path.to_str().expect("include path was not UTF-8")
-                    )
-                })
-                .collect::<Vec<_>>(),
-        ),
+    let (version, include_args) = match user_location() {
+        Some(location) => location,
+        None => system_location(),
     };
 
     let feature = match version.as_ref() {
@@ -33,15 +97,255 @@ fn main() {
     };
     println!("cargo:rustc-cfg=rtmidi_version=\"{}\"", feature);
 
+    // The declarations in src/ffi/handwritten.rs need no bindings at all
+    if env::var_os("CARGO_FEATURE_HANDWRITTEN_FFI").is_some() {
+        return;
+    }
+
+    // The committed bindings for the version found are used instead of running bindgen (and
+    // needing libclang) when asked for
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    if env::var_os("CARGO_FEATURE_PREGENERATED_BINDINGS").is_some() {
+        let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let pregenerated = manifest_dir.join(format!("bindings/{}.rs", feature));
+        println!("cargo:rerun-if-changed={}", pregenerated.display());
+        fs::copy(&pregenerated, out_path.join("bindings.rs"))
+            .unwrap_or_else(|error| panic!("Couldn't copy {}: {}", pregenerated.display(), error));
+        return;
+    }
+
     let bindings = bindgen::Builder::default()
         .header("wrapper.h")
         .clang_args(include_args)
+        .clang_args(cross_args())
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .generate()
         .expect("Unable to generate bindings");
 
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
 }
+
+/// Find RtMidi using the `RTMIDI_DIR`, `RTMIDI_INCLUDE_DIR` and `RTMIDI_LIB_DIR` environment
+/// variables, returning the version (read from the headers) and include arguments.
+///
+/// `RTMIDI_DIR` may be an install prefix (with `include` and `lib` directories) or a source tree
+/// that has been built in place. The other variables override the directories found from it.
+/// Each may be given for a single target (see [`target_var`]).
+fn user_location() -> Option<(String, Vec<String>)> {
+    let dir = target_var("RTMIDI_DIR").map(PathBuf::from);
+    let include_dir = target_var("RTMIDI_INCLUDE_DIR").map(PathBuf::from);
+    let lib_dir = target_var("RTMIDI_LIB_DIR").map(PathBuf::from);
+    if dir.is_none() && include_dir.is_none() && lib_dir.is_none() {
+        return None;
+    }
+
+    let include_dir = include_dir
+        .or_else(|| header_dir(dir.as_ref()?))
+        .expect("rtmidi_c.h not found, set RTMIDI_INCLUDE_DIR");
+    let lib_dir = lib_dir.or_else(|| {
+        let dir = dir.as_ref()?;
+        let candidates = [dir.join("lib"), dir.join(".libs"), dir.clone()];
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_dir())
+            .cloned()
+    });
+    link_rtmidi(lib_dir.as_deref());
+
+    let version = read_version(&include_dir).unwrap_or_else(|| {
+        panic!(
+            "RTMIDI_VERSION not found in {}",
+            include_dir.join("RtMidi.h").display()
+        )
+    });
+    Some((version, vec![include_arg(&include_dir)]))
+}
+
+/// Find an installed RtMidi with `pkg-config` or, for MSVC targets, vcpkg, returning the version
+/// and include arguments.
+///
+/// If neither finds it, RtMidi 4.0.0 is assumed to be on the default library path and where it
+/// was looked for is reported, as the linker's error won't say.
+fn system_location() -> (String, Vec<String>) {
+    let mut searched = Vec::new();
+    match pkg_config::Config::new()
+        .statik(false)
+        .atleast_version("3.0.0")
+        .probe("rtmidi")
+    {
+        Ok(library) => {
+            return (
+                library.version,
+                library
+                    .include_paths
+                    .iter()
+                    .map(|include_path| include_arg(include_path))
+                    .collect(),
+            )
+        }
+        Err(error) => searched.push(format!("pkg-config: {}", error)),
+    }
+    if msvc() {
+        match vcpkg_location() {
+            Ok(location) => return location,
+            Err(error) => searched.push(format!("vcpkg: {}", error)),
+        }
+    }
+
+    println!("cargo:warning=RtMidi not found, assuming RtMidi 4.0.0 on the default library path");
+    println!("cargo:warning=Set RTMIDI_DIR to its install prefix if it is installed elsewhere");
+    for line in searched.iter().flat_map(|searched| searched.lines()) {
+        println!("cargo:warning={}", line);
+    }
+    link_rtmidi(None);
+    ("4.0.0".to_string(), vec![])
+}
+
+/// Find RtMidi installed with vcpkg (`vcpkg install rtmidi`), which also emits the link
+/// directives. `VCPKG_ROOT` selects the vcpkg tree if it isn't integrated with the environment.
+fn vcpkg_location() -> Result<(String, Vec<String>), String> {
+    let library = vcpkg::Config::new()
+        .emit_includes(false)
+        .find_package("rtmidi")
+        .map_err(|error| error.to_string())?;
+    let include_dir = library
+        .include_paths
+        .iter()
+        .find_map(|include_path| header_dir(include_path))
+        .ok_or_else(|| format!("rtmidi_c.h not found in {}", paths(&library.include_paths)))?;
+    let version = read_version(&include_dir).ok_or_else(|| {
+        format!(
+            "RTMIDI_VERSION not found in {}",
+            include_dir.join("RtMidi.h").display()
+        )
+    })?;
+    Ok((version, vec![include_arg(&include_dir)]))
+}
+
+/// Link RtMidi, from `lib_dir` if given.
+///
+/// MSVC builds of RtMidi name the library `rtmidi.lib`, or `rtmidid.lib` for debug builds, and
+/// some Windows packages use `librtmidi.lib`, so the first of those in `lib_dir` is linked.
+fn link_rtmidi(lib_dir: Option<&Path>) {
+    let mut name = "rtmidi";
+    if let Some(lib_dir) = lib_dir {
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        if msvc() {
+            let names = ["rtmidi", "rtmidid", "librtmidi"];
+            match names
+                .iter()
+                .find(|name| lib_dir.join(format!("{}.lib", name)).is_file())
+            {
+                Some(found) => name = found,
+                None => println!(
+                    "cargo:warning=None of rtmidi.lib, rtmidid.lib or librtmidi.lib found in {}",
+                    lib_dir.display()
+                ),
+            }
+        }
+    }
+    println!("cargo:rustc-link-lib={}", name);
+}
+
+/// Clang arguments for the target beyond what bindgen adds itself (it passes `--target` and
+/// `BINDGEN_EXTRA_CLANG_ARGS`): the sysroot from `PKG_CONFIG_SYSROOT_DIR`, which `pkg-config`
+/// also uses, and target-specific `BINDGEN_EXTRA_CLANG_ARGS_<target>` arguments.
+fn cross_args() -> Vec<String> {
+    let mut args = Vec::new();
+    let extra = env::var("BINDGEN_EXTRA_CLANG_ARGS").unwrap_or_default();
+    if let Some(sysroot) = target_var("PKG_CONFIG_SYSROOT_DIR").or_else(|| target_var("SYSROOT")) {
+        if !extra.contains("--sysroot") {
+            args.push(format!("--sysroot={}", Path::new(&sysroot).display()));
+        }
+    }
+    let target = env::var("TARGET").unwrap_or_default();
+    for var in &[
+        format!("BINDGEN_EXTRA_CLANG_ARGS_{}", target),
+        format!("BINDGEN_EXTRA_CLANG_ARGS_{}", target.replace('-', "_")),
+    ] {
+        println!("cargo:rerun-if-env-changed={}", var);
+        if let Ok(value) = env::var(var) {
+            args.extend(value.split_whitespace().map(String::from));
+            break;
+        }
+    }
+    args
+}
+
+/// Returns an environment variable for the target, looked up in the same order as `pkg-config`
+/// and `cc`: `VAR_<target>`, `VAR_<target_with_underscores>`, `TARGET_VAR` when cross-compiling
+/// (`HOST_VAR` otherwise) and then `VAR`
+fn target_var(var: &str) -> Option<OsString> {
+    let target = env::var("TARGET").unwrap_or_default();
+    let kind = if env::var("HOST").ok() == Some(target.clone()) {
+        "HOST"
+    } else {
+        "TARGET"
+    };
+    let names = [
+        format!("{}_{}", var, target),
+        format!("{}_{}", var, target.replace('-', "_")),
+        format!("{}_{}", kind, var),
+        var.to_string(),
+    ];
+    for name in &names {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+    names.iter().find_map(env::var_os)
+}
+
+fn msvc() -> bool {
+    env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc")
+}
+
+/// Returns the directory containing `rtmidi_c.h` in an install prefix, source tree or include
+/// directory
+fn header_dir(dir: &Path) -> Option<PathBuf> {
+    let candidates = [
+        dir.join("include/rtmidi"),
+        dir.join("include"),
+        dir.join("rtmidi"),
+        dir.to_path_buf(),
+    ];
+    candidates
+        .iter()
+        .find(|candidate| candidate.join("rtmidi_c.h").is_file())
+        .cloned()
+}
+
+/// Read the version from the RtMidi.h alongside `rtmidi_c.h`
+fn read_version(include_dir: &Path) -> Option<String> {
+    let header = include_dir.join("RtMidi.h");
+    println!("cargo:rerun-if-changed={}", header.display());
+    header_version(&fs::read_to_string(&header).ok()?)
+}
+
+/// Read the version from the `#define RTMIDI_VERSION "x.y.z"` line of RtMidi.h
+fn header_version(header: &str) -> Option<String> {
+    header.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("#define"), Some("RTMIDI_VERSION"), Some(version)) => {
+                Some(version.trim_matches('"').to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+fn paths(paths: &[PathBuf]) -> String {
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    paths.join(", ")
+}
+
+fn include_arg(include_path: &Path) -> String {
+    format!(
+        "-I{}",
+        include_path.to_str().expect("include path was not UTF-8")
+    )
+}
@@ -0,0 +1,843 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::backend::{Backend, BackendCallback, InputConnection, OutputConnection};
+use crate::error::RtMidiError;
+use crate::midi::TimedOutput;
+use crate::ports::PortKind;
+use crate::threads;
+
+/// Open the sequencer for input and output
+const SND_SEQ_OPEN_DUPLEX: c_int = 3;
+
+/// Query subscriptions where the root port is the sender
+const SND_SEQ_QUERY_SUBS_READ: c_int = 0;
+
+/// Client name used for managing subscriptions
+const CLIENT_NAME: &str = "rtmidi-rs";
+
+/// Port capabilities: readable, writable, and open to subscription by other clients
+const SND_SEQ_PORT_CAP_READ: c_uint = 1 << 0;
+const SND_SEQ_PORT_CAP_WRITE: c_uint = 1 << 1;
+const SND_SEQ_PORT_CAP_SUBS_READ: c_uint = 1 << 5;
+const SND_SEQ_PORT_CAP_SUBS_WRITE: c_uint = 1 << 6;
+const SND_SEQ_PORT_CAP_NO_EXPORT: c_uint = 1 << 7;
+
+/// Port types: ports created by the backend are generic MIDI application ports, and the others
+/// tell hardware ports from software ones
+const SND_SEQ_PORT_TYPE_MIDI_GENERIC: c_uint = 1 << 1;
+const SND_SEQ_PORT_TYPE_HARDWARE: c_uint = 1 << 16;
+const SND_SEQ_PORT_TYPE_SOFTWARE: c_uint = 1 << 17;
+const SND_SEQ_PORT_TYPE_SYNTHESIZER: c_uint = 1 << 18;
+const SND_SEQ_PORT_TYPE_PORT: c_uint = 1 << 19;
+const SND_SEQ_PORT_TYPE_APPLICATION: c_uint = 1 << 20;
+
+/// Name of the kernel client whose ports echo their input
+const THROUGH_CLIENT_NAME: &str = "Midi Through";
+
+/// Event addressing for events sent directly to every subscriber
+const SND_SEQ_QUEUE_DIRECT: u8 = 253;
+const SND_SEQ_ADDRESS_UNKNOWN: u8 = 253;
+const SND_SEQ_ADDRESS_SUBSCRIBERS: u8 = 254;
+
+/// System exclusive event type, whose data is held outside the event
+const SND_SEQ_EVENT_SYSEX: u8 = 130;
+
+/// Event type the encoder leaves when a message is incomplete
+const SND_SEQ_EVENT_NONE: u8 = 255;
+
+/// Queue control event starting a queue
+const SND_SEQ_EVENT_START: c_int = 30;
+
+/// Event flags: timestamps in real time (seconds and nanoseconds), and the mask of the
+/// timestamp flags (the real-time flag, and the relative rather than absolute time mode flag)
+const SND_SEQ_TIME_STAMP_REAL: u8 = 1 << 0;
+const SND_SEQ_TIME_MASK: u8 = 0b11;
+
+/// Size of the MIDI event encoder and decoder buffers, which grow for larger messages
+const CODER_BUFFER_SIZE: usize = 256;
+
+/// Readable file descriptor event for `poll`
+const POLLIN: c_short = 1;
+
+/// How often the input thread checks whether it should stop, in milliseconds
+const INPUT_POLL_TIMEOUT: c_int = 50;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct snd_seq_addr_t {
+    client: u8,
+    port: u8,
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct snd_seq_event_t {
+    kind: u8,
+    flags: u8,
+    tag: u8,
+    queue: u8,
+    time: [u32; 2],
+    source: [u8; 2],
+    dest: [u8; 2],
+    data: [u32; 3],
+}
+
+#[repr(C)]
+struct pollfd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+extern "C" {
+    fn snd_seq_open(
+        handle: *mut *mut c_void,
+        name: *const c_char,
+        streams: c_int,
+        mode: c_int,
+    ) -> c_int;
+    fn snd_seq_close(handle: *mut c_void) -> c_int;
+    fn snd_seq_set_client_name(handle: *mut c_void, name: *const c_char) -> c_int;
+    fn snd_seq_parse_address(
+        handle: *mut c_void,
+        addr: *mut snd_seq_addr_t,
+        arg: *const c_char,
+    ) -> c_int;
+    fn snd_seq_port_subscribe_malloc(ptr: *mut *mut c_void) -> c_int;
+    fn snd_seq_port_subscribe_free(ptr: *mut c_void);
+    fn snd_seq_port_subscribe_set_sender(info: *mut c_void, addr: *const snd_seq_addr_t);
+    fn snd_seq_port_subscribe_set_dest(info: *mut c_void, addr: *const snd_seq_addr_t);
+    fn snd_seq_subscribe_port(handle: *mut c_void, info: *mut c_void) -> c_int;
+    fn snd_seq_unsubscribe_port(handle: *mut c_void, info: *mut c_void) -> c_int;
+    fn snd_seq_query_subscribe_malloc(ptr: *mut *mut c_void) -> c_int;
+    fn snd_seq_query_subscribe_free(ptr: *mut c_void);
+    fn snd_seq_query_subscribe_set_root(info: *mut c_void, addr: *const snd_seq_addr_t);
+    fn snd_seq_query_subscribe_set_type(info: *mut c_void, kind: c_int);
+    fn snd_seq_query_subscribe_set_index(info: *mut c_void, index: c_int);
+    fn snd_seq_query_subscribe_get_index(info: *const c_void) -> c_int;
+    fn snd_seq_query_subscribe_get_addr(info: *const c_void) -> *const snd_seq_addr_t;
+    fn snd_seq_query_port_subscribers(handle: *mut c_void, subs: *mut c_void) -> c_int;
+    fn snd_seq_client_id(handle: *mut c_void) -> c_int;
+    fn snd_seq_client_info_malloc(ptr: *mut *mut c_void) -> c_int;
+    fn snd_seq_client_info_free(ptr: *mut c_void);
+    fn snd_seq_client_info_set_client(info: *mut c_void, client: c_int);
+    fn snd_seq_client_info_get_client(info: *const c_void) -> c_int;
+    fn snd_seq_client_info_get_name(info: *mut c_void) -> *const c_char;
+    fn snd_seq_query_next_client(handle: *mut c_void, info: *mut c_void) -> c_int;
+    fn snd_seq_port_info_malloc(ptr: *mut *mut c_void) -> c_int;
+    fn snd_seq_port_info_free(ptr: *mut c_void);
+    fn snd_seq_port_info_set_client(info: *mut c_void, client: c_int);
+    fn snd_seq_port_info_set_port(info: *mut c_void, port: c_int);
+    fn snd_seq_port_info_get_port(info: *const c_void) -> c_int;
+    fn snd_seq_port_info_get_name(info: *const c_void) -> *const c_char;
+    fn snd_seq_port_info_get_capability(info: *const c_void) -> c_uint;
+    fn snd_seq_port_info_get_type(info: *const c_void) -> c_uint;
+    fn snd_seq_query_next_port(handle: *mut c_void, info: *mut c_void) -> c_int;
+    fn snd_seq_create_simple_port(
+        handle: *mut c_void,
+        name: *const c_char,
+        caps: c_uint,
+        kind: c_uint,
+    ) -> c_int;
+    fn snd_seq_connect_from(handle: *mut c_void, port: c_int, client: c_int, src: c_int) -> c_int;
+    fn snd_seq_connect_to(handle: *mut c_void, port: c_int, client: c_int, dest: c_int) -> c_int;
+    fn snd_seq_alloc_queue(handle: *mut c_void) -> c_int;
+    fn snd_seq_control_queue(
+        handle: *mut c_void,
+        queue: c_int,
+        kind: c_int,
+        value: c_int,
+        event: *mut snd_seq_event_t,
+    ) -> c_int;
+    fn snd_seq_drain_output(handle: *mut c_void) -> c_int;
+    fn snd_seq_event_output_direct(handle: *mut c_void, event: *mut snd_seq_event_t) -> c_int;
+    fn snd_seq_event_input(handle: *mut c_void, event: *mut *mut snd_seq_event_t) -> c_int;
+    fn snd_seq_nonblock(handle: *mut c_void, nonblock: c_int) -> c_int;
+    fn snd_seq_poll_descriptors_count(handle: *mut c_void, events: c_short) -> c_int;
+    fn snd_seq_poll_descriptors(
+        handle: *mut c_void,
+        fds: *mut pollfd,
+        space: c_uint,
+        events: c_short,
+    ) -> c_int;
+    fn snd_midi_event_new(size: usize, coder: *mut *mut c_void) -> c_int;
+    fn snd_midi_event_free(coder: *mut c_void);
+    fn snd_midi_event_resize_buffer(coder: *mut c_void, size: usize) -> c_int;
+    fn snd_midi_event_no_status(coder: *mut c_void, on: c_int);
+    fn snd_midi_event_reset_encode(coder: *mut c_void);
+    fn snd_midi_event_encode(
+        coder: *mut c_void,
+        buf: *const u8,
+        count: c_long,
+        event: *mut snd_seq_event_t,
+    ) -> c_long;
+    fn snd_midi_event_decode(
+        coder: *mut c_void,
+        buf: *mut u8,
+        count: c_long,
+        event: *const snd_seq_event_t,
+    ) -> c_long;
+    fn snd_strerror(errnum: c_int) -> *const c_char;
+    fn poll(fds: *mut pollfd, count: c_ulong, timeout: c_int) -> c_int;
+}
+
+/// ALSA sequencer port address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlsaAddress {
+    pub client: u8,
+    pub port: u8,
+}
+
+impl fmt::Display for AlsaAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.client, self.port)
+    }
+}
+
+impl From<snd_seq_addr_t> for AlsaAddress {
+    fn from(addr: snd_seq_addr_t) -> Self {
+        AlsaAddress {
+            client: addr.client,
+            port: addr.port,
+        }
+    }
+}
+
+impl From<AlsaAddress> for snd_seq_addr_t {
+    fn from(address: AlsaAddress) -> Self {
+        snd_seq_addr_t {
+            client: address.client,
+            port: address.port,
+        }
+    }
+}
+
+/// ALSA sequencer subscription management
+///
+/// Opens its own ALSA sequencer client to create and remove subscriptions (connections) between
+/// any two ports, like `aconnect`, not just those opened by this crate.
+/// ```no_run
+/// use rtmidi::{AlsaSequencer, RtMidiError};
+///
+/// fn patch() -> Result<(), RtMidiError> {
+///     let sequencer = AlsaSequencer::new()?;
+///     let keyboard = sequencer.parse_address("Keystation:0")?;
+///     let synth = sequencer.parse_address("128:0")?;
+///     if !sequencer.subscribers(keyboard)?.contains(&synth) {
+///         sequencer.subscribe(keyboard, synth)?;
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct AlsaSequencer(*mut c_void);
+
+// The sequencer handle isn't tied to the thread that opened it
+unsafe impl Send for AlsaSequencer {}
+
+impl AlsaSequencer {
+    /// Open a connection to the ALSA sequencer
+    pub fn new() -> Result<Self, RtMidiError> {
+        AlsaSequencer::open(CLIENT_NAME)
+    }
+
+    fn open(client_name: &str) -> Result<Self, RtMidiError> {
+        let default = CString::new("default")?;
+        let name = CString::new(client_name)?;
+        let mut handle = ptr::null_mut();
+        check(unsafe { snd_seq_open(&mut handle, default.as_ptr(), SND_SEQ_OPEN_DUPLEX, 0) })?;
+        let sequencer = AlsaSequencer(handle);
+        check(unsafe { snd_seq_set_client_name(handle, name.as_ptr()) })?;
+        Ok(sequencer)
+    }
+
+    /// Parse an address given as "client:port", where the client may be a number or (part of) a
+    /// client name, as accepted by `aconnect`
+    pub fn parse_address(&self, address: &str) -> Result<AlsaAddress, RtMidiError> {
+        let arg = CString::new(address)?;
+        let mut addr = snd_seq_addr_t { client: 0, port: 0 };
+        check(unsafe { snd_seq_parse_address(self.0, &mut addr, arg.as_ptr()) })
+            .map_err(|_| RtMidiError::Error(format!("Invalid ALSA address '{}'", address)))?;
+        Ok(addr.into())
+    }
+
+    /// Subscribe a destination port to a sender port, so events from the sender are delivered to
+    /// the destination
+    pub fn subscribe(&self, sender: AlsaAddress, dest: AlsaAddress) -> Result<(), RtMidiError> {
+        self.with_subscription(sender, dest, |info| unsafe {
+            snd_seq_subscribe_port(self.0, info)
+        })
+    }
+
+    /// Remove the subscription of a destination port to a sender port
+    pub fn unsubscribe(&self, sender: AlsaAddress, dest: AlsaAddress) -> Result<(), RtMidiError> {
+        self.with_subscription(sender, dest, |info| unsafe {
+            snd_seq_unsubscribe_port(self.0, info)
+        })
+    }
+
+    /// Returns the ports subscribed to a sender port
+    pub fn subscribers(&self, sender: AlsaAddress) -> Result<Vec<AlsaAddress>, RtMidiError> {
+        let mut info = ptr::null_mut();
+        check(unsafe { snd_seq_query_subscribe_malloc(&mut info) })?;
+        let root = snd_seq_addr_t::from(sender);
+        let mut subscribers = Vec::new();
+        unsafe {
+            snd_seq_query_subscribe_set_root(info, &root);
+            snd_seq_query_subscribe_set_type(info, SND_SEQ_QUERY_SUBS_READ);
+            snd_seq_query_subscribe_set_index(info, 0);
+            while snd_seq_query_port_subscribers(self.0, info) >= 0 {
+                subscribers.push((*snd_seq_query_subscribe_get_addr(info)).into());
+                let next = snd_seq_query_subscribe_get_index(info) + 1;
+                snd_seq_query_subscribe_set_index(info, next);
+            }
+            snd_seq_query_subscribe_free(info);
+        }
+        Ok(subscribers)
+    }
+
+    /// Returns the kind of every port of other clients, by port name
+    pub(crate) fn port_kinds(&self) -> Result<Vec<(String, PortKind)>, RtMidiError> {
+        let ports = self.ports(0)?;
+        Ok(ports
+            .into_iter()
+            .map(|(name, _, kind)| {
+                let kind = if name.starts_with(THROUGH_CLIENT_NAME) {
+                    PortKind::Through
+                } else if kind & (SND_SEQ_PORT_TYPE_HARDWARE | SND_SEQ_PORT_TYPE_PORT) != 0 {
+                    PortKind::Hardware
+                } else if kind
+                    & (SND_SEQ_PORT_TYPE_SOFTWARE
+                        | SND_SEQ_PORT_TYPE_SYNTHESIZER
+                        | SND_SEQ_PORT_TYPE_APPLICATION)
+                    != 0
+                {
+                    PortKind::Software
+                } else {
+                    PortKind::Unknown
+                };
+                (name, kind)
+            })
+            .collect())
+    }
+
+    /// Returns the name, address and type of every port of other clients with all the given
+    /// capabilities, named as by RtMidi ("client:port client-number:port-number")
+    fn ports(&self, caps: c_uint) -> Result<Vec<(String, AlsaAddress, c_uint)>, RtMidiError> {
+        let (mut client_info, mut port_info) = (ptr::null_mut(), ptr::null_mut());
+        check(unsafe { snd_seq_client_info_malloc(&mut client_info) })?;
+        if let Err(e) = check(unsafe { snd_seq_port_info_malloc(&mut port_info) }) {
+            unsafe { snd_seq_client_info_free(client_info) };
+            return Err(e);
+        }
+        let own = unsafe { snd_seq_client_id(self.0) };
+        let mut ports = Vec::new();
+        unsafe {
+            snd_seq_client_info_set_client(client_info, -1);
+            while snd_seq_query_next_client(self.0, client_info) >= 0 {
+                let client = snd_seq_client_info_get_client(client_info);
+                if client == own {
+                    continue;
+                }
+                let client_name = string(snd_seq_client_info_get_name(client_info));
+                snd_seq_port_info_set_client(port_info, client);
+                snd_seq_port_info_set_port(port_info, -1);
+                while snd_seq_query_next_port(self.0, port_info) >= 0 {
+                    let capability = snd_seq_port_info_get_capability(port_info);
+                    if capability & caps != caps || capability & SND_SEQ_PORT_CAP_NO_EXPORT != 0 {
+                        continue;
+                    }
+                    let port = snd_seq_port_info_get_port(port_info);
+                    let name = format!(
+                        "{}:{} {}:{}",
+                        client_name,
+                        string(snd_seq_port_info_get_name(port_info)),
+                        client,
+                        port
+                    );
+                    let address = AlsaAddress {
+                        client: client as u8,
+                        port: port as u8,
+                    };
+                    ports.push((name, address, snd_seq_port_info_get_type(port_info)));
+                }
+            }
+            snd_seq_port_info_free(port_info);
+            snd_seq_client_info_free(client_info);
+        }
+        Ok(ports)
+    }
+
+    /// Returns the address of the port with a name, as returned by [`AlsaSequencer::ports`]
+    fn find(&self, caps: c_uint, name: &str) -> Result<AlsaAddress, RtMidiError> {
+        self.ports(caps)?
+            .into_iter()
+            .find(|(port, _, _)| port == name)
+            .map(|(_, address, _)| address)
+            .ok_or_else(|| RtMidiError::PortNotFound(name.to_string()))
+    }
+
+    /// Create a port of this client, returning its number
+    fn create_port(&self, name: &str, caps: c_uint) -> Result<c_int, RtMidiError> {
+        let name = CString::new(name)?;
+        let kind = SND_SEQ_PORT_TYPE_MIDI_GENERIC | SND_SEQ_PORT_TYPE_APPLICATION;
+        let port = unsafe { snd_seq_create_simple_port(self.0, name.as_ptr(), caps, kind) };
+        check(port).map(|_| port)
+    }
+
+    fn with_subscription<F>(
+        &self,
+        sender: AlsaAddress,
+        dest: AlsaAddress,
+        f: F,
+    ) -> Result<(), RtMidiError>
+    where
+        F: FnOnce(*mut c_void) -> c_int,
+    {
+        let mut info = ptr::null_mut();
+        check(unsafe { snd_seq_port_subscribe_malloc(&mut info) })?;
+        let (sender, dest) = (snd_seq_addr_t::from(sender), snd_seq_addr_t::from(dest));
+        unsafe {
+            snd_seq_port_subscribe_set_sender(info, &sender);
+            snd_seq_port_subscribe_set_dest(info, &dest);
+        }
+        let result = check(f(info));
+        unsafe { snd_seq_port_subscribe_free(info) };
+        result
+    }
+}
+
+impl Drop for AlsaSequencer {
+    fn drop(&mut self) {
+        unsafe { snd_seq_close(self.0) };
+    }
+}
+
+/// Native ALSA sequencer [`Backend`]
+///
+/// Provides the ports of the ALSA sequencer to a [`crate::MidiSystem`] using the sequencer API
+/// directly rather than through RtMidi, with the same port names as RtMidi's ALSA API (e.g.
+/// "Keystation:Keystation MIDI 1 24:0"). Each open port has its own sequencer client, and
+/// messages from an input are delivered on a thread of its own.
+/// ```no_run
+/// use rtmidi::{AlsaBackend, MidiSystem, RtMidiApi, RtMidiError};
+///
+/// fn open() -> Result<MidiSystem, RtMidiError> {
+///     let system = MidiSystem::new(RtMidiApi::RtMidiDummy, "My Application")?
+///         .backend(AlsaBackend::new("My Application")?);
+///     system.route("Keystation", "FLUID Synth")?;
+///     Ok(system)
+/// }
+/// ```
+pub struct AlsaBackend(Mutex<AlsaSequencer>);
+
+impl AlsaBackend {
+    /// Open a sequencer client, used to list ports, with a name
+    pub fn new(client_name: &str) -> Result<Self, RtMidiError> {
+        AlsaSequencer::open(client_name).map(|sequencer| AlsaBackend(Mutex::new(sequencer)))
+    }
+
+    /// Open an input port of a new client, named `own_port` and connected to the port named
+    /// `port`, or left for others to connect to (a virtual port) without one. System exclusive
+    /// data is passed on as it arrives, rather than as whole messages, while `sysex_streams` is
+    /// above zero.
+    pub(crate) fn connect_input(
+        &self,
+        port: Option<&str>,
+        client_name: &str,
+        own_port: &str,
+        callback: BackendCallback,
+        sysex_streams: Arc<AtomicUsize>,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        let sequencer = AlsaSequencer::open(client_name)?;
+        let source = port
+            .map(|port| sequencer.find(SND_SEQ_PORT_CAP_READ | SND_SEQ_PORT_CAP_SUBS_READ, port))
+            .transpose()?;
+        let own = sequencer.create_port(
+            own_port,
+            SND_SEQ_PORT_CAP_WRITE | SND_SEQ_PORT_CAP_SUBS_WRITE,
+        )?;
+        if let Some(source) = source {
+            check(unsafe {
+                snd_seq_connect_from(
+                    sequencer.0,
+                    own,
+                    c_int::from(source.client),
+                    c_int::from(source.port),
+                )
+            })?;
+        }
+        check(unsafe { snd_seq_nonblock(sequencer.0, 1) })?;
+        let decoder = Coder::new()?;
+        unsafe { snd_midi_event_no_status(decoder.0, 1) };
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            threads::spawn(
+                format!("rtmidi-in:{}", port.unwrap_or(own_port)),
+                move || receive(&sequencer, decoder, &stop, &sysex_streams, &callback),
+            )
+        };
+        Ok(Box::new(AlsaInput {
+            stop,
+            thread: Some(thread),
+        }))
+    }
+
+    /// Open an output port of a new client, named `own_port` and connected to the port named
+    /// `port`, or left for others to connect to (a virtual port) without one
+    pub(crate) fn connect_output(
+        &self,
+        port: Option<&str>,
+        client_name: &str,
+        own_port: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        let sequencer = AlsaSequencer::open(client_name)?;
+        let dest = port
+            .map(|port| sequencer.find(SND_SEQ_PORT_CAP_WRITE | SND_SEQ_PORT_CAP_SUBS_WRITE, port))
+            .transpose()?;
+        let own =
+            sequencer.create_port(own_port, SND_SEQ_PORT_CAP_READ | SND_SEQ_PORT_CAP_SUBS_READ)?;
+        if let Some(dest) = dest {
+            check(unsafe {
+                snd_seq_connect_to(
+                    sequencer.0,
+                    own,
+                    c_int::from(dest.client),
+                    c_int::from(dest.port),
+                )
+            })?;
+        }
+        Ok(Box::new(AlsaOutput(Mutex::new((
+            sequencer,
+            Coder::new()?,
+            own as u8,
+        )))))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AlsaSequencer> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Backend for AlsaBackend {
+    fn name(&self) -> &str {
+        "alsa"
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        let ports = self
+            .lock()
+            .ports(SND_SEQ_PORT_CAP_READ | SND_SEQ_PORT_CAP_SUBS_READ)?;
+        Ok(ports.into_iter().map(|(name, _, _)| name).collect())
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        let ports = self
+            .lock()
+            .ports(SND_SEQ_PORT_CAP_WRITE | SND_SEQ_PORT_CAP_SUBS_WRITE)?;
+        Ok(ports.into_iter().map(|(name, _, _)| name).collect())
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        self.connect_input(
+            Some(port),
+            client_name,
+            client_name,
+            callback,
+            Arc::default(),
+        )
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        self.connect_output(Some(port), client_name, client_name)
+    }
+}
+
+/// MIDI byte stream to sequencer event encoder (or decoder)
+struct Coder(*mut c_void, usize);
+
+unsafe impl Send for Coder {}
+
+impl Coder {
+    fn new() -> Result<Self, RtMidiError> {
+        let mut coder = ptr::null_mut();
+        check(unsafe { snd_midi_event_new(CODER_BUFFER_SIZE, &mut coder) })?;
+        Ok(Coder(coder, CODER_BUFFER_SIZE))
+    }
+
+    /// Make sure the buffer holds at least `size` bytes
+    fn reserve(&mut self, size: usize) -> Result<(), RtMidiError> {
+        if size > self.1 {
+            check(unsafe { snd_midi_event_resize_buffer(self.0, size) })?;
+            self.1 = size;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Coder {
+    fn drop(&mut self) {
+        unsafe { snd_midi_event_free(self.0) };
+    }
+}
+
+/// Input port opened by an [`AlsaBackend`], whose thread stops when dropped
+struct AlsaInput {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputConnection for AlsaInput {}
+
+impl Drop for AlsaInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Receive events until stopped, passing each message to the callback
+fn receive(
+    sequencer: &AlsaSequencer,
+    mut decoder: Coder,
+    stop: &AtomicBool,
+    sysex_streams: &AtomicUsize,
+    f: &BackendCallback,
+) {
+    let count = unsafe { snd_seq_poll_descriptors_count(sequencer.0, POLLIN) }.max(0);
+    let mut fds: Vec<pollfd> = (0..count)
+        .map(|_| pollfd {
+            fd: 0,
+            events: 0,
+            revents: 0,
+        })
+        .collect();
+    unsafe { snd_seq_poll_descriptors(sequencer.0, fds.as_mut_ptr(), count as c_uint, POLLIN) };
+    let mut buffer = vec![0; CODER_BUFFER_SIZE];
+    let mut sysex = Vec::new();
+    let mut last: Option<Instant> = None;
+    while !stop.load(Ordering::Relaxed) {
+        if unsafe { poll(fds.as_mut_ptr(), fds.len() as c_ulong, INPUT_POLL_TIMEOUT) } <= 0 {
+            continue;
+        }
+        let mut event = ptr::null_mut();
+        while unsafe { snd_seq_event_input(sequencer.0, &mut event) } >= 0 {
+            let event = unsafe { *event };
+            if event.kind == SND_SEQ_EVENT_SYSEX {
+                // The data is held outside the event, and its length is the first field
+                let size = event.data[0] as usize;
+                if decoder.reserve(size).is_err() {
+                    continue;
+                }
+                buffer.resize(buffer.len().max(size), 0);
+            }
+            let length = unsafe {
+                snd_midi_event_decode(
+                    decoder.0,
+                    buffer.as_mut_ptr(),
+                    buffer.len() as c_long,
+                    &event,
+                )
+            };
+            // Events other than MIDI messages (e.g. port notifications) can't be decoded
+            if length <= 0 {
+                continue;
+            }
+            let data = &buffer[..length as usize];
+            // Long system exclusive messages arrive in several events, possibly with real-time
+            // messages in between, and are assembled unless streamed
+            let message = if data[0] < 0xF8
+                && (!sysex.is_empty() || (data[0] == 0xF0 && data.last() != Some(&0xF7)))
+            {
+                sysex.extend_from_slice(data);
+                if sysex.last() != Some(&0xF7) && sysex_streams.load(Ordering::Relaxed) == 0 {
+                    continue;
+                }
+                std::mem::take(&mut sysex)
+            } else {
+                data.to_vec()
+            };
+            let now = Instant::now();
+            let delta = last.map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+            last = Some(now);
+            f(delta, &message);
+        }
+    }
+}
+
+/// Output port opened by an [`AlsaBackend`]
+struct AlsaOutput(Mutex<(AlsaSequencer, Coder, u8)>);
+
+impl OutputConnection for AlsaOutput {
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        let mut output = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let (sequencer, encoder, port) = &mut *output;
+        let mut event = encode(encoder, *port, message)?;
+        event.queue = SND_SEQ_QUEUE_DIRECT;
+        check(unsafe { snd_seq_event_output_direct(sequencer.0, &mut event) })
+    }
+}
+
+/// A sequencer client of our own, connected to the port an RtMidi output is connected to, for
+/// scheduling messages on a queue (which RtMidi doesn't support) so the kernel delivers them at
+/// the right time
+pub(crate) struct QueuedOutput {
+    sequencer: AlsaSequencer,
+    encoder: Coder,
+    port: u8,
+    queue: c_int,
+    // When the queue was started, its time being relative to this
+    start: Instant,
+}
+
+impl QueuedOutput {
+    /// Create a client sending to the port with the given name (RtMidi's port name)
+    pub fn new(client_name: &str, port_name: &str) -> Result<Self, RtMidiError> {
+        let sequencer = AlsaSequencer::open(client_name)?;
+        let dest = sequencer.find(
+            SND_SEQ_PORT_CAP_WRITE | SND_SEQ_PORT_CAP_SUBS_WRITE,
+            port_name,
+        )?;
+        let port = sequencer.create_port(
+            client_name,
+            SND_SEQ_PORT_CAP_READ | SND_SEQ_PORT_CAP_SUBS_READ,
+        )?;
+        check(unsafe {
+            snd_seq_connect_to(
+                sequencer.0,
+                port,
+                c_int::from(dest.client),
+                c_int::from(dest.port),
+            )
+        })?;
+        // The queue is freed when the client is closed
+        let queue = unsafe { snd_seq_alloc_queue(sequencer.0) };
+        check(queue)?;
+        check(unsafe {
+            snd_seq_control_queue(sequencer.0, queue, SND_SEQ_EVENT_START, 0, ptr::null_mut())
+        })?;
+        check(unsafe { snd_seq_drain_output(sequencer.0) })?;
+        Ok(QueuedOutput {
+            sequencer,
+            encoder: Coder::new()?,
+            port: port as u8,
+            queue,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl TimedOutput for QueuedOutput {
+    fn send_at(&mut self, at: Instant, message: &[u8]) -> Result<(), RtMidiError> {
+        let mut event = encode(&mut self.encoder, self.port, message)?;
+        let time = at.max(Instant::now()).duration_since(self.start);
+        event.queue = self.queue as u8;
+        event.flags = (event.flags & !SND_SEQ_TIME_MASK) | SND_SEQ_TIME_STAMP_REAL;
+        event.time = [time.as_secs() as u32, time.subsec_nanos()];
+        check(unsafe { snd_seq_event_output_direct(self.sequencer.0, &mut event) })
+    }
+}
+
+/// Encode a message as an event from one of our ports to its subscribers
+fn encode(encoder: &mut Coder, port: u8, message: &[u8]) -> Result<snd_seq_event_t, RtMidiError> {
+    encoder.reserve(message.len())?;
+    unsafe { snd_midi_event_reset_encode(encoder.0) };
+    let mut event = snd_seq_event_t::default();
+    let used = unsafe {
+        snd_midi_event_encode(
+            encoder.0,
+            message.as_ptr(),
+            message.len() as c_long,
+            &mut event,
+        )
+    };
+    if used < message.len() as c_long || event.kind == SND_SEQ_EVENT_NONE {
+        return Err(RtMidiError::InvalidMessage(format!(
+            "ALSA can't encode message {:02x?}",
+            message
+        )));
+    }
+    event.source = [0, port];
+    event.dest = [SND_SEQ_ADDRESS_SUBSCRIBERS, SND_SEQ_ADDRESS_UNKNOWN];
+    Ok(event)
+}
+
+/// Convert a C string returned by ALSA, which may be null
+unsafe fn string(s: *const c_char) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+/// Convert a negative ALSA return value to an error
+fn check(result: c_int) -> Result<(), RtMidiError> {
+    if result >= 0 {
+        return Ok(());
+    }
+    let message = unsafe { CStr::from_ptr(snd_strerror(result)) };
+    Err(RtMidiError::Error(message.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::{encode, snd_seq_addr_t, snd_seq_event_t, AlsaAddress, Coder, SND_SEQ_EVENT_SYSEX};
+    use crate::RtMidiError;
+
+    #[test]
+    fn event_layout() {
+        assert_eq!(mem::size_of::<snd_seq_event_t>(), 28);
+        assert_eq!(mem::size_of::<snd_seq_addr_t>(), 2);
+        // Where libasound puts a note's channel, note and velocity, and the length of a system
+        // exclusive message
+        let mut encoder = Coder::new().unwrap();
+        let event = encode(&mut encoder, 0, &[0x93, 60, 100]).unwrap();
+        assert_eq!(event.kind, 6);
+        assert_eq!(event.data[0].to_ne_bytes(), [3, 60, 100, 0]);
+        let event = encode(&mut encoder, 0, &[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert_eq!(event.kind, SND_SEQ_EVENT_SYSEX);
+        assert_eq!(event.data[0], 6);
+    }
+
+    #[test]
+    fn display() {
+        let address = AlsaAddress {
+            client: 128,
+            port: 0,
+        };
+        assert_eq!(address.to_string(), "128:0");
+    }
+
+    #[test]
+    fn encode_incomplete() {
+        let mut encoder = Coder::new().unwrap();
+        assert!(encode(&mut encoder, 0, &[0x90, 60, 100]).is_ok());
+        assert!(matches!(
+            encode(&mut encoder, 0, &[0x90, 60]),
+            Err(RtMidiError::InvalidMessage(_))
+        ));
+    }
+}
@@ -1,11 +1,12 @@
 use std::ffi::CStr;
 use std::fmt;
 
+use crate::error::RtMidiError;
 use crate::ffi;
 
 /// MIDI API specifier
 #[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum RtMidiApi {
     Unspecified = ffi::RtMidiApi_RTMIDI_API_UNSPECIFIED,
     MacOSXCore = ffi::RtMidiApi_RTMIDI_API_MACOSX_CORE,
@@ -15,6 +16,58 @@ pub enum RtMidiApi {
     RtMidiDummy = ffi::RtMidiApi_RTMIDI_API_RTMIDI_DUMMY,
 }
 
+impl RtMidiApi {
+    /// Returns the APIs compiled into the RtMidi library, in the order they are tried when no API
+    /// is specified
+    pub fn compiled() -> Vec<RtMidiApi> {
+        ffi::compiled_api()
+            .into_iter()
+            .map(RtMidiApi::from)
+            .collect()
+    }
+
+    /// Returns the API to create a client with for this one, which with the `dummy-only` feature
+    /// is always the dummy API. That's an error if the RtMidi library wasn't built with it,
+    /// rather than letting RtMidi fall back to a real API.
+    pub(crate) fn resolve(self) -> Result<RtMidiApi, RtMidiError> {
+        if cfg!(feature = "dummy-only") {
+            if !RtMidiApi::compiled().contains(&RtMidiApi::RtMidiDummy) {
+                return Err(RtMidiError::Error(
+                    "The RtMidi library wasn't built with its dummy API".to_string(),
+                ));
+            }
+            return Ok(RtMidiApi::RtMidiDummy);
+        }
+        Ok(self)
+    }
+
+    /// Returns a short, stable identifier for the API (e.g. "alsa"), matching the names used by
+    /// RtMidi
+    pub fn name(&self) -> &'static str {
+        match self {
+            RtMidiApi::Unspecified => "unspecified",
+            RtMidiApi::MacOSXCore => "core",
+            RtMidiApi::LinuxALSA => "alsa",
+            RtMidiApi::UnixJack => "jack",
+            RtMidiApi::WindowsMM => "winmm",
+            RtMidiApi::RtMidiDummy => "dummy",
+        }
+    }
+
+    /// Returns a human-readable name for the API (e.g. "ALSA"), matching the display names used
+    /// by RtMidi 4
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            RtMidiApi::Unspecified => "Unknown",
+            RtMidiApi::MacOSXCore => "CoreMidi",
+            RtMidiApi::LinuxALSA => "ALSA",
+            RtMidiApi::UnixJack => "Jack",
+            RtMidiApi::WindowsMM => "Windows MultiMedia",
+            RtMidiApi::RtMidiDummy => "Dummy",
+        }
+    }
+}
+
 impl From<u32> for RtMidiApi {
     fn from(api: u32) -> Self {
         match api {
@@ -29,9 +82,46 @@ impl From<u32> for RtMidiApi {
     }
 }
 
+/// Uses RtMidi's display name where the library provides one (RtMidi 3 doesn't), otherwise
+/// [`RtMidiApi::display_name`]
 impl fmt::Display for RtMidiApi {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let display_name = unsafe { CStr::from_ptr(ffi::rtmidi_api_display_name(*self as u32)) };
-        write!(f, "{}", display_name.to_str().map_err(|_| fmt::Error)?)
+        let ptr = unsafe { ffi::rtmidi_api_display_name(*self as u32) };
+        let display_name = if ptr.is_null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+        };
+        f.write_str(display_name.unwrap_or_else(|| self.display_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RtMidiApi;
+
+    #[test]
+    fn compiled() {
+        let apis = RtMidiApi::compiled();
+        assert!(!apis.is_empty());
+        assert!(!apis.contains(&RtMidiApi::Unspecified));
+    }
+
+    #[test]
+    fn display() {
+        for api in RtMidiApi::compiled() {
+            assert!(!api.to_string().is_empty());
+        }
+        assert_eq!(RtMidiApi::LinuxALSA.display_name(), "ALSA");
+    }
+
+    #[test]
+    fn resolve() {
+        let api = RtMidiApi::LinuxALSA.resolve();
+        if cfg!(feature = "dummy-only") {
+            assert_eq!(api, Ok(RtMidiApi::RtMidiDummy));
+        } else {
+            assert_eq!(api, Ok(RtMidiApi::LinuxALSA));
+        }
     }
 }
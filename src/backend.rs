@@ -0,0 +1,169 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::api::RtMidiApi;
+use crate::error::RtMidiError;
+use crate::midi_in::{RtMidiIn, RtMidiInArgs};
+use crate::midi_out::{RtMidiOut, RtMidiOutArgs};
+use crate::ports::port_names;
+
+/// Receives each message from an input opened by a [`Backend`], with the time in seconds since
+/// the previous message
+pub type BackendCallback = Box<dyn Fn(f64, &[u8]) + Send>;
+
+/// A MIDI transport
+///
+/// Backends provide ports to a [`crate::MidiSystem`], which lists them alongside those of the
+/// native RtMidi API and opens them by name in the same way. Implement this to add other
+/// transports, such as network sessions, serial ports or in-process devices.
+pub trait Backend: Send + Sync {
+    /// Returns a short, stable identifier for the backend (e.g. "alsa" or "network")
+    fn name(&self) -> &str;
+
+    /// Returns the names of the available input ports
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError>;
+
+    /// Returns the names of the available output ports
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError>;
+
+    /// Open an input port by name, passing every message it receives to `callback` until the
+    /// returned connection is dropped
+    fn open_input(
+        &self,
+        port: &str,
+        client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError>;
+
+    /// Open an output port by name
+    fn open_output(
+        &self,
+        port: &str,
+        client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError>;
+}
+
+/// An open input port of a [`Backend`], which is closed when dropped
+pub trait InputConnection: Send + Sync {}
+
+/// An open output port of a [`Backend`], which is closed when dropped
+pub trait OutputConnection: Send + Sync {
+    /// Send a single, complete message
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError>;
+}
+
+impl InputConnection for RtMidiIn {}
+
+impl OutputConnection for RtMidiOut {
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.message(message)
+    }
+}
+
+/// [`Backend`] for a native RtMidi API
+pub struct RtMidiBackend {
+    api: RtMidiApi,
+    // Clients used to enumerate ports
+    clients: Mutex<(RtMidiIn, RtMidiOut)>,
+}
+
+impl RtMidiBackend {
+    /// Create a backend for an API ([`RtMidiApi::Unspecified`] for the first available)
+    pub fn new(api: RtMidiApi, client_name: &str) -> Result<Self, RtMidiError> {
+        let input = RtMidiIn::new(RtMidiInArgs {
+            api,
+            client_name,
+            ..Default::default()
+        })?;
+        let output = RtMidiOut::new(RtMidiOutArgs {
+            api,
+            client_name,
+            ..Default::default()
+        })?;
+        Ok(RtMidiBackend {
+            api: input.current_api(),
+            clients: Mutex::new((input, output)),
+        })
+    }
+
+    /// Returns the API in use
+    pub fn api(&self) -> RtMidiApi {
+        self.api
+    }
+
+    fn lock(&self) -> MutexGuard<'_, (RtMidiIn, RtMidiOut)> {
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Backend for RtMidiBackend {
+    fn name(&self) -> &str {
+        self.api.name()
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        let clients = self.lock();
+        port_names(clients.0.port_count()?, |port| clients.0.port_name(port))
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        let clients = self.lock();
+        port_names(clients.1.port_count()?, |port| clients.1.port_name(port))
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        let number = self
+            .input_ports()?
+            .iter()
+            .position(|name| name == port)
+            .ok_or_else(|| RtMidiError::PortNotFound(port.to_string()))?;
+        let input = RtMidiIn::new(RtMidiInArgs {
+            api: self.api,
+            client_name,
+            ..Default::default()
+        })?;
+        input.open_port(number as u32, client_name)?;
+        input.set_callback(callback)?;
+        Ok(Box::new(input))
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        let number = self
+            .output_ports()?
+            .iter()
+            .position(|name| name == port)
+            .ok_or_else(|| RtMidiError::PortNotFound(port.to_string()))?;
+        let output = RtMidiOut::new(RtMidiOutArgs {
+            api: self.api,
+            client_name,
+            ..Default::default()
+        })?;
+        output.open_port(number as u32, client_name)?;
+        Ok(Box::new(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, RtMidiBackend};
+    use crate::{RtMidiApi, RtMidiError};
+
+    #[test]
+    fn rtmidi_backend() {
+        let backend = RtMidiBackend::new(RtMidiApi::Unspecified, "Test").unwrap();
+        assert_eq!(backend.name(), backend.api().name());
+        assert!(backend.input_ports().is_ok());
+        assert_eq!(
+            backend.open_output("No Such Port", "Test").err(),
+            Some(RtMidiError::PortNotFound("No Such Port".to_string()))
+        );
+    }
+}
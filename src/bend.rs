@@ -0,0 +1,118 @@
+use crate::value;
+
+/// RPN parameter number controllers (MSB and LSB), and Data Entry (MSB and LSB)
+const RPN_MSB: u8 = 101;
+const RPN_LSB: u8 = 100;
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+
+/// Pitch Bend Sensitivity RPN, and the null RPN that deselects it again
+const PITCH_BEND_SENSITIVITY: u8 = 0;
+const NULL_RPN: u8 = 127;
+
+/// General MIDI default pitch bend range, in semitones
+const DEFAULT_SEMITONES: u8 = 2;
+
+/// How far a full pitch bend moves the pitch either way
+///
+/// Set on a device with the Pitch Bend Sensitivity RPN, see
+/// [`OutputChannel::set_pitch_bend_range`](crate::OutputChannel::set_pitch_bend_range). The
+/// default is the General MIDI range of 2 semitones.
+/// ```
+/// use rtmidi::PitchBendRange;
+///
+/// let range = PitchBendRange::new(12, 0);
+/// assert_eq!(range.to_bend(0.0), 8192);
+/// assert_eq!(range.to_bend(-12.0), 0);
+/// assert_eq!(range.to_bend(6.0), 12288);
+/// assert_eq!(range.from_bend(0), -12.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PitchBendRange {
+    pub semitones: u8,
+    pub cents: u8,
+}
+
+impl Default for PitchBendRange {
+    fn default() -> Self {
+        PitchBendRange::new(DEFAULT_SEMITONES, 0)
+    }
+}
+
+impl PitchBendRange {
+    /// A range of semitones and cents (0-99). Values are masked to 7 bits.
+    pub fn new(semitones: u8, cents: u8) -> Self {
+        PitchBendRange {
+            semitones: semitones & 0x7F,
+            cents: cents & 0x7F,
+        }
+    }
+
+    /// Returns the range in semitones
+    pub fn as_semitones(&self) -> f64 {
+        f64::from(self.semitones) + f64::from(self.cents) / 100.0
+    }
+
+    /// Returns the pitch bend value (8192 is centre) that moves the pitch by an offset in
+    /// semitones, clamped to the range
+    pub fn to_bend(&self, semitones: f64) -> u16 {
+        let range = self.as_semitones();
+        if range == 0.0 {
+            return value::to_pitch_bend(0.0);
+        }
+        value::to_pitch_bend(semitones / range)
+    }
+
+    /// Returns the offset in semitones that a pitch bend value moves the pitch by
+    pub fn from_bend(&self, bend: u16) -> f64 {
+        value::from_pitch_bend(bend) * self.as_semitones()
+    }
+
+    /// Returns the Control Change messages that set the range on a channel (0-15): the Pitch
+    /// Bend Sensitivity RPN, followed by the null RPN so later Data Entry messages don't change
+    /// it
+    pub fn rpn_messages(&self, channel: u8) -> [[u8; 3]; 6] {
+        let status = 0xB0 | (channel & 0x0F);
+        [
+            [status, RPN_MSB, 0],
+            [status, RPN_LSB, PITCH_BEND_SENSITIVITY],
+            [status, DATA_ENTRY_MSB, self.semitones & 0x7F],
+            [status, DATA_ENTRY_LSB, self.cents & 0x7F],
+            [status, RPN_MSB, NULL_RPN],
+            [status, RPN_LSB, NULL_RPN],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PitchBendRange;
+
+    #[test]
+    fn bend() {
+        let range = PitchBendRange::default();
+        assert_eq!(range.to_bend(2.0), 16383);
+        assert_eq!(range.to_bend(5.0), 16383);
+        assert_eq!(range.to_bend(-1.0), 4096);
+        assert_eq!(range.from_bend(8192), 0.0);
+        let range = PitchBendRange::new(0, 50);
+        assert_eq!(range.as_semitones(), 0.5);
+        assert_eq!(range.to_bend(0.25), 12288);
+        assert_eq!(PitchBendRange::new(0, 0).to_bend(1.0), 8192);
+    }
+
+    #[test]
+    fn rpn_messages() {
+        assert_eq!(
+            PitchBendRange::new(12, 0).rpn_messages(3),
+            [
+                [0xB3, 101, 0],
+                [0xB3, 100, 0],
+                [0xB3, 6, 12],
+                [0xB3, 38, 0],
+                [0xB3, 101, 127],
+                [0xB3, 100, 127],
+            ]
+        );
+    }
+}
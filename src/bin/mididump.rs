@@ -0,0 +1,112 @@
+//! Print incoming MIDI messages
+//!
+//! ```text
+//! USAGE:
+//!     mididump [--raw] [PORT]
+//! ```
+//!
+//! Without a port the available input ports are listed. The port may be given as an index (as
+//! listed) or as a name, in which case the first port whose name contains it is opened.
+use std::cell::Cell;
+use std::env;
+use std::process;
+use std::thread;
+
+use rtmidi::{MidiMessage, RtMidiError, RtMidiIn, RtMidiInArgs, RtMidiPort};
+
+const USAGE: &str = "USAGE:
+    mididump [--raw] [PORT]
+
+Lists input ports, or prints messages received on PORT (an index or part of a port name).
+
+OPTIONS:
+    -r, --raw     Print messages as hex bytes instead of decoding them
+    -h, --help    Print this help";
+
+fn main() {
+    let mut raw = false;
+    let mut port = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-r" | "--raw" => raw = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if port.is_none() && !arg.starts_with('-') => port = Some(arg),
+            _ => {
+                eprintln!("Unexpected argument '{}'\n\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    if let Err(e) = run(port, raw) {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn run(port: Option<String>, raw: bool) -> Result<(), RtMidiError> {
+    let input = RtMidiIn::new(RtMidiInArgs {
+        client_name: "mididump",
+        ..Default::default()
+    })?;
+
+    let port = match port {
+        Some(port) => port,
+        None => return list(&input),
+    };
+    let number = find(&input, &port)?;
+    let name = input.port_name(number)?.to_string();
+
+    // Timestamps are printed as the time since the first message
+    let time = Cell::new(0.0);
+    input.set_callback(move |delta, message| {
+        time.set(time.get() + delta);
+        if raw {
+            let bytes: Vec<_> = message.iter().map(|b| format!("{:02X}", b)).collect();
+            println!("{:12.6}  {}", time.get(), bytes.join(" "));
+        } else {
+            match MidiMessage::parse(message) {
+                Ok(message) => println!("{:12.6}  {:?}", time.get(), message),
+                Err(e) => println!("{:12.6}  {:?} {:02X?}", time.get(), e, message),
+            }
+        }
+    })?;
+    input.ignore_types(false, false, false)?;
+    input.open_port(number, "mididump")?;
+    eprintln!("Listening on '{}'", name);
+
+    loop {
+        thread::park();
+    }
+}
+
+fn list(input: &RtMidiIn) -> Result<(), RtMidiError> {
+    let count = input.port_count()?;
+    if count == 0 {
+        println!("No MIDI input ports available");
+    }
+    for port in 0..count {
+        println!("{}: {}", port, input.port_name(port)?);
+    }
+    Ok(())
+}
+
+fn find(input: &RtMidiIn, port: &str) -> Result<RtMidiPort, RtMidiError> {
+    let count = input.port_count()?;
+    if let Ok(number) = port.parse() {
+        if number < count {
+            return Ok(number);
+        }
+    }
+    for number in 0..count {
+        if input.port_name(number)?.contains(port) {
+            return Ok(number);
+        }
+    }
+    Err(RtMidiError::Error(format!(
+        "MIDI input port '{}' not found",
+        port
+    )))
+}
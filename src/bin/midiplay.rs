@@ -0,0 +1,131 @@
+//! Play a Standard MIDI File to an output port
+//!
+//! ```text
+//! USAGE:
+//!     midiplay [--port PORT] FILE
+//!     midiplay --list
+//! ```
+//!
+//! The port may be given as an index (as listed) or as a name, in which case the first port
+//! whose name contains it is opened. The first port is used by default.
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rtmidi::{RtMidiError, RtMidiOut, RtMidiOutArgs, RtMidiPort, Smf};
+
+const USAGE: &str = "USAGE:
+    midiplay [--port PORT] FILE
+    midiplay --list
+
+Plays a Standard MIDI File to PORT (an index or part of a port name, default 0).
+
+OPTIONS:
+    -p, --port PORT    Output port to play to
+    -l, --list         List output ports
+    -h, --help         Print this help";
+
+/// Time allowed to queue the file before playback starts
+const LEAD_IN: Duration = Duration::from_millis(200);
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = None;
+    let mut path = None;
+    let mut list = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "--port" => port = args.next(),
+            "-l" | "--list" => list = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => usage(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    let result = match (list, path) {
+        (true, _) => list_ports(),
+        (false, Some(path)) => play(&path, port.as_deref().unwrap_or("0")),
+        (false, None) => usage("Missing FILE"),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    eprintln!("{}\n\n{}", error, USAGE);
+    process::exit(2);
+}
+
+fn output() -> Result<RtMidiOut, RtMidiError> {
+    RtMidiOut::new(RtMidiOutArgs {
+        client_name: "midiplay",
+        ..Default::default()
+    })
+}
+
+fn list_ports() -> Result<(), RtMidiError> {
+    let output = output()?;
+    let count = output.port_count()?;
+    if count == 0 {
+        println!("No MIDI output ports available");
+    }
+    for port in 0..count {
+        println!("{}: {}", port, output.port_name(port)?);
+    }
+    Ok(())
+}
+
+fn play(path: &str, port: &str) -> Result<(), RtMidiError> {
+    let data = fs::read(path)
+        .map_err(|e| RtMidiError::Error(format!("Failed to read '{}': {}", path, e)))?;
+    let smf = Smf::parse(&data)?;
+    let messages = smf.messages();
+
+    let output = output()?;
+    let number = find(&output, port)?;
+    output.open_port(number, "midiplay")?;
+    eprintln!("Playing '{}' to '{}'", path, output.port_name(number)?);
+
+    let scheduler = output.scheduler()?;
+    let start = Instant::now() + LEAD_IN;
+    for (offset, message) in &messages {
+        scheduler.schedule_at(start + *offset, message)?;
+    }
+    if let Some((end, _)) = messages.last() {
+        let end = start + *end;
+        thread::sleep(end.saturating_duration_since(Instant::now()));
+    }
+
+    // Silence anything left sounding, e.g. by a file without note offs
+    for channel in 0..16 {
+        let channel = output.channel(channel);
+        channel.all_notes_off()?;
+        channel.reset_all_controllers()?;
+    }
+    Ok(())
+}
+
+fn find(output: &RtMidiOut, port: &str) -> Result<RtMidiPort, RtMidiError> {
+    let count = output.port_count()?;
+    if let Ok(number) = port.parse() {
+        if number < count {
+            return Ok(number);
+        }
+    }
+    for number in 0..count {
+        if output.port_name(number)?.contains(port) {
+            return Ok(number);
+        }
+    }
+    Err(RtMidiError::Error(format!(
+        "MIDI output port '{}' not found",
+        port
+    )))
+}
@@ -0,0 +1,200 @@
+//! List the compiled MIDI APIs and their input and output ports
+//!
+//! ```text
+//! USAGE:
+//!     midiprobe [--json]
+//! ```
+//!
+//! Each port is given an ID made from the API, direction and port name, which (unlike the port
+//! index) stays the same when other devices are connected or removed.
+use std::env;
+use std::process;
+
+use rtmidi::{RtMidiApi, RtMidiError, RtMidiIn, RtMidiInArgs, RtMidiOut, RtMidiOutArgs};
+
+const USAGE: &str = "USAGE:
+    midiprobe [--json]
+
+Lists the compiled MIDI APIs and their input and output ports.
+
+OPTIONS:
+    -j, --json    Print JSON instead of text
+    -h, --help    Print this help";
+
+const CLIENT_NAME: &str = "midiprobe";
+
+struct Api {
+    api: RtMidiApi,
+    inputs: Vec<Port>,
+    outputs: Vec<Port>,
+    error: Option<RtMidiError>,
+}
+
+struct Port {
+    index: u32,
+    name: String,
+    id: String,
+}
+
+fn main() {
+    let mut json = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-j" | "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("Unexpected argument '{}'\n\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let apis: Vec<_> = RtMidiApi::compiled().into_iter().map(probe).collect();
+    if json {
+        print_json(&apis);
+    } else {
+        print_text(&apis);
+    }
+}
+
+fn probe(api: RtMidiApi) -> Api {
+    let mut result = Api {
+        api,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        error: None,
+    };
+    let ports = || -> Result<(Vec<Port>, Vec<Port>), RtMidiError> {
+        let input = RtMidiIn::new(RtMidiInArgs {
+            api,
+            client_name: CLIENT_NAME,
+            ..Default::default()
+        })?;
+        let output = RtMidiOut::new(RtMidiOutArgs {
+            api,
+            client_name: CLIENT_NAME,
+            ..Default::default()
+        })?;
+        let mut inputs = Vec::new();
+        for index in 0..input.port_count()? {
+            inputs.push(port(api, "in", index, input.port_name(index)?));
+        }
+        let mut outputs = Vec::new();
+        for index in 0..output.port_count()? {
+            outputs.push(port(api, "out", index, output.port_name(index)?));
+        }
+        Ok((inputs, outputs))
+    };
+    match ports() {
+        Ok((inputs, outputs)) => {
+            result.inputs = inputs;
+            result.outputs = outputs;
+        }
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
+
+fn port(api: RtMidiApi, direction: &str, index: u32, name: &str) -> Port {
+    // ALSA port names end with client and port numbers (e.g. "Device MIDI 1 28:0"), which change
+    // when devices are reconnected, so they are left out of the ID
+    let stable = match name.rsplit_once(' ') {
+        Some((device, suffix))
+            if suffix.contains(':')
+                && suffix
+                    .split(':')
+                    .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())) =>
+        {
+            device
+        }
+        _ => name,
+    };
+    Port {
+        index,
+        name: name.to_string(),
+        id: format!("{}:{}:{}", api.name(), direction, stable),
+    }
+}
+
+fn print_text(apis: &[Api]) {
+    for (i, api) in apis.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{} ({})", api.api, api.api.name());
+        if let Some(e) = &api.error {
+            println!("  Error: {:?}", e);
+            continue;
+        }
+        for (title, ports) in &[("Inputs", &api.inputs), ("Outputs", &api.outputs)] {
+            println!("  {}:", title);
+            if ports.is_empty() {
+                println!("    (none)");
+            }
+            for port in ports.iter() {
+                println!("    {}: {}  [{}]", port.index, port.name, port.id);
+            }
+        }
+    }
+}
+
+fn print_json(apis: &[Api]) {
+    let apis: Vec<_> = apis
+        .iter()
+        .map(|api| {
+            let error = match &api.error {
+                Some(e) => string(&format!("{:?}", e)),
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"name\":{},\"display_name\":{},\"error\":{},\"inputs\":{},\"outputs\":{}}}",
+                string(api.api.name()),
+                string(&api.api.to_string()),
+                error,
+                ports_json(&api.inputs),
+                ports_json(&api.outputs)
+            )
+        })
+        .collect();
+    println!(
+        "{{\"version\":{},\"apis\":[{}]}}",
+        string(env!("CARGO_PKG_VERSION")),
+        apis.join(",")
+    );
+}
+
+fn ports_json(ports: &[Port]) -> String {
+    let ports: Vec<_> = ports
+        .iter()
+        .map(|port| {
+            format!(
+                "{{\"index\":{},\"name\":{},\"id\":{}}}",
+                port.index,
+                string(&port.name),
+                string(&port.id)
+            )
+        })
+        .collect();
+    format!("[{}]", ports.join(","))
+}
+
+/// Encode a JSON string
+fn string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::error::RtMidiError;
+use crate::midi_in::RtMidiIn;
+use crate::midi_out::RtMidiOut;
+use crate::subscribe::Subscription;
+
+/// A message recorded by a [`Capture`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedMessage {
+    /// Time since the first message of the session
+    pub time: Duration,
+    pub message: Vec<u8>,
+}
+
+/// Messages recorded by a [`Capture`], in the order they were received
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureSession {
+    pub messages: Vec<CapturedMessage>,
+    /// Number of messages discarded from the start of the session because the capture is bounded
+    pub dropped: usize,
+}
+
+impl CaptureSession {
+    /// Returns the time of the last message
+    pub fn duration(&self) -> Duration {
+        self.messages
+            .last()
+            .map(|message| message.time)
+            .unwrap_or_default()
+    }
+
+    /// Send the session to an output with its original timing, scaled by `speed` (e.g. 2.0 plays
+    /// twice as fast).
+    ///
+    /// Messages are scheduled with the output's [`crate::Scheduler`], starting now, and this
+    /// returns without waiting for them to be sent. Returns the time until the last message is
+    /// sent.
+    pub fn replay(&self, output: &RtMidiOut, speed: f64) -> Result<Duration, RtMidiError> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(RtMidiError::Error(format!(
+                "Invalid replay speed {}",
+                speed
+            )));
+        }
+        let scheduler = output.scheduler()?;
+        let start = Instant::now();
+        for message in &self.messages {
+            scheduler.schedule_at(start + message.time.div_f64(speed), &message.message)?;
+        }
+        Ok(self.duration().div_f64(speed))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    messages: VecDeque<CapturedMessage>,
+    capacity: Option<usize>,
+    dropped: usize,
+    time: Option<Duration>,
+}
+
+/// Timestamped input recorder
+///
+/// Stores every message received by an input, with its time relative to the first message
+/// (accumulated from RtMidi's delta-times), in an in-memory [`CaptureSession`]. An unbounded
+/// capture keeps everything; a bounded capture keeps only the most recent messages. Clones refer
+/// to the same capture.
+/// ```
+/// use rtmidi::{Capture, RtMidiIn};
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// let capture = Capture::new();
+/// let recording = capture.attach(&input);
+/// input.set_callback(|_timestamp, _message| {}).unwrap();
+///
+/// // Later
+/// drop(recording);
+/// for message in capture.session().messages {
+///     println!("{:?}: {:02x?}", message.time, message.message);
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<State>>);
+
+impl Capture {
+    /// Create an unbounded capture
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a capture that keeps only the last `capacity` messages
+    pub fn bounded(capacity: usize) -> Self {
+        Capture(Arc::new(Mutex::new(State {
+            capacity: Some(capacity),
+            ..Default::default()
+        })))
+    }
+
+    /// Record the messages received by an input until the returned [`Subscription`] is dropped.
+    ///
+    /// Messages are recorded as they are delivered to the input's callback or retrieved with
+    /// [`RtMidiIn::message`].
+    pub fn attach(&self, input: &RtMidiIn) -> Subscription {
+        let capture = self.clone();
+        input.subscribe(move |timestamp, message| capture.record(timestamp, message))
+    }
+
+    /// Record a message, given its delta-time in seconds since the previous message
+    pub fn record(&self, delta: f64, message: &[u8]) {
+        let mut state = self.lock();
+        let time = match state.time {
+            Some(time) => time + Duration::from_secs_f64(delta.max(0.0)),
+            None => Duration::from_secs(0),
+        };
+        state.time = Some(time);
+        if state.capacity == Some(0) {
+            state.dropped += 1;
+            return;
+        }
+        if Some(state.messages.len()) == state.capacity {
+            state.messages.pop_front();
+            state.dropped += 1;
+        }
+        state.messages.push_back(CapturedMessage {
+            time,
+            message: message.to_vec(),
+        });
+    }
+
+    /// Returns a copy of the messages recorded so far
+    pub fn session(&self) -> CaptureSession {
+        let state = self.lock();
+        CaptureSession {
+            messages: state.messages.iter().cloned().collect(),
+            dropped: state.dropped,
+        }
+    }
+
+    /// Returns the messages recorded so far and starts a new session
+    pub fn take(&self) -> CaptureSession {
+        let mut state = self.lock();
+        let session = CaptureSession {
+            messages: state.messages.drain(..).collect(),
+            dropped: state.dropped,
+        };
+        state.dropped = 0;
+        state.time = None;
+        session
+    }
+
+    /// Send the messages recorded so far to an output with their original timing (see
+    /// [`CaptureSession::replay`])
+    pub fn replay(&self, output: &RtMidiOut, speed: f64) -> Result<Duration, RtMidiError> {
+        self.session().replay(output, speed)
+    }
+
+    /// Returns the number of messages recorded
+    pub fn len(&self) -> usize {
+        self.lock().messages.len()
+    }
+
+    /// Returns [`true`] if no messages have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.lock().messages.is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Capture, CapturedMessage};
+    use crate::midi_out::RtMidiOut;
+
+    #[test]
+    fn record() {
+        let capture = Capture::new();
+        capture.record(5.0, &[0x90, 60, 100]);
+        capture.record(0.5, &[0x80, 60, 0]);
+        let session = capture.session();
+        assert_eq!(
+            session.messages,
+            vec![
+                CapturedMessage {
+                    time: Duration::from_secs(0),
+                    message: vec![0x90, 60, 100]
+                },
+                CapturedMessage {
+                    time: Duration::from_millis(500),
+                    message: vec![0x80, 60, 0]
+                }
+            ]
+        );
+        assert_eq!(session.duration(), Duration::from_millis(500));
+        assert_eq!(capture.take(), session);
+        assert!(capture.is_empty());
+    }
+
+    #[test]
+    fn bounded() {
+        let capture = Capture::bounded(2);
+        for note in 60..64 {
+            capture.record(0.1, &[0x90, note, 100]);
+        }
+        let session = capture.session();
+        assert_eq!(session.dropped, 2);
+        assert_eq!(session.messages[0].message, vec![0x90, 62, 100]);
+        assert_eq!(capture.len(), 2);
+    }
+
+    #[test]
+    fn replay() {
+        let capture = Capture::new();
+        capture.record(0.0, &[0x90, 60, 100]);
+        capture.record(0.2, &[0x80, 60, 0]);
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert_eq!(
+            capture.replay(&output, 2.0).unwrap(),
+            Duration::from_millis(100)
+        );
+        assert!(capture.replay(&output, 0.0).is_err());
+    }
+}
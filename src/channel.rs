@@ -0,0 +1,148 @@
+use crate::bend::PitchBendRange;
+use crate::error::RtMidiError;
+use crate::message::ChannelMode;
+use crate::midi_out::RtMidiOut;
+
+/// A view of an [`RtMidiOut`] bound to a single MIDI channel
+///
+/// Created with [`RtMidiOut::channel`]. The channel voice helpers stamp every message with the
+/// bound channel so it doesn't need repeating at each call site. Data values are masked to 7
+/// bits (14 bits for pitch bend).
+///
+/// ```
+/// use rtmidi::{RtMidiOut, RtMidiError};
+///
+/// fn kick(output: &RtMidiOut) -> Result<(), RtMidiError> {
+///     let drums = output.channel(9);
+///     drums.note_on(36, 100)?;
+///     drums.note_off(36, 0)
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct OutputChannel<'a> {
+    output: &'a RtMidiOut,
+    channel: u8,
+}
+
+impl<'a> OutputChannel<'a> {
+    pub(crate) fn new(output: &'a RtMidiOut, channel: u8) -> Self {
+        assert!(channel < 16, "Invalid MIDI channel {}", channel);
+        OutputChannel { output, channel }
+    }
+
+    /// Returns the channel number (0-15)
+    pub fn number(&self) -> u8 {
+        self.channel
+    }
+
+    /// Send a Note Off message
+    pub fn note_off(&self, note: u8, velocity: u8) -> Result<(), RtMidiError> {
+        self.send(0x80, &[note, velocity])
+    }
+
+    /// Send a Note On message
+    pub fn note_on(&self, note: u8, velocity: u8) -> Result<(), RtMidiError> {
+        self.send(0x90, &[note, velocity])
+    }
+
+    /// Send a Polyphonic Key Pressure (aftertouch) message
+    pub fn poly_pressure(&self, note: u8, pressure: u8) -> Result<(), RtMidiError> {
+        self.send(0xA0, &[note, pressure])
+    }
+
+    /// Send a Control Change message
+    pub fn control_change(&self, controller: u8, value: u8) -> Result<(), RtMidiError> {
+        self.send(0xB0, &[controller, value])
+    }
+
+    /// Send a Program Change message
+    pub fn program_change(&self, program: u8) -> Result<(), RtMidiError> {
+        self.send(0xC0, &[program])
+    }
+
+    /// Send a Channel Pressure (aftertouch) message
+    pub fn channel_pressure(&self, pressure: u8) -> Result<(), RtMidiError> {
+        self.send(0xD0, &[pressure])
+    }
+
+    /// Send a Pitch Bend message with a 14-bit value (8192 is centre)
+    pub fn pitch_bend(&self, value: u16) -> Result<(), RtMidiError> {
+        self.send(0xE0, &[value as u8, (value >> 7) as u8])
+    }
+
+    /// Send a Pitch Bend message moving the pitch by an offset in semitones, using the range last
+    /// set with [`OutputChannel::set_pitch_bend_range`]
+    pub fn pitch_bend_semitones(&self, semitones: f64) -> Result<(), RtMidiError> {
+        self.pitch_bend(self.pitch_bend_range().to_bend(semitones))
+    }
+
+    /// Set the channel's pitch bend range with the Pitch Bend Sensitivity RPN, and remember it for
+    /// converting semitone offsets
+    pub fn set_pitch_bend_range(&self, range: PitchBendRange) -> Result<(), RtMidiError> {
+        for message in range.rpn_messages(self.channel).iter() {
+            self.output.message(message)?;
+        }
+        self.output.set_pitch_bend_range(self.channel, range);
+        Ok(())
+    }
+
+    /// Returns the pitch bend range last set on the channel, or the General MIDI default of 2
+    /// semitones
+    pub fn pitch_bend_range(&self) -> PitchBendRange {
+        self.output.pitch_bend_range(self.channel)
+    }
+
+    /// Send a Channel Mode message
+    pub fn channel_mode(&self, mode: ChannelMode) -> Result<(), RtMidiError> {
+        let (controller, value) = mode.to_controller();
+        self.control_change(controller, value)
+    }
+
+    /// Send All Sound Off, immediately silencing all sounding notes
+    pub fn all_sound_off(&self) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::AllSoundOff)
+    }
+
+    /// Send Reset All Controllers
+    pub fn reset_all_controllers(&self) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::ResetAllControllers)
+    }
+
+    /// Send Local Control on or off
+    pub fn local_control(&self, on: bool) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::LocalControl(on))
+    }
+
+    /// Send All Notes Off
+    pub fn all_notes_off(&self) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::AllNotesOff)
+    }
+
+    /// Send Omni Mode on or off
+    pub fn omni(&self, on: bool) -> Result<(), RtMidiError> {
+        self.channel_mode(if on {
+            ChannelMode::OmniOn
+        } else {
+            ChannelMode::OmniOff
+        })
+    }
+
+    /// Send Mono Mode on with the given number of channels (0 for as many as the receiver has
+    /// voices)
+    pub fn mono(&self, channels: u8) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::MonoOn(channels))
+    }
+
+    /// Send Poly Mode on
+    pub fn poly(&self) -> Result<(), RtMidiError> {
+        self.channel_mode(ChannelMode::PolyOn)
+    }
+
+    fn send(&self, status: u8, data: &[u8]) -> Result<(), RtMidiError> {
+        let mut message = [status | self.channel, 0, 0];
+        for (byte, value) in message[1..].iter_mut().zip(data) {
+            *byte = value & 0x7F;
+        }
+        self.output.message(&message[..=data.len()])
+    }
+}
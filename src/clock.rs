@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use crate::tempo;
+
+/// Default tempo in beats per minute
+pub const DEFAULT_TEMPO: f64 = 120.0;
+
+/// Musical clock
+///
+/// Maps between points in time and positions in beats, counting from an origin (beat zero) at a
+/// fixed tempo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    origin: Instant,
+    tempo: f64,
+}
+
+impl Clock {
+    /// Create a clock at the given tempo (in beats per minute) with beat zero at `origin`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tempo` is not a finite number greater than zero.
+    pub fn new(origin: Instant, tempo: f64) -> Self {
+        tempo::check(tempo);
+        Clock { origin, tempo }
+    }
+
+    /// Returns the instant of beat zero
+    pub fn origin(&self) -> Instant {
+        self.origin
+    }
+
+    /// Returns the tempo in beats per minute
+    pub fn tempo(&self) -> f64 {
+        self.tempo
+    }
+
+    /// Returns the duration of a single beat
+    pub fn beat_duration(&self) -> Duration {
+        Duration::from_secs_f64(60.0 / self.tempo)
+    }
+
+    /// Returns the position in beats at an instant (negative before the origin)
+    pub fn beat_at(&self, at: Instant) -> f64 {
+        seconds_since(self.origin, at) * self.tempo / 60.0
+    }
+
+    /// Returns the instant of a position in beats
+    pub fn instant_at(&self, beat: f64) -> Instant {
+        offset(self.origin, beat * 60.0 / self.tempo)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new(Instant::now(), DEFAULT_TEMPO)
+    }
+}
+
+/// Largest offset in seconds (about a century), far beyond anything scheduled but within the
+/// range of an [`Instant`] on every platform
+const MAX_OFFSET: f64 = 3_155_760_000.0;
+
+/// Returns the signed number of seconds from `origin` to `at`
+pub(crate) fn seconds_since(origin: Instant, at: Instant) -> f64 {
+    if at >= origin {
+        (at - origin).as_secs_f64()
+    } else {
+        -(origin - at).as_secs_f64()
+    }
+}
+
+/// Offset an instant by a signed number of seconds, saturating at [`MAX_OFFSET`] either way.
+/// NaN leaves the instant as it is.
+pub(crate) fn offset(at: Instant, seconds: f64) -> Instant {
+    if seconds.is_nan() {
+        return at;
+    }
+    let seconds = seconds.clamp(-MAX_OFFSET, MAX_OFFSET);
+    if seconds >= 0.0 {
+        at.checked_add(Duration::from_secs_f64(seconds))
+            .unwrap_or(at)
+    } else {
+        at.checked_sub(Duration::from_secs_f64(-seconds))
+            .unwrap_or(at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{offset, Clock, MAX_OFFSET};
+
+    #[test]
+    fn beats() {
+        let origin = Instant::now() + Duration::from_secs(10);
+        let clock = Clock::new(origin, 120.0);
+        assert_eq!(clock.beat_duration(), Duration::from_millis(500));
+        assert_eq!(clock.beat_at(origin + Duration::from_secs(2)), 4.0);
+        assert_eq!(clock.beat_at(origin - Duration::from_secs(1)), -2.0);
+        assert_eq!(clock.instant_at(3.0), origin + Duration::from_millis(1500));
+        assert_eq!(clock.instant_at(-1.0), origin - Duration::from_millis(500));
+    }
+
+    #[test]
+    fn offset_saturates() {
+        let at = Instant::now();
+        assert_eq!(offset(at, f64::NAN), at);
+        assert_eq!(
+            offset(at, f64::INFINITY),
+            at + Duration::from_secs_f64(MAX_OFFSET)
+        );
+        assert_eq!(offset(at, f64::NEG_INFINITY), offset(at, -MAX_OFFSET));
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_tempo() {
+        Clock::new(Instant::now(), 0.0);
+    }
+}
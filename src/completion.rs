@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::error::RtMidiError;
+
+#[derive(Default)]
+struct Shared {
+    result: Option<Result<(), RtMidiError>>,
+    waker: Option<Waker>,
+}
+
+/// Sending half of a [`SendFuture`], resolved by the output worker thread
+pub struct Completion(Arc<Mutex<Shared>>);
+
+impl Completion {
+    pub fn new() -> (Completion, SendFuture) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        (Completion(Arc::clone(&shared)), SendFuture(shared))
+    }
+
+    pub fn complete(self, result: Result<(), RtMidiError>) {
+        let waker = {
+            let mut shared = lock(&self.0);
+            shared.result = Some(result);
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake()
+        }
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        // Dropped without completing, e.g. because the worker stopped
+        let waker = {
+            let mut shared = lock(&self.0);
+            if shared.result.is_none() {
+                shared.result = Some(Err(RtMidiError::Error(
+                    "Output worker thread has stopped".to_string(),
+                )));
+            }
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake()
+        }
+    }
+}
+
+/// Future returned by [`crate::RtMidiOut::send_async`]
+///
+/// Resolves once the message has been passed to the backend, with any error it raised.
+pub struct SendFuture(Arc<Mutex<Shared>>);
+
+impl Future for SendFuture {
+    type Output = Result<(), RtMidiError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = lock(&self.0);
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+
+    use super::Completion;
+    use crate::error::RtMidiError;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn complete() {
+        let waker = Arc::new(Noop).into();
+        let mut context = Context::from_waker(&waker);
+        let (completion, mut future) = Completion::new();
+        assert!(Pin::new(&mut future).poll(&mut context).is_pending());
+        completion.complete(Ok(()));
+        assert_eq!(
+            Pin::new(&mut future).poll(&mut context),
+            Poll::Ready(Ok(()))
+        );
+
+        let (completion, mut future) = Completion::new();
+        drop(completion);
+        assert!(matches!(
+            Pin::new(&mut future).poll(&mut context),
+            Poll::Ready(Err(RtMidiError::Error(_)))
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::time::Instant;
+
+use crate::error::RtMidiError;
+use crate::midi::TimedOutput;
+use crate::ports::PortKind;
+
+type MIDIObjectRef = u32;
+type MIDITimeStamp = u64;
+type ItemCount = usize;
+type OSStatus = i32;
+type CFStringRef = *const c_void;
+
+/// `kCFStringEncodingUTF8`
+const UTF8: u32 = 0x0800_0100;
+
+/// Name of the device providing the inter-application buses, whose ports echo their input
+const IAC_DEVICE_NAME: &str = "IAC Driver";
+
+/// Name of the device providing network sessions
+const NETWORK_DEVICE_NAME: &str = "Network";
+
+/// Size of the buffer used to convert property strings
+const BUFFER_SIZE: usize = 1024;
+
+/// Room for a packet list's header, on top of the message it carries
+const PACKET_LIST_HEADER_SIZE: usize = 64;
+
+/// `mach_timebase_info_data_t`: host time ticks are `numer / denom` nanoseconds
+#[repr(C)]
+#[derive(Default)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+extern "C" {
+    static kMIDIPropertyName: CFStringRef;
+    static kMIDIPropertyManufacturer: CFStringRef;
+    static kMIDIPropertyModel: CFStringRef;
+    static kMIDIPropertyDisplayName: CFStringRef;
+    static kMIDIPropertyUniqueID: CFStringRef;
+    static kMIDIPropertyOffline: CFStringRef;
+
+    fn MIDIGetNumberOfDevices() -> ItemCount;
+    fn MIDIGetDevice(index: ItemCount) -> MIDIObjectRef;
+    fn MIDIDeviceGetNumberOfEntities(device: MIDIObjectRef) -> ItemCount;
+    fn MIDIDeviceGetEntity(device: MIDIObjectRef, index: ItemCount) -> MIDIObjectRef;
+    fn MIDIEntityGetNumberOfSources(entity: MIDIObjectRef) -> ItemCount;
+    fn MIDIEntityGetSource(entity: MIDIObjectRef, index: ItemCount) -> MIDIObjectRef;
+    fn MIDIEntityGetNumberOfDestinations(entity: MIDIObjectRef) -> ItemCount;
+    fn MIDIEntityGetDestination(entity: MIDIObjectRef, index: ItemCount) -> MIDIObjectRef;
+    fn MIDIGetNumberOfDestinations() -> ItemCount;
+    fn MIDIGetDestination(index: ItemCount) -> MIDIObjectRef;
+    fn MIDIClientCreate(
+        name: CFStringRef,
+        notify: *const c_void,
+        notify_ref: *mut c_void,
+        client: *mut MIDIObjectRef,
+    ) -> OSStatus;
+    fn MIDIClientDispose(client: MIDIObjectRef) -> OSStatus;
+    fn MIDIOutputPortCreate(
+        client: MIDIObjectRef,
+        name: CFStringRef,
+        port: *mut MIDIObjectRef,
+    ) -> OSStatus;
+    fn MIDIPacketListInit(list: *mut c_void) -> *mut c_void;
+    fn MIDIPacketListAdd(
+        list: *mut c_void,
+        list_size: usize,
+        packet: *mut c_void,
+        time: MIDITimeStamp,
+        length: usize,
+        data: *const u8,
+    ) -> *mut c_void;
+    fn MIDISend(port: MIDIObjectRef, destination: MIDIObjectRef, list: *const c_void) -> OSStatus;
+    fn MIDIObjectGetStringProperty(
+        object: MIDIObjectRef,
+        property: CFStringRef,
+        value: *mut CFStringRef,
+    ) -> OSStatus;
+    fn MIDIObjectGetIntegerProperty(
+        object: MIDIObjectRef,
+        property: CFStringRef,
+        value: *mut i32,
+    ) -> OSStatus;
+
+    fn CFStringGetCString(
+        string: CFStringRef,
+        buffer: *mut c_char,
+        size: isize,
+        encoding: u32,
+    ) -> u8;
+    fn CFStringCreateWithCString(
+        allocator: *const c_void,
+        string: *const c_char,
+        encoding: u32,
+    ) -> CFStringRef;
+    fn CFRelease(object: *const c_void);
+
+    fn mach_absolute_time() -> u64;
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> c_int;
+}
+
+/// A CoreMIDI device, such as a USB interface or a driver-provided port group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreMidiDevice {
+    pub name: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub unique_id: i32,
+    /// The device is known to the system but not currently present
+    pub offline: bool,
+    pub entities: Vec<CoreMidiEntity>,
+}
+
+/// A CoreMIDI entity: a group of endpoints of a device, such as a pair of DIN ports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreMidiEntity {
+    pub name: String,
+    pub unique_id: i32,
+    pub sources: Vec<CoreMidiEndpoint>,
+    pub destinations: Vec<CoreMidiEndpoint>,
+}
+
+/// A CoreMIDI endpoint: a source (input port) or destination (output port)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreMidiEndpoint {
+    pub name: String,
+    /// The name shown to users, which includes the device name where needed. RtMidi uses this
+    /// as the port name.
+    pub display_name: String,
+    pub unique_id: i32,
+    pub offline: bool,
+}
+
+/// Returns the CoreMIDI device → entity → endpoint hierarchy.
+///
+/// The flat port lists of [`crate::RtMidiIn`] and [`crate::RtMidiOut`] contain the online
+/// endpoints of this tree (and virtual endpoints, which belong to no device). The unique IDs are
+/// persistent and can be used to find the same endpoint again.
+pub fn coremidi_devices() -> Result<Vec<CoreMidiDevice>, RtMidiError> {
+    let mut devices = Vec::new();
+    for index in 0..unsafe { MIDIGetNumberOfDevices() } {
+        let device = unsafe { MIDIGetDevice(index) };
+        let mut entities = Vec::new();
+        for index in 0..unsafe { MIDIDeviceGetNumberOfEntities(device) } {
+            let entity = unsafe { MIDIDeviceGetEntity(device, index) };
+            let sources = (0..unsafe { MIDIEntityGetNumberOfSources(entity) })
+                .map(|index| endpoint(unsafe { MIDIEntityGetSource(entity, index) }))
+                .collect::<Result<_, _>>()?;
+            let destinations = (0..unsafe { MIDIEntityGetNumberOfDestinations(entity) })
+                .map(|index| endpoint(unsafe { MIDIEntityGetDestination(entity, index) }))
+                .collect::<Result<_, _>>()?;
+            entities.push(CoreMidiEntity {
+                name: string(entity, unsafe { kMIDIPropertyName })?,
+                unique_id: integer(entity, unsafe { kMIDIPropertyUniqueID }),
+                sources,
+                destinations,
+            });
+        }
+        devices.push(CoreMidiDevice {
+            name: string(device, unsafe { kMIDIPropertyName })?,
+            manufacturer: string(device, unsafe { kMIDIPropertyManufacturer })?,
+            model: string(device, unsafe { kMIDIPropertyModel })?,
+            unique_id: integer(device, unsafe { kMIDIPropertyUniqueID }),
+            offline: integer(device, unsafe { kMIDIPropertyOffline }) != 0,
+            entities,
+        });
+    }
+    Ok(devices)
+}
+
+/// Returns the kind of the endpoints of every device, by display name (the port name). Virtual
+/// endpoints belong to no device, so aren't listed.
+pub(crate) fn port_kinds() -> Result<Vec<(String, PortKind)>, RtMidiError> {
+    let mut kinds = Vec::new();
+    for device in coremidi_devices()? {
+        let kind = match device.name.as_str() {
+            IAC_DEVICE_NAME => PortKind::Through,
+            NETWORK_DEVICE_NAME => PortKind::Software,
+            _ => PortKind::Hardware,
+        };
+        for entity in device.entities {
+            for endpoint in entity.sources.into_iter().chain(entity.destinations) {
+                kinds.push((endpoint.display_name, kind));
+            }
+        }
+    }
+    Ok(kinds)
+}
+
+/// A CoreMIDI output port of our own, connected to the destination an RtMidi output is
+/// connected to, for sending messages with timestamps (which RtMidi doesn't support) so CoreMIDI
+/// sends them at the right time
+pub(crate) struct TimestampedOutput {
+    client: MIDIObjectRef,
+    port: MIDIObjectRef,
+    destination: MIDIObjectRef,
+    timebase: MachTimebaseInfo,
+}
+
+impl TimestampedOutput {
+    /// Create a port for the destination with the given display name (RtMidi's port name)
+    pub fn new(client_name: &str, port_name: &str) -> Result<Self, RtMidiError> {
+        let destination = (0..unsafe { MIDIGetNumberOfDestinations() })
+            .map(|index| unsafe { MIDIGetDestination(index) })
+            .find(|&destination| {
+                matches!(string(destination, unsafe { kMIDIPropertyDisplayName }), Ok(name) if name == port_name)
+            })
+            .ok_or_else(|| RtMidiError::PortNotFound(port_name.to_string()))?;
+        let name = cf_string(client_name)?;
+        let mut client = 0;
+        let status = unsafe { MIDIClientCreate(name, ptr::null(), ptr::null_mut(), &mut client) };
+        let mut port = 0;
+        let status = match status {
+            0 => unsafe { MIDIOutputPortCreate(client, name, &mut port) },
+            status => status,
+        };
+        unsafe { CFRelease(name) };
+        let mut output = TimestampedOutput {
+            client,
+            port,
+            destination,
+            timebase: MachTimebaseInfo::default(),
+        };
+        if status != 0 || unsafe { mach_timebase_info(&mut output.timebase) } != 0 {
+            return Err(RtMidiError::Error(format!(
+                "Unable to create CoreMIDI output port ({})",
+                status
+            )));
+        }
+        Ok(output)
+    }
+}
+
+impl TimedOutput for TimestampedOutput {
+    fn send_at(&mut self, at: Instant, message: &[u8]) -> Result<(), RtMidiError> {
+        let delay = at.saturating_duration_since(Instant::now()).as_nanos();
+        let ticks = delay * u128::from(self.timebase.denom) / u128::from(self.timebase.numer);
+        let time = unsafe { mach_absolute_time() } + ticks as u64;
+        // Aligned for the packet list's fields
+        let mut buffer = vec![0u64; PACKET_LIST_HEADER_SIZE / 8 + message.len() / 8 + 1];
+        let list_size = buffer.len() * 8;
+        let list = buffer.as_mut_ptr() as *mut c_void;
+        let packet = unsafe { MIDIPacketListInit(list) };
+        let packet = unsafe {
+            MIDIPacketListAdd(
+                list,
+                list_size,
+                packet,
+                time,
+                message.len(),
+                message.as_ptr(),
+            )
+        };
+        if packet.is_null() {
+            return Err(RtMidiError::InvalidMessage("message too long".to_string()));
+        }
+        match unsafe { MIDISend(self.port, self.destination, list) } {
+            0 => Ok(()),
+            status => Err(RtMidiError::Error(format!("MIDISend failed ({})", status))),
+        }
+    }
+}
+
+impl Drop for TimestampedOutput {
+    fn drop(&mut self) {
+        // Disposes of the port too
+        unsafe { MIDIClientDispose(self.client) };
+    }
+}
+
+fn cf_string(string: &str) -> Result<CFStringRef, RtMidiError> {
+    let string = CString::new(string)?;
+    let string = unsafe { CFStringCreateWithCString(ptr::null(), string.as_ptr(), UTF8) };
+    if string.is_null() {
+        return Err(RtMidiError::NullPointer);
+    }
+    Ok(string)
+}
+
+fn endpoint(endpoint: MIDIObjectRef) -> Result<CoreMidiEndpoint, RtMidiError> {
+    Ok(CoreMidiEndpoint {
+        name: string(endpoint, unsafe { kMIDIPropertyName })?,
+        display_name: string(endpoint, unsafe { kMIDIPropertyDisplayName })?,
+        unique_id: integer(endpoint, unsafe { kMIDIPropertyUniqueID }),
+        offline: integer(endpoint, unsafe { kMIDIPropertyOffline }) != 0,
+    })
+}
+
+/// Returns a string property, or an empty string if the object doesn't have it
+fn string(object: MIDIObjectRef, property: CFStringRef) -> Result<String, RtMidiError> {
+    let mut value: CFStringRef = ptr::null();
+    if unsafe { MIDIObjectGetStringProperty(object, property, &mut value) } != 0 || value.is_null()
+    {
+        return Ok(String::new());
+    }
+    let mut buffer = [0 as c_char; BUFFER_SIZE];
+    let converted =
+        unsafe { CFStringGetCString(value, buffer.as_mut_ptr(), BUFFER_SIZE as isize, UTF8) };
+    unsafe { CFRelease(value) };
+    if converted == 0 {
+        return Err(RtMidiError::Error(
+            "Unable to convert CoreMIDI property".to_string(),
+        ));
+    }
+    Ok(unsafe { CStr::from_ptr(buffer.as_ptr()) }
+        .to_str()?
+        .to_string())
+}
+
+/// Returns an integer property, or zero if the object doesn't have it
+fn integer(object: MIDIObjectRef, property: CFStringRef) -> i32 {
+    let mut value = 0;
+    match unsafe { MIDIObjectGetIntegerProperty(object, property, &mut value) } {
+        0 => value,
+        _ => 0,
+    }
+}
@@ -0,0 +1,305 @@
+/// Input message decoder
+///
+/// Sits between the backend and the user so that every message handed over is complete and
+/// well-formed. All decoding is opt-in; with everything disabled messages are passed through
+/// untouched.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    running_status: bool,
+    split: bool,
+    note_offs: bool,
+    status: Option<u8>,
+    buffer: Vec<u8>,
+    // Inside a system exclusive message, part of which has been passed on
+    sysex: bool,
+}
+
+impl Decoder {
+    /// Enable or disable running status resolution
+    pub fn set_running_status(&mut self, enabled: bool) {
+        self.running_status = enabled;
+        self.status = None;
+        self.sysex = false;
+    }
+
+    /// Enable or disable splitting of buffers containing multiple messages
+    pub fn set_split(&mut self, enabled: bool) {
+        self.split = enabled;
+        self.buffer.clear();
+        self.sysex = false;
+    }
+
+    /// Enable or disable conversion of note-ons with zero velocity into note-offs
+    pub fn set_note_offs(&mut self, enabled: bool) {
+        self.note_offs = enabled;
+    }
+
+    /// Decode a buffer received from the backend, passing each resulting message to `f`
+    pub fn decode<F: FnMut(&[u8])>(&mut self, data: &[u8], mut f: F) {
+        if !self.note_offs {
+            return self.decode_messages(data, f);
+        }
+        self.decode_messages(data, |message| match *message {
+            // A note-on with zero velocity is a note-off with a release velocity of 64
+            [status, note, 0] if status & 0xF0 == 0x90 => f(&[0x80 | status & 0x0F, note, 64]),
+            _ => f(message),
+        })
+    }
+
+    fn decode_messages<F: FnMut(&[u8])>(&mut self, data: &[u8], mut f: F) {
+        if self.split {
+            return self.decode_split(data, f);
+        }
+        if !self.running_status {
+            return f(data);
+        }
+        match data.first() {
+            // The rest of a system exclusive message passed on in parts
+            Some(&byte) if self.sysex && (byte < 0x80 || byte == 0xF7) => {
+                self.sysex = data.last() != Some(&0xF7);
+                f(data);
+            }
+            // Data bytes without a status byte: repeat the last channel status
+            Some(&byte) if byte < 0x80 => {
+                if let Some(status) = self.status {
+                    self.buffer.clear();
+                    self.buffer.push(status);
+                    self.buffer.extend_from_slice(data);
+                    f(&self.buffer);
+                }
+                // Orphan data bytes are dropped
+            }
+            Some(&status) => {
+                if status < 0xF8 {
+                    self.sysex = status == 0xF0 && data.last() != Some(&0xF7);
+                }
+                self.track(status);
+                f(data);
+            }
+            None => f(data),
+        }
+    }
+
+    /// Walk the buffer by status byte and message length, passing each message to `f`
+    fn decode_split<F: FnMut(&[u8])>(&mut self, data: &[u8], mut f: F) {
+        // Running status always applies within a buffer, but only carries over between buffers
+        // when running status resolution is enabled
+        if !self.running_status {
+            self.status = None;
+        }
+        // A message left incomplete by the last buffer is completed by this one
+        let mut remaining = match self.buffer.first() {
+            _ if self.sysex => None,
+            Some(&status) => {
+                data_length(status).map(|length| (length + 1).saturating_sub(self.buffer.len()))
+            }
+            None => Some(0),
+        };
+        for &byte in data {
+            match byte {
+                // Real-time messages may be interleaved with any other message
+                0xF8..=0xFF => f(&[byte]),
+                0xF7 if remaining.is_none() => {
+                    self.buffer.push(byte);
+                    f(&self.buffer);
+                    self.buffer.clear();
+                    remaining = Some(0);
+                }
+                // An end of exclusive outside a system exclusive message is dropped, along with
+                // any incomplete message
+                0xF7 => {
+                    self.track(byte);
+                    self.buffer.clear();
+                    remaining = Some(0);
+                }
+                0x80..=0xF6 => {
+                    // A new status byte truncates any incomplete message
+                    self.track(byte);
+                    self.buffer.clear();
+                    self.buffer.push(byte);
+                    remaining = data_length(byte);
+                }
+                _ => match remaining {
+                    None => self.buffer.push(byte),
+                    Some(0) => match self.status {
+                        Some(status) => {
+                            self.buffer.clear();
+                            self.buffer.extend_from_slice(&[status, byte]);
+                            remaining = data_length(status).map(|length| length - 1);
+                        }
+                        // Orphan data bytes are dropped
+                        None => continue,
+                    },
+                    Some(length) => {
+                        self.buffer.push(byte);
+                        remaining = Some(length - 1);
+                    }
+                },
+            }
+            if remaining == Some(0) && !self.buffer.is_empty() {
+                f(&self.buffer);
+                self.buffer.clear();
+            }
+        }
+        // System exclusive data is passed on as it arrives, so a long message isn't held back,
+        // while an incomplete message is kept until the next buffer
+        self.sysex = remaining.is_none();
+        if self.sysex && !self.buffer.is_empty() {
+            f(&self.buffer);
+            self.buffer.clear();
+        }
+    }
+
+    fn track(&mut self, status: u8) {
+        match status {
+            // Channel messages set the running status
+            0x80..=0xEF => self.status = Some(status),
+            // System common messages cancel it
+            0xF0..=0xF7 => self.status = None,
+            // System real-time messages leave it unchanged
+            _ => {}
+        }
+    }
+}
+
+/// Returns the number of data bytes following a status byte, or [`None`] for system exclusive
+/// messages, which are terminated by `0xF7`
+pub fn data_length(status: u8) -> Option<usize> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF => Some(2),
+        0xC0..=0xDF => Some(1),
+        0xF0 => None,
+        0xF1 | 0xF3 => Some(1),
+        0xF2 => Some(2),
+        _ => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+
+    fn decode(decoder: &mut Decoder, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        decoder.decode(data, |message| messages.push(message.to_vec()));
+        messages
+    }
+
+    #[test]
+    fn passthrough() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decode(&mut decoder, &[64, 90]), vec![vec![64, 90]]);
+    }
+
+    #[test]
+    fn running_status() {
+        let mut decoder = Decoder::default();
+        decoder.set_running_status(true);
+        assert!(decode(&mut decoder, &[64, 90]).is_empty());
+        assert_eq!(
+            decode(&mut decoder, &[144, 64, 90]),
+            vec![vec![144, 64, 90]]
+        );
+        assert_eq!(decode(&mut decoder, &[0xF8]), vec![vec![0xF8]]);
+        assert_eq!(decode(&mut decoder, &[65, 90]), vec![vec![144, 65, 90]]);
+        assert_eq!(decode(&mut decoder, &[0xF6]), vec![vec![0xF6]]);
+        assert!(decode(&mut decoder, &[66, 90]).is_empty());
+        // The parts of a system exclusive message are passed on as they are
+        assert_eq!(decode(&mut decoder, &[0xF0, 1]), vec![vec![0xF0, 1]]);
+        assert_eq!(decode(&mut decoder, &[2, 0xF7]), vec![vec![2, 0xF7]]);
+        assert!(decode(&mut decoder, &[3]).is_empty());
+    }
+
+    #[test]
+    fn note_offs() {
+        let mut decoder = Decoder::default();
+        decoder.set_note_offs(true);
+        assert_eq!(
+            decode(&mut decoder, &[0x93, 60, 0]),
+            vec![vec![0x83, 60, 64]]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0x93, 60, 1]),
+            vec![vec![0x93, 60, 1]]
+        );
+        decoder.set_split(true);
+        assert_eq!(
+            decode(&mut decoder, &[0x90, 60, 0, 62, 0, 0xB0, 7, 0]),
+            vec![vec![0x80, 60, 64], vec![0x80, 62, 64], vec![0xB0, 7, 0]]
+        );
+    }
+
+    #[test]
+    fn split() {
+        let mut decoder = Decoder::default();
+        decoder.set_split(true);
+        assert_eq!(
+            decode(&mut decoder, &[144, 64, 90, 192, 5, 0xF8, 176, 7, 100]),
+            vec![
+                vec![144, 64, 90],
+                vec![192, 5],
+                vec![0xF8],
+                vec![176, 7, 100]
+            ]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[144, 64, 90, 65, 90, 0xF0, 1, 0xF8, 2, 0xF7]),
+            vec![
+                vec![144, 64, 90],
+                vec![144, 65, 90],
+                vec![0xF8],
+                vec![0xF0, 1, 2, 0xF7]
+            ]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[1, 144, 64, 128, 64, 0]),
+            vec![vec![128, 64, 0]]
+        );
+    }
+
+    #[test]
+    fn split_incomplete() {
+        let mut decoder = Decoder::default();
+        decoder.set_split(true);
+        // Completed by the next buffer
+        assert!(decode(&mut decoder, &[224, 0]).is_empty());
+        assert_eq!(decode(&mut decoder, &[64, 144, 60]), vec![vec![224, 0, 64]]);
+        // Truncated by the next status byte
+        assert_eq!(
+            decode(&mut decoder, &[0xF8, 128, 60, 0]),
+            vec![vec![0xF8], vec![128, 60, 0]]
+        );
+        // Unterminated system exclusive data is passed on as it arrives
+        assert_eq!(
+            decode(&mut decoder, &[0xF0, 0x7E, 0x7F]),
+            vec![vec![0xF0, 0x7E, 0x7F]]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0x06, 0xF8, 0x01, 0xF7, 192, 5]),
+            vec![vec![0xF8], vec![0x06, 0x01, 0xF7], vec![192, 5]]
+        );
+    }
+
+    #[test]
+    fn split_orphan_end_of_exclusive() {
+        let mut decoder = Decoder::default();
+        decoder.set_split(true);
+        assert_eq!(
+            decode(&mut decoder, &[0xF7, 144, 64, 0xF7, 176, 7, 100, 0xF7]),
+            vec![vec![176, 7, 100]]
+        );
+        assert!(decode(&mut decoder, &[0xF7]).is_empty());
+    }
+
+    #[test]
+    fn split_running_status() {
+        let mut decoder = Decoder::default();
+        decoder.set_split(true);
+        decoder.set_running_status(true);
+        assert_eq!(decode(&mut decoder, &[176, 1, 2]), vec![vec![176, 1, 2]]);
+        assert_eq!(
+            decode(&mut decoder, &[1, 3, 1, 4]),
+            vec![vec![176, 1, 3], vec![176, 1, 4]]
+        );
+    }
+}
@@ -10,6 +10,22 @@ pub enum RtMidiError {
     Utf8(Utf8Error),
     NullString(NulError),
     NullPointer,
+    /// The output queue is full and the message was not sent
+    WouldBlock,
+    /// Malformed MIDI message data
+    InvalidMessage(String),
+    /// Malformed Standard MIDI File or MIDI log data
+    InvalidFile(String),
+    /// The device for the open port is no longer present on the system
+    Disconnected,
+    /// The requested feature is not supported by the current API
+    Unsupported(String),
+    /// The operation was stopped with a [`crate::CancelToken`]
+    Cancelled,
+    /// No reply was received from a device in time
+    Timeout,
+    /// No port matched the given name
+    PortNotFound(String),
 }
 
 impl From<ffi::RtMidiWrapper> for Result<(), RtMidiError> {
@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// MIDI connection event
+///
+/// Events are delivered to the callback registered with [`crate::RtMidiIn::set_event_callback`]
+/// or [`crate::RtMidiOut::set_event_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtMidiEvent {
+    /// The device stopped sending Active Sensing (or any other) messages for longer than the
+    /// watchdog timeout, so the connection should be considered dead.
+    ConnectionLost,
+    /// The device for the open port was removed from the system. The operation that detected it
+    /// returns [`crate::RtMidiError::Disconnected`].
+    Disconnected,
+}
+
+type Callback = Box<dyn Fn(RtMidiEvent) + Send>;
+
+/// Shared, replaceable event callback
+#[derive(Clone, Default)]
+pub struct EventHandler(Arc<Mutex<Option<Callback>>>);
+
+impl EventHandler {
+    pub fn set(&self, callback: Option<Callback>) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    }
+
+    pub fn emit(&self, event: RtMidiEvent) {
+        if let Some(callback) = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            callback(event)
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::error::RtMidiError;
+use crate::midi_in::{RtMidiIn, RtMidiInArgs};
+use crate::midi_out::{RtMidiOut, RtMidiOutArgs};
+
+type Rule = Box<dyn Fn(&[u8]) -> Vec<(Duration, Vec<u8>)> + Send + Sync>;
+
+/// Scripted virtual device for integration tests
+///
+/// A fake device is configured with rules that reply to the messages it receives, then opened
+/// as a pair of virtual ports with [`FakeDevice::open`]. The code under test connects its own
+/// [`RtMidiIn`] and [`RtMidiOut`] to those ports (by name) as it would to real hardware, so
+/// device protocols (e.g. librarians and editors) can be tested without the device. Virtual
+/// ports are provided by the backend, so this requires ALSA, CoreMIDI or JACK.
+/// ```no_run
+/// use std::time::Duration;
+/// use rtmidi::{FakeDevice, RtMidiError};
+///
+/// fn test_identity() -> Result<(), RtMidiError> {
+///     // Reply to an Identity Request
+///     let request = [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+///     let reply = [0xF0, 0x7E, 0x00, 0x06, 0x02, 0x41, 0x10, 0x00, 0x01, 0x00, 0xF7];
+///     let device = FakeDevice::new()
+///         .reply_to(&request, Duration::from_millis(5), &reply)
+///         .open("Fake Synth")?;
+///     // Open "Fake Synth" with the application's input and output and run the test...
+///     assert_eq!(device.received().len(), 1);
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct FakeDevice {
+    rules: Vec<Rule>,
+}
+
+impl FakeDevice {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Reply to a message with `reply` after a delay
+    pub fn reply_to(self, request: &[u8], delay: Duration, reply: &[u8]) -> Self {
+        let (request, reply) = (request.to_vec(), reply.to_vec());
+        self.rule(move |message| {
+            if message == request.as_slice() {
+                vec![(delay, reply.clone())]
+            } else {
+                Vec::new()
+            }
+        })
+    }
+
+    /// Add a rule that returns the replies to a message, each with the delay after which it is
+    /// sent
+    ///
+    /// Every rule is applied to every message received, and the replies of all rules are sent.
+    pub fn rule<F>(mut self, rule: F) -> Self
+    where
+        F: Fn(&[u8]) -> Vec<(Duration, Vec<u8>)> + Send + Sync + 'static,
+    {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Returns the replies to a message
+    pub fn replies(&self, message: &[u8]) -> Vec<(Duration, Vec<u8>)> {
+        self.rules.iter().flat_map(|rule| rule(message)).collect()
+    }
+
+    /// Open the device as a virtual input port and a virtual output port, both named `name`.
+    ///
+    /// The device receives messages sent to its input port and sends its replies from its
+    /// output port. It is closed when the returned [`FakeConnection`] is dropped.
+    pub fn open(self, name: &str) -> Result<FakeConnection, RtMidiError> {
+        let input = RtMidiIn::new(RtMidiInArgs {
+            client_name: name,
+            ..Default::default()
+        })?;
+        let output = RtMidiOut::new(RtMidiOutArgs {
+            client_name: name,
+            ..Default::default()
+        })?;
+        output.open_virtual_port(name)?;
+        let scheduler = output.scheduler()?;
+        let received = Arc::new(Mutex::new(Vec::new()));
+        {
+            let received = Arc::clone(&received);
+            input.set_callback(move |_timestamp, message| {
+                lock(&received).push(message.to_vec());
+                for (delay, reply) in self.replies(message) {
+                    // Errors are left for the code under test to notice as missing replies
+                    let _ = scheduler.schedule_in(delay, &reply);
+                }
+            })?;
+        }
+        input.ignore_types(false, false, false)?;
+        input.open_virtual_port(name)?;
+        Ok(FakeConnection {
+            _input: input,
+            _output: output,
+            received,
+        })
+    }
+}
+
+/// An open [`FakeDevice`]
+pub struct FakeConnection {
+    _input: RtMidiIn,
+    _output: RtMidiOut,
+    received: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl FakeConnection {
+    /// Returns the messages received by the device so far
+    pub fn received(&self) -> Vec<Vec<u8>> {
+        lock(&self.received).clone()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FakeDevice;
+
+    #[test]
+    fn replies() {
+        let device = FakeDevice::new()
+            .reply_to(&[0xB0, 0x00, 0x00], Duration::from_millis(5), &[0xF8])
+            .rule(|message| match message.first() {
+                Some(0x90) => vec![(Duration::from_secs(0), vec![0xFE])],
+                _ => Vec::new(),
+            });
+        assert_eq!(
+            device.replies(&[0xB0, 0x00, 0x00]),
+            vec![(Duration::from_millis(5), vec![0xF8])]
+        );
+        assert_eq!(
+            device.replies(&[0x90, 60, 100]),
+            vec![(Duration::from_secs(0), vec![0xFE])]
+        );
+        assert!(device.replies(&[0x80, 60, 0]).is_empty());
+    }
+
+    #[test]
+    fn open() {
+        let device = FakeDevice::new().open("Fake Device").unwrap();
+        assert!(device.received().is_empty());
+    }
+}
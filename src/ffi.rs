@@ -2,30 +2,56 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(dead_code)]
+// `size_t` is `usize` in the pregenerated bindings but not in bindgen's
+#![allow(clippy::unnecessary_cast)]
+
+#[cfg(any(feature = "handwritten-ffi", rtmidi_stub, rtmidi_native))]
+mod handwritten;
+#[cfg(rtmidi_native)]
+mod native;
+#[cfg(rtmidi_stub)]
+mod stub;
 
 #[cfg(rtmidi_version = "v4_0_0")]
 mod lib {
     use std::ffi::c_void;
+
+    use super::{invoke_callback, CallbackData};
+    use std::ptr;
     use std::slice;
 
+    #[cfg(all(feature = "handwritten-ffi", not(any(rtmidi_stub, rtmidi_native))))]
+    pub use super::handwritten::*;
+    #[cfg(rtmidi_native)]
+    pub use super::native::*;
+    #[cfg(rtmidi_stub)]
+    pub use super::stub::*;
+    #[cfg(not(any(feature = "handwritten-ffi", rtmidi_stub, rtmidi_native)))]
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-    pub fn create_callback<F: Fn(f64, &[u8])>(
+    pub fn compiled_api() -> Vec<RtMidiApi> {
+        let count = unsafe { rtmidi_get_compiled_api(ptr::null_mut(), 0) };
+        let mut apis = vec![0; count.max(0) as usize];
+        let count = unsafe { rtmidi_get_compiled_api(apis.as_mut_ptr(), apis.len() as u32) };
+        apis.truncate(count.max(0) as usize);
+        apis
+    }
+
+    pub fn create_callback<F: Fn(f64, &[u8]) + Send + 'static>(
         f: F,
     ) -> (
-        unsafe extern "C" fn(f64, *const u8, u64, *mut c_void),
-        *mut F,
+        unsafe extern "C" fn(f64, *const u8, size_t, *mut c_void),
+        CallbackData,
     ) {
-        unsafe extern "C" fn trampoline<F: Fn(f64, &[u8])>(
+        unsafe extern "C" fn trampoline(
             timestamp: f64,
             data: *const u8,
-            size: u64,
+            size: size_t,
             func: *mut c_void,
         ) {
-            let messages = slice::from_raw_parts(data, size as usize);
-            (*(func as *mut F))(timestamp, messages)
+            invoke_callback(func, timestamp, slice::from_raw_parts(data, size as usize))
         }
-        (trampoline::<F>, Box::into_raw(Box::new(f)))
+        (trampoline, CallbackData::new(f))
     }
 }
 
@@ -35,10 +61,17 @@ pub use lib::*;
 #[cfg(rtmidi_version = "v3_0_0")]
 mod lib {
     use std::ffi::c_void;
+
+    use super::{invoke_callback, CallbackData};
     use std::os::raw::{c_char, c_uchar};
     use std::ptr;
     use std::slice;
 
+    #[cfg(all(feature = "handwritten-ffi", not(rtmidi_stub)))]
+    pub use super::handwritten::*;
+    #[cfg(rtmidi_stub)]
+    pub use super::stub::*;
+    #[cfg(not(any(feature = "handwritten-ffi", rtmidi_stub)))]
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
     pub const RtMidiApi_RTMIDI_API_UNSPECIFIED: RtMidiApi = RtMidiApi_RT_MIDI_API_UNSPECIFIED;
@@ -48,32 +81,92 @@ mod lib {
     pub const RtMidiApi_RTMIDI_API_WINDOWS_MM: RtMidiApi = RtMidiApi_RT_MIDI_API_WINDOWS_MM;
     pub const RtMidiApi_RTMIDI_API_RTMIDI_DUMMY: RtMidiApi = RtMidiApi_RT_MIDI_API_RTMIDI_DUMMY;
 
-    pub fn rtmidi_api_display_name(_api: u32) -> *const c_char {
+    /// Not available in RtMidi 3, so always returns null
+    pub unsafe fn rtmidi_api_display_name(_api: u32) -> *const c_char {
         ptr::null()
     }
 
-    pub fn create_callback<F: Fn(f64, &[u8])>(
+    pub fn compiled_api() -> Vec<RtMidiApi> {
+        // Returns the number of APIs when passed null, otherwise fills the array
+        let count = unsafe { rtmidi_get_compiled_api(ptr::null_mut()) };
+        let mut apis = vec![0; count.max(0) as usize];
+        let mut ptr = apis.as_mut_ptr();
+        if !apis.is_empty() {
+            unsafe { rtmidi_get_compiled_api(&mut ptr) };
+        }
+        apis
+    }
+
+    pub fn create_callback<F: Fn(f64, &[u8]) + Send + 'static>(
         f: F,
-    ) -> (unsafe extern "C" fn(f64, *const u8, *mut c_void), *mut F) {
-        unsafe extern "C" fn trampoline<F: Fn(f64, &[u8])>(
-            timestamp: f64,
-            data: *const u8,
-            func: *mut c_void,
-        ) {
-            let messages = slice::from_raw_parts(data, 3);
-            (*(func as *mut F))(timestamp, messages)
+    ) -> (
+        unsafe extern "C" fn(f64, *const u8, *mut c_void),
+        CallbackData,
+    ) {
+        unsafe extern "C" fn trampoline(timestamp: f64, data: *const u8, func: *mut c_void) {
+            invoke_callback(func, timestamp, slice::from_raw_parts(data, 3))
         }
-        (trampoline::<F>, Box::into_raw(Box::new(f)))
+        (trampoline, CallbackData::new(f))
     }
 
+    extern "C" {
+        fn free(ptr: *mut c_void);
+    }
+
+    /// Matches the RtMidi 4 signature: `size` is the size of the `message` buffer on entry, and
+    /// is set to the size of the message (which is only copied if it fits). RtMidi 3 allocates
+    /// the message itself, so it is copied into the buffer and freed.
     pub unsafe fn wrap_rtmidi_in_get_message(
         device: RtMidiInPtr,
-        mut message: *mut c_uchar,
+        message: *mut c_uchar,
         size: *mut size_t,
     ) -> f64 {
-        rtmidi_in_get_message(device, &mut message, size)
+        let capacity = *size;
+        let mut data: *mut c_uchar = ptr::null_mut();
+        *size = 0;
+        let timestamp = rtmidi_in_get_message(device, &mut data, size);
+        if !data.is_null() {
+            if *size <= capacity {
+                ptr::copy_nonoverlapping(data, message, *size as usize);
+            }
+            free(data as *mut c_void);
+        }
+        timestamp
     }
 }
 
 #[cfg(rtmidi_version = "v3_0_0")]
 pub use lib::{wrap_rtmidi_in_get_message as rtmidi_in_get_message, *};
+
+type Handler = Box<dyn Fn(f64, &[u8]) + Send>;
+
+/// Closure passed to RtMidi as the user data of an input callback, freed when dropped
+///
+/// RtMidi invokes the callback until it's cancelled, so this must only be dropped once the
+/// callback can no longer be running.
+pub struct CallbackData(*mut Handler);
+
+unsafe impl Send for CallbackData {}
+
+impl CallbackData {
+    fn new<F: Fn(f64, &[u8]) + Send + 'static>(f: F) -> Self {
+        let handler: Handler = Box::new(f);
+        CallbackData(Box::into_raw(Box::new(handler)))
+    }
+
+    pub fn as_ptr(&self) -> *mut std::ffi::c_void {
+        self.0 as *mut std::ffi::c_void
+    }
+}
+
+impl Drop for CallbackData {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.0) });
+    }
+}
+
+/// Invoke the closure of a callback from its user data (see [`CallbackData::as_ptr`]), as RtMidi
+/// does
+pub unsafe fn invoke_callback(user_data: *mut std::ffi::c_void, timestamp: f64, message: &[u8]) {
+    (*(user_data as *const Handler))(timestamp, message)
+}
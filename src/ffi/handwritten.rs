@@ -0,0 +1,127 @@
+//! Declarations of RtMidi's C API (`rtmidi_c.h`), written by hand rather than generated by
+//! bindgen. Names follow bindgen's, so the rest of the crate can use either.
+
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_void};
+
+/// `size_t` is `usize` on every target Rust supports, which is also how bindgen and the `libc`
+/// crate declare it, so there's no need to depend on `libc` for it
+pub type size_t = usize;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct RtMidiWrapper {
+    pub ptr: *mut c_void,
+    pub data: *mut c_void,
+    pub ok: bool,
+    pub msg: *const c_char,
+}
+
+pub type RtMidiPtr = *mut RtMidiWrapper;
+pub type RtMidiInPtr = *mut RtMidiWrapper;
+pub type RtMidiOutPtr = *mut RtMidiWrapper;
+
+pub type RtMidiApi = c_uint;
+
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_UNSPECIFIED: RtMidiApi = 0;
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_MACOSX_CORE: RtMidiApi = 1;
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_LINUX_ALSA: RtMidiApi = 2;
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_UNIX_JACK: RtMidiApi = 3;
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_WINDOWS_MM: RtMidiApi = 4;
+#[cfg(rtmidi_version = "v4_0_0")]
+pub const RtMidiApi_RTMIDI_API_RTMIDI_DUMMY: RtMidiApi = 5;
+
+// RtMidi 3 has the same values with an extra underscore in the names
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_UNSPECIFIED: RtMidiApi = 0;
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_MACOSX_CORE: RtMidiApi = 1;
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_LINUX_ALSA: RtMidiApi = 2;
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_UNIX_JACK: RtMidiApi = 3;
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_WINDOWS_MM: RtMidiApi = 4;
+#[cfg(rtmidi_version = "v3_0_0")]
+pub const RtMidiApi_RT_MIDI_API_RTMIDI_DUMMY: RtMidiApi = 5;
+
+#[cfg(rtmidi_version = "v4_0_0")]
+pub type RtMidiCCallback = Option<
+    unsafe extern "C" fn(
+        timestamp: f64,
+        message: *const c_uchar,
+        message_size: size_t,
+        user_data: *mut c_void,
+    ),
+>;
+
+/// RtMidi 3 doesn't pass the message size: the message is assumed to be 3 bytes long
+#[cfg(rtmidi_version = "v3_0_0")]
+pub type RtMidiCCallback =
+    Option<unsafe extern "C" fn(timestamp: f64, message: *const c_uchar, user_data: *mut c_void)>;
+
+extern "C" {
+    #[cfg(rtmidi_version = "v4_0_0")]
+    pub fn rtmidi_get_compiled_api(apis: *mut RtMidiApi, apis_size: c_uint) -> c_int;
+    #[cfg(rtmidi_version = "v3_0_0")]
+    pub fn rtmidi_get_compiled_api(apis: *mut *mut RtMidiApi) -> c_int;
+    #[cfg(rtmidi_version = "v4_0_0")]
+    pub fn rtmidi_api_name(api: RtMidiApi) -> *const c_char;
+    #[cfg(rtmidi_version = "v4_0_0")]
+    pub fn rtmidi_api_display_name(api: RtMidiApi) -> *const c_char;
+    #[cfg(rtmidi_version = "v4_0_0")]
+    pub fn rtmidi_compiled_api_by_name(name: *const c_char) -> RtMidiApi;
+
+    pub fn rtmidi_open_port(device: RtMidiPtr, port_number: c_uint, port_name: *const c_char);
+    pub fn rtmidi_open_virtual_port(device: RtMidiPtr, port_name: *const c_char);
+    pub fn rtmidi_close_port(device: RtMidiPtr);
+    pub fn rtmidi_get_port_count(device: RtMidiPtr) -> c_uint;
+    pub fn rtmidi_get_port_name(device: RtMidiPtr, port_number: c_uint) -> *const c_char;
+
+    pub fn rtmidi_in_create_default() -> RtMidiInPtr;
+    pub fn rtmidi_in_create(
+        api: RtMidiApi,
+        client_name: *const c_char,
+        queue_size_limit: c_uint,
+    ) -> RtMidiInPtr;
+    pub fn rtmidi_in_free(device: RtMidiInPtr);
+    pub fn rtmidi_in_get_current_api(device: RtMidiPtr) -> RtMidiApi;
+    pub fn rtmidi_in_set_callback(
+        device: RtMidiInPtr,
+        callback: RtMidiCCallback,
+        user_data: *mut c_void,
+    );
+    pub fn rtmidi_in_cancel_callback(device: RtMidiInPtr);
+    pub fn rtmidi_in_ignore_types(
+        device: RtMidiInPtr,
+        midi_sysex: bool,
+        midi_time: bool,
+        midi_sense: bool,
+    );
+    #[cfg(rtmidi_version = "v4_0_0")]
+    pub fn rtmidi_in_get_message(
+        device: RtMidiInPtr,
+        message: *mut c_uchar,
+        size: *mut size_t,
+    ) -> f64;
+    #[cfg(rtmidi_version = "v3_0_0")]
+    pub fn rtmidi_in_get_message(
+        device: RtMidiInPtr,
+        message: *mut *mut c_uchar,
+        size: *mut size_t,
+    ) -> f64;
+
+    pub fn rtmidi_out_create_default() -> RtMidiOutPtr;
+    pub fn rtmidi_out_create(api: RtMidiApi, client_name: *const c_char) -> RtMidiOutPtr;
+    pub fn rtmidi_out_free(device: RtMidiOutPtr);
+    pub fn rtmidi_out_get_current_api(device: RtMidiPtr) -> RtMidiApi;
+    pub fn rtmidi_out_send_message(
+        device: RtMidiOutPtr,
+        message: *const c_uchar,
+        length: c_int,
+    ) -> c_int;
+}
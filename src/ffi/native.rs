@@ -0,0 +1,502 @@
+//! RtMidi's C API implemented on the crate's own backend for the target (the ALSA sequencer on
+//! Linux, Windows Multimedia on Windows), so `RtMidiIn` and `RtMidiOut` work without RtMidi.
+//! Each client is boxed in the wrapper's `ptr`, and errors are reported through `ok` and `msg`
+//! as RtMidi reports them.
+
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+// The types and constants, while the functions below take precedence over the declarations
+pub use super::handwritten::*;
+#[cfg(target_os = "linux")]
+use crate::alsa::AlsaBackend as Native;
+use crate::backend::{Backend, InputConnection, OutputConnection};
+use crate::error::RtMidiError;
+use crate::gate::CallbackGate;
+use crate::system::PortDirection;
+#[cfg(target_os = "windows")]
+use crate::winmm::WinMmBackend as Native;
+
+/// The API the backend stands in for, and RtMidi's name and display name for it
+#[cfg(target_os = "linux")]
+const API: RtMidiApi = RtMidiApi_RTMIDI_API_LINUX_ALSA;
+#[cfg(target_os = "linux")]
+const NAMES: (&[u8], &[u8]) = (b"alsa\0", b"ALSA\0");
+#[cfg(target_os = "windows")]
+const API: RtMidiApi = RtMidiApi_RTMIDI_API_WINDOWS_MM;
+#[cfg(target_os = "windows")]
+const NAMES: (&[u8], &[u8]) = (b"winmm\0", b"Windows MultiMedia\0");
+
+/// RtMidi's default client names
+const DEFAULT_INPUT_NAME: &[u8] = b"RtMidi Input Client\0";
+const DEFAULT_OUTPUT_NAME: &[u8] = b"RtMidi Output Client\0";
+
+/// Client behind an `RtMidiWrapper`
+struct Client {
+    backend: Option<Native>,
+    client_name: String,
+    direction: PortDirection,
+    input: Arc<Mutex<Input>>,
+    // Streams of system exclusive data, see `rtmidi_in_sysex_streams`
+    sysex_streams: Arc<AtomicUsize>,
+    input_connection: Option<Box<dyn InputConnection>>,
+    output_connection: Option<Box<dyn OutputConnection>>,
+    // Port names returned, which callers may hold on to for as long as the client exists
+    names: Vec<CString>,
+    // Message of the last error, which `msg` points to
+    error: CString,
+}
+
+impl Client {
+    fn backend(&self) -> Result<&Native, RtMidiError> {
+        self.backend
+            .as_ref()
+            .ok_or_else(|| RtMidiError::Error(self.error.to_string_lossy().into_owned()))
+    }
+
+    fn ports(&self) -> Result<Vec<String>, RtMidiError> {
+        match self.direction {
+            PortDirection::Input => self.backend()?.input_ports(),
+            PortDirection::Output => self.backend()?.output_ports(),
+        }
+    }
+
+    fn port(&self, port_number: c_uint) -> Result<String, RtMidiError> {
+        self.ports()?
+            .into_iter()
+            .nth(port_number as usize)
+            .ok_or_else(|| RtMidiError::Error(format!("Invalid port number {}", port_number)))
+    }
+
+    /// Open the port with a number, or a virtual port with [`None`]
+    fn open(&mut self, port_number: Option<c_uint>, port_name: &str) -> Result<(), RtMidiError> {
+        if self.input_connection.is_some() || self.output_connection.is_some() {
+            return Err(RtMidiError::Error("A port is already open".to_string()));
+        }
+        let port = port_number
+            .map(|port_number| self.port(port_number))
+            .transpose()?;
+        let (backend, port) = (self.backend()?, port.as_deref());
+        match self.direction {
+            PortDirection::Input => {
+                let input = Arc::clone(&self.input);
+                let connection = backend.connect_input(
+                    port,
+                    &self.client_name,
+                    port_name,
+                    Box::new(move |delta, message| receive(&input, delta, message)),
+                    Arc::clone(&self.sysex_streams),
+                )?;
+                self.input_connection = Some(connection);
+            }
+            PortDirection::Output => {
+                let connection = backend.connect_output(port, &self.client_name, port_name)?;
+                self.output_connection = Some(connection);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a name that lives as long as the client, reusing an earlier copy if there is one
+    fn keep(&mut self, name: String) -> Result<*const c_char, RtMidiError> {
+        let name = CString::new(name)?;
+        match self.names.iter().find(|kept| **kept == name) {
+            Some(kept) => Ok(kept.as_ptr()),
+            None => {
+                self.names.push(name);
+                Ok(self.names[self.names.len() - 1].as_ptr())
+            }
+        }
+    }
+}
+
+/// Where an input client's messages go, shared with the backend's input thread
+struct Input {
+    callback: Option<Callback>,
+    queue: VecDeque<(f64, Vec<u8>)>,
+    queue_size_limit: usize,
+    // Whether system exclusive, timing and active sensing messages are ignored
+    ignore: (bool, bool, bool),
+    // Time since the last message passed on, for the ignored messages since
+    skipped: f64,
+    // Inside a system exclusive message passed on in parts
+    sysex: bool,
+}
+
+/// Callback set on an input, with its user data as an address so the input can be shared
+struct Callback {
+    function: unsafe extern "C" fn(f64, *const c_uchar, size_t, *mut c_void),
+    user_data: usize,
+    // Entered for each call, so cancelling waits for a call in progress to return
+    gate: Arc<CallbackGate>,
+}
+
+/// Pass a message received to the callback, or queue it, unless it's ignored
+fn receive(input: &Mutex<Input>, delta: f64, message: &[u8]) {
+    let mut state = lock(input);
+    let continued =
+        state.sysex && matches!(message.first(), Some(&byte) if byte < 0x80 || byte == 0xF7);
+    // Real-time messages may be interleaved with the parts
+    if !matches!(message.first(), Some(0xF8..=0xFF)) {
+        state.sysex =
+            (continued || message.first() == Some(&0xF0)) && message.last() != Some(&0xF7);
+    }
+    let ignored = match message.first() {
+        _ if continued => state.ignore.0,
+        Some(0xF0) => state.ignore.0,
+        Some(0xF1) | Some(0xF8) | Some(0xF9) => state.ignore.1,
+        Some(0xFE) => state.ignore.2,
+        _ => false,
+    };
+    if ignored {
+        state.skipped += delta;
+        return;
+    }
+    let delta = delta + mem::take(&mut state.skipped);
+    match &state.callback {
+        Some(callback) => {
+            let (function, user_data) = (callback.function, callback.user_data as *mut c_void);
+            let gate = Arc::clone(&callback.gate);
+            let invocation = gate.enter();
+            // Unlocked, so the callback can be cancelled from the callback itself
+            drop(state);
+            if invocation.is_some() {
+                unsafe { function(delta, message.as_ptr(), message.len(), user_data) };
+            }
+        }
+        // Dropped once the queue is full, as RtMidi does
+        None if state.queue.len() < state.queue_size_limit => {
+            state.queue.push_back((delta, message.to_vec()))
+        }
+        None => {}
+    }
+}
+
+fn create(
+    client_name: *const c_char,
+    direction: PortDirection,
+    queue_size_limit: c_uint,
+) -> *mut RtMidiWrapper {
+    let client_name = unsafe { CStr::from_ptr(client_name) }
+        .to_string_lossy()
+        .into_owned();
+    let backend = native(&client_name);
+    let error = match &backend {
+        Ok(_) => CString::default(),
+        Err(e) => message(e),
+    };
+    let client = Box::new(Client {
+        backend: backend.ok(),
+        client_name,
+        direction,
+        input: Arc::new(Mutex::new(Input {
+            callback: None,
+            queue: VecDeque::new(),
+            queue_size_limit: queue_size_limit as usize,
+            ignore: (true, true, true),
+            skipped: 0.0,
+            sysex: false,
+        })),
+        sysex_streams: Arc::default(),
+        input_connection: None,
+        output_connection: None,
+        names: Vec::new(),
+        error,
+    });
+    Box::into_raw(Box::new(RtMidiWrapper {
+        ok: client.backend.is_some(),
+        msg: client.error.as_ptr(),
+        ptr: Box::into_raw(client) as *mut c_void,
+        data: ptr::null_mut(),
+    }))
+}
+
+#[cfg(target_os = "linux")]
+fn native(client_name: &str) -> Result<Native, RtMidiError> {
+    Native::new(client_name)
+}
+
+/// Windows Multimedia has no clients, so there's nothing to open or name
+#[cfg(target_os = "windows")]
+fn native(_client_name: &str) -> Result<Native, RtMidiError> {
+    Ok(Native::new())
+}
+
+unsafe fn free(device: RtMidiPtr) {
+    if !device.is_null() {
+        let device = Box::from_raw(device);
+        drop(Box::from_raw(device.ptr as *mut Client));
+    }
+}
+
+/// Run `f` on a device's client, reporting its error through the device (and returning
+/// `failed`) if it fails
+unsafe fn with_client<T, F>(device: RtMidiPtr, failed: T, f: F) -> T
+where
+    F: FnOnce(&mut Client) -> Result<T, RtMidiError>,
+{
+    let client = &mut *((*device).ptr as *mut Client);
+    match f(client) {
+        Ok(value) => {
+            (*device).ok = true;
+            value
+        }
+        Err(e) => {
+            client.error = message(&e);
+            (*device).ok = false;
+            (*device).msg = client.error.as_ptr();
+            failed
+        }
+    }
+}
+
+fn message(e: &RtMidiError) -> CString {
+    let message = match e {
+        RtMidiError::Error(message) => message.clone(),
+        e => format!("{:?}", e),
+    };
+    CString::new(message.replace('\0', "")).unwrap_or_default()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub unsafe fn rtmidi_get_compiled_api(apis: *mut RtMidiApi, apis_size: c_uint) -> c_int {
+    if !apis.is_null() && apis_size > 0 {
+        *apis = API;
+    }
+    1
+}
+
+pub unsafe fn rtmidi_api_name(api: RtMidiApi) -> *const c_char {
+    if api == API {
+        NAMES.0.as_ptr() as *const c_char
+    } else {
+        ptr::null()
+    }
+}
+
+pub unsafe fn rtmidi_api_display_name(api: RtMidiApi) -> *const c_char {
+    if api == API {
+        NAMES.1.as_ptr() as *const c_char
+    } else {
+        ptr::null()
+    }
+}
+
+pub unsafe fn rtmidi_compiled_api_by_name(name: *const c_char) -> RtMidiApi {
+    if CStr::from_ptr(name).to_bytes_with_nul() == NAMES.0 {
+        API
+    } else {
+        RtMidiApi_RTMIDI_API_UNSPECIFIED
+    }
+}
+
+pub unsafe fn rtmidi_open_port(device: RtMidiPtr, port_number: c_uint, port_name: *const c_char) {
+    let port_name = CStr::from_ptr(port_name).to_string_lossy();
+    with_client(device, (), |client| {
+        client.open(Some(port_number), &port_name)
+    })
+}
+
+pub unsafe fn rtmidi_open_virtual_port(device: RtMidiPtr, port_name: *const c_char) {
+    let port_name = CStr::from_ptr(port_name).to_string_lossy();
+    with_client(device, (), |client| client.open(None, &port_name))
+}
+
+pub unsafe fn rtmidi_close_port(device: RtMidiPtr) {
+    with_client(device, (), |client| {
+        client.input_connection = None;
+        client.output_connection = None;
+        Ok(())
+    })
+}
+
+pub unsafe fn rtmidi_get_port_count(device: RtMidiPtr) -> c_uint {
+    with_client(device, 0, |client| Ok(client.ports()?.len() as c_uint))
+}
+
+pub unsafe fn rtmidi_get_port_name(device: RtMidiPtr, port_number: c_uint) -> *const c_char {
+    with_client(device, ptr::null(), |client| {
+        let name = client.port(port_number)?;
+        client.keep(name)
+    })
+}
+
+pub unsafe fn rtmidi_in_create_default() -> RtMidiInPtr {
+    create(
+        DEFAULT_INPUT_NAME.as_ptr() as *const c_char,
+        PortDirection::Input,
+        100,
+    )
+}
+
+pub unsafe fn rtmidi_in_create(
+    _api: RtMidiApi,
+    client_name: *const c_char,
+    queue_size_limit: c_uint,
+) -> RtMidiInPtr {
+    create(client_name, PortDirection::Input, queue_size_limit)
+}
+
+pub unsafe fn rtmidi_in_free(device: RtMidiInPtr) {
+    free(device)
+}
+
+pub unsafe fn rtmidi_in_get_current_api(_device: RtMidiPtr) -> RtMidiApi {
+    API
+}
+
+pub unsafe fn rtmidi_in_set_callback(
+    device: RtMidiInPtr,
+    callback: RtMidiCCallback,
+    user_data: *mut c_void,
+) {
+    with_client(device, (), |client| {
+        let callback = callback.map(|function| Callback {
+            function,
+            user_data: user_data as usize,
+            gate: Arc::default(),
+        });
+        let replaced = mem::replace(&mut lock(&client.input).callback, callback);
+        if let Some(replaced) = replaced {
+            replaced.gate.close();
+        }
+        Ok(())
+    })
+}
+
+pub unsafe fn rtmidi_in_cancel_callback(device: RtMidiInPtr) {
+    with_client(device, (), |client| {
+        let cancelled = lock(&client.input).callback.take();
+        if let Some(cancelled) = cancelled {
+            cancelled.gate.close();
+        }
+        Ok(())
+    })
+}
+
+/// Not in RtMidi's API: returns the number of streams of system exclusive data (see
+/// `RtMidiIn::stream_sysex`). While there are any, the data is passed to the callback in parts as
+/// it arrives rather than as whole messages.
+pub unsafe fn rtmidi_in_sysex_streams(device: RtMidiInPtr) -> Arc<AtomicUsize> {
+    Arc::clone(&(*((*device).ptr as *const Client)).sysex_streams)
+}
+
+pub unsafe fn rtmidi_in_ignore_types(
+    device: RtMidiInPtr,
+    midi_sysex: bool,
+    midi_time: bool,
+    midi_sense: bool,
+) {
+    with_client(device, (), |client| {
+        lock(&client.input).ignore = (midi_sysex, midi_time, midi_sense);
+        Ok(())
+    })
+}
+
+/// `size` is the size of the `message` buffer on entry, and is set to the size of the message,
+/// which is only copied if it fits (and is lost otherwise), as with RtMidi 4
+pub unsafe fn rtmidi_in_get_message(
+    device: RtMidiInPtr,
+    message: *mut c_uchar,
+    size: *mut size_t,
+) -> f64 {
+    let capacity = *size;
+    *size = 0;
+    with_client(device, 0.0, |client| {
+        let (delta, data) = match lock(&client.input).queue.pop_front() {
+            Some(queued) => queued,
+            None => return Ok(0.0),
+        };
+        *size = data.len();
+        if data.len() <= capacity {
+            ptr::copy_nonoverlapping(data.as_ptr(), message, data.len());
+        }
+        Ok(delta)
+    })
+}
+
+pub unsafe fn rtmidi_out_create_default() -> RtMidiOutPtr {
+    create(
+        DEFAULT_OUTPUT_NAME.as_ptr() as *const c_char,
+        PortDirection::Output,
+        0,
+    )
+}
+
+pub unsafe fn rtmidi_out_create(_api: RtMidiApi, client_name: *const c_char) -> RtMidiOutPtr {
+    create(client_name, PortDirection::Output, 0)
+}
+
+pub unsafe fn rtmidi_out_free(device: RtMidiOutPtr) {
+    free(device)
+}
+
+pub unsafe fn rtmidi_out_get_current_api(_device: RtMidiPtr) -> RtMidiApi {
+    API
+}
+
+pub unsafe fn rtmidi_out_send_message(
+    device: RtMidiOutPtr,
+    message: *const c_uchar,
+    length: c_int,
+) -> c_int {
+    let message = slice::from_raw_parts(message, length.max(0) as usize);
+    with_client(device, -1, |client| match &client.output_connection {
+        Some(output) => output.send(message).map(|_| 0),
+        // Like RtMidi, which only warns
+        None => Ok(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use super::{receive, Input};
+
+    fn input(queue_size_limit: usize, ignore: (bool, bool, bool)) -> Mutex<Input> {
+        Mutex::new(Input {
+            callback: None,
+            queue: VecDeque::new(),
+            queue_size_limit,
+            ignore,
+            skipped: 0.0,
+            sysex: false,
+        })
+    }
+
+    #[test]
+    fn ignore_types() {
+        let input = input(2, (true, true, false));
+        receive(&input, 0.5, &[0x90, 60, 100]);
+        receive(&input, 0.25, &[0xF8]);
+        receive(&input, 0.25, &[0xF0, 0x7E, 0xF7]);
+        receive(&input, 0.5, &[0xFE]);
+        // Dropped, as the queue is full
+        receive(&input, 0.5, &[0x80, 60, 0]);
+        let queue: Vec<_> = input.lock().unwrap().queue.drain(..).collect();
+        assert_eq!(queue, [(0.5, vec![0x90, 60, 100]), (1.0, vec![0xFE])]);
+    }
+
+    #[test]
+    fn ignore_sysex_parts() {
+        let input = input(8, (true, false, false));
+        receive(&input, 0.0, &[0xF0, 0x7E, 0x7F]);
+        receive(&input, 0.0, &[0xF8]);
+        receive(&input, 0.0, &[0x06, 0x01]);
+        receive(&input, 0.0, &[0x02, 0xF7]);
+        receive(&input, 0.0, &[0x90, 60, 100]);
+        let queue: Vec<_> = input.lock().unwrap().queue.drain(..).collect();
+        assert_eq!(queue, [(0.0, vec![0xF8]), (0.0, vec![0x90, 60, 100])]);
+    }
+}
@@ -0,0 +1,161 @@
+//! Stand-ins for RtMidi's C API, used to build without RtMidi (such as on docs.rs). Creating a
+//! client always fails, so the rest are never reached in practice.
+
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_void};
+use std::ptr;
+
+// The types and constants, while the functions below take precedence over the declarations
+pub use super::handwritten::*;
+
+const UNAVAILABLE: &[u8] = b"RtMidi isn't available in this build\0";
+
+fn unavailable(device: RtMidiPtr) {
+    if !device.is_null() {
+        unsafe {
+            (*device).ok = false;
+            (*device).msg = UNAVAILABLE.as_ptr() as *const c_char;
+        }
+    }
+}
+
+fn create() -> *mut RtMidiWrapper {
+    Box::into_raw(Box::new(RtMidiWrapper {
+        ptr: ptr::null_mut(),
+        data: ptr::null_mut(),
+        ok: false,
+        msg: UNAVAILABLE.as_ptr() as *const c_char,
+    }))
+}
+
+unsafe fn free(device: RtMidiPtr) {
+    if !device.is_null() {
+        drop(Box::from_raw(device));
+    }
+}
+
+#[cfg(rtmidi_version = "v4_0_0")]
+pub unsafe fn rtmidi_get_compiled_api(_apis: *mut RtMidiApi, _apis_size: c_uint) -> c_int {
+    0
+}
+
+#[cfg(rtmidi_version = "v3_0_0")]
+pub unsafe fn rtmidi_get_compiled_api(_apis: *mut *mut RtMidiApi) -> c_int {
+    0
+}
+
+pub unsafe fn rtmidi_api_name(_api: RtMidiApi) -> *const c_char {
+    ptr::null()
+}
+
+pub unsafe fn rtmidi_api_display_name(_api: RtMidiApi) -> *const c_char {
+    ptr::null()
+}
+
+pub unsafe fn rtmidi_compiled_api_by_name(_name: *const c_char) -> RtMidiApi {
+    0
+}
+
+pub unsafe fn rtmidi_open_port(device: RtMidiPtr, _port_number: c_uint, _port_name: *const c_char) {
+    unavailable(device)
+}
+
+pub unsafe fn rtmidi_open_virtual_port(device: RtMidiPtr, _port_name: *const c_char) {
+    unavailable(device)
+}
+
+pub unsafe fn rtmidi_close_port(_device: RtMidiPtr) {}
+
+pub unsafe fn rtmidi_get_port_count(device: RtMidiPtr) -> c_uint {
+    unavailable(device);
+    0
+}
+
+pub unsafe fn rtmidi_get_port_name(device: RtMidiPtr, _port_number: c_uint) -> *const c_char {
+    unavailable(device);
+    ptr::null()
+}
+
+pub unsafe fn rtmidi_in_create_default() -> RtMidiInPtr {
+    create()
+}
+
+pub unsafe fn rtmidi_in_create(
+    _api: RtMidiApi,
+    _client_name: *const c_char,
+    _queue_size_limit: c_uint,
+) -> RtMidiInPtr {
+    create()
+}
+
+pub unsafe fn rtmidi_in_free(device: RtMidiInPtr) {
+    free(device)
+}
+
+pub unsafe fn rtmidi_in_get_current_api(_device: RtMidiPtr) -> RtMidiApi {
+    0
+}
+
+pub unsafe fn rtmidi_in_set_callback(
+    device: RtMidiInPtr,
+    _callback: RtMidiCCallback,
+    _user_data: *mut c_void,
+) {
+    unavailable(device)
+}
+
+pub unsafe fn rtmidi_in_cancel_callback(_device: RtMidiInPtr) {}
+
+pub unsafe fn rtmidi_in_ignore_types(
+    _device: RtMidiInPtr,
+    _midi_sysex: bool,
+    _midi_time: bool,
+    _midi_sense: bool,
+) {
+}
+
+#[cfg(rtmidi_version = "v4_0_0")]
+pub unsafe fn rtmidi_in_get_message(
+    device: RtMidiInPtr,
+    _message: *mut c_uchar,
+    size: *mut size_t,
+) -> f64 {
+    unavailable(device);
+    *size = 0;
+    0.0
+}
+
+#[cfg(rtmidi_version = "v3_0_0")]
+pub unsafe fn rtmidi_in_get_message(
+    device: RtMidiInPtr,
+    _message: *mut *mut c_uchar,
+    size: *mut size_t,
+) -> f64 {
+    unavailable(device);
+    *size = 0;
+    0.0
+}
+
+pub unsafe fn rtmidi_out_create_default() -> RtMidiOutPtr {
+    create()
+}
+
+pub unsafe fn rtmidi_out_create(_api: RtMidiApi, _client_name: *const c_char) -> RtMidiOutPtr {
+    create()
+}
+
+pub unsafe fn rtmidi_out_free(device: RtMidiOutPtr) {
+    free(device)
+}
+
+pub unsafe fn rtmidi_out_get_current_api(_device: RtMidiPtr) -> RtMidiApi {
+    0
+}
+
+pub unsafe fn rtmidi_out_send_message(
+    device: RtMidiOutPtr,
+    _message: *const c_uchar,
+    _length: c_int,
+) -> c_int {
+    unavailable(device);
+    -1
+}
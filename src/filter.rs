@@ -0,0 +1,107 @@
+use std::ops::RangeInclusive;
+
+/// Data Entry MSB and LSB controllers, whose values apply to the selected (N)RPN
+const DATA_ENTRY: [u8; 2] = [6, 38];
+
+/// Data Increment and Decrement controllers, whose values are commands rather than settings
+const DATA_STEP: [u8; 2] = [96, 97];
+
+/// NRPN and RPN parameter number controllers
+const PARAMETER_NUMBERS: RangeInclusive<u8> = 98..=101;
+
+/// First channel mode controller (All Sound Off)
+const CHANNEL_MODE: u8 = 120;
+
+/// Duplicate value filter
+///
+/// Recognises Control Change and Channel Pressure messages that repeat the last value received
+/// for the same controller (or channel pressure) on the same channel, which some controllers
+/// send continuously. Controllers whose repeated values are meaningful aren't filtered: Data
+/// Increment and Decrement, channel mode messages (such as All Notes Off), and Data Entry after
+/// the parameter number has changed.
+///
+/// Used by [`crate::RtMidiIn::filter_duplicates`]; can also be used directly in a callback.
+#[derive(Debug, Clone)]
+pub struct DuplicateFilter {
+    // Last value of each controller, by channel
+    controllers: [[Option<u8>; 128]; 16],
+    pressure: [Option<u8>; 16],
+}
+
+impl Default for DuplicateFilter {
+    fn default() -> Self {
+        DuplicateFilter {
+            controllers: [[None; 128]; 16],
+            pressure: [None; 16],
+        }
+    }
+}
+
+impl DuplicateFilter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record a message, returning [`true`] if it repeats the last value and should be dropped
+    pub fn is_duplicate(&mut self, message: &[u8]) -> bool {
+        let (last, value) = match *message {
+            [status, controller, value] if status & 0xF0 == 0xB0 => {
+                let controllers = &mut self.controllers[usize::from(status & 0x0F)];
+                if PARAMETER_NUMBERS.contains(&controller) {
+                    for &data in &DATA_ENTRY {
+                        controllers[usize::from(data)] = None;
+                    }
+                }
+                if DATA_STEP.contains(&controller) || controller >= CHANNEL_MODE {
+                    return false;
+                }
+                (&mut controllers[usize::from(controller & 0x7F)], value)
+            }
+            [status, value] if status & 0xF0 == 0xD0 => {
+                (&mut self.pressure[usize::from(status & 0x0F)], value)
+            }
+            _ => return false,
+        };
+        last.replace(value) == Some(value)
+    }
+
+    /// Forget the last values, so the next value of every controller is passed
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DuplicateFilter;
+
+    #[test]
+    fn duplicates() {
+        let mut filter = DuplicateFilter::new();
+        let input: &[(&[u8], bool)] = &[
+            (&[0xB0, 7, 100], false),
+            (&[0xB0, 7, 100], true),
+            (&[0xB1, 7, 100], false),
+            (&[0xB0, 7, 101], false),
+            (&[0xD0, 20], false),
+            (&[0xD0, 20], true),
+            (&[0x90, 60, 100], false),
+            (&[0x90, 60, 100], false),
+            (&[0xB0, 123, 0], false),
+            (&[0xB0, 123, 0], false),
+            (&[0xB0, 96, 1], false),
+            (&[0xB0, 96, 1], false),
+            // Data entry for two different parameters
+            (&[0xB0, 6, 2], false),
+            (&[0xB0, 6, 2], true),
+            (&[0xB0, 101, 0], false),
+            (&[0xB0, 100, 1], false),
+            (&[0xB0, 6, 2], false),
+        ];
+        for (index, &(message, duplicate)) in input.iter().enumerate() {
+            assert_eq!(filter.is_duplicate(message), duplicate, "message {}", index);
+        }
+        filter.reset();
+        assert!(!filter.is_duplicate(&[0xB0, 7, 101]));
+    }
+}
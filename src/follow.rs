@@ -0,0 +1,200 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::clock::{self, Clock};
+use crate::error::RtMidiError;
+use crate::midi_in::RtMidiIn;
+use crate::scheduler::Scheduler;
+
+/// MIDI Timing Clock messages per beat (quarter note)
+const CLOCKS_PER_BEAT: f64 = 24.0;
+
+/// Weight given to each new clock interval when estimating the tempo
+const TEMPO_SMOOTHING: f64 = 0.1;
+
+/// Fraction of the phase error corrected on each clock
+const PHASE_CORRECTION: f64 = 0.2;
+
+/// Follows an external MIDI clock
+///
+/// Locks the tempo and phase of a [`Scheduler`]'s clock to MIDI Timing Clock (`F8`) messages
+/// from an external master, so beat positions line up with the master's. Start (`FA`), Continue
+/// (`FB`), Stop (`FC`) and Song Position Pointer (`F2`) messages set the position in the song.
+///
+/// The tempo is smoothed over successive clocks to remove jitter and the phase is nudged towards
+/// the master on every clock, so drift between the two clocks is corrected without jumps.
+/// Messages can be passed to [`ClockFollower::process`] from an existing input callback, or
+/// [`ClockFollower::follow`] used to install a callback.
+/// ```
+/// use rtmidi::{ClockFollower, RtMidiError, RtMidiIn, RtMidiOut};
+///
+/// fn sync(input: &RtMidiIn, output: &RtMidiOut) -> Result<ClockFollower, RtMidiError> {
+///     let follower = ClockFollower::new(output.scheduler()?);
+///     follower.follow(input)?;
+///     Ok(follower)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ClockFollower {
+    scheduler: Scheduler,
+    state: Arc<Mutex<State>>,
+}
+
+impl ClockFollower {
+    /// Create a follower that sets the clock of the given scheduler
+    pub fn new(scheduler: Scheduler) -> Self {
+        ClockFollower {
+            scheduler,
+            state: Default::default(),
+        }
+    }
+
+    /// Process a message received now. Messages other than clock and song position messages are
+    /// ignored.
+    pub fn process(&self, message: &[u8]) {
+        self.process_at(Instant::now(), message)
+    }
+
+    /// Process a message received at the given instant
+    pub fn process_at(&self, at: Instant, message: &[u8]) {
+        if let Some(clock) = self.lock().process(at, message) {
+            self.scheduler.set_clock(clock);
+        }
+    }
+
+    /// Returns [`true`] if the master is playing (after Start or Continue, until Stop)
+    pub fn is_running(&self) -> bool {
+        self.lock().running
+    }
+
+    /// Follow the clock received on an input.
+    ///
+    /// Replaces the input's callback and stops the input ignoring timing messages (System
+    /// Exclusive and Active Sensing messages are ignored).
+    pub fn follow(&self, input: &RtMidiIn) -> Result<(), RtMidiError> {
+        let follower = self.clone();
+        input.set_callback(move |_timestamp, message| follower.process(message))?;
+        input.ignore_types(true, false, true)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    running: bool,
+    // Song position of the next clock, in clocks
+    position: u64,
+    // The position was set, so the phase is snapped rather than corrected on the next clock
+    relocated: bool,
+    last: Option<Instant>,
+    // Smoothed interval between clocks in seconds
+    interval: Option<f64>,
+    clock: Option<Clock>,
+}
+
+impl State {
+    /// Process a message, returning an updated clock
+    fn process(&mut self, at: Instant, message: &[u8]) -> Option<Clock> {
+        match *message {
+            [0xF8, ..] => return self.tick(at),
+            [0xFA, ..] => {
+                self.running = true;
+                self.position = 0;
+                self.relocated = true;
+            }
+            [0xFB, ..] => {
+                self.running = true;
+                self.relocated = true;
+            }
+            [0xFC, ..] => self.running = false,
+            [0xF2, lsb, msb] => {
+                // Song position is counted in sixteenth notes, each six clocks
+                self.position = (u64::from(msb & 0x7F) << 7 | u64::from(lsb & 0x7F)) * 6;
+                self.relocated = true;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn tick(&mut self, at: Instant) -> Option<Clock> {
+        // The tempo is tracked while stopped, as masters usually send clock continuously
+        if let Some(last) = self.last {
+            let interval = at.saturating_duration_since(last).as_secs_f64();
+            self.interval = Some(match self.interval {
+                Some(smoothed) => smoothed + (interval - smoothed) * TEMPO_SMOOTHING,
+                None => interval,
+            });
+        }
+        self.last = Some(at);
+        if !self.running {
+            return None;
+        }
+        let beat = self.position as f64 / CLOCKS_PER_BEAT;
+        self.position += 1;
+        let interval = match self.interval {
+            Some(interval) if interval > 0.0 => interval,
+            _ => return None,
+        };
+        let tempo = 60.0 / (interval * CLOCKS_PER_BEAT);
+        let beat = match self.clock {
+            Some(clock) if !self.relocated => {
+                let predicted = clock.beat_at(at);
+                if (beat - predicted).abs() > 1.0 / CLOCKS_PER_BEAT {
+                    beat
+                } else {
+                    predicted + (beat - predicted) * PHASE_CORRECTION
+                }
+            }
+            _ => beat,
+        };
+        self.relocated = false;
+        let clock = Clock::new(clock::offset(at, -beat * 60.0 / tempo), tempo);
+        self.clock = Some(clock);
+        Some(clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::State;
+
+    /// Clock interval at 120 BPM
+    const INTERVAL: Duration = Duration::from_micros(500_000 / 24);
+
+    #[test]
+    fn tempo_and_phase() {
+        let mut state = State::default();
+        let start = Instant::now();
+        assert_eq!(state.process(start, &[0xF8]), None);
+        assert_eq!(state.process(start, &[0xFA]), None);
+        let mut clock = None;
+        for tick in 1..=48 {
+            clock = state.process(start + INTERVAL * tick, &[0xF8]);
+        }
+        let clock = clock.unwrap();
+        assert!((clock.tempo() - 120.0).abs() < 0.01);
+        // The first clock after Start is beat zero
+        assert!((clock.beat_at(start + INTERVAL) - 0.0).abs() < 0.001);
+        assert!((clock.beat_at(start + INTERVAL * 49) - 2.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn stop_and_song_position() {
+        let mut state = State::default();
+        let start = Instant::now();
+        state.process(start, &[0xF8]);
+        state.process(start, &[0xF2, 8, 0]);
+        state.process(start, &[0xFB]);
+        let clock = state.process(start + INTERVAL, &[0xF8]).unwrap();
+        // Eight sixteenth notes in
+        assert!((clock.beat_at(start + INTERVAL) - 2.0).abs() < 0.001);
+        state.process(start, &[0xFC]);
+        assert_eq!(state.process(start + INTERVAL * 2, &[0xF8]), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, ThreadId};
+
+/// Guards a callback invoked from another thread, so it can be shut off safely.
+///
+/// Each invocation runs inside [`CallbackGate::enter`]. Once [`CallbackGate::close`] returns, no
+/// invocation is running and none will start, so whatever the callback uses can be released.
+#[derive(Default)]
+pub struct CallbackGate {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    closed: bool,
+    // Threads inside an invocation
+    running: Vec<ThreadId>,
+}
+
+impl CallbackGate {
+    /// Start an invocation, which lasts until the returned guard is dropped. Returns [`None`] once
+    /// the gate is closed.
+    pub fn enter(&self) -> Option<Invocation<'_>> {
+        let mut state = self.lock();
+        if state.closed {
+            return None;
+        }
+        state.running.push(thread::current().id());
+        Some(Invocation(self))
+    }
+
+    /// Stop further invocations, waiting for any that are running to finish.
+    ///
+    /// An invocation on the calling thread (the callback closing its own gate) isn't waited for,
+    /// as it can't finish until this returns. Returns [`false`] if there is one, as the callback
+    /// is then still running.
+    pub fn close(&self) -> bool {
+        let current = thread::current().id();
+        let mut state = self.lock();
+        state.closed = true;
+        while state.running.iter().any(|&id| id != current) {
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        state.running.is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A running invocation, see [`CallbackGate::enter`]
+pub struct Invocation<'a>(&'a CallbackGate);
+
+impl Drop for Invocation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        let current = thread::current().id();
+        if let Some(index) = state.running.iter().position(|&id| id == current) {
+            state.running.swap_remove(index);
+        }
+        self.0.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::CallbackGate;
+
+    #[test]
+    fn close_waits() {
+        for _ in 0..200 {
+            let gate = Arc::new(CallbackGate::default());
+            let running = Arc::new(AtomicBool::new(false));
+            let closed = Arc::new(AtomicBool::new(false));
+            let invoker = {
+                let (gate, running, closed) = (gate.clone(), running.clone(), closed.clone());
+                thread::spawn(move || {
+                    while let Some(_invocation) = gate.enter() {
+                        running.store(true, Ordering::SeqCst);
+                        assert!(!closed.load(Ordering::SeqCst));
+                        thread::sleep(Duration::from_micros(50));
+                        running.store(false, Ordering::SeqCst);
+                    }
+                })
+            };
+            thread::sleep(Duration::from_micros(100));
+            assert!(gate.close());
+            closed.store(true, Ordering::SeqCst);
+            assert!(!running.load(Ordering::SeqCst));
+            invoker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn close_from_invocation() {
+        let gate = CallbackGate::default();
+        let invocation = gate.enter().unwrap();
+        assert!(!gate.close());
+        drop(invocation);
+        assert!(gate.enter().is_none());
+    }
+}
@@ -0,0 +1,299 @@
+/// Novation manufacturer ID, and the Launchpad product ID
+const NOVATION_ID: [u8; 3] = [0x00, 0x20, 0x29];
+const LAUNCHPAD: u8 = 0x02;
+/// Launchpad X and Launchpad Mini MK3 device IDs
+const LAUNCHPAD_X: u8 = 0x0C;
+const LAUNCHPAD_MINI_MK3: u8 = 0x0D;
+/// Launchpad LED lighting command, its RGB lighting type, and the layout select command
+const LED_LIGHTING: u8 = 0x03;
+const RGB: u8 = 0x03;
+const LAYOUT: u8 = 0x00;
+/// Programmer layout, where pads are notes 11 to 88
+const PROGRAMMER_LAYOUT: u8 = 0x7F;
+/// Most pads that fit in one Launchpad LED lighting message
+const MAX_PADS: usize = 81;
+
+/// Colour of a pad, with 8 bits per component
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PadColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl PadColor {
+    pub const OFF: PadColor = PadColor::new(0, 0, 0);
+    pub const RED: PadColor = PadColor::new(255, 0, 0);
+    pub const GREEN: PadColor = PadColor::new(0, 255, 0);
+    pub const BLUE: PadColor = PadColor::new(0, 0, 255);
+    pub const YELLOW: PadColor = PadColor::new(255, 255, 0);
+    pub const WHITE: PadColor = PadColor::new(255, 255, 255);
+
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        PadColor { red, green, blue }
+    }
+
+    fn distance(&self, other: &PadColor) -> u32 {
+        let component = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+        component(self.red, other.red)
+            + component(self.green, other.green)
+            + component(self.blue, other.blue)
+    }
+}
+
+/// Where a grid's pads are: the notes of each row follow on from the note at its left, and
+/// rows are `row_stride` notes apart. Row 0 is the bottom row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GridLayout {
+    pub columns: u8,
+    pub rows: u8,
+    /// Note of the bottom left pad
+    pub origin: u8,
+    pub row_stride: u8,
+    /// Channel (0-15) the pads' notes are sent and received on
+    pub channel: u8,
+}
+
+/// How pad colours are sent
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Colors {
+    /// Note On velocities picking from the device's colour palette
+    Palette(Vec<(PadColor, u8)>),
+    /// Launchpad RGB lighting system exclusive messages, for a device ID
+    Launchpad(u8),
+}
+
+/// A pad grid controller, addressed by pad coordinates
+///
+/// Translates between pads, numbered from the bottom left, and the notes a device uses for
+/// them, and builds the messages that light pads, so step sequencers and other grid interfaces
+/// don't need to know each device's layout. Devices with RGB pads get the exact colour, others
+/// the nearest in their palette.
+/// ```
+/// use rtmidi::{Grid, PadColor};
+///
+/// let grid = Grid::launchpad_x();
+/// assert_eq!(grid.size(), (8, 8));
+/// assert_eq!(grid.note(0, 0), Some(11));
+/// assert_eq!(grid.pad(&[0x90, 88, 100]), Some((7, 7, 100)));
+/// assert_eq!(
+///     grid.light(2, 0, PadColor::RED),
+///     [0xF0, 0x00, 0x20, 0x29, 0x02, 0x0C, 0x03, 0x03, 13, 127, 0, 0, 0xF7]
+/// );
+///
+/// let grid = Grid::apc_mini();
+/// assert_eq!(grid.light(0, 7, PadColor::YELLOW), [0x90, 56, 5]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid {
+    layout: GridLayout,
+    colors: Colors,
+    init: Vec<Vec<u8>>,
+}
+
+impl Grid {
+    /// A grid lit with Note On messages, whose velocities pick from a palette of colours
+    pub fn new(layout: GridLayout, palette: &[(PadColor, u8)]) -> Self {
+        Grid {
+            layout,
+            colors: Colors::Palette(palette.to_vec()),
+            init: Vec::new(),
+        }
+    }
+
+    /// Novation Launchpad X, in programmer mode
+    pub fn launchpad_x() -> Self {
+        Grid::launchpad(LAUNCHPAD_X)
+    }
+
+    /// Novation Launchpad Mini MK3, in programmer mode
+    pub fn launchpad_mini_mk3() -> Self {
+        Grid::launchpad(LAUNCHPAD_MINI_MK3)
+    }
+
+    /// Akai APC Mini (the original, with green, red and yellow pads)
+    pub fn apc_mini() -> Self {
+        let layout = GridLayout {
+            columns: 8,
+            rows: 8,
+            origin: 0,
+            row_stride: 8,
+            channel: 0,
+        };
+        Grid::new(
+            layout,
+            &[
+                (PadColor::OFF, 0),
+                (PadColor::GREEN, 1),
+                (PadColor::RED, 3),
+                (PadColor::YELLOW, 5),
+            ],
+        )
+    }
+
+    fn launchpad(device_id: u8) -> Self {
+        let layout = GridLayout {
+            columns: 8,
+            rows: 8,
+            origin: 11,
+            row_stride: 10,
+            channel: 0,
+        };
+        Grid {
+            layout,
+            colors: Colors::Launchpad(device_id),
+            init: vec![launchpad_message(device_id, &[LAYOUT, PROGRAMMER_LAYOUT])],
+        }
+    }
+
+    /// Returns the number of columns and rows
+    pub fn size(&self) -> (u8, u8) {
+        (self.layout.columns, self.layout.rows)
+    }
+
+    /// Returns the messages that put the device in the mode the grid expects, to send once the
+    /// port is open
+    pub fn init_messages(&self) -> &[Vec<u8>] {
+        &self.init
+    }
+
+    /// Returns the note of a pad, or [`None`] if it's outside the grid
+    pub fn note(&self, x: u8, y: u8) -> Option<u8> {
+        let layout = &self.layout;
+        if x >= layout.columns || y >= layout.rows {
+            return None;
+        }
+        let note =
+            u16::from(layout.origin) + u16::from(y) * u16::from(layout.row_stride) + u16::from(x);
+        if note < 0x80 {
+            Some(note as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pad and velocity of a Note On or Note Off message from one of the grid's
+    /// pads, with a velocity of 0 for Note Off
+    pub fn pad(&self, message: &[u8]) -> Option<(u8, u8, u8)> {
+        let (note, velocity) = match *message {
+            [status, note, velocity] if status == 0x90 | self.layout.channel => (note, velocity),
+            [status, note, _] if status == 0x80 | self.layout.channel => (note, 0),
+            _ => return None,
+        };
+        let offset = note.checked_sub(self.layout.origin)?;
+        let stride = self.layout.row_stride.max(1);
+        let (x, y) = (offset % stride, offset / stride);
+        if self.note(x, y) == Some(note) {
+            Some((x, y, velocity))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the message that lights a pad, which is empty if the pad is outside the grid
+    pub fn light(&self, x: u8, y: u8, color: PadColor) -> Vec<u8> {
+        self.light_pads(&[(x, y, color)])
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// Returns the messages that light several pads, combined into as few as the device allows.
+    /// Pads outside the grid are skipped.
+    pub fn light_pads(&self, pads: &[(u8, u8, PadColor)]) -> Vec<Vec<u8>> {
+        let notes = pads
+            .iter()
+            .filter_map(|&(x, y, color)| Some((self.note(x, y)?, color)));
+        match self.colors {
+            Colors::Palette(ref palette) => notes
+                .map(|(note, color)| {
+                    let velocity = palette
+                        .iter()
+                        .min_by_key(|(entry, _)| entry.distance(&color))
+                        .map_or(0, |&(_, velocity)| velocity);
+                    vec![0x90 | self.layout.channel, note, velocity & 0x7F]
+                })
+                .collect(),
+            Colors::Launchpad(device_id) => {
+                let notes = notes.collect::<Vec<_>>();
+                notes
+                    .chunks(MAX_PADS)
+                    .map(|notes| {
+                        let mut data = vec![LED_LIGHTING];
+                        for (note, color) in notes {
+                            data.extend_from_slice(&[
+                                RGB,
+                                *note,
+                                color.red >> 1,
+                                color.green >> 1,
+                                color.blue >> 1,
+                            ]);
+                        }
+                        launchpad_message(device_id, &data)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn launchpad_message(device_id: u8, data: &[u8]) -> Vec<u8> {
+    let mut message = vec![0xF0];
+    message.extend_from_slice(&NOVATION_ID);
+    message.extend_from_slice(&[LAUNCHPAD, device_id]);
+    message.extend_from_slice(data);
+    message.push(0xF7);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, GridLayout, PadColor};
+
+    #[test]
+    fn layout() {
+        let grid = Grid::launchpad_mini_mk3();
+        assert_eq!(grid.note(7, 0), Some(18));
+        assert_eq!(grid.note(0, 1), Some(21));
+        assert_eq!(grid.note(8, 0), None);
+        assert_eq!(grid.pad(&[0x90, 19, 0]), None);
+        assert_eq!(grid.pad(&[0x80, 34, 64]), Some((3, 2, 0)));
+        assert_eq!(grid.pad(&[0x91, 34, 64]), None);
+        assert_eq!(grid.pad(&[0x90, 5, 64]), None);
+        assert_eq!(
+            grid.init_messages(),
+            [vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x0D, 0x00, 0x7F, 0xF7]]
+        );
+    }
+
+    #[test]
+    fn palette() {
+        let layout = GridLayout {
+            columns: 4,
+            rows: 4,
+            origin: 36,
+            row_stride: 4,
+            channel: 9,
+        };
+        let grid = Grid::new(
+            layout,
+            &[(PadColor::OFF, 0), (PadColor::new(0, 0, 200), 45)],
+        );
+        assert_eq!(grid.light(3, 3, PadColor::BLUE), [0x99, 51, 45]);
+        assert_eq!(grid.light(0, 0, PadColor::new(10, 10, 10)), [0x99, 36, 0]);
+        assert!(grid.light(4, 0, PadColor::BLUE).is_empty());
+        assert_eq!(grid.pad(&[0x99, 41, 100]), Some((1, 1, 100)));
+    }
+
+    #[test]
+    fn launchpad_batch() {
+        let grid = Grid::launchpad_x();
+        let pads = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y, PadColor::GREEN)))
+            .collect::<Vec<_>>();
+        let messages = grid.light_pads(&pads);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].len(), 8 + 64 * 5);
+        assert_eq!(messages[0][7..12], [0x03, 11, 0, 127, 0]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+/// Whether a message was received or sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageDirection {
+    Input,
+    Output,
+}
+
+/// A message kept for debugging, returned by [`crate::RtMidiIn::recent`] and
+/// [`crate::RtMidiOut::recent`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentMessage {
+    /// When the message was received or sent
+    pub time: Instant,
+    pub direction: MessageDirection,
+    pub message: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Ring {
+    capacity: usize,
+    messages: VecDeque<RecentMessage>,
+}
+
+/// Shared ring buffer of the most recent messages, disabled (with a capacity of zero) by default
+#[derive(Clone, Default)]
+pub struct History(Arc<Mutex<Ring>>);
+
+impl History {
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut ring = self.lock();
+        ring.capacity = capacity;
+        while ring.messages.len() > capacity {
+            ring.messages.pop_front();
+        }
+    }
+
+    pub fn record(&self, direction: MessageDirection, message: &[u8]) {
+        let mut ring = self.lock();
+        if ring.capacity == 0 {
+            return;
+        }
+        if ring.messages.len() == ring.capacity {
+            ring.messages.pop_front();
+        }
+        ring.messages.push_back(RecentMessage {
+            time: Instant::now(),
+            direction,
+            message: message.to_vec(),
+        });
+    }
+
+    pub fn recent(&self) -> Vec<RecentMessage> {
+        self.lock().messages.iter().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ring> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, MessageDirection};
+
+    #[test]
+    fn record() {
+        let history = History::default();
+        history.record(MessageDirection::Input, &[0xF8]);
+        assert!(history.recent().is_empty());
+        history.set_capacity(2);
+        for note in 60..63 {
+            history.record(MessageDirection::Output, &[0x90, note, 100]);
+        }
+        let recent = history.recent();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].message, vec![0x90, 61, 100]);
+        assert_eq!(recent[1].direction, MessageDirection::Output);
+        history.set_capacity(1);
+        assert_eq!(history.recent()[0].message, vec![0x90, 62, 100]);
+    }
+}
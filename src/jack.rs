@@ -0,0 +1,414 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_ulong, c_void};
+use std::time::Instant;
+
+use crate::error::RtMidiError;
+use crate::midi::TimedOutput;
+
+/// Don't start the JACK server if it isn't running
+const JACK_NO_START_SERVER: c_int = 0x01;
+
+/// Client name used for queries
+const CLIENT_NAME: &str = "rtmidi-rs";
+
+/// Type name pattern matching JACK MIDI ports
+const MIDI_TYPE: &str = "midi";
+
+/// Error returned by `jack_connect` for ports that are already connected (the same on Linux and
+/// macOS)
+const EEXIST: c_int = 17;
+
+/// Type of JACK MIDI ports
+const JACK_DEFAULT_MIDI_TYPE: &str = "8 bit raw midi";
+
+/// Size of the ring buffer passing scheduled messages to the process callback, in bytes
+const RING_SIZE: usize = 64 * 1024;
+/// Size of a scheduled message's header in the ring buffer: its frame time (u32) and length
+/// (u32), both native-endian
+const EVENT_HEADER_SIZE: usize = 8;
+
+type JackProcessCallback = extern "C" fn(nframes: u32, arg: *mut c_void) -> c_int;
+
+/// Port flags
+const JACK_PORT_IS_INPUT: c_ulong = 0x1;
+const JACK_PORT_IS_OUTPUT: c_ulong = 0x2;
+const JACK_PORT_IS_PHYSICAL: c_int = 0x4;
+
+extern "C" {
+    fn jack_client_open(
+        client_name: *const c_char,
+        options: c_int,
+        status: *mut c_int,
+        ...
+    ) -> *mut c_void;
+    fn jack_client_close(client: *mut c_void) -> c_int;
+    fn jack_port_by_name(client: *mut c_void, port_name: *const c_char) -> *mut c_void;
+    fn jack_port_get_aliases(port: *const c_void, aliases: *const *mut c_char) -> c_int;
+    fn jack_port_name_size() -> c_int;
+    fn jack_get_ports(
+        client: *mut c_void,
+        port_name_pattern: *const c_char,
+        type_name_pattern: *const c_char,
+        flags: c_ulong,
+    ) -> *mut *const c_char;
+    fn jack_port_get_all_connections(
+        client: *const c_void,
+        port: *const c_void,
+    ) -> *mut *const c_char;
+    fn jack_connect(
+        client: *mut c_void,
+        source_port: *const c_char,
+        destination_port: *const c_char,
+    ) -> c_int;
+    fn jack_disconnect(
+        client: *mut c_void,
+        source_port: *const c_char,
+        destination_port: *const c_char,
+    ) -> c_int;
+    fn jack_port_flags(port: *const c_void) -> c_int;
+    fn jack_free(ptr: *mut c_void);
+    fn jack_port_register(
+        client: *mut c_void,
+        port_name: *const c_char,
+        port_type: *const c_char,
+        flags: c_ulong,
+        buffer_size: c_ulong,
+    ) -> *mut c_void;
+    fn jack_port_name(port: *const c_void) -> *const c_char;
+    fn jack_set_process_callback(
+        client: *mut c_void,
+        callback: JackProcessCallback,
+        arg: *mut c_void,
+    ) -> c_int;
+    fn jack_activate(client: *mut c_void) -> c_int;
+    fn jack_deactivate(client: *mut c_void) -> c_int;
+    fn jack_get_sample_rate(client: *mut c_void) -> u32;
+    fn jack_frame_time(client: *const c_void) -> u32;
+    fn jack_last_frame_time(client: *const c_void) -> u32;
+    fn jack_port_get_buffer(port: *mut c_void, nframes: u32) -> *mut c_void;
+    fn jack_midi_clear_buffer(buffer: *mut c_void);
+    fn jack_midi_event_reserve(buffer: *mut c_void, time: u32, size: usize) -> *mut u8;
+    fn jack_ringbuffer_create(size: usize) -> *mut c_void;
+    fn jack_ringbuffer_free(ring: *mut c_void);
+    fn jack_ringbuffer_read_space(ring: *const c_void) -> usize;
+    fn jack_ringbuffer_write_space(ring: *const c_void) -> usize;
+    fn jack_ringbuffer_peek(ring: *mut c_void, dest: *mut c_char, count: usize) -> usize;
+    fn jack_ringbuffer_read(ring: *mut c_void, dest: *mut c_char, count: usize) -> usize;
+    fn jack_ringbuffer_read_advance(ring: *mut c_void, count: usize);
+    fn jack_ringbuffer_write(ring: *mut c_void, src: *const c_char, count: usize) -> usize;
+}
+
+/// JACK port direction, from the port's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JackPortDirection {
+    /// Ports that receive data (e.g. "system:midi_playback_1")
+    Input,
+    /// Ports that send data (e.g. "system:midi_capture_1")
+    Output,
+}
+
+/// JACK connection management
+///
+/// Opens its own connection to a running JACK server (it is never started) to list, connect and
+/// disconnect MIDI ports, so a saved patch layout can be restored without `jack_connect`. Ports
+/// are identified by their full name, e.g. "system:midi_capture_1". Ports opened by this crate
+/// are named after the client and port names given when creating and opening them, e.g.
+/// "RtMidi Input Client:My Input".
+/// ```no_run
+/// use rtmidi::{JackClient, JackPortDirection, RtMidiError};
+///
+/// fn connect_all(destination: &str) -> Result<(), RtMidiError> {
+///     let jack = JackClient::new()?;
+///     for port in jack.ports("system:", JackPortDirection::Output)? {
+///         if !jack.connections(&port)?.iter().any(|connected| connected == destination) {
+///             jack.connect(&port, destination)?;
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct JackClient(*mut c_void);
+
+impl JackClient {
+    /// Connect to the JACK server, returning an error if it isn't running
+    pub fn new() -> Result<Self, RtMidiError> {
+        JackClient::open(CLIENT_NAME)
+    }
+
+    fn open(client_name: &str) -> Result<Self, RtMidiError> {
+        let name = CString::new(client_name)?;
+        let mut status = 0;
+        let client = unsafe { jack_client_open(name.as_ptr(), JACK_NO_START_SERVER, &mut status) };
+        if client.is_null() {
+            Err(RtMidiError::Error(format!(
+                "Unable to connect to the JACK server (status {:#x})",
+                status
+            )))
+        } else {
+            Ok(JackClient(client))
+        }
+    }
+
+    /// Returns the full names of MIDI ports whose names match a regular expression (an empty
+    /// pattern matches all ports)
+    pub fn ports(
+        &self,
+        pattern: &str,
+        direction: JackPortDirection,
+    ) -> Result<Vec<String>, RtMidiError> {
+        let pattern = CString::new(pattern)?;
+        let midi = CString::new(MIDI_TYPE)?;
+        let flags = match direction {
+            JackPortDirection::Input => JACK_PORT_IS_INPUT,
+            JackPortDirection::Output => JACK_PORT_IS_OUTPUT,
+        };
+        names(unsafe { jack_get_ports(self.0, pattern.as_ptr(), midi.as_ptr(), flags) })
+    }
+
+    /// Returns the full names of the ports connected to a port
+    pub fn connections(&self, port_name: &str) -> Result<Vec<String>, RtMidiError> {
+        let port = self.port(port_name)?;
+        names(unsafe { jack_port_get_all_connections(self.0, port) })
+    }
+
+    /// Returns [`true`] if a port belongs to a physical device
+    pub fn is_physical(&self, port_name: &str) -> Result<bool, RtMidiError> {
+        let port = self.port(port_name)?;
+        Ok(unsafe { jack_port_flags(port) } & JACK_PORT_IS_PHYSICAL != 0)
+    }
+
+    /// Connect an output port to an input port
+    pub fn connect(&self, source: &str, destination: &str) -> Result<(), RtMidiError> {
+        let (source_name, destination_name) = (CString::new(source)?, CString::new(destination)?);
+        match unsafe { jack_connect(self.0, source_name.as_ptr(), destination_name.as_ptr()) } {
+            // Already connected
+            0 | EEXIST => Ok(()),
+            code => Err(RtMidiError::Error(format!(
+                "Unable to connect JACK port '{}' to '{}' (error {})",
+                source, destination, code
+            ))),
+        }
+    }
+
+    /// Disconnect an output port from an input port
+    pub fn disconnect(&self, source: &str, destination: &str) -> Result<(), RtMidiError> {
+        let (source_name, destination_name) = (CString::new(source)?, CString::new(destination)?);
+        match unsafe { jack_disconnect(self.0, source_name.as_ptr(), destination_name.as_ptr()) } {
+            0 => Ok(()),
+            code => Err(RtMidiError::Error(format!(
+                "Unable to disconnect JACK port '{}' from '{}' (error {})",
+                source, destination, code
+            ))),
+        }
+    }
+
+    fn port(&self, port_name: &str) -> Result<*mut c_void, RtMidiError> {
+        let name = CString::new(port_name)?;
+        let port = unsafe { jack_port_by_name(self.0, name.as_ptr()) };
+        if port.is_null() {
+            Err(RtMidiError::Error(format!(
+                "JACK port '{}' not found",
+                port_name
+            )))
+        } else {
+            Ok(port)
+        }
+    }
+
+    /// Returns the aliases of a port, given its full name (e.g. "system:midi_capture_1")
+    pub fn port_aliases(&self, port_name: &str) -> Result<Vec<String>, RtMidiError> {
+        let port = self.port(port_name)?;
+        // A port has at most two aliases, each up to the maximum port name size
+        let size = unsafe { jack_port_name_size() }.max(1) as usize;
+        let mut buffers = [vec![0 as c_char; size], vec![0 as c_char; size]];
+        let aliases = [buffers[0].as_mut_ptr(), buffers[1].as_mut_ptr()];
+        let count = unsafe { jack_port_get_aliases(port, aliases.as_ptr()) };
+        buffers
+            .iter()
+            .take(count.max(0) as usize)
+            .map(|buffer| {
+                let alias = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+                Ok(alias.to_str()?.to_string())
+            })
+            .collect()
+    }
+}
+
+impl Drop for JackClient {
+    fn drop(&mut self) {
+        unsafe { jack_client_close(self.0) };
+    }
+}
+
+/// A JACK client of our own, connected to the port an RtMidi output is connected to, whose
+/// process callback writes scheduled messages at their frame within the period (which RtMidi
+/// can't), so they line up with the audio of other JACK clients
+pub(crate) struct JackOutput {
+    client: JackClient,
+    // Borrowed by the process callback until the client is deactivated
+    state: Box<ProcessState>,
+    sample_rate: u32,
+}
+
+// The ring buffer has a single writer (the owner of the output) and reader (the process callback)
+unsafe impl Send for JackOutput {}
+
+/// State passed to the process callback
+struct ProcessState {
+    client: *mut c_void,
+    port: *mut c_void,
+    ring: *mut c_void,
+}
+
+impl JackOutput {
+    /// Create a client sending to the port with the given full name (RtMidi's port name)
+    pub fn new(client_name: &str, port_name: &str) -> Result<Self, RtMidiError> {
+        let client = JackClient::open(client_name)?;
+        client.port(port_name)?;
+        let (name, kind) = (CString::new("out")?, CString::new(JACK_DEFAULT_MIDI_TYPE)?);
+        let port = unsafe {
+            jack_port_register(
+                client.0,
+                name.as_ptr(),
+                kind.as_ptr(),
+                JACK_PORT_IS_OUTPUT,
+                0,
+            )
+        };
+        let ring = unsafe { jack_ringbuffer_create(RING_SIZE) };
+        if port.is_null() || ring.is_null() {
+            if !ring.is_null() {
+                unsafe { jack_ringbuffer_free(ring) };
+            }
+            return Err(RtMidiError::Error(
+                "Unable to create JACK output port".to_string(),
+            ));
+        }
+        let output = JackOutput {
+            sample_rate: unsafe { jack_get_sample_rate(client.0) },
+            state: Box::new(ProcessState {
+                client: client.0,
+                port,
+                ring,
+            }),
+            client,
+        };
+        let arg = &*output.state as *const ProcessState as *mut c_void;
+        if unsafe { jack_set_process_callback(output.client.0, process, arg) } != 0
+            || unsafe { jack_activate(output.client.0) } != 0
+        {
+            return Err(RtMidiError::Error(
+                "Unable to activate JACK client".to_string(),
+            ));
+        }
+        let own = unsafe { CStr::from_ptr(jack_port_name(port)) }.to_str()?;
+        output.client.connect(own, port_name)?;
+        Ok(output)
+    }
+}
+
+impl TimedOutput for JackOutput {
+    fn send_at(&mut self, at: Instant, message: &[u8]) -> Result<(), RtMidiError> {
+        let ring = self.state.ring;
+        if unsafe { jack_ringbuffer_write_space(ring) } < EVENT_HEADER_SIZE + message.len() {
+            return Err(RtMidiError::WouldBlock);
+        }
+        let delay = at.saturating_duration_since(Instant::now()).as_secs_f64();
+        let frames = (delay * f64::from(self.sample_rate)).round() as u32;
+        let time = unsafe { jack_frame_time(self.client.0) }.wrapping_add(frames);
+        // Written in one go, so the process callback never sees a header without its message
+        let mut event = Vec::with_capacity(EVENT_HEADER_SIZE + message.len());
+        event.extend_from_slice(&time.to_ne_bytes());
+        event.extend_from_slice(&(message.len() as u32).to_ne_bytes());
+        event.extend_from_slice(message);
+        unsafe { jack_ringbuffer_write(ring, event.as_ptr() as *const c_char, event.len()) };
+        Ok(())
+    }
+}
+
+impl Drop for JackOutput {
+    fn drop(&mut self) {
+        // The process callback has returned for good once the client is deactivated
+        unsafe {
+            jack_deactivate(self.client.0);
+            jack_ringbuffer_free(self.state.ring);
+        }
+    }
+}
+
+/// Write the messages due in this period to the port, at their frame within it. Messages that
+/// are late are written at the start of the period.
+extern "C" fn process(nframes: u32, arg: *mut c_void) -> c_int {
+    let state = unsafe { &*(arg as *const ProcessState) };
+    let buffer = unsafe { jack_port_get_buffer(state.port, nframes) };
+    unsafe { jack_midi_clear_buffer(buffer) };
+    let start = unsafe { jack_last_frame_time(state.client) };
+    let mut offset = 0;
+    let mut header = [0u8; EVENT_HEADER_SIZE];
+    while unsafe { jack_ringbuffer_read_space(state.ring) } >= EVENT_HEADER_SIZE {
+        unsafe {
+            jack_ringbuffer_peek(
+                state.ring,
+                header.as_mut_ptr() as *mut c_char,
+                EVENT_HEADER_SIZE,
+            )
+        };
+        let time = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]);
+        let length = u32::from_ne_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if unsafe { jack_ringbuffer_read_space(state.ring) } < EVENT_HEADER_SIZE + length {
+            break;
+        }
+        // Frames wrap around, so late messages are a "negative" distance from the start
+        let frame = time.wrapping_sub(start) as i32;
+        if frame >= nframes as i32 {
+            break;
+        }
+        // Events must be in order within the period
+        offset = offset.max(frame.max(0) as u32);
+        unsafe {
+            jack_ringbuffer_read_advance(state.ring, EVENT_HEADER_SIZE);
+            let data = jack_midi_event_reserve(buffer, offset, length);
+            if data.is_null() {
+                // The port's buffer is full, so the message is dropped
+                jack_ringbuffer_read_advance(state.ring, length);
+            } else {
+                jack_ringbuffer_read(state.ring, data as *mut c_char, length);
+            }
+        }
+    }
+    0
+}
+
+/// Copy and free a null-terminated array of port names returned by JACK
+fn names(array: *mut *const c_char) -> Result<Vec<String>, RtMidiError> {
+    if array.is_null() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    let mut result = Ok(());
+    for i in 0.. {
+        let name = unsafe { *array.add(i) };
+        if name.is_null() {
+            break;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name) => names.push(name.to_string()),
+            Err(e) => result = Err(e.into()),
+        }
+    }
+    unsafe { jack_free(array as *mut c_void) };
+    result.map(|_| names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JackClient;
+
+    #[test]
+    fn new() {
+        // Only connects if a JACK server is running
+        if let Ok(jack) = JackClient::new() {
+            assert!(jack.connections("rtmidi-rs:missing").is_err());
+        }
+    }
+}
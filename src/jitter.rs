@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::midi_in::RtMidiIn;
+use crate::subscribe::Subscription;
+
+/// Number of intervals kept by a [`JitterMonitor`] unless set with [`JitterMonitor::capacity`]
+pub const DEFAULT_JITTER_CAPACITY: usize = 4096;
+
+/// Upper bounds of the [`JitterStats::histogram`] buckets, in microseconds
+const BUCKETS: [u64; 10] = [
+    100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000,
+];
+
+/// Messages whose timing is measured by a [`JitterMonitor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JitterMessages {
+    /// MIDI Timing Clock (`F8`)
+    #[default]
+    Clock,
+    /// Note On messages (with a non-zero velocity) on any channel
+    NoteOn,
+}
+
+impl JitterMessages {
+    fn matches(self, message: &[u8]) -> bool {
+        match self {
+            JitterMessages::Clock => message.first() == Some(&0xF8),
+            JitterMessages::NoteOn => matches!(*message, [status, _, velocity]
+                if status & 0xF0 == 0x90 && velocity > 0),
+        }
+    }
+}
+
+/// Statistics of the intervals between messages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JitterStats {
+    /// Number of intervals measured
+    pub count: usize,
+    pub mean: Duration,
+    /// Standard deviation of the intervals
+    pub std_dev: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Largest difference between an interval and the mean
+    pub worst: Duration,
+    /// Number of intervals by difference from the mean: each bucket is an upper bound and the
+    /// number of intervals within it (and above the previous bound). The last bucket is
+    /// unbounded ([`Duration::MAX`]).
+    pub histogram: Vec<(Duration, usize)>,
+}
+
+impl JitterStats {
+    fn new<'a, I: Iterator<Item = &'a f64> + Clone>(intervals: I) -> Self {
+        let count = intervals.clone().count();
+        if count == 0 {
+            return Default::default();
+        }
+        let mean = intervals.clone().sum::<f64>() / count as f64;
+        let variance = intervals
+            .clone()
+            .map(|interval| (interval - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        let (min, max) = intervals
+            .clone()
+            .fold((f64::MAX, 0.0f64), |(min, max), &i| {
+                (min.min(i), max.max(i))
+            });
+        let mut histogram: Vec<_> = BUCKETS
+            .iter()
+            .map(|&bound| (Duration::from_micros(bound), 0))
+            .chain(Some((Duration::MAX, 0)))
+            .collect();
+        let mut worst = 0.0f64;
+        for interval in intervals {
+            let deviation = (interval - mean).abs();
+            worst = worst.max(deviation);
+            let deviation = Duration::from_secs_f64(deviation);
+            if let Some(bucket) = histogram.iter_mut().find(|(bound, _)| deviation <= *bound) {
+                bucket.1 += 1;
+            }
+        }
+        JitterStats {
+            count,
+            mean: Duration::from_secs_f64(mean),
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+            min: Duration::from_secs_f64(min),
+            max: Duration::from_secs_f64(max),
+            worst: Duration::from_secs_f64(worst),
+            histogram,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Intervals {
+    // Intervals in seconds from RtMidi's timestamps and from the arrival times
+    timestamps: VecDeque<f64>,
+    arrivals: VecDeque<f64>,
+    // Time since the last matching message, accumulated from delta-times
+    elapsed: Option<f64>,
+    last: Option<Instant>,
+}
+
+struct State {
+    messages: JitterMessages,
+    capacity: usize,
+    intervals: Intervals,
+}
+
+/// Input timing jitter analysis
+///
+/// Measures the intervals between clock or note messages received by an input, so timing
+/// problems can be traced to their source. Two sets of intervals are kept: those between the
+/// timestamps RtMidi gives each message, which show the jitter of the device and the
+/// backend, and those between the times messages are delivered to the application, which add
+/// the scheduling of RtMidi's input thread and the time taken by other callbacks. Jitter in the
+/// application's own handling can be measured by timing the callback against the latter.
+///
+/// Only the most recent intervals are kept (see [`JitterMonitor::capacity`]). Clones refer to
+/// the same monitor.
+/// ```
+/// use rtmidi::{JitterMessages, JitterMonitor, RtMidiIn};
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// let jitter = JitterMonitor::new(JitterMessages::Clock);
+/// let _monitor = jitter.attach(&input);
+/// input.set_callback(|_timestamp, _message| {}).unwrap();
+/// input.ignore_types(true, false, true).unwrap();
+///
+/// // Later
+/// let stats = jitter.timestamp_stats();
+/// println!("{:?} ± {:?} (worst {:?})", stats.mean, stats.std_dev, stats.worst);
+/// ```
+#[derive(Clone)]
+pub struct JitterMonitor(Arc<Mutex<State>>);
+
+impl JitterMonitor {
+    pub fn new(messages: JitterMessages) -> Self {
+        JitterMonitor(Arc::new(Mutex::new(State {
+            messages,
+            capacity: DEFAULT_JITTER_CAPACITY,
+            intervals: Default::default(),
+        })))
+    }
+
+    /// Set the number of intervals kept (default [`DEFAULT_JITTER_CAPACITY`])
+    pub fn capacity(self, capacity: usize) -> Self {
+        self.lock().capacity = capacity;
+        self
+    }
+
+    /// Measure the messages received by an input until the returned [`Subscription`] is dropped
+    pub fn attach(&self, input: &RtMidiIn) -> Subscription {
+        let monitor = self.clone();
+        input.subscribe(move |timestamp, message| monitor.record(timestamp, message))
+    }
+
+    /// Record a message delivered now, given its delta-time in seconds since the previous message
+    pub fn record(&self, delta: f64, message: &[u8]) {
+        self.record_at(Instant::now(), delta, message)
+    }
+
+    /// Record a message delivered at the given instant
+    pub fn record_at(&self, at: Instant, delta: f64, message: &[u8]) {
+        let mut state = self.lock();
+        let (messages, capacity) = (state.messages, state.capacity);
+        let intervals = &mut state.intervals;
+        if let Some(elapsed) = &mut intervals.elapsed {
+            *elapsed += delta.max(0.0);
+        }
+        if !messages.matches(message) {
+            return;
+        }
+        if let (Some(elapsed), Some(last)) = (intervals.elapsed, intervals.last) {
+            let arrival = at.saturating_duration_since(last).as_secs_f64();
+            for (queue, interval) in [
+                (&mut intervals.timestamps, elapsed),
+                (&mut intervals.arrivals, arrival),
+            ] {
+                queue.push_back(interval);
+                while queue.len() > capacity {
+                    queue.pop_front();
+                }
+            }
+        }
+        intervals.elapsed = Some(0.0);
+        intervals.last = Some(at);
+    }
+
+    /// Returns statistics of the intervals between the messages' timestamps
+    pub fn timestamp_stats(&self) -> JitterStats {
+        JitterStats::new(self.lock().intervals.timestamps.iter())
+    }
+
+    /// Returns statistics of the intervals between the times the messages were delivered
+    pub fn arrival_stats(&self) -> JitterStats {
+        JitterStats::new(self.lock().intervals.arrivals.iter())
+    }
+
+    /// Discard the intervals measured so far
+    pub fn reset(&self) {
+        self.lock().intervals = Default::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{JitterMessages, JitterMonitor};
+
+    #[test]
+    fn clock() {
+        let monitor = JitterMonitor::new(JitterMessages::Clock);
+        let start = Instant::now();
+        // Clocks timestamped 20ms apart (with a note in between) and delivered late
+        let messages: [(u64, f64, &[u8]); 5] = [
+            (0, 0.0, &[0xF8]),
+            (20, 0.02, &[0xF8]),
+            (25, 0.005, &[0x90, 60, 100]),
+            (41, 0.015, &[0xF8]),
+            (61, 0.02, &[0xF8]),
+        ];
+        for &(at, delta, message) in &messages {
+            monitor.record_at(start + Duration::from_millis(at), delta, message);
+        }
+
+        let stats = monitor.timestamp_stats();
+        assert_eq!(stats.count, 3);
+        assert!((stats.mean.as_secs_f64() - 0.02).abs() < 1e-9);
+        assert!(stats.worst < Duration::from_micros(1));
+        assert_eq!(stats.histogram[0].1, 3);
+
+        let stats = monitor.arrival_stats();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, Duration::from_millis(20));
+        assert_eq!(stats.max, Duration::from_millis(21));
+        // Mean of 20.333ms
+        assert_eq!(stats.worst.as_micros(), 666);
+        assert_eq!(stats.histogram.iter().map(|(_, n)| n).sum::<usize>(), 3);
+        assert_eq!(stats.histogram[2].1, 2);
+        assert_eq!(stats.histogram[3].1, 1);
+
+        monitor.reset();
+        assert_eq!(monitor.timestamp_stats().count, 0);
+    }
+
+    #[test]
+    fn capacity() {
+        let monitor = JitterMonitor::new(JitterMessages::NoteOn).capacity(2);
+        let start = Instant::now();
+        for index in 0..5 {
+            let at = start + Duration::from_millis(index * 100);
+            monitor.record_at(at, 0.1, &[0x90, 60, 100]);
+            monitor.record_at(at, 0.0, &[0x90, 60, 0]);
+        }
+        let stats = monitor.timestamp_stats();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.std_dev, Duration::from_secs(0));
+    }
+}
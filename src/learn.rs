@@ -0,0 +1,141 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::error::RtMidiError;
+use crate::midi_in::RtMidiIn;
+
+/// Time spent collecting further events after the first assignable event, to filter out jitter
+pub const LEARN_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Type of an assignable control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LearnKind {
+    Note,
+    ControlChange,
+    PitchBend,
+}
+
+/// Descriptor for a control captured by [`midi_learn`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Learned {
+    pub kind: LearnKind,
+    /// MIDI channel (0-15)
+    pub channel: u8,
+    /// Note or controller number (always 0 for pitch bend)
+    pub number: u8,
+}
+
+impl Learned {
+    /// Returns the descriptor for an assignable message (note, control change or pitch bend)
+    pub fn from_message(message: &[u8]) -> Option<Self> {
+        let (&status, data) = message.split_first()?;
+        let channel = status & 0x0F;
+        let (kind, number) = match (status & 0xF0, data) {
+            (0x80, [note, _]) | (0x90, [note, _]) => (LearnKind::Note, *note),
+            (0xB0, [controller, _]) => (LearnKind::ControlChange, *controller),
+            (0xE0, [_, _]) => (LearnKind::PitchBend, 0),
+            _ => return None,
+        };
+        Some(Learned {
+            kind,
+            channel,
+            number,
+        })
+    }
+}
+
+/// Wait for the next assignable event (note, control change or pitch bend) on an input and
+/// return a descriptor for it, or [`None`] if nothing arrives within `timeout`.
+///
+/// After the first assignable event, events are collected for a further [`LEARN_DEBOUNCE`] and
+/// the control seen most often is returned, so a stray message from a noisy knob doesn't win over
+/// the one being moved.
+///
+/// The input must already be open. Any callback set on the input is replaced (and cancelled
+/// once learning is complete), so it must be set again afterwards.
+pub fn midi_learn(input: &RtMidiIn, timeout: Duration) -> Result<Option<Learned>, RtMidiError> {
+    let (sender, receiver) = mpsc::channel();
+    input.set_callback(move |_timestamp, message| {
+        if let Some(learned) = Learned::from_message(message) {
+            let _ = sender.send(learned);
+        }
+    })?;
+
+    let mut events = Vec::new();
+    if let Ok(learned) = receiver.recv_timeout(timeout) {
+        events.push(learned);
+        let deadline = Instant::now() + LEARN_DEBOUNCE;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match receiver.recv_timeout(remaining) {
+                Ok(learned) => events.push(learned),
+                Err(_) => break,
+            }
+        }
+    }
+
+    input.cancel_callback()?;
+    Ok(most_frequent(&events))
+}
+
+/// Returns the most frequent descriptor, preferring the earliest on a tie
+fn most_frequent(events: &[Learned]) -> Option<Learned> {
+    let count = |learned: &Learned| events.iter().filter(|event| *event == learned).count();
+    events
+        .iter()
+        .fold(None, |best: Option<(Learned, usize)>, event| {
+            let n = count(event);
+            match best {
+                Some((_, m)) if m >= n => best,
+                _ => Some((*event, n)),
+            }
+        })
+        .map(|(learned, _)| learned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{most_frequent, LearnKind, Learned};
+
+    fn learned(kind: LearnKind, channel: u8, number: u8) -> Learned {
+        Learned {
+            kind,
+            channel,
+            number,
+        }
+    }
+
+    #[test]
+    fn from_message() {
+        assert_eq!(
+            Learned::from_message(&[0x93, 64, 90]),
+            Some(learned(LearnKind::Note, 3, 64))
+        );
+        assert_eq!(
+            Learned::from_message(&[0x80, 64, 0]),
+            Some(learned(LearnKind::Note, 0, 64))
+        );
+        assert_eq!(
+            Learned::from_message(&[0xB1, 7, 100]),
+            Some(learned(LearnKind::ControlChange, 1, 7))
+        );
+        assert_eq!(
+            Learned::from_message(&[0xEF, 0, 64]),
+            Some(learned(LearnKind::PitchBend, 15, 0))
+        );
+        assert_eq!(Learned::from_message(&[0xC0, 5]), None);
+        assert_eq!(Learned::from_message(&[0xF8]), None);
+        assert_eq!(Learned::from_message(&[0xB0, 7]), None);
+    }
+
+    #[test]
+    fn debounce() {
+        let knob = learned(LearnKind::ControlChange, 0, 74);
+        let jitter = learned(LearnKind::ControlChange, 0, 1);
+        assert_eq!(
+            most_frequent(&[jitter, knob, knob, jitter, knob]),
+            Some(knob)
+        );
+        assert_eq!(most_frequent(&[jitter, knob]), Some(jitter));
+        assert_eq!(most_frequent(&[]), None);
+    }
+}
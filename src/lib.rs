@@ -150,18 +150,167 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Thread Safety
+//!
+//! [`RtMidiIn`] and [`RtMidiOut`] are [`Send`] and [`Sync`], so they can be moved to another
+//! thread or shared between threads (e.g. in an [`std::sync::Arc`]). All methods take `&self`;
+//! calls into RtMidi are serialized by an internal lock, so an instance can be used from several
+//! threads at once.
+//!
+//! Callbacks are invoked on RtMidi's input thread (or one of this crate's own threads), so they
+//! must be `Send + 'static`. To handle input with data that can't leave the current thread, use
+//! [`RtMidiIn::set_callback_on_current_thread`].
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+mod alsa;
 mod api;
+mod backend;
+mod bend;
+mod capture;
+mod channel;
+mod clock;
+#[cfg(feature = "async")]
+mod completion;
+#[cfg(all(feature = "coremidi", target_os = "macos"))]
+mod coremidi;
+mod decoder;
 mod error;
+mod event;
+mod fake;
 mod ffi;
+mod filter;
+mod follow;
+mod gate;
+mod grid;
+mod history;
+#[cfg(feature = "jack")]
+mod jack;
+mod jitter;
+mod learn;
+#[cfg(feature = "link")]
+mod link;
+mod local;
+mod log;
+mod mackie;
+mod message;
+mod metronome;
+mod microtonal;
 mod midi;
 mod midi_in;
 mod midi_out;
+mod notes;
+mod options;
+mod parameter;
+mod pipe;
+mod port_name;
+mod ports;
+mod quirks;
+mod roland;
+#[cfg(feature = "scala")]
+mod scala;
+mod scheduler;
+#[cfg(all(feature = "shm", unix))]
+mod shm;
+#[cfg(feature = "smf")]
+mod smf;
+#[cfg(unix)]
+mod socket;
+#[cfg(feature = "smf")]
+mod source;
+mod stream;
+mod subscribe;
+mod sysex;
+mod system;
+mod tempo;
+mod threads;
+mod throttle;
+mod timer;
+mod transaction;
+// Public so the transforms keep their own namespace, as some of their names (e.g. `Scale`,
+// `Mapping`) would clash with other types at the crate root
+pub mod transform;
+mod transport;
+mod universal;
+mod usb;
+// Public as its free functions (e.g. `value::to_7bit`) read better qualified by the module
+pub mod value;
+#[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+mod virtualmidi;
+mod watchdog;
+#[cfg(all(feature = "winmm", target_os = "windows"))]
+mod winmm;
+mod worker;
 
 /// A MIDI input/output port identifier
 pub type RtMidiPort = u32;
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+pub use alsa::{AlsaAddress, AlsaBackend, AlsaSequencer};
 pub use api::RtMidiApi;
+pub use backend::{Backend, BackendCallback, InputConnection, OutputConnection, RtMidiBackend};
+pub use bend::PitchBendRange;
+pub use capture::{Capture, CaptureSession, CapturedMessage};
+pub use channel::OutputChannel;
+pub use clock::{Clock, DEFAULT_TEMPO};
+#[cfg(feature = "async")]
+pub use completion::SendFuture;
+#[cfg(all(feature = "coremidi", target_os = "macos"))]
+pub use coremidi::{coremidi_devices, CoreMidiDevice, CoreMidiEndpoint, CoreMidiEntity};
 pub use error::RtMidiError;
+pub use event::RtMidiEvent;
+pub use fake::{FakeConnection, FakeDevice};
+pub use filter::DuplicateFilter;
+pub use follow::ClockFollower;
+pub use grid::{Grid, GridLayout, PadColor};
+pub use history::{MessageDirection, RecentMessage};
+#[cfg(feature = "jack")]
+pub use jack::{JackClient, JackPortDirection};
+pub use jitter::{JitterMessages, JitterMonitor, JitterStats, DEFAULT_JITTER_CAPACITY};
+pub use learn::{midi_learn, LearnKind, Learned, LEARN_DEBOUNCE};
+#[cfg(feature = "link")]
+pub use link::{Link, LinkFollower, LINK_SYNC_INTERVAL};
+pub use local::LocalCallback;
+pub use log::{read_log, LogFormat, LogWriter};
+pub use mackie::{LedState, MackieButton, MackieEvent, MackieFeedback, RingMode};
+pub use message::{ChannelMode, MidiMessage, ShortMessage};
+pub use metronome::{Metronome, MetronomeRunner};
+pub use microtonal::MicrotonalNote;
+pub use midi::RecoveryPolicy;
 pub use midi_in::{RtMidiIn, RtMidiInArgs};
-pub use midi_out::{RtMidiOut, RtMidiOutArgs};
+pub use midi_out::{NoteHandle, RtMidiOut, RtMidiOutArgs, SharedMidiOut};
+pub use notes::NotesOffPolicy;
+pub use options::{CoreMidiProtocol, OpenOptions};
+pub use parameter::{Parameter, ParameterEncoding, ParameterMap, ParameterProtocol};
+pub use pipe::{PipeBackend, PipeFraming};
+pub use port_name::{PortName, PortSuffix};
+pub use ports::{input_ports, output_ports, PortInfo, PortKind};
+pub use quirks::{register_quirks, DeviceIdentity, DeviceMatch, Quirks};
+pub use roland::{RolandDevice, RolandMessage, ROLAND_ID};
+#[cfg(feature = "scala")]
+pub use scala::{KeyboardMapping, Scale, Tuning};
+pub use scheduler::{
+    Bars, Beats, Humanize, PendingPolicy, Quantize, Scheduler, DEFAULT_BEATS_PER_BAR,
+};
+#[cfg(all(feature = "shm", unix))]
+pub use shm::{SharedMemoryBackend, DEFAULT_SHM_CAPACITY};
+#[cfg(feature = "smf")]
+pub use smf::{Division, Smf, SmfEvent, Track, TrackEvent};
+#[cfg(unix)]
+pub use socket::SocketBackend;
+#[cfg(feature = "smf")]
+pub use source::SmfSource;
+pub use stream::SysExChunk;
+pub use subscribe::Subscription;
+pub use sysex::{CancelToken, Checksum, SysExArgs, SysExBuilder};
+pub use system::{DeviceEvent, MidiInput, MidiOutput, MidiSystem, PortDirection};
+pub use tempo::TempoMap;
+pub use throttle::DIN_MIDI_BYTES_PER_SECOND;
+pub use timer::TimerStrategy;
+pub use transaction::{SysExTransaction, DEFAULT_REPLY_TIMEOUT};
+pub use transport::{Transport, TransportEvent, TransportState};
+pub use universal::{UniversalSysEx, ALL_DEVICES};
+pub use usb::UsbId;
+pub use watchdog::{ACTIVE_SENSING_INTERVAL, ACTIVE_SENSING_TIMEOUT};
+#[cfg(all(feature = "winmm", target_os = "windows"))]
+pub use winmm::WinMmBackend;
@@ -0,0 +1,244 @@
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::clock::{self, Clock};
+use crate::error::RtMidiError;
+use crate::scheduler::Scheduler;
+use crate::threads;
+
+/// Interval at which a [`LinkFollower`] updates its scheduler's clock
+pub const LINK_SYNC_INTERVAL: Duration = Duration::from_millis(10);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct AblLink {
+    impl_: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct AblLinkSessionState {
+    impl_: *mut c_void,
+}
+
+extern "C" {
+    fn abl_link_create(bpm: f64) -> AblLink;
+    fn abl_link_destroy(link: AblLink);
+    fn abl_link_is_enabled(link: AblLink) -> bool;
+    fn abl_link_enable(link: AblLink, enable: bool);
+    fn abl_link_num_peers(link: AblLink) -> u64;
+    fn abl_link_clock_micros(link: AblLink) -> i64;
+    fn abl_link_create_session_state() -> AblLinkSessionState;
+    fn abl_link_destroy_session_state(session_state: AblLinkSessionState);
+    fn abl_link_capture_app_session_state(link: AblLink, session_state: AblLinkSessionState);
+    fn abl_link_commit_app_session_state(link: AblLink, session_state: AblLinkSessionState);
+    fn abl_link_tempo(session_state: AblLinkSessionState) -> f64;
+    fn abl_link_set_tempo(session_state: AblLinkSessionState, bpm: f64, at_time: i64);
+    fn abl_link_beat_at_time(session_state: AblLinkSessionState, time: i64, quantum: f64) -> f64;
+    fn abl_link_request_beat_at_time(
+        session_state: AblLinkSessionState,
+        beat: f64,
+        time: i64,
+        quantum: f64,
+    );
+}
+
+struct Session {
+    link: AblLink,
+    // Reused for every capture, so the app thread doesn't allocate
+    state: Mutex<AblLinkSessionState>,
+}
+
+// The Link object may be used from any thread, and the session state is behind a mutex
+unsafe impl Send for Session {}
+unsafe impl Sync for Session {}
+
+impl Session {
+    /// Capture the session state, apply `f` and optionally commit the result
+    fn with_state<T, F>(&self, commit: bool, f: F) -> T
+    where
+        F: FnOnce(AblLinkSessionState, i64) -> T,
+    {
+        let state = self.lock();
+        unsafe { abl_link_capture_app_session_state(self.link, *state) };
+        let result = f(*state, unsafe { abl_link_clock_micros(self.link) });
+        if commit {
+            unsafe { abl_link_commit_app_session_state(self.link, *state) };
+        }
+        result
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AblLinkSessionState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {
+            abl_link_destroy_session_state(*self.lock());
+            abl_link_destroy(self.link);
+        }
+    }
+}
+
+/// Ableton Link session
+///
+/// Joins a Link session on the local network (once enabled), sharing tempo and beat phase with
+/// other Link-enabled applications. A [`Scheduler`] can be locked to the session with
+/// [`Link::follow`], so messages scheduled in musical time line up with the other peers. Beats
+/// are aligned to the session's phase over a `quantum` (e.g. 4.0 to align bars of four beats).
+/// Clones refer to the same session.
+///
+/// Requires the `link` feature, and Link's C library (`abl_link`, built from Link's
+/// `extensions/abl_link`), which is found in `ABL_LINK_LIB_DIR` if set.
+/// ```no_run
+/// use rtmidi::{Link, RtMidiError, RtMidiOut};
+///
+/// fn play(output: &RtMidiOut) -> Result<(), RtMidiError> {
+///     let link = Link::new(120.0)?;
+///     link.enable(true);
+///     let _follower = link.follow(output.scheduler()?, 4.0);
+///     // Schedule messages in musical time...
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Link(Arc<Session>);
+
+impl Link {
+    /// Create a session with an initial tempo in beats per minute. The session isn't joined
+    /// until enabled.
+    pub fn new(tempo: f64) -> Result<Self, RtMidiError> {
+        let link = unsafe { abl_link_create(tempo) };
+        if link.impl_.is_null() {
+            return Err(RtMidiError::NullPointer);
+        }
+        let state = unsafe { abl_link_create_session_state() };
+        if state.impl_.is_null() {
+            unsafe { abl_link_destroy(link) };
+            return Err(RtMidiError::NullPointer);
+        }
+        Ok(Link(Arc::new(Session {
+            link,
+            state: Mutex::new(state),
+        })))
+    }
+
+    /// Join (or leave) the Link session on the network
+    pub fn enable(&self, enabled: bool) {
+        unsafe { abl_link_enable(self.0.link, enabled) }
+    }
+
+    /// Returns [`true`] if the session is joined
+    pub fn is_enabled(&self) -> bool {
+        unsafe { abl_link_is_enabled(self.0.link) }
+    }
+
+    /// Returns the number of other peers in the session
+    pub fn peers(&self) -> u64 {
+        unsafe { abl_link_num_peers(self.0.link) }
+    }
+
+    /// Returns the session tempo in beats per minute
+    pub fn tempo(&self) -> f64 {
+        self.0
+            .with_state(false, |state, _| unsafe { abl_link_tempo(state) })
+    }
+
+    /// Change the session tempo for every peer
+    pub fn set_tempo(&self, tempo: f64) {
+        self.0.with_state(true, |state, now| unsafe {
+            abl_link_set_tempo(state, tempo, now)
+        })
+    }
+
+    /// Request that the session's beat `beat` is now, phase aligned over `quantum`. With other
+    /// peers present, the beat is moved to the next time it matches their phase instead.
+    pub fn request_beat(&self, beat: f64, quantum: f64) {
+        self.0.with_state(true, |state, now| unsafe {
+            abl_link_request_beat_at_time(state, beat, now, quantum)
+        })
+    }
+
+    /// Returns a clock matching the session's current tempo and beat, phase aligned over
+    /// `quantum`
+    pub fn clock(&self, quantum: f64) -> Clock {
+        let (at, beat, tempo) = self.0.with_state(false, |state, now| unsafe {
+            let at = Instant::now();
+            (
+                at,
+                abl_link_beat_at_time(state, now, quantum),
+                abl_link_tempo(state),
+            )
+        });
+        Clock::new(clock::offset(at, -beat * 60.0 / tempo), tempo)
+    }
+
+    /// Set the clock of a scheduler to the session's current tempo and beat (see
+    /// [`Link::clock`])
+    pub fn sync(&self, scheduler: &Scheduler, quantum: f64) {
+        scheduler.set_clock(self.clock(quantum));
+    }
+
+    /// Keep the clock of a scheduler in sync with the session from a new thread, updating it
+    /// every [`LINK_SYNC_INTERVAL`] until the returned [`LinkFollower`] is dropped
+    pub fn follow(&self, scheduler: Scheduler, quantum: f64) -> LinkFollower {
+        self.sync(&scheduler, quantum);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (link, stop) = (self.clone(), Arc::clone(&stop));
+            threads::spawn("rtmidi-link".to_string(), move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(LINK_SYNC_INTERVAL);
+                    link.sync(&scheduler, quantum);
+                }
+            })
+        };
+        LinkFollower {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Keeps a scheduler in sync with a [`Link`] session until dropped
+pub struct LinkFollower {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for LinkFollower {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::Link;
+    use crate::midi_out::RtMidiOut;
+
+    #[test]
+    fn follow() {
+        let link = Link::new(90.0).unwrap();
+        assert!(!link.is_enabled());
+        assert_eq!(link.peers(), 0);
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let scheduler = output.scheduler().unwrap();
+        drop(link.follow(scheduler.clone(), 4.0));
+        assert_eq!(scheduler.clock().tempo(), link.tempo());
+        let clock = link.clock(4.0);
+        assert!(
+            (clock.beat_at(Instant::now()) - scheduler.clock().beat_at(Instant::now())).abs() < 0.1
+        );
+    }
+}
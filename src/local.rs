@@ -0,0 +1,113 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+
+type Callback = Box<dyn FnMut(f64, &[u8])>;
+
+/// Input callback confined to the thread that registered it
+///
+/// Returned by [`crate::RtMidiIn::set_callback_on_current_thread`]. Incoming messages are sent
+/// over a channel from RtMidi's input thread and the callback is only ever invoked by
+/// [`LocalCallback::pump`], [`LocalCallback::pump_timeout`] or [`LocalCallback::run`], on the thread
+/// that calls them. This lets the callback capture data that can't be sent to another thread,
+/// such as GUI state (e.g. an `Rc<RefCell<_>>`). `LocalCallback` is itself not `Send`, so it
+/// stays on the registering thread.
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use rtmidi::RtMidiIn;
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// let notes = Rc::new(RefCell::new(Vec::new()));
+/// let mut callback = {
+///     let notes = Rc::clone(&notes);
+///     input
+///         .set_callback_on_current_thread(move |_timestamp, message| {
+///             notes.borrow_mut().push(message.to_vec())
+///         })
+///         .unwrap()
+/// };
+///
+/// // Called from the application's event loop
+/// callback.pump();
+/// ```
+pub struct LocalCallback {
+    receiver: Receiver<(f64, Vec<u8>)>,
+    callback: Callback,
+}
+
+impl LocalCallback {
+    pub(crate) fn new<F: FnMut(f64, &[u8]) + 'static>(
+        receiver: Receiver<(f64, Vec<u8>)>,
+        callback: F,
+    ) -> Self {
+        LocalCallback {
+            receiver,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Invoke the callback for every message received so far, without blocking. Returns the
+    /// number of messages dispatched.
+    pub fn pump(&mut self) -> usize {
+        let mut count = 0;
+        loop {
+            match self.receiver.try_recv() {
+                Ok((timestamp, message)) => (self.callback)(timestamp, &message),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return count,
+            }
+            count += 1;
+        }
+    }
+
+    /// Wait up to `timeout` for messages, then invoke the callback for every message received.
+    /// Returns the number of messages dispatched.
+    pub fn pump_timeout(&mut self, timeout: Duration) -> usize {
+        match self.receiver.recv_timeout(timeout) {
+            Ok((timestamp, message)) => {
+                (self.callback)(timestamp, &message);
+                1 + self.pump()
+            }
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => 0,
+        }
+    }
+
+    /// Invoke the callback for messages as they arrive, blocking the current thread. Returns
+    /// once the input stops sending messages to this callback.
+    pub fn run(&mut self) {
+        while let Ok((timestamp, message)) = self.receiver.recv() {
+            (self.callback)(timestamp, &message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::LocalCallback;
+
+    #[test]
+    fn pump() {
+        let (sender, receiver) = mpsc::channel();
+        let messages = Rc::new(RefCell::new(Vec::new()));
+        let mut callback = {
+            let messages = Rc::clone(&messages);
+            LocalCallback::new(receiver, move |_timestamp, message: &[u8]| {
+                messages.borrow_mut().push(message.to_vec())
+            })
+        };
+        assert_eq!(callback.pump(), 0);
+        sender.send((0.0, vec![0x90, 60, 100])).unwrap();
+        sender.send((0.1, vec![0x80, 60, 0])).unwrap();
+        assert_eq!(callback.pump_timeout(Duration::from_millis(10)), 2);
+        assert_eq!(
+            *messages.borrow(),
+            vec![vec![0x90, 60, 100], vec![0x80, 60, 0]]
+        );
+        drop(sender);
+        callback.run();
+    }
+}
@@ -0,0 +1,387 @@
+use std::io::{self, BufRead, Write};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::capture::{CaptureSession, CapturedMessage};
+use crate::error::RtMidiError;
+use crate::message::MidiMessage;
+use crate::midi_in::RtMidiIn;
+use crate::subscribe::Subscription;
+
+/// Text format of a MIDI log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Comma separated values, with a header row and the columns
+    /// `time,port,type,channel,data1,data2,raw`
+    Csv,
+    /// One JSON object per line, with the decoded fields of each message named, e.g.
+    /// `{"time":0.500000,"port":"Synth","type":"program_change","channel":0,"program":5,`
+    /// `"raw":"C0 05"}`
+    JsonLines,
+}
+
+/// Writes MIDI messages to a CSV or JSON-Lines log
+///
+/// Each message is written with its time in seconds, the port name, its type, channel and data
+/// fields as decoded by [`MidiMessage::parse`] (empty if it can't be decoded), and its raw bytes
+/// in hex. Logs can be written from a [`CaptureSession`] or live from an input, and opened in
+/// external tools or attached to bug reports.
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::{LogFormat, LogWriter};
+///
+/// let mut log = LogWriter::new(Vec::new(), LogFormat::Csv).port("Synth");
+/// log.write(Duration::from_millis(500), &[0x90, 60, 100]).unwrap();
+/// assert_eq!(
+///     String::from_utf8(log.into_inner()).unwrap(),
+///     "time,port,type,channel,data1,data2,raw\n0.500000,Synth,note_on,0,60,100,90 3C 64\n"
+/// );
+/// ```
+pub struct LogWriter<W> {
+    writer: W,
+    format: LogFormat,
+    port: String,
+    header: bool,
+}
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(writer: W, format: LogFormat) -> Self {
+        LogWriter {
+            writer,
+            format,
+            port: String::new(),
+            header: false,
+        }
+    }
+
+    /// Set the port name written with each message
+    pub fn port(mut self, port: &str) -> Self {
+        self.port = port.to_string();
+        self
+    }
+
+    /// Write a message, given its time since the start of the log
+    pub fn write(&mut self, time: Duration, message: &[u8]) -> io::Result<()> {
+        let (kind, channel, fields) = decode(message);
+        let time = format!("{:.6}", time.as_secs_f64());
+        let raw = hex(message);
+        match self.format {
+            LogFormat::Csv => {
+                if !self.header {
+                    self.header = true;
+                    writeln!(self.writer, "time,port,type,channel,data1,data2,raw")?;
+                }
+                let field = |index: usize| {
+                    fields
+                        .get(index)
+                        .map(|(_, value)| value.to_string())
+                        .unwrap_or_default()
+                };
+                writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{},{}",
+                    time,
+                    csv_escape(&self.port),
+                    kind,
+                    channel
+                        .map(|channel| channel.to_string())
+                        .unwrap_or_default(),
+                    field(0),
+                    field(1),
+                    raw
+                )
+            }
+            LogFormat::JsonLines => {
+                let mut line = format!(
+                    "{{\"time\":{},\"port\":\"{}\",\"type\":\"{}\"",
+                    time,
+                    json_escape(&self.port),
+                    kind
+                );
+                if let Some(channel) = channel {
+                    line.push_str(&format!(",\"channel\":{}", channel));
+                }
+                for (name, value) in fields {
+                    line.push_str(&format!(",\"{}\":{}", name, value));
+                }
+                writeln!(self.writer, "{},\"raw\":\"{}\"}}", line, raw)
+            }
+        }
+    }
+
+    /// Write every message of a capture session
+    pub fn write_session(&mut self, session: &CaptureSession) -> io::Result<()> {
+        for message in &session.messages {
+            self.write(message.time, &message.message)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> LogWriter<W> {
+    /// Write the messages received by an input as they arrive, until the returned
+    /// [`Subscription`] is dropped.
+    ///
+    /// Times are accumulated from the input's delta-times, starting from zero at the first
+    /// message. Writing stops at the first error.
+    pub fn attach(self, input: &RtMidiIn) -> Subscription {
+        let state = Mutex::new((Some(self), None::<Duration>));
+        input.subscribe(move |delta, message| {
+            let mut state = lock(&state);
+            let (log, time) = &mut *state;
+            let now = match *time {
+                Some(time) => time + Duration::from_secs_f64(delta.max(0.0)),
+                None => Duration::from_secs(0),
+            };
+            *time = Some(now);
+            if let Some(writer) = log {
+                if writer.write(now, message).is_err() || writer.writer.flush().is_err() {
+                    *log = None;
+                }
+            }
+        })
+    }
+}
+
+/// Read a log written by [`LogWriter`] back into a [`CaptureSession`]
+///
+/// Only the time and raw bytes of each message are read; the decoded fields are ignored. The
+/// session can then be replayed through an output (see [`CaptureSession::replay`]), e.g. to turn
+/// a log attached to a bug report into a reproducible test.
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::{read_log, LogFormat};
+///
+/// let log = "time,port,type,channel,data1,data2,raw\n0.500000,Synth,note_on,0,60,100,90 3C 64\n";
+/// let session = read_log(log.as_bytes(), LogFormat::Csv).unwrap();
+/// assert_eq!(session.messages[0].time, Duration::from_millis(500));
+/// assert_eq!(session.messages[0].message, [0x90, 60, 100]);
+/// ```
+pub fn read_log<R: BufRead>(reader: R, format: LogFormat) -> Result<CaptureSession, RtMidiError> {
+    let mut session = CaptureSession::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| RtMidiError::Error(e.to_string()))?;
+        let line = line.trim();
+        let header = format == LogFormat::Csv && index == 0 && line.starts_with("time,");
+        if line.is_empty() || header {
+            continue;
+        }
+        let fields = match format {
+            // The port is the only quoted field, and may contain commas
+            LogFormat::Csv => line.split(',').next().zip(line.rsplit(',').next()),
+            LogFormat::JsonLines => json_number(line, "time").zip(json_string(line, "raw")),
+        };
+        let invalid = |reason: &str| {
+            RtMidiError::InvalidFile(format!("Line {} of log: {}", index + 1, reason))
+        };
+        let (time, raw) = fields.ok_or_else(|| invalid("missing time or raw bytes"))?;
+        let time = time
+            .parse::<f64>()
+            .ok()
+            .filter(|time| time.is_finite() && *time >= 0.0)
+            .ok_or_else(|| invalid("invalid time"))?;
+        let message = raw
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|message| !message.is_empty())
+            .ok_or_else(|| invalid("invalid raw bytes"))?;
+        session.messages.push(CapturedMessage {
+            time: Duration::from_secs_f64(time),
+            message,
+        });
+    }
+    Ok(session)
+}
+
+/// Returns the text of a number field in a JSON object written by [`LogWriter`]
+fn json_number<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line.find(&format!("\"{}\":", name))? + name.len() + 3;
+    let value = &line[start..];
+    Some(value[..value.find(&[',', '}'][..])?].trim())
+}
+
+/// Returns a string field (without escapes) of a JSON object written by [`LogWriter`]
+fn json_string<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line.rfind(&format!("\"{}\":\"", name))? + name.len() + 4;
+    let value = &line[start..];
+    Some(&value[..value.find('"')?])
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the type name, channel and named data fields of a message
+fn decode(message: &[u8]) -> (&'static str, Option<u8>, Vec<(&'static str, u32)>) {
+    let decoded = match MidiMessage::parse(message) {
+        Ok(decoded) => decoded,
+        Err(_) => return ("unknown", None, Vec::new()),
+    };
+    let channel = decoded.channel();
+    let (kind, fields) = match decoded {
+        MidiMessage::NoteOff { note, velocity, .. } => (
+            "note_off",
+            vec![("note", note.into()), ("velocity", velocity.into())],
+        ),
+        MidiMessage::NoteOn { note, velocity, .. } => (
+            "note_on",
+            vec![("note", note.into()), ("velocity", velocity.into())],
+        ),
+        MidiMessage::PolyPressure { note, pressure, .. } => (
+            "poly_pressure",
+            vec![("note", note.into()), ("pressure", pressure.into())],
+        ),
+        MidiMessage::ControlChange {
+            controller, value, ..
+        } => (
+            "control_change",
+            vec![("controller", controller.into()), ("value", value.into())],
+        ),
+        MidiMessage::ChannelMode { mode, .. } => {
+            let (controller, value) = mode.to_controller();
+            (
+                "channel_mode",
+                vec![("controller", controller.into()), ("value", value.into())],
+            )
+        }
+        MidiMessage::ProgramChange { program, .. } => {
+            ("program_change", vec![("program", program.into())])
+        }
+        MidiMessage::ChannelPressure { pressure, .. } => {
+            ("channel_pressure", vec![("pressure", pressure.into())])
+        }
+        MidiMessage::PitchBend { value, .. } => ("pitch_bend", vec![("value", value.into())]),
+        MidiMessage::SysEx(_) => ("sysex", Vec::new()),
+        MidiMessage::TimeCodeQuarterFrame(value) => ("time_code", vec![("value", value.into())]),
+        MidiMessage::SongPosition(position) => {
+            ("song_position", vec![("position", position.into())])
+        }
+        MidiMessage::SongSelect(song) => ("song_select", vec![("song", song.into())]),
+        MidiMessage::TuneRequest => ("tune_request", Vec::new()),
+        MidiMessage::TimingClock => ("clock", Vec::new()),
+        MidiMessage::Start => ("start", Vec::new()),
+        MidiMessage::Continue => ("continue", Vec::new()),
+        MidiMessage::Stop => ("stop", Vec::new()),
+        MidiMessage::ActiveSensing => ("active_sensing", Vec::new()),
+        MidiMessage::SystemReset => ("reset", Vec::new()),
+    };
+    (kind, channel, fields)
+}
+
+fn hex(message: &[u8]) -> String {
+    message
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{read_log, LogFormat, LogWriter};
+    use crate::capture::{CaptureSession, CapturedMessage};
+
+    fn session() -> CaptureSession {
+        CaptureSession {
+            messages: vec![
+                CapturedMessage {
+                    time: Duration::from_secs(0),
+                    message: vec![0xB1, 7, 100],
+                },
+                CapturedMessage {
+                    time: Duration::from_millis(1250),
+                    message: vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7],
+                },
+                CapturedMessage {
+                    time: Duration::from_millis(1500),
+                    message: vec![0x90, 60],
+                },
+            ],
+            dropped: 0,
+        }
+    }
+
+    fn write(format: LogFormat, port: &str) -> String {
+        let mut log = LogWriter::new(Vec::new(), format).port(port);
+        log.write_session(&session()).unwrap();
+        String::from_utf8(log.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            write(LogFormat::Csv, "Synth, \"A\""),
+            "time,port,type,channel,data1,data2,raw\n\
+             0.000000,\"Synth, \"\"A\"\"\",control_change,1,7,100,B1 07 64\n\
+             1.250000,\"Synth, \"\"A\"\"\",sysex,,,,F0 7E 7F 06 01 F7\n\
+             1.500000,\"Synth, \"\"A\"\"\",unknown,,,,90 3C\n"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_eq!(
+            write(LogFormat::JsonLines, "Synth \"A\""),
+            "{\"time\":0.000000,\"port\":\"Synth \\\"A\\\"\",\"type\":\"control_change\",\
+             \"channel\":1,\"controller\":7,\"value\":100,\"raw\":\"B1 07 64\"}\n\
+             {\"time\":1.250000,\"port\":\"Synth \\\"A\\\"\",\"type\":\"sysex\",\
+             \"raw\":\"F0 7E 7F 06 01 F7\"}\n\
+             {\"time\":1.500000,\"port\":\"Synth \\\"A\\\"\",\"type\":\"unknown\",\
+             \"raw\":\"90 3C\"}\n"
+        );
+    }
+
+    #[test]
+    fn read() {
+        for &format in &[LogFormat::Csv, LogFormat::JsonLines] {
+            let log = write(format, "Synth, \"A\"");
+            assert_eq!(read_log(log.as_bytes(), format).unwrap(), session());
+        }
+        let log = "\n{\"type\":\"clock\",\"raw\":\"F8\",\"time\":2.5}\n";
+        assert_eq!(
+            read_log(log.as_bytes(), LogFormat::JsonLines)
+                .unwrap()
+                .messages,
+            vec![CapturedMessage {
+                time: Duration::from_millis(2500),
+                message: vec![0xF8]
+            }]
+        );
+        assert!(read_log("0.5,Synth,,,,,9X".as_bytes(), LogFormat::Csv).is_err());
+        assert!(read_log("-1,Synth,,,,,F8".as_bytes(), LogFormat::Csv).is_err());
+        assert!(read_log("{\"time\":0}".as_bytes(), LogFormat::JsonLines).is_err());
+    }
+}
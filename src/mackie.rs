@@ -0,0 +1,487 @@
+use crate::error::RtMidiError;
+
+/// Mackie manufacturer ID
+const MACKIE_ID: [u8; 3] = [0x00, 0x00, 0x66];
+/// Model IDs of the Mackie Control main unit and extender
+const MACKIE_CONTROL: u8 = 0x14;
+const MACKIE_EXTENDER: u8 = 0x15;
+/// LCD write command ID
+const LCD: u8 = 0x12;
+/// Characters on the LCD (two lines of 56)
+const LCD_SIZE: u8 = 112;
+
+/// Strips, plus the master fader which is the last "strip"
+const STRIPS: u8 = 8;
+const MASTER: u8 = 8;
+/// First note of the fader touch sensors, one per strip and the master fader
+const FADER_TOUCH: u8 = 0x68;
+/// First controller of the V-Pots and of their LED rings, and the jog wheel controller
+const VPOT: u8 = 0x10;
+const VPOT_RING: u8 = 0x30;
+const JOG: u8 = 0x3C;
+
+/// Mackie Control button, identified by its note number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MackieButton {
+    /// Record arm of a strip (0-7)
+    Record(u8),
+    Solo(u8),
+    Mute(u8),
+    Select(u8),
+    /// Pressing a strip's V-Pot
+    VPot(u8),
+    Rewind,
+    FastForward,
+    Stop,
+    Play,
+    /// Transport record, rather than a strip's record arm
+    TransportRecord,
+    /// Any other button, by note number
+    Other(u8),
+}
+
+impl MackieButton {
+    /// Returns the button of a note number
+    pub fn from_note(note: u8) -> Self {
+        let strip = note % STRIPS;
+        match note & 0x7F {
+            0x00..=0x07 => MackieButton::Record(strip),
+            0x08..=0x0F => MackieButton::Solo(strip),
+            0x10..=0x17 => MackieButton::Mute(strip),
+            0x18..=0x1F => MackieButton::Select(strip),
+            0x20..=0x27 => MackieButton::VPot(strip),
+            0x5B => MackieButton::Rewind,
+            0x5C => MackieButton::FastForward,
+            0x5D => MackieButton::Stop,
+            0x5E => MackieButton::Play,
+            0x5F => MackieButton::TransportRecord,
+            note => MackieButton::Other(note),
+        }
+    }
+
+    /// Returns the note number of the button
+    pub fn note(&self) -> u8 {
+        match *self {
+            MackieButton::Record(strip) => strip % STRIPS,
+            MackieButton::Solo(strip) => 0x08 + strip % STRIPS,
+            MackieButton::Mute(strip) => 0x10 + strip % STRIPS,
+            MackieButton::Select(strip) => 0x18 + strip % STRIPS,
+            MackieButton::VPot(strip) => 0x20 + strip % STRIPS,
+            MackieButton::Rewind => 0x5B,
+            MackieButton::FastForward => 0x5C,
+            MackieButton::Stop => 0x5D,
+            MackieButton::Play => 0x5E,
+            MackieButton::TransportRecord => 0x5F,
+            MackieButton::Other(note) => note & 0x7F,
+        }
+    }
+}
+
+/// Message from a Mackie Control surface to the host
+///
+/// Strips are numbered 0-7, with the master fader as strip 8. Fader positions are 14-bit values
+/// (most surfaces only use the upper 10 bits). Sent with [`MackieEvent::to_bytes`] by controller
+/// emulators.
+/// ```
+/// use rtmidi::{MackieButton, MackieEvent};
+///
+/// assert_eq!(
+///     MackieEvent::parse(&[0xE2, 0x00, 0x40]).unwrap(),
+///     MackieEvent::Fader { strip: 2, position: 0x2000 }
+/// );
+/// assert_eq!(
+///     MackieEvent::parse(&[0xB0, 0x11, 0x43]).unwrap(),
+///     MackieEvent::VPot { strip: 1, ticks: -3 }
+/// );
+/// assert_eq!(
+///     MackieEvent::parse(&[0x90, 0x5E, 0x7F]).unwrap(),
+///     MackieEvent::Button { button: MackieButton::Play, pressed: true }
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MackieEvent {
+    Fader {
+        strip: u8,
+        position: u16,
+    },
+    FaderTouch {
+        strip: u8,
+        touched: bool,
+    },
+    /// A V-Pot turned by a number of ticks, negative for counterclockwise
+    VPot {
+        strip: u8,
+        ticks: i8,
+    },
+    /// The jog wheel turned by a number of ticks, negative for counterclockwise
+    Jog {
+        ticks: i8,
+    },
+    Button {
+        button: MackieButton,
+        pressed: bool,
+    },
+}
+
+impl MackieEvent {
+    /// Parse a message from a surface
+    pub fn parse(message: &[u8]) -> Result<Self, RtMidiError> {
+        let (status, data1, data2) = match *message {
+            [status, data1, data2] if data1 < 0x80 && data2 < 0x80 => (status, data1, data2),
+            _ => return Err(invalid("not a Mackie Control message")),
+        };
+        let channel = status & 0x0F;
+        Ok(match status & 0xF0 {
+            0xE0 if channel <= MASTER => MackieEvent::Fader {
+                strip: channel,
+                position: u16::from(data2) << 7 | u16::from(data1),
+            },
+            0x80 | 0x90 if channel == 0 => {
+                let pressed = status & 0xF0 == 0x90 && data2 > 0;
+                if (FADER_TOUCH..=FADER_TOUCH + MASTER).contains(&data1) {
+                    MackieEvent::FaderTouch {
+                        strip: data1 - FADER_TOUCH,
+                        touched: pressed,
+                    }
+                } else {
+                    MackieEvent::Button {
+                        button: MackieButton::from_note(data1),
+                        pressed,
+                    }
+                }
+            }
+            0xB0 if channel == 0 && (VPOT..VPOT + STRIPS).contains(&data1) => MackieEvent::VPot {
+                strip: data1 - VPOT,
+                ticks: decode_ticks(data2),
+            },
+            0xB0 if channel == 0 && data1 == JOG => MackieEvent::Jog {
+                ticks: decode_ticks(data2),
+            },
+            _ => return Err(invalid("not a Mackie Control message")),
+        })
+    }
+
+    /// Returns the message for the event
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            MackieEvent::Fader { strip, position } => fader(strip, position),
+            MackieEvent::FaderTouch { strip, touched } => {
+                vec![0x90, FADER_TOUCH + strip.min(MASTER), on(touched)]
+            }
+            MackieEvent::VPot { strip, ticks } => {
+                vec![0xB0, VPOT + strip % STRIPS, encode_ticks(ticks)]
+            }
+            MackieEvent::Jog { ticks } => vec![0xB0, JOG, encode_ticks(ticks)],
+            MackieEvent::Button { button, pressed } => vec![0x90, button.note(), on(pressed)],
+        }
+    }
+}
+
+/// State of a button's LED
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LedState {
+    #[default]
+    Off,
+    On,
+    Blink,
+}
+
+/// How a V-Pot's LED ring shows its position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RingMode {
+    /// A single LED
+    #[default]
+    Dot,
+    /// LEDs from the centre to the position, e.g. for gain
+    BoostCut,
+    /// LEDs from the left to the position, e.g. for send level
+    Wrap,
+    /// LEDs spreading out from the centre, e.g. for width
+    Spread,
+}
+
+/// Message from the host to a Mackie Control surface, parsed by surface emulators
+/// ```
+/// use rtmidi::{LedState, MackieButton, MackieFeedback};
+///
+/// let message = MackieFeedback::Led { button: MackieButton::Mute(3), state: LedState::On };
+/// assert_eq!(message.to_bytes(), [0x90, 0x13, 0x7F]);
+///
+/// let message = MackieFeedback::Lcd { extender: false, offset: 56, text: "Vocals".into() };
+/// assert_eq!(
+///     message.to_bytes(),
+///     [0xF0, 0x00, 0x00, 0x66, 0x14, 0x12, 56, b'V', b'o', b'c', b'a', b'l', b's', 0xF7]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MackieFeedback {
+    /// Move a motorised fader
+    Fader { strip: u8, position: u16 },
+    Led {
+        button: MackieButton,
+        state: LedState,
+    },
+    /// Set a V-Pot's LED ring to a position from 0 (off) to 11, optionally lighting the centre
+    /// LED below the ring
+    VPotRing {
+        strip: u8,
+        mode: RingMode,
+        position: u8,
+        centre: bool,
+    },
+    /// Set a strip's level meter, from 0 to 12 (the meter falls back by itself)
+    Meter { strip: u8, level: u8 },
+    /// Write text to the LCD of the main unit or an extender, starting at a character offset
+    /// (0-55 for the top line, 56-111 for the bottom line). Characters other than ASCII are
+    /// shown as `?`.
+    Lcd {
+        extender: bool,
+        offset: u8,
+        text: String,
+    },
+}
+
+impl MackieFeedback {
+    /// Parse a message from the host
+    pub fn parse(message: &[u8]) -> Result<Self, RtMidiError> {
+        if let Some(body) = message
+            .strip_prefix(&[0xF0])
+            .and_then(|body| body.strip_prefix(&MACKIE_ID[..]))
+        {
+            return match *body {
+                [model, LCD, offset, ref rest @ ..]
+                    if (model == MACKIE_CONTROL || model == MACKIE_EXTENDER)
+                        && offset < LCD_SIZE =>
+                {
+                    let text = rest
+                        .strip_suffix(&[0xF7])
+                        .filter(|text| text.iter().all(|&byte| byte < 0x80))
+                        .ok_or_else(|| invalid("invalid LCD message"))?;
+                    Ok(MackieFeedback::Lcd {
+                        extender: model == MACKIE_EXTENDER,
+                        offset,
+                        text: text.iter().map(|&byte| char::from(byte)).collect(),
+                    })
+                }
+                _ => Err(invalid("unknown system exclusive message")),
+            };
+        }
+        match *message {
+            [0xD0, value] if value < 0x80 => Ok(MackieFeedback::Meter {
+                strip: value >> 4,
+                level: value & 0x0F,
+            }),
+            [0x90, note, velocity] if note < 0x80 => Ok(MackieFeedback::Led {
+                button: MackieButton::from_note(note),
+                state: match velocity {
+                    0 => LedState::Off,
+                    1 => LedState::Blink,
+                    _ => LedState::On,
+                },
+            }),
+            [0xB0, controller, value]
+                if (VPOT_RING..VPOT_RING + STRIPS).contains(&controller) && value < 0x80 =>
+            {
+                Ok(MackieFeedback::VPotRing {
+                    strip: controller - VPOT_RING,
+                    mode: match value >> 4 & 0x03 {
+                        0 => RingMode::Dot,
+                        1 => RingMode::BoostCut,
+                        2 => RingMode::Wrap,
+                        _ => RingMode::Spread,
+                    },
+                    position: value & 0x0F,
+                    centre: value & 0x40 != 0,
+                })
+            }
+            _ => match MackieEvent::parse(message)? {
+                MackieEvent::Fader { strip, position } => {
+                    Ok(MackieFeedback::Fader { strip, position })
+                }
+                _ => Err(invalid("not a Mackie Control message")),
+            },
+        }
+    }
+
+    /// Returns the message for the feedback
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            MackieFeedback::Fader { strip, position } => fader(strip, position),
+            MackieFeedback::Led { button, state } => {
+                let velocity = match state {
+                    LedState::Off => 0x00,
+                    LedState::On => 0x7F,
+                    LedState::Blink => 0x01,
+                };
+                vec![0x90, button.note(), velocity]
+            }
+            MackieFeedback::VPotRing {
+                strip,
+                mode,
+                position,
+                centre,
+            } => {
+                let value = (centre as u8) << 6 | (mode as u8) << 4 | position.min(11);
+                vec![0xB0, VPOT_RING + strip % STRIPS, value]
+            }
+            MackieFeedback::Meter { strip, level } => {
+                vec![0xD0, (strip % STRIPS) << 4 | level.min(12)]
+            }
+            MackieFeedback::Lcd {
+                extender,
+                offset,
+                ref text,
+            } => {
+                let model = if extender {
+                    MACKIE_EXTENDER
+                } else {
+                    MACKIE_CONTROL
+                };
+                let offset = offset.min(LCD_SIZE - 1);
+                let mut message = vec![0xF0];
+                message.extend_from_slice(&MACKIE_ID);
+                message.extend_from_slice(&[model, LCD, offset]);
+                message.extend(
+                    text.chars()
+                        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                        .take(usize::from(LCD_SIZE - offset)),
+                );
+                message.push(0xF7);
+                message
+            }
+        }
+    }
+}
+
+fn fader(strip: u8, position: u16) -> Vec<u8> {
+    let position = position.min(0x3FFF);
+    vec![
+        0xE0 | strip.min(MASTER),
+        (position & 0x7F) as u8,
+        (position >> 7) as u8,
+    ]
+}
+
+fn on(on: bool) -> u8 {
+    if on {
+        0x7F
+    } else {
+        0x00
+    }
+}
+
+/// Relative controller values: the number of ticks, with bit 6 set for counterclockwise
+fn decode_ticks(value: u8) -> i8 {
+    let ticks = (value & 0x3F) as i8;
+    if value & 0x40 != 0 {
+        -ticks
+    } else {
+        ticks
+    }
+}
+
+fn encode_ticks(ticks: i8) -> u8 {
+    let magnitude = ticks.unsigned_abs().min(0x3F);
+    if ticks < 0 {
+        0x40 | magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn invalid(message: &str) -> RtMidiError {
+    RtMidiError::InvalidMessage(format!("Mackie Control: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LedState, MackieButton, MackieEvent, MackieFeedback, RingMode};
+
+    #[test]
+    fn events() {
+        let events = [
+            MackieEvent::Fader {
+                strip: 8,
+                position: 0x3FFF,
+            },
+            MackieEvent::FaderTouch {
+                strip: 0,
+                touched: true,
+            },
+            MackieEvent::VPot { strip: 7, ticks: 5 },
+            MackieEvent::Jog { ticks: -1 },
+            MackieEvent::Button {
+                button: MackieButton::Select(4),
+                pressed: false,
+            },
+            MackieEvent::Button {
+                button: MackieButton::Other(0x54),
+                pressed: true,
+            },
+        ];
+        for event in events.iter() {
+            assert_eq!(MackieEvent::parse(&event.to_bytes()).unwrap(), *event);
+        }
+        assert_eq!(
+            MackieEvent::parse(&[0x80, 0x1C, 0x40]).unwrap(),
+            MackieEvent::Button {
+                button: MackieButton::Select(4),
+                pressed: false,
+            }
+        );
+        assert!(MackieEvent::parse(&[0xE9, 0x00, 0x00]).is_err());
+        assert!(MackieEvent::parse(&[0xB0, 0x07, 0x7F]).is_err());
+    }
+
+    #[test]
+    fn feedback() {
+        let messages = [
+            MackieFeedback::Fader {
+                strip: 3,
+                position: 0x1234,
+            },
+            MackieFeedback::Led {
+                button: MackieButton::Record(0),
+                state: LedState::Blink,
+            },
+            MackieFeedback::VPotRing {
+                strip: 2,
+                mode: RingMode::BoostCut,
+                position: 6,
+                centre: true,
+            },
+            MackieFeedback::Meter { strip: 5, level: 9 },
+            MackieFeedback::Lcd {
+                extender: true,
+                offset: 7,
+                text: "Bass".to_string(),
+            },
+        ];
+        for message in messages.iter() {
+            assert_eq!(
+                MackieFeedback::parse(&message.to_bytes()).unwrap(),
+                *message
+            );
+        }
+        assert_eq!(
+            MackieFeedback::VPotRing {
+                strip: 2,
+                mode: RingMode::BoostCut,
+                position: 6,
+                centre: true,
+            }
+            .to_bytes(),
+            [0xB0, 0x32, 0x56]
+        );
+        // Text is cut off at the end of the LCD
+        let message = MackieFeedback::Lcd {
+            extender: false,
+            offset: 110,
+            text: "Drums".to_string(),
+        };
+        assert_eq!(
+            message.to_bytes(),
+            [0xF0, 0x00, 0x00, 0x66, 0x14, 0x12, 110, b'D', b'r', 0xF7]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use std::convert::TryFrom;
+
+use crate::error::RtMidiError;
+
+/// Channel Mode message (controllers 120-127)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelMode {
+    /// All Sound Off (CC120)
+    AllSoundOff,
+    /// Reset All Controllers (CC121)
+    ResetAllControllers,
+    /// Local Control on or off (CC122)
+    LocalControl(bool),
+    /// All Notes Off (CC123)
+    AllNotesOff,
+    /// Omni Mode Off (CC124)
+    OmniOff,
+    /// Omni Mode On (CC125)
+    OmniOn,
+    /// Mono Mode On with the given number of channels, or 0 for as many as the receiver has
+    /// voices (CC126)
+    MonoOn(u8),
+    /// Poly Mode On (CC127)
+    PolyOn,
+}
+
+impl ChannelMode {
+    /// Returns the Channel Mode message for a controller number and value, if it is one
+    pub fn from_controller(controller: u8, value: u8) -> Option<Self> {
+        Some(match controller {
+            120 => ChannelMode::AllSoundOff,
+            121 => ChannelMode::ResetAllControllers,
+            122 => ChannelMode::LocalControl(value >= 64),
+            123 => ChannelMode::AllNotesOff,
+            124 => ChannelMode::OmniOff,
+            125 => ChannelMode::OmniOn,
+            126 => ChannelMode::MonoOn(value),
+            127 => ChannelMode::PolyOn,
+            _ => return None,
+        })
+    }
+
+    /// Returns the controller number and value for this message
+    pub fn to_controller(self) -> (u8, u8) {
+        match self {
+            ChannelMode::AllSoundOff => (120, 0),
+            ChannelMode::ResetAllControllers => (121, 0),
+            ChannelMode::LocalControl(on) => (122, if on { 127 } else { 0 }),
+            ChannelMode::AllNotesOff => (123, 0),
+            ChannelMode::OmniOff => (124, 0),
+            ChannelMode::OmniOn => (125, 0),
+            ChannelMode::MonoOn(channels) => (126, channels & 0x7F),
+            ChannelMode::PolyOn => (127, 0),
+        }
+    }
+}
+
+/// A typed MIDI message
+///
+/// Channels are numbered 0-15. Control changes on controllers 120-127 are parsed as
+/// [`MidiMessage::ChannelMode`] rather than [`MidiMessage::ControlChange`].
+///
+/// ```
+/// use rtmidi::{ChannelMode, MidiMessage};
+///
+/// assert_eq!(
+///     MidiMessage::parse(&[0x93, 60, 100]).unwrap(),
+///     MidiMessage::NoteOn { channel: 3, note: 60, velocity: 100 }
+/// );
+/// assert_eq!(
+///     MidiMessage::parse(&[0xB0, 122, 0]).unwrap(),
+///     MidiMessage::ChannelMode { channel: 0, mode: ChannelMode::LocalControl(false) }
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ChannelMode {
+        channel: u8,
+        mode: ChannelMode,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    /// 14-bit pitch bend value (8192 is centre)
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    /// System exclusive message, including the leading `0xF0` and trailing `0xF7`
+    SysEx(Vec<u8>),
+    TimeCodeQuarterFrame(u8),
+    /// Song position in MIDI beats (sixteenth notes)
+    SongPosition(u16),
+    SongSelect(u8),
+    TuneRequest,
+    TimingClock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    SystemReset,
+}
+
+impl MidiMessage {
+    /// Parse a single, complete MIDI message
+    pub fn parse(bytes: &[u8]) -> Result<Self, RtMidiError> {
+        let (&status, data) = bytes
+            .split_first()
+            .ok_or_else(|| invalid("empty message"))?;
+        if status < 0x80 {
+            return Err(invalid(format!(
+                "missing status byte (found 0x{:02x})",
+                status
+            )));
+        }
+        if status == 0xF0 {
+            return match data.split_last() {
+                Some((0xF7, body)) if body.iter().all(|&byte| byte < 0x80) => {
+                    Ok(MidiMessage::SysEx(bytes.to_vec()))
+                }
+                Some((0xF7, _)) => Err(invalid("status byte inside system exclusive message")),
+                _ => Err(invalid("unterminated system exclusive message")),
+            };
+        }
+        if let Some(&byte) = data.iter().find(|&&byte| byte >= 0x80) {
+            return Err(invalid(format!(
+                "unexpected status byte 0x{:02x} in data",
+                byte
+            )));
+        }
+        let expected = data_length(status);
+        if data.len() != expected {
+            return Err(invalid(format!(
+                "status 0x{:02x} expects {} data bytes but found {}",
+                status,
+                expected,
+                data.len()
+            )));
+        }
+        let channel = status & 0x0F;
+        let data14 = |lsb: u8, msb: u8| (msb as u16) << 7 | lsb as u16;
+        Ok(match (status & 0xF0, status, data) {
+            (0x80, _, &[note, velocity]) => MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            },
+            (0x90, _, &[note, velocity]) => MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            },
+            (0xA0, _, &[note, pressure]) => MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            },
+            (0xB0, _, &[controller, value]) => {
+                match ChannelMode::from_controller(controller, value) {
+                    Some(mode) => MidiMessage::ChannelMode { channel, mode },
+                    None => MidiMessage::ControlChange {
+                        channel,
+                        controller,
+                        value,
+                    },
+                }
+            }
+            (0xC0, _, &[program]) => MidiMessage::ProgramChange { channel, program },
+            (0xD0, _, &[pressure]) => MidiMessage::ChannelPressure { channel, pressure },
+            (0xE0, _, &[lsb, msb]) => MidiMessage::PitchBend {
+                channel,
+                value: data14(lsb, msb),
+            },
+            (_, 0xF1, &[value]) => MidiMessage::TimeCodeQuarterFrame(value),
+            (_, 0xF2, &[lsb, msb]) => MidiMessage::SongPosition(data14(lsb, msb)),
+            (_, 0xF3, &[song]) => MidiMessage::SongSelect(song),
+            (_, 0xF6, _) => MidiMessage::TuneRequest,
+            (_, 0xF8, _) => MidiMessage::TimingClock,
+            (_, 0xFA, _) => MidiMessage::Start,
+            (_, 0xFB, _) => MidiMessage::Continue,
+            (_, 0xFC, _) => MidiMessage::Stop,
+            (_, 0xFE, _) => MidiMessage::ActiveSensing,
+            (_, 0xFF, _) => MidiMessage::SystemReset,
+            _ => return Err(invalid(format!("undefined status byte 0x{:02x}", status))),
+        })
+    }
+
+    /// Returns the channel (0-15) of a channel message
+    pub fn channel(&self) -> Option<u8> {
+        match *self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ChannelMode { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// Encode the message as bytes. Channels and data values are masked to their valid range.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let channel = |status: u8, channel: u8| status | (channel & 0x0F);
+        let data14 = |value: u16| [(value & 0x7F) as u8, ((value >> 7) & 0x7F) as u8];
+        let mut bytes = match *self {
+            MidiMessage::NoteOff {
+                channel: c,
+                note,
+                velocity,
+            } => vec![channel(0x80, c), note, velocity],
+            MidiMessage::NoteOn {
+                channel: c,
+                note,
+                velocity,
+            } => vec![channel(0x90, c), note, velocity],
+            MidiMessage::PolyPressure {
+                channel: c,
+                note,
+                pressure,
+            } => vec![channel(0xA0, c), note, pressure],
+            MidiMessage::ControlChange {
+                channel: c,
+                controller,
+                value,
+            } => vec![channel(0xB0, c), controller, value],
+            MidiMessage::ChannelMode { channel: c, mode } => {
+                let (controller, value) = mode.to_controller();
+                vec![channel(0xB0, c), controller, value]
+            }
+            MidiMessage::ProgramChange {
+                channel: c,
+                program,
+            } => vec![channel(0xC0, c), program],
+            MidiMessage::ChannelPressure {
+                channel: c,
+                pressure,
+            } => vec![channel(0xD0, c), pressure],
+            MidiMessage::PitchBend { channel: c, value } => {
+                let [lsb, msb] = data14(value);
+                vec![channel(0xE0, c), lsb, msb]
+            }
+            MidiMessage::SysEx(ref bytes) => return bytes.clone(),
+            MidiMessage::TimeCodeQuarterFrame(value) => vec![0xF1, value],
+            MidiMessage::SongPosition(position) => {
+                let [lsb, msb] = data14(position);
+                vec![0xF2, lsb, msb]
+            }
+            MidiMessage::SongSelect(song) => vec![0xF3, song],
+            MidiMessage::TuneRequest => vec![0xF6],
+            MidiMessage::TimingClock => vec![0xF8],
+            MidiMessage::Start => vec![0xFA],
+            MidiMessage::Continue => vec![0xFB],
+            MidiMessage::Stop => vec![0xFC],
+            MidiMessage::ActiveSensing => vec![0xFE],
+            MidiMessage::SystemReset => vec![0xFF],
+        };
+        for byte in bytes.iter_mut().skip(1) {
+            *byte &= 0x7F;
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for MidiMessage {
+    type Error = RtMidiError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        MidiMessage::parse(bytes)
+    }
+}
+
+impl From<&MidiMessage> for Vec<u8> {
+    fn from(message: &MidiMessage) -> Self {
+        message.to_bytes()
+    }
+}
+
+/// A message of up to three bytes (any message other than system exclusive), stored inline
+///
+/// Created from a byte array for [`crate::RtMidiOut::short_message`].
+/// ```
+/// use rtmidi::ShortMessage;
+///
+/// let message = ShortMessage::from([0x90, 60, 100]);
+/// assert_eq!(message.as_bytes(), &[0x90, 60, 100]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShortMessage {
+    bytes: [u8; 3],
+    len: u8,
+}
+
+impl ShortMessage {
+    /// Returns the bytes of the message
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl AsRef<[u8]> for ShortMessage {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl From<[u8; 1]> for ShortMessage {
+    fn from([status]: [u8; 1]) -> Self {
+        ShortMessage {
+            bytes: [status, 0, 0],
+            len: 1,
+        }
+    }
+}
+
+impl From<[u8; 2]> for ShortMessage {
+    fn from([status, data]: [u8; 2]) -> Self {
+        ShortMessage {
+            bytes: [status, data, 0],
+            len: 2,
+        }
+    }
+}
+
+impl From<[u8; 3]> for ShortMessage {
+    fn from(bytes: [u8; 3]) -> Self {
+        ShortMessage { bytes, len: 3 }
+    }
+}
+
+/// Returns the number of data bytes following a (non system exclusive) status byte
+fn data_length(status: u8) -> usize {
+    crate::decoder::data_length(status).unwrap_or(0)
+}
+
+fn invalid<T: Into<String>>(reason: T) -> RtMidiError {
+    RtMidiError::InvalidMessage(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelMode, MidiMessage, ShortMessage};
+    use crate::error::RtMidiError;
+
+    #[test]
+    fn parse() {
+        let messages: &[(&[u8], MidiMessage)] = &[
+            (
+                &[0x81, 60, 64],
+                MidiMessage::NoteOff {
+                    channel: 1,
+                    note: 60,
+                    velocity: 64,
+                },
+            ),
+            (
+                &[0xBF, 7, 100],
+                MidiMessage::ControlChange {
+                    channel: 15,
+                    controller: 7,
+                    value: 100,
+                },
+            ),
+            (
+                &[0xE0, 0, 64],
+                MidiMessage::PitchBend {
+                    channel: 0,
+                    value: 8192,
+                },
+            ),
+            (
+                &[0xC2, 5],
+                MidiMessage::ProgramChange {
+                    channel: 2,
+                    program: 5,
+                },
+            ),
+            (&[0xF2, 1, 1], MidiMessage::SongPosition(129)),
+            (
+                &[0xF0, 0x7E, 0xF7],
+                MidiMessage::SysEx(vec![0xF0, 0x7E, 0xF7]),
+            ),
+            (&[0xF8], MidiMessage::TimingClock),
+        ];
+        for (bytes, message) in messages {
+            assert_eq!(&MidiMessage::parse(bytes).unwrap(), message);
+            assert_eq!(&message.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn parse_channel_mode() {
+        let modes = [
+            (120, 0, ChannelMode::AllSoundOff),
+            (121, 0, ChannelMode::ResetAllControllers),
+            (122, 127, ChannelMode::LocalControl(true)),
+            (122, 0, ChannelMode::LocalControl(false)),
+            (123, 0, ChannelMode::AllNotesOff),
+            (124, 0, ChannelMode::OmniOff),
+            (125, 0, ChannelMode::OmniOn),
+            (126, 4, ChannelMode::MonoOn(4)),
+            (127, 0, ChannelMode::PolyOn),
+        ];
+        for &(controller, value, mode) in modes.iter() {
+            let message = MidiMessage::ChannelMode { channel: 2, mode };
+            assert_eq!(
+                MidiMessage::parse(&[0xB2, controller, value]).unwrap(),
+                message
+            );
+            assert_eq!(message.to_bytes(), vec![0xB2, controller, value]);
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let invalid: &[&[u8]] = &[
+            &[],
+            &[60, 90],
+            &[0x90, 60],
+            &[0x90, 60, 90, 1],
+            &[0x90, 0x80, 90],
+            &[0xF0, 1, 2],
+            &[0xF0, 1, 0x90, 0xF7],
+            &[0xF4],
+        ];
+        for bytes in invalid {
+            assert!(matches!(
+                MidiMessage::parse(bytes),
+                Err(RtMidiError::InvalidMessage(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn short_message() {
+        assert_eq!(ShortMessage::from([0xF8]).as_bytes(), &[0xF8]);
+        assert_eq!(ShortMessage::from([0xC0, 5]).as_bytes(), &[0xC0, 5]);
+        assert_eq!(
+            ShortMessage::from([0x90, 60, 100]).as_ref(),
+            &[0x90, 60, 100]
+        );
+    }
+}
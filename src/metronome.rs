@@ -0,0 +1,254 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::clock::{self, Clock};
+use crate::error::RtMidiError;
+use crate::scheduler::Scheduler;
+use crate::threads;
+
+/// How far ahead clicks are scheduled
+const LOOKAHEAD: Duration = Duration::from_millis(100);
+
+/// Interval at which a [`MetronomeRunner`] schedules clicks
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Metronome
+///
+/// Clicks on every beat of a [`Scheduler`]'s clock, with one note on the first beat of each bar
+/// and another on the other beats (General MIDI wood blocks on channel 10 by default). Clicks are
+/// scheduled a little ahead from a separate thread, so they are as accurate as any other
+/// scheduled message and follow changes to the clock.
+///
+/// A count-in of a number of bars can be played before an action, such as starting playback or
+/// recording, with [`Metronome::count_in`].
+/// ```no_run
+/// use rtmidi::{Bars, Beats, Metronome, RtMidiError, RtMidiOut};
+///
+/// fn record(output: &RtMidiOut) -> Result<(), RtMidiError> {
+///     let scheduler = output.scheduler()?;
+///     scheduler.set_tempo(96.0);
+///     let metronome = Metronome::new().channel(0).notes(84, 72).velocity(80);
+///     let _count_in = metronome.count_in(scheduler.clone(), 2, move || {
+///         // The song starts at beat zero, two bars after the count-in started
+///         println!("Recording...");
+///         scheduler.schedule_at_bar(Bars(0), Beats(0.0), &[144, 60, 90]).unwrap();
+///     });
+///     // Keep the count-in (and the process) alive until the action has run...
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metronome {
+    channel: u8,
+    downbeat: u8,
+    beat: u8,
+    velocity: u8,
+    length: Duration,
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Metronome {
+            channel: 9,
+            downbeat: 76,
+            beat: 77,
+            velocity: 100,
+            length: Duration::from_millis(50),
+        }
+    }
+}
+
+impl Metronome {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the channel of the clicks (0 to 15, default 9)
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = channel & 0x0F;
+        self
+    }
+
+    /// Set the notes played on the first beat of each bar and on the other beats (default 76
+    /// and 77)
+    pub fn notes(mut self, downbeat: u8, beat: u8) -> Self {
+        self.downbeat = downbeat & 0x7F;
+        self.beat = beat & 0x7F;
+        self
+    }
+
+    /// Set the velocity of the clicks (default 100)
+    pub fn velocity(mut self, velocity: u8) -> Self {
+        self.velocity = velocity.clamp(1, 0x7F);
+        self
+    }
+
+    /// Set the time between the Note On and Note Off of each click (default 50ms)
+    pub fn length(mut self, length: Duration) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Schedule the click of a beat, counted from beat zero of the scheduler's clock (and so
+    /// negative before it)
+    pub fn schedule(&self, scheduler: &Scheduler, beat: i64) -> Result<(), RtMidiError> {
+        let note = self.note(beat, scheduler.beats_per_bar());
+        let at = scheduler.instant_at(beat as f64);
+        scheduler.schedule_at(at, &[0x90 | self.channel, note, self.velocity])?;
+        scheduler.schedule_at(at + self.length, &[0x80 | self.channel, note, 0])
+    }
+
+    /// Click on every beat from the next, until the returned [`MetronomeRunner`] is dropped
+    pub fn start(&self, scheduler: Scheduler) -> MetronomeRunner {
+        let next = scheduler.beat_at(Instant::now()).ceil() as i64;
+        self.run(scheduler, next, None)
+    }
+
+    /// Play a count-in of `bars` bars, then run `action`.
+    ///
+    /// The scheduler's clock is moved (keeping its tempo or tempo map) so beat zero is at the
+    /// end of the count-in, which starts now. `action` is run at beat zero from the metronome's
+    /// thread, unless the returned [`MetronomeRunner`] is dropped first. The metronome stops
+    /// after the count-in; use [`Metronome::start`] to keep it clicking.
+    pub fn count_in<F>(&self, scheduler: Scheduler, bars: u32, action: F) -> MetronomeRunner
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let beats = bars * scheduler.beats_per_bar();
+        let clock = scheduler.clock();
+        let tempo_map = scheduler.tempo_map();
+        let seconds = match &tempo_map {
+            Some(tempo_map) => -tempo_map.seconds_at(-f64::from(beats)),
+            None => f64::from(beats) * 60.0 / clock.tempo(),
+        };
+        scheduler.set_clock(Clock::new(
+            clock::offset(Instant::now(), seconds),
+            clock.tempo(),
+        ));
+        scheduler.set_tempo_map(tempo_map);
+        self.run(scheduler, -i64::from(beats), Some(Box::new(action)))
+    }
+
+    /// Returns the note of the click of a beat
+    fn note(&self, beat: i64, beats_per_bar: u32) -> u8 {
+        if beat.rem_euclid(i64::from(beats_per_bar.max(1))) == 0 {
+            self.downbeat
+        } else {
+            self.beat
+        }
+    }
+
+    fn run(
+        &self,
+        scheduler: Scheduler,
+        mut next: i64,
+        mut action: Option<Box<dyn FnOnce() + Send>>,
+    ) -> MetronomeRunner {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (metronome, stop) = (self.clone(), Arc::clone(&stop));
+            threads::spawn("rtmidi-metronome".to_string(), move || {
+                // A count-in ends at beat zero
+                let end = if action.is_some() { 0 } else { i64::MAX };
+                while !stop.load(Ordering::Relaxed) {
+                    let now = Instant::now();
+                    let horizon = scheduler.beat_at(now + LOOKAHEAD);
+                    while (next as f64) < horizon && next < end {
+                        if metronome.schedule(&scheduler, next).is_err() {
+                            return;
+                        }
+                        next += 1;
+                    }
+                    let mut sleep = POLL_INTERVAL;
+                    if action.is_some() {
+                        let start = scheduler.instant_at(0.0);
+                        if start <= now {
+                            if let Some(action) = action.take() {
+                                action();
+                            }
+                            return;
+                        }
+                        sleep = sleep.min(start - now);
+                    }
+                    thread::sleep(sleep);
+                }
+            })
+        };
+        MetronomeRunner {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Runs a [`Metronome`] until dropped
+pub struct MetronomeRunner {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetronomeRunner {
+    /// Returns [`true`] once the metronome has stopped: after a count-in's action has run, or if
+    /// its output was dropped
+    pub fn is_finished(&self) -> bool {
+        match &self.thread {
+            Some(thread) => thread.is_finished(),
+            None => true,
+        }
+    }
+}
+
+impl Drop for MetronomeRunner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use super::Metronome;
+    use crate::midi_out::RtMidiOut;
+    use crate::tempo::TempoMap;
+
+    #[test]
+    fn notes() {
+        let metronome = Metronome::new().notes(60, 61);
+        assert_eq!(metronome.note(0, 3), 60);
+        assert_eq!(metronome.note(2, 3), 61);
+        assert_eq!(metronome.note(-3, 3), 60);
+        assert_eq!(metronome.note(-1, 3), 61);
+    }
+
+    #[test]
+    fn count_in() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let scheduler = output.scheduler().unwrap();
+        // A count-in of two bars of 10ms beats
+        let tempo_map = TempoMap::new(6000.0).change(8.0, 12000.0);
+        scheduler.set_tempo_map(Some(tempo_map.clone()));
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
+        let runner = {
+            let scheduler = scheduler.clone();
+            Metronome::new().count_in(scheduler.clone(), 2, move || {
+                sender.send(scheduler.beat_at(Instant::now())).unwrap();
+            })
+        };
+        let beat = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(80));
+        assert!((0.0..1.0).contains(&beat));
+        assert_eq!(scheduler.tempo_map(), Some(tempo_map));
+        drop(runner);
+
+        let runner = Metronome::new().start(scheduler);
+        assert!(!runner.is_finished());
+    }
+}
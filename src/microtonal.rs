@@ -0,0 +1,144 @@
+/// Pitch bend centre, for channels without a microtonal note
+const CENTRE: u16 = 0x2000;
+
+/// A note sent with [`crate::RtMidiOut::send_note_cents`], to end with
+/// [`crate::RtMidiOut::release_note_cents`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MicrotonalNote {
+    /// Channel the note was sent on, which may differ from the one asked for when rotating
+    pub channel: u8,
+    pub note: u8,
+    /// Pitch bend sent before the note
+    pub bend: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    bend: u16,
+    // Notes sounding with the bend
+    notes: usize,
+    // When the channel was last given a note, to rotate through the least recently used
+    used: u64,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        ChannelState {
+            bend: CENTRE,
+            notes: 0,
+            used: 0,
+        }
+    }
+}
+
+/// Picks channels for microtonal notes, so that notes needing different pitch bends don't share
+/// a channel where it can be avoided
+#[derive(Debug)]
+pub(crate) struct Microtonal {
+    // Consecutive channels to rotate across, starting from the note's channel
+    rotation: u8,
+    channels: [ChannelState; 16],
+    counter: u64,
+}
+
+impl Default for Microtonal {
+    fn default() -> Self {
+        Microtonal {
+            rotation: 1,
+            channels: [ChannelState::default(); 16],
+            counter: 0,
+        }
+    }
+}
+
+impl Microtonal {
+    pub fn set_rotation(&mut self, channels: u8) {
+        self.rotation = channels.clamp(1, 16);
+    }
+
+    /// Returns the channel to send a note needing a bend on, starting from `channel`, and
+    /// whether the bend has to be sent first. The channel is one already bent by the same
+    /// amount, otherwise the least recently used free channel, otherwise the least recently
+    /// used channel.
+    pub fn note_on(&mut self, channel: u8, bend: u16) -> (u8, bool) {
+        let pool = (0..self.rotation).map(|offset| (channel + offset) % 16);
+        let channel = pool
+            .clone()
+            .find(|&c| {
+                let state = &self.channels[usize::from(c)];
+                state.notes > 0 && state.bend == bend
+            })
+            .or_else(|| {
+                pool.clone()
+                    .filter(|&c| self.channels[usize::from(c)].notes == 0)
+                    .min_by_key(|&c| self.channels[usize::from(c)].used)
+            })
+            .or_else(|| pool.min_by_key(|&c| self.channels[usize::from(c)].used))
+            .unwrap_or(channel);
+        self.counter += 1;
+        let state = &mut self.channels[usize::from(channel)];
+        let changed = state.bend != bend;
+        if changed {
+            // Any notes still sounding have been bent away, so stop counting them
+            state.notes = 0;
+        }
+        state.bend = bend;
+        state.notes += 1;
+        state.used = self.counter;
+        (channel, changed)
+    }
+
+    /// Records a note ending, returning whether the channel's bend should be restored to the
+    /// centre as its last microtonal note has ended
+    pub fn note_off(&mut self, note: &MicrotonalNote) -> bool {
+        let state = &mut self.channels[usize::from(note.channel & 0x0F)];
+        if state.bend != note.bend || state.notes == 0 {
+            return false;
+        }
+        state.notes -= 1;
+        if state.notes == 0 && state.bend != CENTRE {
+            state.bend = CENTRE;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Microtonal, MicrotonalNote};
+
+    fn note(channel: u8, bend: u16) -> MicrotonalNote {
+        MicrotonalNote {
+            channel,
+            note: 60,
+            bend,
+        }
+    }
+
+    #[test]
+    fn single_channel() {
+        let mut microtonal = Microtonal::default();
+        assert_eq!(microtonal.note_on(2, 8192), (2, false));
+        assert_eq!(microtonal.note_on(2, 9000), (2, true));
+        assert_eq!(microtonal.note_on(2, 9000), (2, false));
+        assert!(!microtonal.note_off(&note(2, 9000)));
+        assert!(microtonal.note_off(&note(2, 9000)));
+        assert!(!microtonal.note_off(&note(2, 9000)));
+    }
+
+    #[test]
+    fn rotation() {
+        let mut microtonal = Microtonal::default();
+        microtonal.set_rotation(3);
+        assert_eq!(microtonal.note_on(1, 9000), (1, true));
+        assert_eq!(microtonal.note_on(1, 7000), (2, true));
+        assert_eq!(microtonal.note_on(1, 9000), (1, false));
+        assert_eq!(microtonal.note_on(1, 8192), (3, false));
+        // Every channel is busy, so the least recently used is taken over
+        assert_eq!(microtonal.note_on(1, 6000), (2, true));
+        assert!(!microtonal.note_off(&note(2, 7000)));
+        assert!(microtonal.note_off(&note(2, 6000)));
+        assert_eq!(microtonal.note_on(1, 5000), (2, true));
+    }
+}
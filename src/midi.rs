@@ -1,7 +1,18 @@
 use std::ffi::{CStr, CString};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::error::RtMidiError;
+use crate::event::{EventHandler, RtMidiEvent};
 use crate::ffi;
+use crate::history::{History, MessageDirection};
+#[cfg(feature = "jack")]
+use crate::jack;
+use crate::notes::{NotesOffPolicy, SoundingNotes};
+use crate::ports::OwnName;
+use crate::quirks::{QuirkState, Quirks};
+#[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+use crate::virtualmidi::{HandlerSlot, VirtualPort};
 use crate::RtMidiPort;
 
 pub fn open_port<T: AsRef<str>>(
@@ -46,13 +57,389 @@ pub fn port_name<'a>(
     ptr: *mut ffi::RtMidiWrapper,
     port_number: RtMidiPort,
 ) -> Result<&'a str, RtMidiError> {
+    Ok(port_name_c(ptr, port_number)?.to_str()?)
+}
+
+/// Returns the name of a port, replacing any invalid UTF-8
+pub fn port_name_lossy(
+    ptr: *mut ffi::RtMidiWrapper,
+    port_number: RtMidiPort,
+) -> Result<String, RtMidiError> {
+    Ok(port_name_c(ptr, port_number)?
+        .to_string_lossy()
+        .into_owned())
+}
+
+fn port_name_c<'a>(
+    ptr: *mut ffi::RtMidiWrapper,
+    port_number: RtMidiPort,
+) -> Result<&'a CStr, RtMidiError> {
     let port_name = unsafe { ffi::rtmidi_get_port_name(ptr, port_number) };
     match unsafe { Result::<(), RtMidiError>::from(*ptr) } {
         Ok(_) if port_name.is_null() => Err(RtMidiError::NullPointer),
-        Ok(_) => {
-            let port_name = unsafe { CStr::from_ptr(port_name) };
-            Ok(port_name.to_str()?)
-        }
+        Ok(_) => Ok(unsafe { CStr::from_ptr(port_name) }),
         Err(e) => Err(e),
     }
 }
+
+/// Returns the aliases of a JACK port
+#[cfg(feature = "jack")]
+pub fn port_aliases(
+    ptr: *mut ffi::RtMidiWrapper,
+    port_number: RtMidiPort,
+) -> Result<Vec<String>, RtMidiError> {
+    let name = port_name(ptr, port_number)?;
+    jack::JackClient::new()?.port_aliases(name)
+}
+
+pub fn send_message(ptr: *mut ffi::RtMidiWrapper, message: &[u8]) -> Result<(), RtMidiError> {
+    unsafe {
+        ffi::rtmidi_out_send_message(ptr, message.as_ptr(), message.len() as i32);
+        (*ptr).into()
+    }
+}
+
+/// Policy for recovering from backend errors
+///
+/// When sending or receiving fails with a driver or system error (e.g. after a USB reset), the
+/// port is closed and re-opened (by name, so it is found even if the port numbering changed)
+/// and the operation retried, up to `retries` times with `delay` between attempts. The original
+/// error is returned if the connection can't be recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        RecoveryPolicy {
+            retries: 3,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Output through the MIDI system's own API, to the destination of an RtMidi output, which can
+/// send messages at a future time (which RtMidi can't)
+pub trait TimedOutput: Send {
+    /// Send a message at a future time, or immediately if the time has passed
+    fn send_at(&mut self, at: Instant, message: &[u8]) -> Result<(), RtMidiError>;
+}
+
+/// How the open port was opened, so it can be opened again
+#[derive(Debug, Clone)]
+enum Connection {
+    Port { device_name: String, name: String },
+    Virtual(String),
+}
+
+/// An RtMidi device pointer that may be moved to an internal thread.
+///
+/// RtMidi instances are not thread-safe, so every access must be serialised by the owner (e.g.
+/// with a [`std::sync::Mutex`]).
+pub struct Device {
+    pub ptr: *mut ffi::RtMidiWrapper,
+    pub recovery: Option<RecoveryPolicy>,
+    pub history: History,
+    connection: Option<Connection>,
+    events: EventHandler,
+    // Quirks of the device the output is connected to, if it has any
+    quirks: Option<QuirkState>,
+    notes: SoundingNotes,
+    // Registered so that enumeration can recognise this client's ports
+    client: OwnName,
+    _virtual_port: Option<OwnName>,
+    // teVirtualMIDI port standing in for a virtual port of the Windows MM API
+    #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+    virtual_midi: Option<VirtualPort>,
+    // Handler of the messages received by a teVirtualMIDI input, set with the input's callback
+    #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+    pub virtual_handler: HandlerSlot,
+    // Sends to the same destination as the output through the MIDI system, for messages sent
+    // ahead of time
+    timed: Option<Box<dyn TimedOutput>>,
+}
+
+unsafe impl Send for Device {}
+
+impl Device {
+    pub fn new(ptr: *mut ffi::RtMidiWrapper, client_name: &str, events: EventHandler) -> Self {
+        Device {
+            ptr,
+            recovery: None,
+            history: History::default(),
+            connection: None,
+            events,
+            quirks: None,
+            notes: SoundingNotes::default(),
+            client: OwnName::new(client_name),
+            _virtual_port: None,
+            #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+            virtual_midi: None,
+            #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+            virtual_handler: HandlerSlot::default(),
+            timed: None,
+        }
+    }
+
+    pub fn open_port(&mut self, port_number: RtMidiPort, name: &str) -> Result<(), RtMidiError> {
+        let device_name = port_name(self.ptr, port_number)?.to_string();
+        open_port(self.ptr, port_number, name)?;
+        self.connection = Some(Connection::Port {
+            device_name,
+            name: name.to_string(),
+        });
+        self._virtual_port = None;
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        {
+            self.virtual_midi = None;
+        }
+        self.timed = None;
+        Ok(())
+    }
+
+    pub fn open_virtual_port(&mut self, port_name: &str) -> Result<(), RtMidiError> {
+        open_virtual_port(self.ptr, port_name)?;
+        self.connection = Some(Connection::Virtual(port_name.to_string()));
+        self._virtual_port = Some(OwnName::new(port_name));
+        self.quirks = None;
+        self.timed = None;
+        Ok(())
+    }
+
+    /// Send messages ahead of time through an output to the open port's destination
+    #[cfg_attr(
+        not(any(
+            feature = "jack",
+            all(feature = "alsa", target_os = "linux"),
+            all(feature = "coremidi", target_os = "macos")
+        )),
+        allow(dead_code)
+    )]
+    pub fn set_timed_output(&mut self, output: Option<Box<dyn TimedOutput>>) {
+        self.timed = output;
+    }
+
+    /// Use a teVirtualMIDI port as the virtual port, closing any port opened through RtMidi
+    #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+    pub fn open_virtual_midi(&mut self, port: VirtualPort, port_name: &str) {
+        let _ = close_port(self.ptr);
+        self.connection = Some(Connection::Virtual(port_name.to_string()));
+        self._virtual_port = Some(OwnName::new(port_name));
+        self.quirks = None;
+        self.virtual_midi = Some(port);
+    }
+
+    pub fn close_port(&mut self) -> Result<(), RtMidiError> {
+        // The port is closed even if the notes can't be ended
+        let _ = self.release_notes();
+        self.connection = None;
+        self._virtual_port = None;
+        self.quirks = None;
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        {
+            self.virtual_midi = None;
+        }
+        self.timed = None;
+        close_port(self.ptr)
+    }
+
+    /// Send a message with recovery, recording it in the history and working around the
+    /// device's quirks
+    pub fn send(&mut self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.history.record(MessageDirection::Output, message);
+        self.notes.record(message);
+        match self.quirks.take() {
+            None => self.send_packet(message),
+            Some(mut state) => {
+                let result = state.send(message, |packet| self.send_packet(packet));
+                self.quirks = Some(state);
+                result
+            }
+        }
+    }
+
+    /// Returns whether messages can be handed to the MIDI system ahead of time with
+    /// [`Device::send_at`], which needs a timed output and a device without quirks
+    pub fn can_send_at(&self) -> bool {
+        self.timed.is_some() && self.quirks.is_none()
+    }
+
+    /// Send a message at a future time, recording it in the history now. Sent immediately if
+    /// the MIDI system can't send it later (see [`Device::can_send_at`]).
+    pub fn send_at(&mut self, at: Instant, message: &[u8]) -> Result<(), RtMidiError> {
+        if let (Some(output), None) = (self.timed.as_mut(), &self.quirks) {
+            self.history.record(MessageDirection::Output, message);
+            self.notes.record(message);
+            return output.send_at(at, message);
+        }
+        self.send(message)
+    }
+
+    fn send_packet(&mut self, packet: &[u8]) -> Result<(), RtMidiError> {
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        if let Some(port) = &self.virtual_midi {
+            return port.send(packet);
+        }
+        self.with_recovery(|ptr| send_message(ptr, packet))
+    }
+
+    pub fn set_notes_off_policy(&mut self, policy: NotesOffPolicy) {
+        self.notes.set_policy(policy);
+    }
+
+    /// Send the Note Offs of the notes-off policy, if a port is open
+    pub fn release_notes(&mut self) -> Result<(), RtMidiError> {
+        if self.connection.is_none() {
+            return Ok(());
+        }
+        for message in self.notes.notes_off() {
+            self.send(&message)?;
+        }
+        Ok(())
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+            .as_ref()
+            .map(|state| state.quirks)
+            .unwrap_or_default()
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = if quirks.is_empty() {
+            None
+        } else {
+            Some(QuirkState::new(quirks))
+        };
+    }
+
+    /// Run an operation on the device, recovering the connection and retrying according to the
+    /// recovery policy if it fails.
+    ///
+    /// If the operation still fails and the device has disappeared, [`RtMidiError::Disconnected`]
+    /// is returned and a [`RtMidiEvent::Disconnected`] event emitted.
+    pub fn with_recovery<T, F>(&mut self, mut f: F) -> Result<T, RtMidiError>
+    where
+        F: FnMut(*mut ffi::RtMidiWrapper) -> Result<T, RtMidiError>,
+    {
+        let result = f(self.ptr);
+        if let (Err(RtMidiError::Error(_)), Some(policy), Some(_)) =
+            (&result, self.recovery, &self.connection)
+        {
+            for _ in 0..policy.retries {
+                thread::sleep(policy.delay);
+                if self.reconnect().is_ok() {
+                    if let Ok(value) = f(self.ptr) {
+                        return Ok(value);
+                    }
+                }
+            }
+        }
+        match result {
+            Err(RtMidiError::Error(_)) if self.is_disconnected() => {
+                self.events.emit(RtMidiEvent::Disconnected);
+                Err(RtMidiError::Disconnected)
+            }
+            result => result,
+        }
+    }
+
+    pub fn client_name(&self) -> &str {
+        self.client.name()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Returns the name of the device the port is connected to, or of the virtual port
+    pub fn port_label(&self) -> Option<&str> {
+        match &self.connection {
+            Some(Connection::Port { device_name, .. }) => Some(device_name),
+            Some(Connection::Virtual(name)) => Some(name),
+            None => None,
+        }
+    }
+
+    /// Returns [`true`] if the device for the open port is no longer listed by the backend.
+    ///
+    /// Backends report a missing device with assorted driver-specific error messages, so instead
+    /// of matching on those the port list is checked for the device name.
+    fn is_disconnected(&self) -> bool {
+        match &self.connection {
+            Some(Connection::Port { device_name, .. }) => {
+                matches!(self.find(device_name), Ok(None))
+            }
+            _ => false,
+        }
+    }
+
+    /// Find a port by name, falling back to a port for the same device if the exact name isn't
+    /// found
+    fn find(&self, device_name: &str) -> Result<Option<RtMidiPort>, RtMidiError> {
+        let count = port_count(self.ptr)?;
+        Ok((0..count)
+            .find(|&port| port_name(self.ptr, port) == Ok(device_name))
+            .or_else(|| {
+                (0..count).find(|&port| {
+                    matches!(port_name(self.ptr, port), Ok(port) if same_device(port, device_name))
+                })
+            }))
+    }
+
+    fn reconnect(&mut self) -> Result<(), RtMidiError> {
+        let connection = match self.connection.clone() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        close_port(self.ptr)?;
+        match connection {
+            Connection::Port { device_name, name } => {
+                // Find the port by name, as numbering changes when devices come and go
+                let number = self.find(&device_name)?.ok_or_else(|| {
+                    RtMidiError::Error(format!("MIDI port '{}' not found", device_name))
+                })?;
+                open_port(self.ptr, number, name)
+            }
+            Connection::Virtual(name) => open_virtual_port(self.ptr, name),
+        }
+    }
+}
+
+/// Returns [`true`] if two port names refer to the same device. ALSA port names end with the
+/// client and port numbers (e.g. "Device:Device MIDI 1 28:0"), which may change when a device is
+/// reconnected, so they are ignored.
+fn same_device(a: &str, b: &str) -> bool {
+    fn strip(name: &str) -> &str {
+        match name.rsplit_once(' ') {
+            Some((device, suffix))
+                if suffix.split(':').all(|number| {
+                    !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+                }) && suffix.contains(':') =>
+            {
+                device
+            }
+            _ => name,
+        }
+    }
+    a == b || strip(a) == strip(b)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn same_device() {
+        assert!(super::same_device(
+            "Device:Device MIDI 1 28:0",
+            "Device:Device MIDI 1 32:0"
+        ));
+        assert!(super::same_device("IAC Driver Bus 1", "IAC Driver Bus 1"));
+        assert!(!super::same_device(
+            "Device MIDI 1 28:0",
+            "Device MIDI 2 28:0"
+        ));
+        assert!(!super::same_device("Device 1", "Device 2"));
+    }
+}
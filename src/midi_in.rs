@@ -1,13 +1,40 @@
-use std::ffi::{c_void, CString};
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fmt;
+use std::mem;
+#[cfg(rtmidi_native)]
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 
 use crate::api::RtMidiApi;
+use crate::decoder::Decoder;
 use crate::error::RtMidiError;
+use crate::event::{EventHandler, RtMidiEvent};
 use crate::ffi;
-use crate::midi;
+use crate::filter::DuplicateFilter;
+use crate::gate::CallbackGate;
+use crate::history::{MessageDirection, RecentMessage};
+use crate::local::LocalCallback;
+use crate::message::MidiMessage;
+use crate::midi::{self, Device, RecoveryPolicy};
+use crate::options::OpenOptions;
+use crate::ports::{self, PortInfo};
+use crate::stream::{SysExChunk, SysExStream};
+use crate::subscribe::{Subscribers, Subscription};
+use crate::system::PortDirection;
+use crate::threads;
+#[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+use crate::virtualmidi::{VirtualHandler, VirtualPort};
+use crate::watchdog::Watchdog;
 use crate::RtMidiPort;
 
 const DEFAULT_CLIENT_NAME: &str = "RtMidi Input Client";
 
+/// Initial size of the buffer used by [`RtMidiIn::message`]
+const MESSAGE_BUFFER_SIZE: usize = 1024;
+
 /// Input arguments
 ///
 /// Defines arguments used when constructing [`RtMidiIn`].
@@ -65,7 +92,19 @@ impl<'a> Default for RtMidiInArgs<'a> {
 /// }
 ///
 /// ```
-pub struct RtMidiIn(*mut ffi::RtMidiWrapper);
+pub struct RtMidiIn {
+    device: Mutex<Device>,
+    decoder: Arc<Mutex<Decoder>>,
+    pending: Mutex<VecDeque<Vec<u8>>>,
+    buffer: Mutex<Vec<u8>>,
+    events: EventHandler,
+    watchdog: Arc<Watchdog>,
+    subscribers: Subscribers,
+    // Callbacks set, with their gates, closed when they're cancelled and the closures freed
+    callbacks: Mutex<Vec<(Arc<CallbackGate>, ffi::CallbackData)>>,
+    // Name given to RtMidi's input thread by the callback, once a port is opened
+    thread_name: Arc<Mutex<Option<String>>>,
+}
 
 impl RtMidiIn {
     /// Default constructor that allows an optional api, client name and queue size using the
@@ -79,18 +118,30 @@ impl RtMidiIn {
     /// order of use is ALSA, JACK (Linux) and CORE, JACK (macOS).
     pub fn new(args: RtMidiInArgs) -> Result<Self, RtMidiError> {
         let client_name = CString::new(args.client_name)?;
+        let api = args.api.resolve()?;
         let ptr = unsafe {
-            ffi::rtmidi_in_create(args.api as u32, client_name.as_ptr(), args.queue_size_limit)
+            ffi::rtmidi_in_create(api as u32, client_name.as_ptr(), args.queue_size_limit)
         };
+        let events = EventHandler::default();
         match unsafe { Result::<(), RtMidiError>::from(*ptr) } {
-            Ok(_) => Ok(RtMidiIn(ptr)),
+            Ok(_) => Ok(RtMidiIn {
+                device: Mutex::new(Device::new(ptr, args.client_name, events.clone())),
+                decoder: Arc::new(Mutex::new(Decoder::default())),
+                pending: Mutex::new(VecDeque::new()),
+                buffer: Mutex::new(vec![0; MESSAGE_BUFFER_SIZE]),
+                events,
+                watchdog: Arc::new(Watchdog::default()),
+                subscribers: Subscribers::default(),
+                callbacks: Mutex::new(Vec::new()),
+                thread_name: Arc::new(Mutex::new(None)),
+            }),
             Err(e) => Err(e),
         }
     }
 
     /// Returns the MIDI API specifier for the current instance
     pub fn current_api(&self) -> RtMidiApi {
-        let api = unsafe { ffi::rtmidi_in_get_current_api(self.0) };
+        let api = unsafe { ffi::rtmidi_in_get_current_api(self.device().ptr) };
         api.into()
     }
 
@@ -100,32 +151,100 @@ impl RtMidiIn {
         port_number: RtMidiPort,
         port_name: T,
     ) -> Result<(), RtMidiError> {
-        midi::open_port(self.0, port_number, port_name)
+        self.open_port_with_options(port_number, port_name, &OpenOptions::default())
+    }
+
+    /// Open a MIDI input connection with backend-specific options
+    pub fn open_port_with_options<T: AsRef<str>>(
+        &self,
+        port_number: RtMidiPort,
+        port_name: T,
+        options: &OpenOptions,
+    ) -> Result<(), RtMidiError> {
+        let port = || midi::port_name_lossy(self.device().ptr, port_number);
+        options.check(self.current_api(), Some(&port))?;
+        let mut device = self.device();
+        device.open_port(port_number, port_name.as_ref())?;
+        self.name_thread(&device);
+        Ok(())
     }
 
     /// Create a virtual input port, with a name, to allow software connections (macOS, JACK and
-    /// ALSA only).
+    /// ALSA, and Windows MM with the `virtualmidi` feature).
     ///
     /// This function creates a virtual MIDI input port to which other software applications can
     /// connect. This type of functionality is currently only supported by the macOS, any JACK,
-    /// and Linux ALSA APIs (the function returns an error for the other APIs).
+    /// and Linux ALSA APIs (the function returns an error for the other APIs), and by the Windows
+    /// MM API when the `virtualmidi` feature is enabled and the teVirtualMIDI driver (installed
+    /// with loopMIDI) is present. Messages sent to a teVirtualMIDI port are only delivered to the
+    /// callback (see [`RtMidiIn::set_callback`]), not queued for [`RtMidiIn::message`].
     pub fn open_virtual_port<T: AsRef<str>>(&self, port_name: T) -> Result<(), RtMidiError> {
-        midi::open_virtual_port(self.0, port_name)
+        self.open_virtual_port_with_options(port_name, &OpenOptions::default())
+    }
+
+    /// Create a virtual input port with backend-specific options
+    pub fn open_virtual_port_with_options<T: AsRef<str>>(
+        &self,
+        port_name: T,
+        options: &OpenOptions,
+    ) -> Result<(), RtMidiError> {
+        let api = self.current_api();
+        options.check(api, None)?;
+        let mut device = self.device();
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        if api == RtMidiApi::WindowsMM {
+            let port = VirtualPort::input(port_name.as_ref(), device.virtual_handler.clone())?;
+            device.open_virtual_midi(port, port_name.as_ref());
+            self.name_thread(&device);
+            return Ok(());
+        }
+        device.open_virtual_port(port_name.as_ref())?;
+        self.name_thread(&device);
+        Ok(())
+    }
+
+    fn name_thread(&self, device: &Device) {
+        *lock(&self.thread_name) = device
+            .port_label()
+            .map(|label| format!("rtmidi-in:{}", label));
     }
 
     /// Close an open MIDI connection (if one exists)
     pub fn close_port(&self) -> Result<(), RtMidiError> {
-        midi::close_port(self.0)
+        self.device().close_port()
     }
 
     /// Return the number of available MIDI input ports
     pub fn port_count(&self) -> Result<RtMidiPort, RtMidiError> {
-        midi::port_count(self.0)
+        crate::midi::port_count(self.device().ptr)
+    }
+
+    /// Return the available MIDI input ports, in port number order. See [`PortInfo`].
+    pub fn ports(&self) -> Result<Vec<PortInfo>, RtMidiError> {
+        ports::port_info(
+            self.current_api(),
+            PortDirection::Input,
+            self.port_count()?,
+            |port| self.port_name(port),
+        )
     }
 
     /// Return a string identifier for the specified MIDI input port number
     pub fn port_name(&self, port_number: RtMidiPort) -> Result<&str, RtMidiError> {
-        midi::port_name(self.0, port_number)
+        crate::midi::port_name(self.device().ptr, port_number)
+    }
+
+    /// Return the aliases of the specified MIDI input port.
+    ///
+    /// JACK ports may have up to two aliases, which often contain the name of the hardware device
+    /// where the port name (e.g. "system:midi_capture_1") does not. Ports of other APIs have no
+    /// aliases, so an empty list is returned.
+    #[cfg(feature = "jack")]
+    pub fn port_aliases(&self, port_number: RtMidiPort) -> Result<Vec<String>, RtMidiError> {
+        match self.current_api() {
+            RtMidiApi::UnixJack => crate::midi::port_aliases(self.device().ptr, port_number),
+            _ => Ok(Vec::new()),
+        }
     }
 
     /// Set a callback function to be invoked for incoming MIDI messages.
@@ -136,23 +255,235 @@ impl RtMidiIn {
     ///
     /// While not absolutely necessary, it is best to set the callback function before opening a
     /// MIDI port to avoid leaving some messages in the queue.
-    pub fn set_callback<F: Fn(f64, &[u8])>(&self, callback: F) -> Result<(), RtMidiError> {
-        let (callback, user_data) = ffi::create_callback(callback);
-        unsafe {
-            ffi::rtmidi_in_set_callback(self.0, Some(callback), user_data as *mut c_void);
-            (*self.0).into()
-        }
+    ///
+    /// The callback is invoked from RtMidi's input thread, so it must be `Send` and can't borrow
+    /// from the caller. See [`RtMidiIn::set_callback_on_current_thread`] for callbacks that
+    /// capture thread-confined state.
+    pub fn set_callback<F>(&self, callback: F) -> Result<(), RtMidiError>
+    where
+        F: Fn(f64, &[u8]) + Send + 'static,
+    {
+        let decoder = Arc::clone(&self.decoder);
+        let watchdog = Arc::clone(&self.watchdog);
+        let subscribers = self.subscribers.clone();
+        let history = self.device().history.clone();
+        let gate = Arc::new(CallbackGate::default());
+        let invocations = Arc::clone(&gate);
+        let thread_name = Arc::clone(&self.thread_name);
+        let handler = move |timestamp, message: &[u8]| {
+            let _invocation = match invocations.enter() {
+                Some(invocation) => invocation,
+                None => return,
+            };
+            if let Some(name) = lock(&thread_name).take() {
+                threads::set_name(&name);
+            }
+            // Messages split from the same buffer arrived at the same time
+            let mut delta = timestamp;
+            lock(&decoder).decode(message, |message| {
+                watchdog.feed(message);
+                history.record(MessageDirection::Input, message);
+                if !subscribers.emit(delta, message) {
+                    callback(delta, message);
+                }
+                delta = 0.0;
+            })
+        };
+        let device = self.device();
+        // Messages of a teVirtualMIDI port are passed to the same handler
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        let handler = {
+            let handler: VirtualHandler = Arc::new(Mutex::new(handler));
+            device.virtual_handler.set(Some(Arc::clone(&handler)));
+            move |timestamp, message: &[u8]| lock(&handler)(timestamp, message)
+        };
+        let (callback, user_data) = ffi::create_callback(handler);
+        unsafe { ffi::rtmidi_in_set_callback(device.ptr, Some(callback), user_data.as_ptr()) };
+        lock(&self.callbacks).push((gate, user_data));
+        unsafe { (*device.ptr).into() }
+    }
+
+    /// Set a callback function to be invoked with typed MIDI messages.
+    ///
+    /// Like [`RtMidiIn::set_callback`], except each message is parsed into a [`MidiMessage`]
+    /// before the callback is invoked. Data that can't be parsed is skipped; use
+    /// [`RtMidiIn::set_typed_callback_with_fallback`] to handle it instead.
+    /// ```
+    /// use rtmidi::{MidiMessage, RtMidiIn};
+    ///
+    /// let input = RtMidiIn::new(Default::default()).unwrap();
+    /// input
+    ///     .set_typed_callback(|timestamp, message| {
+    ///         if let MidiMessage::NoteOn { note, velocity, .. } = message {
+    ///             println!("{}: note {} velocity {}", timestamp, note, velocity);
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn set_typed_callback<F>(&self, callback: F) -> Result<(), RtMidiError>
+    where
+        F: Fn(f64, MidiMessage) + Send + 'static,
+    {
+        self.set_typed_callback_with_fallback(callback, |_timestamp, _message, _error| {})
+    }
+
+    /// Set a callback function to be invoked with typed MIDI messages, and a fallback for data
+    /// that can't be parsed.
+    ///
+    /// The fallback is passed the timestamp, the raw data bytes and the parse error.
+    pub fn set_typed_callback_with_fallback<F, G>(
+        &self,
+        callback: F,
+        fallback: G,
+    ) -> Result<(), RtMidiError>
+    where
+        F: Fn(f64, MidiMessage) + Send + 'static,
+        G: Fn(f64, &[u8], RtMidiError) + Send + 'static,
+    {
+        self.set_callback(
+            move |timestamp, message| match MidiMessage::parse(message) {
+                Ok(parsed) => callback(timestamp, parsed),
+                Err(e) => fallback(timestamp, message, e),
+            },
+        )
+    }
+
+    /// Set a callback function that is invoked on the current thread.
+    ///
+    /// Unlike [`RtMidiIn::set_callback`], the callback doesn't need to be `Send` and may capture
+    /// thread-confined state. Messages are queued until the returned [`LocalCallback`] is pumped
+    /// (see [`LocalCallback::pump`] and [`LocalCallback::run`]), which invokes the callback.
+    pub fn set_callback_on_current_thread<F: FnMut(f64, &[u8]) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<LocalCallback, RtMidiError> {
+        let (sender, receiver) = mpsc::channel();
+        self.set_callback(move |timestamp, message| {
+            // The receiver may have been dropped, in which case the message is discarded
+            let _ = sender.send((timestamp, message.to_vec()));
+        })?;
+        Ok(LocalCallback::new(receiver, callback))
+    }
+
+    /// Add a subscriber that observes incoming MIDI messages.
+    ///
+    /// Any number of subscribers can be added, e.g. to log or monitor traffic. Each is invoked
+    /// with every message as it is delivered to the callback (see [`RtMidiIn::set_callback`]) or
+    /// retrieved with [`RtMidiIn::message`], before it is handled, without taking it away from
+    /// the main handler. Like the callback, subscribers may be invoked from RtMidi's input
+    /// thread. The subscriber is removed when the returned [`Subscription`] is dropped.
+    /// ```
+    /// use rtmidi::RtMidiIn;
+    ///
+    /// let input = RtMidiIn::new(Default::default()).unwrap();
+    /// let _monitor = input.subscribe(|timestamp, message| println!("{}: {:02x?}", timestamp, message));
+    /// input.set_callback(|_timestamp, _message| {}).unwrap();
+    /// ```
+    pub fn subscribe<F>(&self, subscriber: F) -> Subscription
+    where
+        F: Fn(f64, &[u8]) + Send + 'static,
+    {
+        self.subscribe_with_priority(0, move |timestamp, message| {
+            subscriber(timestamp, message);
+            false
+        })
+    }
+
+    /// Add a subscriber with a priority, which may consume incoming MIDI messages.
+    ///
+    /// Subscribers are invoked in order of descending priority, and those with the same priority
+    /// in the order they were added ([`RtMidiIn::subscribe`] uses a priority of 0). The main
+    /// callback is always invoked last. If a subscriber returns [`true`], the message is consumed:
+    /// it isn't passed to any further subscribers or the callback, or returned by
+    /// [`RtMidiIn::message`]. This allows chains such as filter → recorder → application.
+    /// ```
+    /// use rtmidi::RtMidiIn;
+    ///
+    /// let input = RtMidiIn::new(Default::default()).unwrap();
+    /// // Drop MIDI clock before anything else sees it
+    /// let _filter = input.subscribe_with_priority(100, |_timestamp, message| message == [0xF8]);
+    /// ```
+    pub fn subscribe_with_priority<F>(&self, priority: i32, subscriber: F) -> Subscription
+    where
+        F: Fn(f64, &[u8]) -> bool + Send + 'static,
+    {
+        self.subscribers.add(priority, Box::new(subscriber))
+    }
+
+    /// Deliver incoming system exclusive messages to a callback in chunks.
+    ///
+    /// Each message is passed as [`SysExChunk::Begin`], its data in [`SysExChunk::Data`] chunks
+    /// of at most `chunk_size` bytes, then [`SysExChunk::End`] (or [`SysExChunk::Aborted`] if
+    /// it is cut short), so a large dump can be processed or written out in chunks. Messages
+    /// that arrive in pieces are followed across pieces. System exclusive messages are then
+    /// consumed, so aren't passed to the callback or returned by [`RtMidiIn::message`]; other
+    /// messages are unaffected. Stops when the returned [`Subscription`] is dropped.
+    ///
+    /// Progress can be tracked by counting the bytes passed in [`SysExChunk::Data`] against the
+    /// expected size of the dump, and a transfer abandoned by dropping the [`Subscription`].
+    ///
+    /// RtMidi assembles each message before delivering it, so a whole message is still held in
+    /// memory while it arrives. Built on the crate's own ALSA or Windows Multimedia backend
+    /// instead (with the `native-alsa` or `winmm` feature), the data is passed on as the MIDI
+    /// system delivers it and never held whole. System exclusive messages are ignored unless
+    /// enabled with [`RtMidiIn::ignore_types`].
+    pub fn stream_sysex<F>(&self, chunk_size: usize, callback: F) -> Subscription
+    where
+        F: FnMut(SysExChunk) + Send + 'static,
+    {
+        let stream = Mutex::new(SysExStream::new(chunk_size, callback));
+        let subscription = self.subscribe_with_priority(i32::MAX, move |_timestamp, message| {
+            lock(&stream).process(message)
+        });
+        // The native backends pass the data on in parts while there are streams
+        #[cfg(rtmidi_native)]
+        let subscription = {
+            let streams = unsafe { ffi::rtmidi_in_sysex_streams(self.device().ptr) };
+            streams.fetch_add(1, Ordering::SeqCst);
+            Subscription::new(move || {
+                streams.fetch_sub(1, Ordering::SeqCst);
+                drop(subscription);
+            })
+        };
+        subscription
+    }
+
+    /// Drop incoming Control Change and Channel Pressure messages that repeat the last value for
+    /// the same controller and channel, until the returned [`Subscription`] is dropped.
+    ///
+    /// Reduces the load from controllers that send identical values continuously. Duplicates are
+    /// consumed before any other subscriber or the callback sees them (see
+    /// [`DuplicateFilter`] for the controllers that aren't filtered).
+    pub fn filter_duplicates(&self) -> Subscription {
+        let filter = Mutex::new(DuplicateFilter::new());
+        self.subscribe_with_priority(i32::MAX, move |_timestamp, message| {
+            lock(&filter).is_duplicate(message)
+        })
     }
 
     /// Cancel use of the current callback function (if one exists).
     ///
     /// Subsequent incoming MIDI messages will be written to the queue and can be retrieved with
-    /// [`RtMidiIn::message`].
+    /// [`RtMidiIn::message`]. If the callback is running on RtMidi's input thread, this waits for
+    /// it to return (unless called from the callback itself), and it won't be invoked again.
     pub fn cancel_callback(&self) -> Result<(), RtMidiError> {
-        unsafe {
-            ffi::rtmidi_in_cancel_callback(self.0);
-            (*self.0).into()
+        let result = {
+            let device = self.device();
+            #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+            device.virtual_handler.set(None);
+            unsafe {
+                ffi::rtmidi_in_cancel_callback(device.ptr);
+                (*device.ptr).into()
+            }
+        };
+        let callbacks: Vec<_> = lock(&self.callbacks).drain(..).collect();
+        for (gate, user_data) in callbacks {
+            // A callback cancelling itself is still running, so it can't be freed
+            if !gate.close() {
+                mem::forget(user_data);
+            }
         }
+        result
     }
 
     /// Specify whether certain MIDI message types should be queued or ignored during input.
@@ -166,40 +497,215 @@ impl RtMidiIn {
         midi_time: bool,
         midi_sense: bool,
     ) -> Result<(), RtMidiError> {
+        let device = self.device();
         unsafe {
-            ffi::rtmidi_in_ignore_types(self.0, midi_sysex, midi_time, midi_sense);
-            (*self.0).into()
+            ffi::rtmidi_in_ignore_types(device.ptr, midi_sysex, midi_time, midi_sense);
+            (*device.ptr).into()
         }
     }
 
+    fn device(&self) -> MutexGuard<'_, Device> {
+        lock(&self.device)
+    }
+
+    /// Set a callback function to be invoked for connection events, such as
+    /// [`RtMidiEvent::ConnectionLost`] and [`RtMidiEvent::Disconnected`].
+    ///
+    /// The callback may be invoked from an internal thread.
+    pub fn set_event_callback<F: Fn(RtMidiEvent) + Send + 'static>(&self, callback: F) {
+        self.events.set(Some(Box::new(callback)))
+    }
+
+    /// Cancel use of the current event callback (if one exists)
+    pub fn cancel_event_callback(&self) {
+        self.events.set(None)
+    }
+
+    /// Enable (or disable, with [`None`]) the Active Sensing watchdog.
+    ///
+    /// When a device sends Active Sensing (`0xFE`) messages, the MIDI specification states that
+    /// silence for more than 300ms ([`crate::ACTIVE_SENSING_TIMEOUT`]) means the connection is
+    /// dead. Once enabled, the watchdog is armed by the first Active Sensing message received and
+    /// raises [`RtMidiEvent::ConnectionLost`] through the event callback if no message at all is
+    /// received within the timeout, so applications can silence stuck notes and attempt to
+    /// reconnect. It is then disarmed until Active Sensing is received again.
+    ///
+    /// Active Sensing messages are ignored by default, so [`RtMidiIn::ignore_types`] must be
+    /// used to receive them for the watchdog to work.
+    pub fn set_active_sensing_timeout(&self, timeout: Option<Duration>) {
+        self.watchdog.set_timeout(timeout, &self.events)
+    }
+
+    /// Enable (or disable, with [`None`]) automatic recovery from backend errors.
+    ///
+    /// When retrieving a message with [`RtMidiIn::message`] fails with a driver or system error
+    /// (e.g. ALSA after a USB reset), the port is transparently closed and re-opened and the
+    /// retrieval retried, according to the [`RecoveryPolicy`], before the error is returned.
+    /// Disabled by default.
+    pub fn set_recovery(&self, policy: Option<RecoveryPolicy>) {
+        self.device().recovery = policy;
+    }
+
+    /// Keep the last `capacity` messages received for debugging (zero, the default, disables
+    /// this).
+    ///
+    /// See [`RtMidiIn::recent`].
+    pub fn set_recent_capacity(&self, capacity: usize) {
+        self.device().history.set_capacity(capacity)
+    }
+
+    /// Returns the most recent messages received, oldest first, with the time each arrived.
+    ///
+    /// Messages are recorded as they are delivered to the callback or retrieved with
+    /// [`RtMidiIn::message`], including those consumed by subscribers. Nothing is recorded unless
+    /// enabled with [`RtMidiIn::set_recent_capacity`].
+    pub fn recent(&self) -> Vec<RecentMessage> {
+        self.device().history.recent()
+    }
+
+    /// Enable or disable running status resolution on input.
+    ///
+    /// Some transports deliver data bytes without repeating the status byte of the previous
+    /// channel message ("running status"). When enabled, the last channel status byte is tracked
+    /// and prepended to such messages so that every message passed to the callback (or returned
+    /// by [`RtMidiIn::message`]) starts with a status byte. Data bytes received before any
+    /// status byte, or after a system common message, are dropped. Disabled by default.
+    pub fn set_running_status(&self, enabled: bool) {
+        lock(&self.decoder).set_running_status(enabled)
+    }
+
+    /// Enable or disable splitting of buffers that contain more than one message.
+    ///
+    /// Certain backends and devices deliver multiple MIDI messages in one buffer. When enabled,
+    /// each buffer is walked by status byte and message length and the callback is invoked once
+    /// per message (the first with the buffer's delta-time and the rest with a delta-time of
+    /// zero). Messages retrieved with [`RtMidiIn::message`] are split in the same way. A message
+    /// left incomplete at the end of a buffer is completed by the next one, while a system
+    /// exclusive message is passed on in parts as it arrives. A stray `0xF7` outside a system
+    /// exclusive message is dropped. Disabled by default.
+    pub fn set_split_messages(&self, enabled: bool) {
+        lock(&self.decoder).set_split(enabled)
+    }
+
+    /// Enable or disable conversion of note-ons with zero velocity into note-offs on input.
+    ///
+    /// Many devices end notes with a note-on of zero velocity, which lets them use running
+    /// status. When enabled, these are converted into note-offs with a release velocity of 64
+    /// (which the MIDI specification treats them as), so callbacks and subscribers only ever see
+    /// note-offs. See [`crate::transform::NoteOffs`] for the same conversion, or the reverse, on
+    /// output. Disabled by default.
+    pub fn set_normalize_note_offs(&self, enabled: bool) {
+        lock(&self.decoder).set_note_offs(enabled)
+    }
+
+    /// Set the size of the buffer messages are retrieved into by [`RtMidiIn::message`].
+    ///
+    /// The buffer starts at 1024 bytes and grows automatically, but a message that doesn't fit
+    /// is lost (see [`RtMidiIn::message`]), so set this before receiving large system exclusive
+    /// messages.
+    pub fn set_message_buffer_size(&self, size: usize) {
+        lock(&self.buffer).resize(size.max(1), 0)
+    }
+
     /// Return a vector with the data bytes for the next available MIDI message in the input queue
     /// and the event delta-time in seconds.
     ///
-    /// This function returns immediately whether a new message is available or not. A valid
-    /// message is indicated by a non-zero vector size. An exception is thrown if an error occurs
-    /// during message retrieval or an input connection was not previously established.
+    /// This function returns immediately whether a new message is available or not. If the
+    /// queue is empty, an empty vector is returned (with a delta-time of zero). An error is
+    /// returned if an error occurs during message retrieval or an input connection was not
+    /// previously established.
+    ///
+    /// Messages are copied from RtMidi into a buffer (see
+    /// [`RtMidiIn::set_message_buffer_size`]). RtMidi removes a message from its queue even if it
+    /// doesn't fit, so in that case the message is lost and an error is returned, and the buffer
+    /// is enlarged so later messages of that size are received.
     pub fn message(&self) -> Result<(f64, Vec<u8>), RtMidiError> {
-        let mut length = 0u64;
-        let mut message = Vec::with_capacity(1024);
-        let ptr = message.as_mut_ptr();
-        let timestamp = unsafe { ffi::rtmidi_in_get_message(self.0, ptr, &mut length) };
-        match unsafe { Result::<(), RtMidiError>::from(*self.0) } {
-            Ok(_) => Ok((timestamp, message)),
+        if let Some(message) = lock(&self.pending).pop_front() {
+            return Ok((0.0, message));
+        }
+        let mut buffer = lock(&self.buffer);
+        let mut length = 0;
+        let (result, history) = {
+            let mut device = self.device();
+            let result = device.with_recovery(|device| {
+                length = buffer.len() as ffi::size_t;
+                let timestamp =
+                    unsafe { ffi::rtmidi_in_get_message(device, buffer.as_mut_ptr(), &mut length) };
+                unsafe { Result::<(), RtMidiError>::from(*device) }.map(|_| timestamp)
+            });
+            (result, device.history.clone())
+        };
+        #[allow(clippy::unnecessary_cast)]
+        let length = length as usize;
+        if result.is_ok() && length > buffer.len() {
+            let size = buffer.len();
+            buffer.resize(length.next_power_of_two(), 0);
+            return Err(RtMidiError::Error(format!(
+                "Message of {} bytes exceeded the {} byte buffer and was dropped",
+                length, size
+            )));
+        }
+        let message = &buffer[..length];
+        match result {
+            Ok(timestamp) => {
+                let mut pending = lock(&self.pending);
+                let mut delta = timestamp;
+                lock(&self.decoder).decode(message, |message| {
+                    self.watchdog.feed(message);
+                    history.record(MessageDirection::Input, message);
+                    if !self.subscribers.emit(delta, message) {
+                        pending.push_back(message.to_vec());
+                    }
+                    delta = 0.0;
+                });
+                Ok((timestamp, pending.pop_front().unwrap_or_default()))
+            }
             Err(e) => Err(e),
         }
     }
 }
 
+impl fmt::Debug for RtMidiIn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let api = self.current_api();
+        let device = self.device();
+        f.debug_struct("RtMidiIn")
+            .field("api", &api)
+            .field("client_name", &device.client_name())
+            .field("open", &device.is_open())
+            .field("port", &device.port_label())
+            .finish()
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 impl Drop for RtMidiIn {
     fn drop(&mut self) {
-        unsafe { ffi::rtmidi_in_free(self.0) }
+        self.watchdog.set_timeout(None, &self.events);
+        // A callback in progress mustn't outlive the input
+        if !lock(&self.callbacks).is_empty() {
+            let _ = self.cancel_callback();
+        }
+        unsafe { ffi::rtmidi_in_free(self.device().ptr) }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{RtMidiIn, RtMidiInArgs};
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    use super::{lock, RtMidiIn, RtMidiInArgs};
     use crate::api::RtMidiApi;
+    use crate::ffi;
+    use crate::options::OpenOptions;
+    use crate::ACTIVE_SENSING_TIMEOUT;
 
     #[test]
     fn new() {
@@ -210,6 +716,26 @@ mod tests {
         .is_ok());
     }
 
+    #[test]
+    fn debug() {
+        let instance = RtMidiIn::new(RtMidiInArgs {
+            client_name: "Debug Test",
+            ..Default::default()
+        })
+        .unwrap();
+        let closed = format!("{:?}", instance);
+        assert!(closed.starts_with("RtMidiIn { api: "));
+        assert!(closed.ends_with("client_name: \"Debug Test\", open: false, port: None }"));
+        assert!(instance.open_virtual_port("Debug Port").is_ok());
+        assert!(format!("{:?}", instance).ends_with("open: true, port: Some(\"Debug Port\") }"));
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<RtMidiIn>();
+    }
+
     #[test]
     fn current_api() {
         assert_ne!(
@@ -244,6 +770,14 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn open_virtual_port_with_options() {
+        assert!(RtMidiIn::new(Default::default())
+            .unwrap()
+            .open_virtual_port_with_options("Test", &OpenOptions::new())
+            .is_ok());
+    }
+
     #[test]
     fn close_port() {
         assert!(RtMidiIn::new(Default::default())
@@ -260,6 +794,15 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    #[cfg(feature = "jack")]
+    fn port_aliases() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        if input.current_api() != RtMidiApi::UnixJack {
+            assert_eq!(input.port_aliases(0), Ok(Vec::new()));
+        }
+    }
+
     #[test]
     fn port_name() {
         assert_eq!(
@@ -279,6 +822,54 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn set_typed_callback() {
+        assert!(RtMidiIn::new(Default::default())
+            .unwrap()
+            .set_typed_callback(|_time, _message| {})
+            .is_ok());
+    }
+
+    #[test]
+    fn set_typed_callback_with_fallback() {
+        assert!(RtMidiIn::new(Default::default())
+            .unwrap()
+            .set_typed_callback_with_fallback(|_time, _message| {}, |_time, _data, _error| {})
+            .is_ok());
+    }
+
+    #[test]
+    fn set_callback_on_current_thread() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let mut callback = input
+            .set_callback_on_current_thread(|_time, _message| {})
+            .unwrap();
+        assert_eq!(callback.pump(), 0);
+    }
+
+    #[test]
+    fn subscribe() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let subscription = input.subscribe(|_time, _message| {});
+        assert!(input.set_callback(|_time, _message| {}).is_ok());
+        subscription.unsubscribe();
+    }
+
+    #[test]
+    fn stream_sysex() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let _stream = input.stream_sysex(1024, |_chunk| {});
+        assert!(input.ignore_types(false, true, true).is_ok());
+        assert!(input.message().is_ok());
+    }
+
+    #[test]
+    fn filter_duplicates() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let _filter = input.filter_duplicates();
+        assert!(input.set_callback(|_time, _message| {}).is_ok());
+    }
+
     #[test]
     fn cancel_callback() {
         assert!(RtMidiIn::new(Default::default())
@@ -287,6 +878,81 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn run_local_callback() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let mut callback = input
+            .set_callback_on_current_thread(|_time, _message| {})
+            .unwrap();
+        assert!(input.cancel_callback().is_ok());
+        callback.run();
+
+        let mut callback = input
+            .set_callback_on_current_thread(|_time, _message| {})
+            .unwrap();
+        drop(input);
+        callback.run();
+    }
+
+    #[test]
+    fn drop_with_callback() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    for _ in 0..50 {
+                        let input = RtMidiIn::new(Default::default()).unwrap();
+                        input.set_callback(|_time, _message| {}).unwrap();
+                        drop(input);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Returns an input with a callback running on another thread, as it would on RtMidi's
+    /// input thread, and a flag set when the callback returns
+    fn callback_in_progress() -> (RtMidiIn, Arc<AtomicBool>, JoinHandle<()>) {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let started = Arc::new(Barrier::new(2));
+        let finished = Arc::new(AtomicBool::new(false));
+        let (entered, returned) = (Arc::clone(&started), Arc::clone(&finished));
+        input
+            .set_callback(move |_time, _message| {
+                entered.wait();
+                thread::sleep(Duration::from_millis(50));
+                returned.store(true, Ordering::SeqCst);
+            })
+            .unwrap();
+        let user_data = lock(&input.callbacks)[0].1.as_ptr() as usize;
+        let invoker = thread::spawn(move || unsafe {
+            ffi::invoke_callback(user_data as *mut c_void, 0.0, &[0x90, 60, 100])
+        });
+        started.wait();
+        (input, finished, invoker)
+    }
+
+    #[test]
+    fn cancel_during_callback() {
+        let (input, finished, invoker) = callback_in_progress();
+        assert!(input.cancel_callback().is_ok());
+        assert!(finished.load(Ordering::SeqCst));
+        // The closure has been freed, along with what it captured
+        assert_eq!(Arc::strong_count(&finished), 1);
+        invoker.join().unwrap();
+    }
+
+    #[test]
+    fn drop_during_callback() {
+        let (input, finished, invoker) = callback_in_progress();
+        drop(input);
+        assert!(finished.load(Ordering::SeqCst));
+        assert_eq!(Arc::strong_count(&finished), 1);
+        invoker.join().unwrap();
+    }
+
     #[test]
     fn ignore_types() {
         assert!(RtMidiIn::new(Default::default())
@@ -295,8 +961,52 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn set_event_callback() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        input.set_event_callback(|_event| {});
+        input.set_active_sensing_timeout(Some(ACTIVE_SENSING_TIMEOUT));
+        input.set_active_sensing_timeout(None);
+        input.cancel_event_callback();
+    }
+
+    #[test]
+    fn set_recovery() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        input.set_recovery(Some(Default::default()));
+        assert!(input.open_virtual_port("Test").is_ok());
+        assert!(input.message().is_ok());
+        input.set_recovery(None);
+    }
+
+    #[test]
+    fn recent() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        input.set_recent_capacity(16);
+        assert!(input.recent().is_empty());
+    }
+
+    #[test]
+    fn set_running_status() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        input.set_running_status(true);
+        assert!(input.set_callback(|_time, _message| {}).is_ok());
+        assert!(input.message().is_ok());
+    }
+
+    #[test]
+    fn set_split_messages() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        input.set_split_messages(true);
+        assert!(input.set_callback(|_time, _message| {}).is_ok());
+        assert!(input.message().is_ok());
+    }
+
     #[test]
     fn message() {
-        assert!(RtMidiIn::new(Default::default()).unwrap().message().is_ok());
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        assert_eq!(input.message().unwrap(), (0.0, Vec::new()));
+        input.set_message_buffer_size(64 * 1024);
+        assert!(input.message().is_ok());
     }
 }
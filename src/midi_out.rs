@@ -1,9 +1,44 @@
 use std::ffi::CString;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::alsa::QueuedOutput;
 use crate::api::RtMidiApi;
+use crate::bend::PitchBendRange;
+use crate::channel::OutputChannel;
+#[cfg(feature = "async")]
+use crate::completion::{Completion, SendFuture};
+#[cfg(all(feature = "coremidi", target_os = "macos"))]
+use crate::coremidi::TimestampedOutput;
+use crate::decoder;
 use crate::error::RtMidiError;
+use crate::event::{EventHandler, RtMidiEvent};
 use crate::ffi;
-use crate::midi;
+use crate::history::RecentMessage;
+#[cfg(feature = "jack")]
+use crate::jack::JackOutput;
+use crate::message::{MidiMessage, ShortMessage};
+use crate::microtonal::{Microtonal, MicrotonalNote};
+use crate::midi::{self, Device, RecoveryPolicy};
+use crate::notes::NotesOffPolicy;
+use crate::options::OpenOptions;
+use crate::ports::{self, PortInfo};
+use crate::quirks::{self, Quirks};
+use crate::scheduler::{PendingPolicy, Scheduler};
+use crate::sysex::SysExArgs;
+use crate::system::PortDirection;
+use crate::throttle::RateLimiter;
+use crate::timer::TimerStrategy;
+use crate::transform::Transform;
+use crate::usb;
+#[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+use crate::virtualmidi::VirtualPort;
+use crate::worker::{Handle, Worker};
 use crate::RtMidiPort;
 
 const DEFAULT_CLIENT_NAME: &str = "RtMidi Output Client";
@@ -24,6 +59,8 @@ const DEFAULT_CLIENT_NAME: &str = "RtMidi Output Client";
 pub struct RtMidiOutArgs<'a> {
     pub api: RtMidiApi,
     pub client_name: &'a str,
+    /// Size of the MIDI output queue used by [`RtMidiOut::try_send`] and [`RtMidiOut::send`]
+    pub queue_size_limit: u32,
 }
 
 impl<'a> Default for RtMidiOutArgs<'a> {
@@ -31,6 +68,7 @@ impl<'a> Default for RtMidiOutArgs<'a> {
         RtMidiOutArgs {
             api: RtMidiApi::Unspecified,
             client_name: DEFAULT_CLIENT_NAME,
+            queue_size_limit: 1024,
         }
     }
 }
@@ -55,7 +93,24 @@ impl<'a> Default for RtMidiOutArgs<'a> {
 /// }
 ///
 /// ```
-pub struct RtMidiOut(*mut ffi::RtMidiWrapper);
+pub struct RtMidiOut {
+    device: Arc<Mutex<Device>>,
+    buffer: Mutex<Option<Vec<u8>>>,
+    queue_size_limit: u32,
+    limiter: Arc<Mutex<Option<RateLimiter>>>,
+    worker: Mutex<Option<Worker>>,
+    scheduler: Mutex<Option<Scheduler>>,
+    // Keepalive interval, and whether a port is open
+    keepalive: Mutex<(Option<Duration>, bool)>,
+    validate: AtomicBool,
+    // Reused to collect messages sent with `message_from_iter`
+    scratch: Mutex<Vec<u8>>,
+    events: EventHandler,
+    pending_policy: Mutex<PendingPolicy>,
+    // Pitch bend range last set on each channel
+    bend_ranges: Mutex<[PitchBendRange; 16]>,
+    microtonal: Mutex<Microtonal>,
+}
 
 impl RtMidiOut {
     /// Default constructor that allows an optional api and client name using the
@@ -67,16 +122,36 @@ impl RtMidiOut {
     /// order of use is ALSA, JACK (Linux) and CORE, JACK (macOS).
     pub fn new(args: RtMidiOutArgs) -> Result<Self, RtMidiError> {
         let client_name = CString::new(args.client_name)?;
-        let ptr = unsafe { ffi::rtmidi_out_create(args.api as u32, client_name.as_ptr()) };
+        let api = args.api.resolve()?;
+        let ptr = unsafe { ffi::rtmidi_out_create(api as u32, client_name.as_ptr()) };
+        let events = EventHandler::default();
         match unsafe { Result::<(), RtMidiError>::from(*ptr) } {
-            Ok(_) => Ok(RtMidiOut(ptr)),
+            Ok(_) => Ok(RtMidiOut {
+                device: Arc::new(Mutex::new(Device::new(
+                    ptr,
+                    args.client_name,
+                    events.clone(),
+                ))),
+                buffer: Mutex::new(None),
+                queue_size_limit: args.queue_size_limit,
+                limiter: Arc::new(Mutex::new(None)),
+                worker: Mutex::new(None),
+                scheduler: Mutex::new(None),
+                keepalive: Mutex::new((None, false)),
+                validate: AtomicBool::new(false),
+                scratch: Mutex::new(Vec::new()),
+                events,
+                pending_policy: Mutex::new(PendingPolicy::default()),
+                bend_ranges: Mutex::new([PitchBendRange::default(); 16]),
+                microtonal: Mutex::new(Microtonal::default()),
+            }),
             Err(e) => Err(e),
         }
     }
 
     /// Returns the MIDI API specifier for the current instance
     pub fn current_api(&self) -> RtMidiApi {
-        let api = unsafe { ffi::rtmidi_out_get_current_api(self.0) };
+        let api = unsafe { ffi::rtmidi_out_get_current_api(self.device().ptr) };
         api.into()
     }
 
@@ -86,58 +161,790 @@ impl RtMidiOut {
         port_number: RtMidiPort,
         port_name: T,
     ) -> Result<(), RtMidiError> {
-        midi::open_port(self.0, port_number, port_name)
+        self.open_port_with_options(port_number, port_name, &OpenOptions::default())
+    }
+
+    /// Open a MIDI output connection with backend-specific options.
+    ///
+    /// The [`Quirks`] known for the device behind the port are applied to the connection, see
+    /// [`register_quirks`](crate::register_quirks).
+    pub fn open_port_with_options<T: AsRef<str>>(
+        &self,
+        port_number: RtMidiPort,
+        port_name: T,
+        options: &OpenOptions,
+    ) -> Result<(), RtMidiError> {
+        let api = self.current_api();
+        // Needed for the device's quirks anyway
+        let device_name = midi::port_name_lossy(self.device().ptr, port_number)?;
+        options.check(api, Some(&|| Ok(device_name.clone())))?;
+        let usb = usb::lookup(api, PortDirection::Output, port_number, &device_name);
+        let mut device = self.device();
+        device.open_port(port_number, port_name.as_ref())?;
+        device.set_quirks(quirks::port_quirks(&device_name, usb.as_ref()));
+        #[cfg(all(feature = "coremidi", target_os = "macos"))]
+        if api == RtMidiApi::MacOSXCore {
+            let output = TimestampedOutput::new(device.client_name(), &device_name);
+            device.set_timed_output(output.ok().map(|output| Box::new(output) as _));
+        }
+        #[cfg(feature = "jack")]
+        if api == RtMidiApi::UnixJack {
+            let output = JackOutput::new(device.client_name(), &device_name);
+            device.set_timed_output(output.ok().map(|output| Box::new(output) as _));
+        }
+        #[cfg(all(feature = "alsa", target_os = "linux"))]
+        if api == RtMidiApi::LinuxALSA {
+            let output = QueuedOutput::new(device.client_name(), &device_name);
+            device.set_timed_output(output.ok().map(|output| Box::new(output) as _));
+        }
+        drop(device);
+        self.set_connected(true)
     }
 
     /// Create a virtual output port, with a name, to allow software connections (macOS, JACK and
-    /// ALSA only).
+    /// ALSA, and Windows MM with the `virtualmidi` feature).
     ///
     /// This function creates a virtual MIDI output port to which other software applications can
     /// connect. This type of functionality is currently only supported by the macOS, Linux ALSA
-    /// and JACK APIs (the function does nothing with the other APIs). An error is returned if an
-    /// error occurs while attempting to create the virtual port.
+    /// and JACK APIs (the function does nothing with the other APIs), and by the Windows MM API
+    /// when the `virtualmidi` feature is enabled and the teVirtualMIDI driver (installed with
+    /// loopMIDI) is present. An error is returned if an error occurs while attempting to create
+    /// the virtual port.
     pub fn open_virtual_port<T: AsRef<str>>(&self, port_name: T) -> Result<(), RtMidiError> {
-        midi::open_virtual_port(self.0, port_name)
+        self.open_virtual_port_with_options(port_name, &OpenOptions::default())
+    }
+
+    /// Create a virtual output port with backend-specific options
+    pub fn open_virtual_port_with_options<T: AsRef<str>>(
+        &self,
+        port_name: T,
+        options: &OpenOptions,
+    ) -> Result<(), RtMidiError> {
+        options.check(self.current_api(), None)?;
+        #[cfg(all(feature = "virtualmidi", target_os = "windows"))]
+        if self.current_api() == RtMidiApi::WindowsMM {
+            let port = VirtualPort::output(port_name.as_ref())?;
+            self.device().open_virtual_midi(port, port_name.as_ref());
+            return self.set_connected(true);
+        }
+        self.device().open_virtual_port(port_name.as_ref())?;
+        self.set_connected(true)
     }
 
-    /// Close an open MIDI connection (if one exists)
+    /// Close an open MIDI connection (if one exists).
+    ///
+    /// Queued messages are sent first, and scheduled messages dealt with according to the
+    /// [`PendingPolicy`] (by default only the note-offs of notes sent with
+    /// [`RtMidiOut::send_note`] are sent). Then the Note Offs of the [`NotesOffPolicy`] are sent.
     pub fn close_port(&self) -> Result<(), RtMidiError> {
-        midi::close_port(self.0)
+        let policy = *lock(&self.pending_policy);
+        self.close_port_with(policy)
+    }
+
+    /// Close the port like [`RtMidiOut::close_port`], first sending the scheduled messages that
+    /// become due within `timeout` as they do, then any left at once, so none are lost.
+    pub fn close_with_flush(&self, timeout: Duration) -> Result<(), RtMidiError> {
+        self.close_port_with(PendingPolicy::WaitUntilDue(timeout))
+    }
+
+    /// Set what happens to scheduled messages when the port is closed or the output dropped
+    pub fn set_pending_policy(&self, policy: PendingPolicy) {
+        *lock(&self.pending_policy) = policy;
+    }
+
+    /// Set what is sent before the port is closed or the output dropped, to end notes left
+    /// sounding. Only notes sent after the policy is set are tracked.
+    /// ```
+    /// use rtmidi::{NotesOffPolicy, RtMidiOut};
+    ///
+    /// let output = RtMidiOut::new(Default::default()).unwrap();
+    /// output.set_notes_off_policy(NotesOffPolicy::AllNotesOff);
+    /// ```
+    pub fn set_notes_off_policy(&self, policy: NotesOffPolicy) {
+        self.device().set_notes_off_policy(policy);
+    }
+
+    /// Returns the quirks applied to the open port
+    pub fn quirks(&self) -> Quirks {
+        self.device().quirks()
+    }
+
+    /// Replace the quirks applied to the open port, such as with those of a device identified
+    /// by its reply to an identity request. They're reset when the port is closed or another
+    /// opened.
+    /// ```
+    /// use rtmidi::{DeviceIdentity, RtMidiOut};
+    ///
+    /// fn identified(output: &RtMidiOut, reply: &[u8]) {
+    ///     if let Ok(identity) = DeviceIdentity::parse(reply) {
+    ///         output.set_quirks(identity.quirks());
+    ///     }
+    /// }
+    /// ```
+    pub fn set_quirks(&self, quirks: Quirks) {
+        self.device().set_quirks(quirks);
     }
 
     /// Return the number of available MIDI output ports
     pub fn port_count(&self) -> Result<RtMidiPort, RtMidiError> {
-        midi::port_count(self.0)
+        midi::port_count(self.device().ptr)
+    }
+
+    /// Return the available MIDI output ports, in port number order. See [`PortInfo`].
+    pub fn ports(&self) -> Result<Vec<PortInfo>, RtMidiError> {
+        ports::port_info(
+            self.current_api(),
+            PortDirection::Output,
+            self.port_count()?,
+            |port| self.port_name(port),
+        )
     }
 
     /// Return a string identifier for the specified MIDI output port number
     pub fn port_name(&self, port_number: RtMidiPort) -> Result<&str, RtMidiError> {
-        midi::port_name(self.0, port_number)
+        midi::port_name(self.device().ptr, port_number)
+    }
+
+    /// Return the aliases of the specified MIDI output port.
+    ///
+    /// JACK ports may have up to two aliases, which often contain the name of the hardware device
+    /// where the port name (e.g. "system:midi_capture_1") does not. Ports of other APIs have no
+    /// aliases, so an empty list is returned.
+    #[cfg(feature = "jack")]
+    pub fn port_aliases(&self, port_number: RtMidiPort) -> Result<Vec<String>, RtMidiError> {
+        match self.current_api() {
+            RtMidiApi::UnixJack => midi::port_aliases(self.device().ptr, port_number),
+            _ => Ok(Vec::new()),
+        }
     }
 
     /// Immediately send a single message out an open MIDI output port.
     ///
     /// An error is returned if an error occurs during output or an output connection was not
     /// previously established.
+    ///
+    /// When buffered output is enabled (see [`RtMidiOut::set_buffered`]) the message is instead
+    /// appended to an internal buffer and sent with the next call to [`RtMidiOut::flush`].
+    ///
+    /// When validation is enabled (see [`RtMidiOut::set_validation`]) a malformed message returns
+    /// [`RtMidiError::InvalidMessage`] and is not sent.
     pub fn message(&self, message: &[u8]) -> Result<(), RtMidiError> {
-        let length = message.len();
-        unsafe {
-            ffi::rtmidi_out_send_message(self.0, message.as_ptr(), length as i32);
-            (*self.0).into()
+        if self.validate.load(Ordering::Relaxed) {
+            MidiMessage::parse(message)?;
+        }
+        self.message_unvalidated(message)
+    }
+
+    /// Send a message of up to three bytes given as an array, like [`RtMidiOut::message`].
+    /// ```
+    /// use rtmidi::RtMidiOut;
+    ///
+    /// let output = RtMidiOut::new(Default::default()).unwrap();
+    /// let _ = output.short_message([0x90, 60, 100]);
+    /// let _ = output.short_message([0xC0, 5]);
+    /// ```
+    pub fn short_message<M: Into<ShortMessage>>(&self, message: M) -> Result<(), RtMidiError> {
+        self.message(message.into().as_bytes())
+    }
+
+    /// Send a message given as an iterator of bytes, like [`RtMidiOut::message`].
+    ///
+    /// The bytes are collected into a buffer kept by the output, so no allocation is needed once
+    /// it has grown to fit the largest message.
+    pub fn message_from_iter<I>(&self, bytes: I) -> Result<(), RtMidiError>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let mut scratch = lock(&self.scratch);
+        scratch.clear();
+        scratch.extend(bytes);
+        self.message(&scratch)
+    }
+
+    /// Send a long system exclusive message in chunks, reporting progress.
+    ///
+    /// The message is split into chunks of [`SysExArgs::chunk_size`] bytes, sent one after the
+    /// other with a pause of [`SysExArgs::chunk_interval`] between them, so slow devices can keep
+    /// up with large dumps. After each chunk, `progress` is called with the number of bytes sent
+    /// so far and the total. Chunks are sent immediately, even if buffered output is enabled.
+    ///
+    /// If [`SysExArgs::cancel`] is cancelled, the transfer stops before the next chunk and
+    /// [`RtMidiError::Cancelled`] is returned. If part of the message was already sent, `0xF7`
+    /// is sent to terminate it, so the receiver isn't left waiting for the rest.
+    /// ```
+    /// use rtmidi::{CancelToken, RtMidiError, RtMidiOut, SysExArgs};
+    ///
+    /// fn upload(output: &RtMidiOut, dump: &[u8], cancel: CancelToken) -> Result<(), RtMidiError> {
+    ///     let args = SysExArgs {
+    ///         cancel: Some(cancel),
+    ///         ..Default::default()
+    ///     };
+    ///     output.send_sysex(dump, args, |sent, total| {
+    ///         println!("{}%", sent * 100 / total);
+    ///     })
+    /// }
+    /// ```
+    pub fn send_sysex<F>(
+        &self,
+        message: &[u8],
+        args: SysExArgs,
+        mut progress: F,
+    ) -> Result<(), RtMidiError>
+    where
+        F: FnMut(usize, usize),
+    {
+        if message.first() != Some(&0xF0) || message.last() != Some(&0xF7) {
+            return Err(RtMidiError::InvalidMessage(
+                "system exclusive messages must start with 0xF0 and end with 0xF7".to_string(),
+            ));
+        }
+        let mut sent = 0;
+        for chunk in message.chunks(args.chunk_size.max(1)) {
+            if args.is_cancelled() {
+                if sent > 0 {
+                    self.send_now(&[0xF7])?;
+                }
+                return Err(RtMidiError::Cancelled);
+            }
+            if sent > 0 {
+                thread::sleep(args.chunk_interval);
+            }
+            self.send_now(chunk)?;
+            sent += chunk.len();
+            progress(sent, message.len());
+        }
+        Ok(())
+    }
+
+    /// Send a message like [`RtMidiOut::message`], but without validation.
+    ///
+    /// This is intended for deliberately non-standard data, such as a system exclusive message
+    /// sent in several parts or undefined status bytes used by some devices.
+    pub fn message_unvalidated(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        if let Some(buffer) = lock(&self.buffer).as_mut() {
+            buffer.extend_from_slice(message);
+            return Ok(());
+        }
+        self.send_now(message)
+    }
+
+    /// Returns a view of this output bound to a MIDI channel (0-15), providing typed helpers for
+    /// channel voice messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is greater than 15.
+    pub fn channel(&self, channel: u8) -> OutputChannel<'_> {
+        OutputChannel::new(self, channel)
+    }
+
+    pub(crate) fn pitch_bend_range(&self, channel: u8) -> PitchBendRange {
+        lock(&self.bend_ranges)[usize::from(channel & 0x0F)]
+    }
+
+    pub(crate) fn set_pitch_bend_range(&self, channel: u8, range: PitchBendRange) {
+        lock(&self.bend_ranges)[usize::from(channel & 0x0F)] = range;
+    }
+
+    /// Enable or disable buffered (coalescing) output.
+    ///
+    /// While enabled, [`RtMidiOut::message`] appends to an internal buffer rather than sending
+    /// immediately, and [`RtMidiOut::flush`] sends everything buffered so far together. With the
+    /// CoreMIDI API the buffer is sent as a single packet list, which greatly improves
+    /// throughput for dense bursts of messages (e.g. restoring the state of many controllers).
+    /// The other APIs take one message at a time, so the buffer is split into messages and
+    /// each is sent in turn.
+    ///
+    /// Only complete messages should be buffered, as they are split by their status bytes.
+    /// Disabling buffered output flushes any pending messages.
+    pub fn set_buffered(&self, buffered: bool) -> Result<(), RtMidiError> {
+        if buffered {
+            lock(&self.buffer).get_or_insert_with(Vec::new);
+            Ok(())
+        } else {
+            let result = self.flush();
+            *lock(&self.buffer) = None;
+            result
+        }
+    }
+
+    /// Enable or disable validation of messages passed to [`RtMidiOut::message`].
+    ///
+    /// When enabled, each message must be a single complete MIDI message: a status byte followed
+    /// by the right number of data bytes (all below `0x80`), or a system exclusive message
+    /// terminated by `0xF7`. Anything else (e.g. a truncated message, a misplaced status byte or
+    /// an unterminated sysex) returns a descriptive [`RtMidiError::InvalidMessage`] rather than
+    /// being passed to the device. Use [`RtMidiOut::message_unvalidated`] to bypass validation
+    /// for individual messages. Disabled by default.
+    pub fn set_validation(&self, enabled: bool) {
+        self.validate.store(enabled, Ordering::Relaxed)
+    }
+
+    /// Returns [`true`] if buffered output is enabled
+    pub fn is_buffered(&self) -> bool {
+        lock(&self.buffer).is_some()
+    }
+
+    /// Send all messages buffered since the last flush (see [`RtMidiOut::set_buffered`]).
+    ///
+    /// This does nothing if buffered output is disabled or no messages are pending. The buffer is
+    /// emptied even if an error is returned, and the messages after one that fails aren't sent.
+    pub fn flush(&self) -> Result<(), RtMidiError> {
+        let pending = match lock(&self.buffer).as_mut() {
+            Some(buffer) if !buffer.is_empty() => buffer.split_off(0),
+            _ => return Ok(()),
+        };
+        // Only CoreMIDI takes several messages at once (as a packet list)
+        if self.current_api() == RtMidiApi::MacOSXCore {
+            return self.send_now(&pending);
+        }
+        let mut start = 0;
+        while start < pending.len() {
+            let end = message_end(&pending, start);
+            self.send_now(&pending[start..end])?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Limit the rate at which bytes are sent, or remove the limit with [`None`].
+    ///
+    /// Cheap USB to DIN MIDI interfaces often drop data when sent more than a real MIDI cable can
+    /// carry, e.g. during parameter sweeps or sysex floods. With a limit set, sending waits until
+    /// the previous messages would have been transmitted at the given rate
+    /// ([`crate::DIN_MIDI_BYTES_PER_SECOND`] matches a standard MIDI cable). Single byte system
+    /// real-time messages are never delayed and, when using the output queue, are sent ahead of
+    /// any messages held back by the limit.
+    pub fn set_rate_limit(&self, bytes_per_second: Option<u32>) {
+        *lock(&self.limiter) = bytes_per_second.map(RateLimiter::new);
+    }
+
+    /// Send Active Sensing (`0xFE`) messages at the given interval while a port is open, or stop
+    /// sending them with [`None`].
+    ///
+    /// Some legacy hardware expects a keepalive from the sender and silences itself when none
+    /// arrives. [`crate::ACTIVE_SENSING_INTERVAL`] is a suitable interval. The messages are sent
+    /// from the same internal thread as the output queue (see [`RtMidiOut::try_send`]).
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> Result<(), RtMidiError> {
+        lock(&self.keepalive).0 = interval;
+        self.update_keepalive()
+    }
+
+    /// Append a transform to the output pipeline.
+    ///
+    /// Transforms are applied, in the order they were added, to messages sent through the output
+    /// queue ([`RtMidiOut::try_send`] and [`RtMidiOut::send`]) and run on the same internal
+    /// thread, which also sends any messages they produce later in time. Messages sent with
+    /// [`RtMidiOut::message`] are not transformed.
+    pub fn add_transform<T: Transform + 'static>(&self, transform: T) -> Result<(), RtMidiError> {
+        self.handle().add_transform(Box::new(transform))
+    }
+
+    /// Remove all transforms from the output pipeline
+    pub fn clear_transforms(&self) -> Result<(), RtMidiError> {
+        match lock(&self.worker).as_ref() {
+            Some(worker) => worker.handle().clear_transforms(),
+            None => Ok(()),
+        }
+    }
+
+    /// Set a callback function to be invoked for connection events, such as
+    /// [`RtMidiEvent::Disconnected`].
+    ///
+    /// The callback may be invoked from an internal thread.
+    pub fn set_event_callback<F: Fn(RtMidiEvent) + Send + 'static>(&self, callback: F) {
+        self.events.set(Some(Box::new(callback)))
+    }
+
+    /// Cancel use of the current event callback (if one exists)
+    pub fn cancel_event_callback(&self) {
+        self.events.set(None)
+    }
+
+    /// Enable (or disable, with [`None`]) automatic recovery from backend errors.
+    ///
+    /// When sending fails with a driver or system error (e.g. ALSA after a USB reset), the port
+    /// is transparently closed and re-opened and the message sent again, retrying according to
+    /// the [`RecoveryPolicy`] before the error is returned. This applies to all messages,
+    /// including those sent from the output queue and scheduler. Disabled by default.
+    pub fn set_recovery(&self, policy: Option<RecoveryPolicy>) {
+        self.device().recovery = policy;
+    }
+
+    /// Keep the last `capacity` messages sent for debugging (zero, the default, disables this).
+    ///
+    /// See [`RtMidiOut::recent`].
+    pub fn set_recent_capacity(&self, capacity: usize) {
+        self.device().history.set_capacity(capacity)
+    }
+
+    /// Returns the most recent messages sent, oldest first, with the time each was sent.
+    ///
+    /// Messages are recorded as they are passed to the backend, so this includes messages sent
+    /// from the output queue and scheduler but not those still buffered or queued. Nothing is
+    /// recorded unless enabled with [`RtMidiOut::set_recent_capacity`].
+    pub fn recent(&self) -> Vec<RecentMessage> {
+        self.device().history.recent()
+    }
+
+    /// Queue a message to be sent from an internal thread without blocking.
+    ///
+    /// Messages are held in a bounded queue (sized by [`RtMidiOutArgs::queue_size_limit`]) and
+    /// sent in order by a sender thread that is started on first use, so realtime callers never
+    /// block on a slow backend. [`RtMidiError::WouldBlock`] is returned if the queue is full, in
+    /// which case the message is not sent.
+    ///
+    /// Queued messages bypass buffered output and are not ordered with respect to messages sent
+    /// using [`RtMidiOut::message`]. An error raised by the backend while sending a queued
+    /// message is returned by the next call to [`RtMidiOut::try_send`] or [`RtMidiOut::send`].
+    pub fn try_send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.handle().try_send(message.to_vec())
+    }
+
+    /// Queue a message to be sent from an internal thread, blocking while the queue is full.
+    ///
+    /// See [`RtMidiOut::try_send`] for details of the output queue.
+    pub fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.handle().send(message.to_vec())
+    }
+
+    /// Queue a Note On message on a channel (0-15), and schedule the matching Note Off after
+    /// `duration`.
+    ///
+    /// The messages are sent from the output queue (see [`RtMidiOut::try_send`]), so they are in
+    /// order with other queued messages. The note can be ended early or left sounding with the
+    /// returned [`NoteHandle`]. Notes are never left hanging: the note-offs of any notes still
+    /// sounding are sent when the port is closed or the output is dropped.
+    /// ```
+    /// use std::time::Duration;
+    /// use rtmidi::{RtMidiError, RtMidiOut};
+    ///
+    /// fn chord(output: &RtMidiOut) -> Result<(), RtMidiError> {
+    ///     for &note in &[60, 64, 67] {
+    ///         output.send_note(0, note, 90, Duration::from_millis(500))?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is greater than 15.
+    pub fn send_note(
+        &self,
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        duration: Duration,
+    ) -> Result<NoteHandle, RtMidiError> {
+        assert!(channel < 16, "Invalid MIDI channel {}", channel);
+        let (note, velocity) = (note & 0x7F, velocity & 0x7F);
+        let handle = self.handle();
+        handle.send(vec![0x90 | channel, note, velocity])?;
+        let id =
+            handle.schedule_note_off(Instant::now() + duration, vec![0x80 | channel, note, 0])?;
+        Ok(NoteHandle { handle, id })
+    }
+
+    /// Immediately send a note offset from its key by some cents, sending the pitch bend for the
+    /// offset first. The bend is computed from the channel's pitch bend range, see
+    /// [`OutputChannel::set_pitch_bend_range`], so channels rotated across should share a range.
+    ///
+    /// As pitch bend applies to the whole channel, notes with different offsets on the same
+    /// channel bend each other. With rotation (see [`RtMidiOut::set_note_cents_rotation`]) each
+    /// note is sent on a channel that is free or already bent by the same amount where possible,
+    /// so the returned note's channel may differ from `channel`. End the note with
+    /// [`RtMidiOut::release_note_cents`].
+    /// ```
+    /// use rtmidi::{RtMidiError, RtMidiOut};
+    ///
+    /// fn neutral_third(output: &RtMidiOut) -> Result<(), RtMidiError> {
+    ///     output.set_note_cents_rotation(2);
+    ///     let root = output.send_note_cents(0, 60, 0.0, 90)?;
+    ///     let third = output.send_note_cents(0, 63, 50.0, 90)?;
+    ///     output.release_note_cents(root)?;
+    ///     output.release_note_cents(third)
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is greater than 15.
+    pub fn send_note_cents(
+        &self,
+        channel: u8,
+        note: u8,
+        cents: f64,
+        velocity: u8,
+    ) -> Result<MicrotonalNote, RtMidiError> {
+        assert!(channel < 16, "Invalid MIDI channel {}", channel);
+        let mut microtonal = lock(&self.microtonal);
+        let bend = self.pitch_bend_range(channel).to_bend(cents / 100.0);
+        let (channel, bend_changed) = microtonal.note_on(channel, bend);
+        let output = self.channel(channel);
+        if bend_changed {
+            output.pitch_bend(bend)?;
+        }
+        output.note_on(note, velocity)?;
+        Ok(MicrotonalNote {
+            channel,
+            note: note & 0x7F,
+            bend,
+        })
+    }
+
+    /// End a note sent with [`RtMidiOut::send_note_cents`], then restore its channel's pitch
+    /// bend to the centre if no other note sent that way is sounding on it
+    pub fn release_note_cents(&self, note: MicrotonalNote) -> Result<(), RtMidiError> {
+        let restore = lock(&self.microtonal).note_off(&note);
+        let output = self.channel(note.channel);
+        output.note_off(note.note, 0)?;
+        if restore {
+            output.pitch_bend(0x2000)?;
+        }
+        Ok(())
+    }
+
+    /// Set how many consecutive channels, starting from the one given, notes sent with
+    /// [`RtMidiOut::send_note_cents`] rotate across (1-16). The default of 1 sends every note on
+    /// the channel given.
+    pub fn set_note_cents_rotation(&self, channels: u8) {
+        lock(&self.microtonal).set_rotation(channels);
+    }
+
+    /// Set how the output thread waits for scheduled messages and other timed output.
+    ///
+    /// The default, [`TimerStrategy::Sleep`], is limited to the resolution of the system timer
+    /// (about 15ms on Windows). [`TimerStrategy::HighResolution`] raises the resolution to 1ms on
+    /// Windows, and [`TimerStrategy::Hybrid`] busy-waits just before each message is due for
+    /// sub-millisecond accuracy, using more CPU time. With CoreMIDI, ALSA and JACK,
+    /// [`TimerStrategy::Native`] passes scheduled messages to the MIDI system ahead of time with
+    /// their timestamps instead.
+    pub fn set_timer_strategy(&self, strategy: TimerStrategy) -> Result<(), RtMidiError> {
+        self.handle().set_timer_strategy(strategy)
+    }
+
+    /// Returns whether the open port can have scheduled messages sent by the MIDI system with
+    /// [`TimerStrategy::Native`], which needs the CoreMIDI, ALSA or JACK API (and the matching
+    /// feature) and a device without quirks
+    pub fn supports_native_scheduling(&self) -> bool {
+        self.device().can_send_at()
+    }
+
+    /// Queue a message to be sent from an internal thread, returning a future that resolves
+    /// once it has been passed to the backend.
+    ///
+    /// Like [`RtMidiOut::send`], except the future also resolves with any error raised by the
+    /// backend while sending (or earlier queued messages). Awaiting each message before queuing
+    /// the next lets async applications apply backpressure, e.g. when the rate limit (see
+    /// [`RtMidiOut::set_rate_limit`]) holds messages back. The future doesn't depend on any
+    /// particular async runtime. Queuing only blocks if the queue is full, which can't happen
+    /// while each message is awaited. Requires the `async` feature.
+    /// ```
+    /// use rtmidi::{RtMidiError, RtMidiOut};
+    ///
+    /// async fn play(output: &RtMidiOut) -> Result<(), RtMidiError> {
+    ///     output.send_async(&[0x90, 60, 100]).await?;
+    ///     output.send_async(&[0x80, 60, 0]).await
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn send_async(&self, message: &[u8]) -> SendFuture {
+        let (completion, future) = Completion::new();
+        // If the worker has stopped the completion is dropped, which resolves the future
+        let _ = self
+            .handle()
+            .send_with_completion(message.to_vec(), completion);
+        future
+    }
+
+    /// Returns a handle to the output's scheduler, for sending messages at a future time.
+    ///
+    /// All handles share the same settings (such as the musical clock), and scheduled messages
+    /// are sent from the same internal thread as the output queue (see
+    /// [`RtMidiOut::try_send`]). Messages still scheduled when the port is closed or the output
+    /// dropped are dealt with according to the [`PendingPolicy`].
+    pub fn scheduler(&self) -> Result<Scheduler, RtMidiError> {
+        Ok(lock(&self.scheduler)
+            .get_or_insert_with(|| Scheduler::new(self.handle()))
+            .clone())
+    }
+
+    /// Convert into a [`SharedMidiOut`] handle, which can be cloned to send to this output from
+    /// several places
+    pub fn into_shared(self) -> SharedMidiOut {
+        SharedMidiOut(Arc::new(self))
+    }
+
+    fn close_port_with(&self, policy: PendingPolicy) -> Result<(), RtMidiError> {
+        let handle = lock(&self.worker)
+            .as_ref()
+            .map(|worker| worker.handle().clone());
+        if let Some(handle) = handle {
+            handle.finish(policy)?;
+        }
+        self.set_connected(false)?;
+        self.device().close_port()
+    }
+
+    fn set_connected(&self, connected: bool) -> Result<(), RtMidiError> {
+        lock(&self.keepalive).1 = connected;
+        self.update_keepalive()
+    }
+
+    fn update_keepalive(&self) -> Result<(), RtMidiError> {
+        match *lock(&self.keepalive) {
+            (Some(interval), true) => self.handle().set_keepalive(Some(interval)),
+            _ => match lock(&self.worker).as_ref() {
+                Some(worker) => worker.handle().set_keepalive(None),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Returns a handle to the sender thread, starting it if needed. The handle is cloned so the
+    /// worker isn't locked while sending (which may block).
+    fn handle(&self) -> Handle {
+        lock(&self.worker)
+            .get_or_insert_with(|| {
+                Worker::new(
+                    Arc::clone(&self.device),
+                    Arc::clone(&self.limiter),
+                    self.queue_size_limit as usize,
+                )
+            })
+            .handle()
+            .clone()
+    }
+
+    fn send_now(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        let delay = lock(&self.limiter)
+            .as_mut()
+            .map(|limiter| limiter.reserve(message, Instant::now()));
+        if let Some(delay) = delay {
+            thread::sleep(delay);
         }
+        self.device().send(message)
+    }
+
+    fn device(&self) -> MutexGuard<'_, Device> {
+        lock(&self.device)
+    }
+}
+
+impl fmt::Debug for RtMidiOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let api = self.current_api();
+        let device = self.device();
+        f.debug_struct("RtMidiOut")
+            .field("api", &api)
+            .field("client_name", &device.client_name())
+            .field("open", &device.is_open())
+            .field("port", &device.port_label())
+            .finish()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the end of the message starting at `start` in a buffer of messages: after its data
+/// bytes, after the `0xF7` ending a system exclusive message, or before the next status byte
+/// for anything else (such as data bytes using running status)
+fn message_end(buffer: &[u8], start: usize) -> usize {
+    let rest = &buffer[start + 1..];
+    let length = match decoder::data_length(buffer[start]) {
+        _ if buffer[start] < 0x80 => rest.iter().position(|&byte| byte >= 0x80),
+        Some(length) => Some(length.min(rest.len())),
+        None => rest
+            .iter()
+            .position(|&byte| byte == 0xF7)
+            .map(|end| end + 1),
+    };
+    start + 1 + length.unwrap_or(rest.len())
+}
+
+/// A note sent with [`RtMidiOut::send_note`]
+///
+/// Dropping the handle leaves the note to end after its duration.
+pub struct NoteHandle {
+    handle: Handle,
+    id: u64,
+}
+
+impl NoteHandle {
+    /// End the note now, unless it has already ended
+    pub fn release(self) -> Result<(), RtMidiError> {
+        self.handle.release_note(self.id, true)
+    }
+
+    /// Cancel the scheduled Note Off, leaving the note sounding until a Note Off is sent some
+    /// other way
+    pub fn cancel(self) -> Result<(), RtMidiError> {
+        self.handle.release_note(self.id, false)
+    }
+}
+
+/// Cloneable handle to a MIDI output
+///
+/// Returned by [`RtMidiOut::into_shared`]. Clones refer to the same output, so several parts of
+/// an application (e.g. a UI thread and a sequencer thread) can send to the same open port.
+/// Dereferences to [`RtMidiOut`], and the output is closed when the last handle is dropped.
+/// ```
+/// use std::thread;
+/// use rtmidi::RtMidiOut;
+///
+/// let output = RtMidiOut::new(Default::default()).unwrap().into_shared();
+/// let sequencer = output.clone();
+/// thread::spawn(move || sequencer.message(&[0x90, 60, 100]))
+///     .join()
+///     .unwrap()
+///     .unwrap();
+/// output.message(&[0x80, 60, 0]).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SharedMidiOut(Arc<RtMidiOut>);
+
+impl Deref for SharedMidiOut {
+    type Target = RtMidiOut;
+
+    fn deref(&self) -> &RtMidiOut {
+        &self.0
     }
 }
 
 impl Drop for RtMidiOut {
     fn drop(&mut self) {
-        unsafe { ffi::rtmidi_out_free(self.0) }
+        // Stop the sender thread (sending anything still queued) before freeing the device
+        let policy = *lock(&self.pending_policy);
+        if let Some(worker) = self
+            .worker
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            let _ = worker.handle().finish(policy);
+        }
+        let mut device = self.device();
+        let _ = device.release_notes();
+        unsafe { ffi::rtmidi_out_free(device.ptr) }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
     use super::{RtMidiOut, RtMidiOutArgs};
-    use crate::RtMidiApi;
+    use crate::error::RtMidiError;
+    use crate::options::OpenOptions;
+    use crate::scheduler::{Bars, Beats, PendingPolicy};
+    use crate::sysex::{CancelToken, SysExArgs};
+    use crate::timer::TimerStrategy;
+    use crate::transform::Smoother;
+    use crate::{RtMidiApi, ACTIVE_SENSING_INTERVAL, DIN_MIDI_BYTES_PER_SECOND};
 
     #[test]
     fn new() {
@@ -148,6 +955,38 @@ mod tests {
         .is_ok());
     }
 
+    #[test]
+    fn debug() {
+        let instance = RtMidiOut::new(RtMidiOutArgs {
+            client_name: "Debug Test",
+            ..Default::default()
+        })
+        .unwrap();
+        let closed = format!("{:?}", instance);
+        assert!(closed.starts_with("RtMidiOut { api: "));
+        assert!(closed.ends_with("client_name: \"Debug Test\", open: false, port: None }"));
+        assert!(instance.open_virtual_port("Debug Port").is_ok());
+        assert!(format!("{:?}", instance).ends_with("open: true, port: Some(\"Debug Port\") }"));
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<RtMidiOut>();
+    }
+
+    #[test]
+    fn into_shared() {
+        let output = RtMidiOut::new(Default::default()).unwrap().into_shared();
+        let clone = output.clone();
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(thread::spawn(move || clone.message(&[144, 64, 90]))
+            .join()
+            .unwrap()
+            .is_ok());
+        assert!(output.message(&[128, 64, 40]).is_ok());
+    }
+
     #[test]
     fn current_api() {
         assert_ne!(
@@ -182,6 +1021,14 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn open_virtual_port_with_options() {
+        assert!(RtMidiOut::new(Default::default())
+            .unwrap()
+            .open_virtual_port_with_options("Test", &OpenOptions::new())
+            .is_ok());
+    }
+
     #[test]
     fn close_port() {
         assert!(RtMidiOut::new(Default::default())
@@ -198,6 +1045,15 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    #[cfg(feature = "jack")]
+    fn port_aliases() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        if output.current_api() != RtMidiApi::UnixJack {
+            assert_eq!(output.port_aliases(0), Ok(Vec::new()));
+        }
+    }
+
     #[test]
     fn port_name() {
         assert_eq!(
@@ -216,4 +1072,389 @@ mod tests {
             .message(&[0, 0, 0])
             .is_ok());
     }
+
+    #[test]
+    fn channel() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let channel = output.channel(3);
+        assert_eq!(channel.number(), 3);
+        assert!(channel.note_on(64, 90).is_ok());
+        assert!(channel.control_change(7, 100).is_ok());
+        assert!(channel.pitch_bend(8192).is_ok());
+        assert!(channel.local_control(false).is_ok());
+        assert!(channel.reset_all_controllers().is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn channel_invalid() {
+        RtMidiOut::new(Default::default()).unwrap().channel(16);
+    }
+
+    #[test]
+    fn flush() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.set_buffered(true).is_ok());
+        assert!(output.is_buffered());
+        assert!(output.message(&[176, 7, 100]).is_ok());
+        assert!(output.message(&[176, 10, 64]).is_ok());
+        assert!(output.flush().is_ok());
+        assert!(output.set_buffered(false).is_ok());
+        assert!(!output.is_buffered());
+    }
+
+    #[test]
+    fn flush_separately() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output.set_recent_capacity(8);
+        assert!(output.set_buffered(true).is_ok());
+        assert!(output.message(&[176, 7, 100]).is_ok());
+        assert!(output.message(&[240, 126, 127, 6, 1, 247]).is_ok());
+        assert!(output.message(&[192, 5]).is_ok());
+        assert!(output.message_unvalidated(&[144, 60, 100, 62, 100]).is_ok());
+        assert!(output.flush().is_ok());
+        // Each message is handed to the backend on its own
+        let recent: Vec<_> = output.recent().into_iter().map(|m| m.message).collect();
+        assert_eq!(
+            recent,
+            [
+                vec![176, 7, 100],
+                vec![240, 126, 127, 6, 1, 247],
+                vec![192, 5],
+                vec![144, 60, 100],
+                vec![62, 100]
+            ]
+        );
+    }
+
+    #[test]
+    fn set_rate_limit() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        output.set_rate_limit(Some(DIN_MIDI_BYTES_PER_SECOND));
+        assert!(output.message(&[176, 7, 100]).is_ok());
+        assert!(output.message(&[0xF8]).is_ok());
+        output.set_rate_limit(None);
+        assert!(output.message(&[176, 7, 100]).is_ok());
+    }
+
+    #[test]
+    fn set_keepalive() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.set_keepalive(Some(ACTIVE_SENSING_INTERVAL)).is_ok());
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(output.close_port().is_ok());
+        assert!(output.set_keepalive(None).is_ok());
+    }
+
+    #[test]
+    fn add_transform() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output
+            .add_transform(Smoother::new(Duration::from_millis(100)))
+            .is_ok());
+        assert!(output.try_send(&[176, 7, 0]).is_ok());
+        assert!(output.try_send(&[176, 7, 127]).is_ok());
+        assert!(output.clear_transforms().is_ok());
+    }
+
+    #[test]
+    fn scheduler() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let scheduler = output.scheduler().unwrap();
+        scheduler.set_quantize(Some(Default::default()));
+        assert!(scheduler
+            .schedule_in(Duration::from_millis(10), &[144, 64, 90])
+            .is_ok());
+        assert!(scheduler
+            .schedule_in(Duration::from_millis(20), &[128, 64, 0])
+            .is_ok());
+        scheduler.set_tempo(6000.0);
+        assert!(scheduler
+            .schedule_at_bar(Bars(0), Beats(1.0), &[144, 64, 90])
+            .is_ok());
+        drop(output);
+        assert!(scheduler
+            .schedule_in(Duration::from_millis(0), &[0xF8])
+            .is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn scheduler_invalid_tempo() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        output.scheduler().unwrap().set_tempo(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic]
+    fn scheduler_invalid_latency() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        output
+            .scheduler()
+            .unwrap()
+            .set_latency_compensation(f64::INFINITY);
+    }
+
+    #[test]
+    fn set_event_callback() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        output.set_event_callback(|_event| {});
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(output.message(&[144, 64, 90]).is_ok());
+        output.cancel_event_callback();
+    }
+
+    #[test]
+    fn set_recovery() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        output.set_recovery(Some(Default::default()));
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(output.message(&[144, 64, 90]).is_ok());
+        output.set_recovery(None);
+    }
+
+    #[test]
+    fn set_validation() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output.set_validation(true);
+        assert!(output.message(&[144, 64, 90]).is_ok());
+        for message in [
+            &[144, 64][..],
+            &[144, 64, 144],
+            &[64, 90],
+            &[0xF0, 0x7E, 0x01],
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                output.message(message),
+                Err(RtMidiError::InvalidMessage(_))
+            ));
+        }
+        assert!(output.message_unvalidated(&[0xF0, 0x7E, 0x01]).is_ok());
+        output.set_validation(false);
+        assert!(output.message(&[144, 64]).is_ok());
+    }
+
+    #[test]
+    fn short_message() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(output.short_message([144, 64, 90]).is_ok());
+        assert!(output.short_message([192, 5]).is_ok());
+        assert!(output.short_message([248]).is_ok());
+    }
+
+    #[test]
+    fn message_from_iter() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output.set_validation(true);
+        let sysex = Some(0xF0).into_iter().chain(0..16).chain(Some(0xF7));
+        assert!(output.message_from_iter(sysex).is_ok());
+        assert!(output.message_from_iter(vec![144, 64]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn send_async() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let waker = Arc::new(Noop).into();
+        let mut context = Context::from_waker(&waker);
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        let mut future = output.send_async(&[144, 64, 90]);
+        loop {
+            match Pin::new(&mut future).poll(&mut context) {
+                Poll::Ready(result) => break assert!(result.is_ok()),
+                Poll::Pending => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn set_timer_strategy() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let strategy = TimerStrategy::Hybrid {
+            spin: Duration::from_millis(1),
+        };
+        assert!(output.set_timer_strategy(strategy).is_ok());
+        let scheduler = output.scheduler().unwrap();
+        assert!(scheduler
+            .schedule_in(Duration::from_millis(5), &[144, 64, 90])
+            .is_ok());
+    }
+
+    #[test]
+    fn native_scheduling() {
+        // The dummy API falls back to the software scheduler
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(!output.supports_native_scheduling());
+        let strategy = TimerStrategy::Native {
+            lookahead: Duration::from_millis(50),
+        };
+        assert!(output.set_timer_strategy(strategy).is_ok());
+        let scheduler = output.scheduler().unwrap();
+        assert!(scheduler
+            .schedule_in(Duration::from_millis(5), &[144, 64, 90])
+            .is_ok());
+    }
+
+    #[test]
+    fn send_sysex() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        let mut message = vec![0x01; 12];
+        message[0] = 0xF0;
+        message[11] = 0xF7;
+        let args = SysExArgs {
+            chunk_size: 4,
+            chunk_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let mut progress = Vec::new();
+        assert!(output
+            .send_sysex(&message, args.clone(), |sent, _total| progress.push(sent))
+            .is_ok());
+        assert_eq!(progress, [4, 8, 12]);
+
+        let cancel = CancelToken::new();
+        let args = SysExArgs {
+            cancel: Some(cancel.clone()),
+            ..args
+        };
+        let result = output.send_sysex(&message, args, |_sent, _total| cancel.cancel());
+        assert_eq!(result, Err(RtMidiError::Cancelled));
+        assert!(matches!(
+            output.send_sysex(&[0x90, 60, 100], Default::default(), |_, _| {}),
+            Err(RtMidiError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn send_note() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output.set_recent_capacity(8);
+        let long = Duration::from_secs(60);
+        let _held = output.send_note(0, 60, 100, long).unwrap();
+        output
+            .send_note(0, 62, 100, long)
+            .unwrap()
+            .cancel()
+            .unwrap();
+        output
+            .send_note(1, 64, 100, long)
+            .unwrap()
+            .release()
+            .unwrap();
+        output
+            .send_note(1, 65, 100, Duration::from_millis(0))
+            .unwrap();
+        assert!(output.close_port().is_ok());
+        let recent: Vec<_> = output.recent().into_iter().map(|m| m.message).collect();
+        assert_eq!(recent.len(), 7);
+        // Every note but the cancelled one has ended
+        for note_off in &[[128, 60, 0], [129, 64, 0], [129, 65, 0]] {
+            assert!(recent.contains(&note_off.to_vec()));
+        }
+        assert!(!recent.contains(&vec![128, 62, 0]));
+    }
+
+    #[test]
+    fn close_with_flush() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output.set_recent_capacity(8);
+        let scheduler = output.scheduler().unwrap();
+        let start = Instant::now();
+        scheduler
+            .schedule_in(Duration::from_millis(20), &[144, 60, 100])
+            .unwrap();
+        scheduler
+            .schedule_in(Duration::from_secs(60), &[128, 60, 0])
+            .unwrap();
+        assert!(output.close_with_flush(Duration::from_millis(50)).is_ok());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_secs(10));
+        let recent: Vec<_> = output.recent().into_iter().map(|m| m.message).collect();
+        assert_eq!(recent, [vec![144, 60, 100], vec![128, 60, 0]]);
+
+        // By default messages still scheduled are discarded
+        assert!(output.open_virtual_port("Test").is_ok());
+        scheduler
+            .schedule_in(Duration::from_secs(60), &[144, 62, 100])
+            .unwrap();
+        assert!(output.close_port().is_ok());
+        assert_eq!(output.recent().len(), 2);
+
+        output.set_pending_policy(PendingPolicy::Flush);
+        assert!(output.open_virtual_port("Test").is_ok());
+        scheduler
+            .schedule_in(Duration::from_secs(60), &[144, 64, 100])
+            .unwrap();
+        assert!(output.close_port().is_ok());
+        assert_eq!(output.recent().len(), 3);
+    }
+
+    #[test]
+    fn close_with_flush_concurrently() {
+        let output = RtMidiOut::new(Default::default()).unwrap().into_shared();
+        assert!(output.open_virtual_port("Test").is_ok());
+        output
+            .scheduler()
+            .unwrap()
+            .schedule_in(Duration::from_secs(60), &[144, 60, 100])
+            .unwrap();
+        let clone = output.clone();
+        let other = thread::spawn(move || clone.close_with_flush(Duration::from_millis(100)));
+        thread::sleep(Duration::from_millis(20));
+        assert!(output.close_with_flush(Duration::from_millis(100)).is_ok());
+        assert!(other.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn recent() {
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        assert!(output.open_virtual_port("Test").is_ok());
+        assert!(output.message(&[144, 64, 90]).is_ok());
+        output.set_recent_capacity(1);
+        assert!(output.message(&[144, 64, 90]).is_ok());
+        assert!(output.message(&[128, 64, 40]).is_ok());
+        let recent = output.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].message, vec![128, 64, 40]);
+    }
+
+    #[test]
+    fn try_send() {
+        assert!(RtMidiOut::new(Default::default())
+            .unwrap()
+            .try_send(&[144, 64, 90])
+            .is_ok());
+    }
+
+    #[test]
+    fn send() {
+        let output = RtMidiOut::new(RtMidiOutArgs {
+            queue_size_limit: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        for _ in 0..10 {
+            assert!(output.send(&[144, 64, 90]).is_ok());
+        }
+    }
 }
@@ -0,0 +1,102 @@
+/// What [`crate::RtMidiOut`] sends before its port is closed or it's dropped, so that an
+/// application exiting in the middle of a performance doesn't leave notes stuck on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NotesOffPolicy {
+    /// Send nothing
+    #[default]
+    Nothing,
+    /// Send a Note Off for every note that was sent and hasn't ended
+    Tracked,
+    /// Send the tracked Note Offs, then All Notes Off on every channel, for notes sent before the
+    /// policy was set or that the receiver otherwise thinks are on
+    AllNotesOff,
+}
+
+/// All Notes Off controller number
+const ALL_NOTES_OFF: u8 = 123;
+/// All Sound Off controller number, which also ends notes
+const ALL_SOUND_OFF: u8 = 120;
+
+/// Tracks the notes sounding on an output
+#[derive(Debug, Default)]
+pub(crate) struct SoundingNotes {
+    policy: NotesOffPolicy,
+    // One bit per key of each channel
+    keys: [u128; 16],
+}
+
+impl SoundingNotes {
+    pub fn set_policy(&mut self, policy: NotesOffPolicy) {
+        self.policy = policy;
+        if policy == NotesOffPolicy::Nothing {
+            self.keys = [0; 16];
+        }
+    }
+
+    /// Update the sounding notes with a message sent
+    pub fn record(&mut self, message: &[u8]) {
+        if self.policy == NotesOffPolicy::Nothing {
+            return;
+        }
+        let (status, key, value) = match *message {
+            [status, key, value] => (status, key & 0x7F, value),
+            _ => return,
+        };
+        let channel = usize::from(status & 0x0F);
+        match status & 0xF0 {
+            0x90 if value > 0 => self.keys[channel] |= 1 << key,
+            0x80 | 0x90 => self.keys[channel] &= !(1 << key),
+            0xB0 if key == ALL_NOTES_OFF || key == ALL_SOUND_OFF => self.keys[channel] = 0,
+            _ => {}
+        }
+    }
+
+    /// Returns the messages to send under the policy, forgetting the sounding notes
+    pub fn notes_off(&mut self) -> Vec<[u8; 3]> {
+        let mut messages = Vec::new();
+        if self.policy == NotesOffPolicy::Nothing {
+            return messages;
+        }
+        for (channel, keys) in self.keys.iter_mut().enumerate() {
+            for key in 0..128u8 {
+                if *keys & 1 << key != 0 {
+                    messages.push([0x80 | channel as u8, key, 0]);
+                }
+            }
+            *keys = 0;
+        }
+        if self.policy == NotesOffPolicy::AllNotesOff {
+            messages.extend((0..16).map(|channel| [0xB0 | channel, ALL_NOTES_OFF, 0]));
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NotesOffPolicy, SoundingNotes};
+
+    #[test]
+    fn notes_off() {
+        let mut notes = SoundingNotes::default();
+        notes.record(&[0x90, 60, 100]);
+        assert!(notes.notes_off().is_empty());
+
+        notes.set_policy(NotesOffPolicy::Tracked);
+        notes.record(&[0x90, 60, 100]);
+        notes.record(&[0x91, 64, 100]);
+        notes.record(&[0x92, 67, 100]);
+        notes.record(&[0x90, 60, 0]);
+        notes.record(&[0xB2, 123, 0]);
+        notes.record(&[0x91, 127, 1]);
+        assert_eq!(notes.notes_off(), [[0x81, 64, 0], [0x81, 127, 0]]);
+        assert!(notes.notes_off().is_empty());
+
+        notes.set_policy(NotesOffPolicy::AllNotesOff);
+        notes.record(&[0x9F, 36, 90]);
+        let messages = notes.notes_off();
+        assert_eq!(messages.len(), 17);
+        assert_eq!(messages[0], [0x8F, 36, 0]);
+        assert_eq!(messages[16], [0xBF, 123, 0]);
+    }
+}
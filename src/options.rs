@@ -0,0 +1,137 @@
+use crate::api::RtMidiApi;
+use crate::error::RtMidiError;
+#[cfg(feature = "jack")]
+use crate::jack::JackClient;
+
+/// CoreMIDI protocol for a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoreMidiProtocol {
+    /// MIDI 1.0 byte stream
+    #[default]
+    Midi1,
+    /// MIDI 2.0 Universal MIDI Packets
+    Midi2,
+}
+
+/// Backend-specific options for opening a port
+///
+/// Passed to `open_port_with_options` and `open_virtual_port_with_options` on [`crate::RtMidiIn`]
+/// and [`crate::RtMidiOut`]. Each option only applies to its own API and is ignored by the others.
+/// Options the backend can't provide return [`RtMidiError::Unsupported`] when the port is opened,
+/// rather than being silently ignored. New options may be added without breaking existing code.
+/// ```
+/// use rtmidi::{OpenOptions, RtMidiOut};
+///
+/// let output = RtMidiOut::new(Default::default()).unwrap();
+/// let options = OpenOptions::new().jack_physical_only(true);
+/// let _ = output.open_port_with_options(0, "Hardware Output", &options);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OpenOptions {
+    /// Windows Multimedia: use the stream API (`midiStream*`) rather than short messages
+    pub windows_stream: bool,
+    /// CoreMIDI: protocol used for the connection
+    pub coremidi_protocol: CoreMidiProtocol,
+    /// JACK: only open ports of physical devices (requires the `jack` feature)
+    pub jack_physical_only: bool,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set whether the Windows Multimedia stream API is used
+    pub fn windows_stream(mut self, windows_stream: bool) -> Self {
+        self.windows_stream = windows_stream;
+        self
+    }
+
+    /// Set the CoreMIDI protocol
+    pub fn coremidi_protocol(mut self, protocol: CoreMidiProtocol) -> Self {
+        self.coremidi_protocol = protocol;
+        self
+    }
+
+    /// Set whether only ports of physical devices may be opened under JACK
+    pub fn jack_physical_only(mut self, physical_only: bool) -> Self {
+        self.jack_physical_only = physical_only;
+        self
+    }
+
+    /// Check the options can be honoured when opening a port with the given API. `port` looks up
+    /// the name of the port, only if a check needs it, or is [`None`] for a virtual port.
+    pub(crate) fn check(
+        &self,
+        api: RtMidiApi,
+        port: Option<&dyn Fn() -> Result<String, RtMidiError>>,
+    ) -> Result<(), RtMidiError> {
+        match api {
+            RtMidiApi::WindowsMM if self.windows_stream => Err(unsupported(
+                "The Windows Multimedia stream API is not supported by RtMidi",
+            )),
+            RtMidiApi::MacOSXCore if self.coremidi_protocol != CoreMidiProtocol::Midi1 => Err(
+                unsupported("Only the MIDI 1.0 protocol is supported by RtMidi for CoreMIDI"),
+            ),
+            RtMidiApi::UnixJack if self.jack_physical_only => match port {
+                Some(port) => check_physical(&port()?),
+                None => Err(unsupported("Virtual ports are not physical")),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "jack")]
+fn check_physical(port: &str) -> Result<(), RtMidiError> {
+    if JackClient::new()?.is_physical(port)? {
+        Ok(())
+    } else {
+        Err(RtMidiError::Error(format!(
+            "JACK port '{}' is not a physical port",
+            port
+        )))
+    }
+}
+
+#[cfg(not(feature = "jack"))]
+fn check_physical(_port: &str) -> Result<(), RtMidiError> {
+    Err(unsupported(
+        "Filtering physical JACK ports requires the jack feature",
+    ))
+}
+
+fn unsupported(reason: &str) -> RtMidiError {
+    RtMidiError::Unsupported(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoreMidiProtocol, OpenOptions};
+    use crate::api::RtMidiApi;
+    use crate::error::RtMidiError;
+
+    #[test]
+    fn check() {
+        let options = OpenOptions::new()
+            .windows_stream(true)
+            .coremidi_protocol(CoreMidiProtocol::Midi2);
+        let port = || Ok("Port".to_string());
+        assert!(options.check(RtMidiApi::LinuxALSA, Some(&port)).is_ok());
+        assert!(matches!(
+            options.check(RtMidiApi::WindowsMM, Some(&port)),
+            Err(RtMidiError::Unsupported(_))
+        ));
+        assert!(matches!(
+            options.check(RtMidiApi::MacOSXCore, None),
+            Err(RtMidiError::Unsupported(_))
+        ));
+        assert!(OpenOptions::new().check(RtMidiApi::UnixJack, None).is_ok());
+        // The port's name is only looked up when a check needs it
+        let missing = || Err(RtMidiError::NullPointer);
+        assert!(OpenOptions::new()
+            .check(RtMidiApi::UnixJack, Some(&missing))
+            .is_ok());
+    }
+}
@@ -0,0 +1,265 @@
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use crate::error::RtMidiError;
+use crate::transaction::SysExTransaction;
+
+/// How a parameter value is split into system exclusive data bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParameterEncoding {
+    /// 7 bits per byte, most significant byte first
+    #[default]
+    SevenBit,
+    /// 4 bits per byte ("nibblized"), most significant nibble first
+    Nibbles,
+}
+
+/// A device parameter
+///
+/// Describes where a parameter is stored in the device's address space, the range of its raw
+/// value and how that is scaled to the value shown to the user:
+/// `value = (raw - offset) * scale`.
+/// ```
+/// use rtmidi::Parameter;
+///
+/// // Pan, stored as 1 to 127 with 64 as the centre, shown as -63 to +63
+/// let pan = Parameter::new("Pan", 0x40_1C, 1..=127).offset(64);
+/// assert_eq!(pan.to_value(0x40), 0.0);
+/// assert_eq!(pan.to_raw(-63.0).unwrap(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub address: u32,
+    /// Range of the raw value
+    pub range: RangeInclusive<u32>,
+    /// Number of data bytes
+    pub size: usize,
+    pub encoding: ParameterEncoding,
+    pub offset: i64,
+    pub scale: f64,
+}
+
+impl Parameter {
+    /// Create a single byte parameter with an unscaled value
+    pub fn new(name: &str, address: u32, range: RangeInclusive<u32>) -> Self {
+        Parameter {
+            name: name.to_string(),
+            address,
+            range,
+            size: 1,
+            encoding: ParameterEncoding::SevenBit,
+            offset: 0,
+            scale: 1.0,
+        }
+    }
+
+    /// Set the number of data bytes and how the value is split between them
+    pub fn size(mut self, size: usize, encoding: ParameterEncoding) -> Self {
+        self.size = size;
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the raw value that corresponds to zero
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the size of each step of the raw value
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Returns the value for a raw value
+    pub fn to_value(&self, raw: u32) -> f64 {
+        (i64::from(raw) - self.offset) as f64 * self.scale
+    }
+
+    /// Returns the raw value nearest a value, or an error if it is out of range
+    pub fn to_raw(&self, value: f64) -> Result<u32, RtMidiError> {
+        let raw = (value / self.scale).round() + self.offset as f64;
+        if !(raw >= f64::from(*self.range.start()) && raw <= f64::from(*self.range.end())) {
+            return Err(RtMidiError::Error(format!(
+                "Value {} is out of range for parameter {}",
+                value, self.name
+            )));
+        }
+        Ok(raw as u32)
+    }
+
+    /// Returns the data bytes for a raw value
+    pub fn encode(&self, raw: u32) -> Vec<u8> {
+        let bits = self.bits();
+        (0..self.size)
+            .rev()
+            .map(|index| {
+                let shift = bits * index as u32;
+                let value = raw.checked_shr(shift).unwrap_or(0);
+                (value & ((1 << bits) - 1)) as u8
+            })
+            .collect()
+    }
+
+    /// Returns the raw value of data bytes
+    pub fn decode(&self, data: &[u8]) -> Result<u32, RtMidiError> {
+        let bits = self.bits();
+        if data.len() != self.size || data.iter().any(|&byte| u32::from(byte) >> bits != 0) {
+            return Err(RtMidiError::InvalidMessage(format!(
+                "invalid data for parameter {}",
+                self.name
+            )));
+        }
+        Ok(data.iter().fold(0, |raw, &byte| {
+            raw.checked_shl(bits).unwrap_or(0) | u32::from(byte)
+        }))
+    }
+
+    fn bits(&self) -> u32 {
+        match self.encoding {
+            ParameterEncoding::SevenBit => 7,
+            ParameterEncoding::Nibbles => 4,
+        }
+    }
+}
+
+/// System exclusive messages that read and write a device's address space
+///
+/// Implemented for [`crate::RolandDevice`]; other devices can be supported by implementing it.
+pub trait ParameterProtocol {
+    /// Returns a message writing `data` to `address`
+    fn write_message(&self, address: u32, data: &[u8]) -> Result<Vec<u8>, RtMidiError>;
+    /// Returns a message requesting `size` bytes from `address`
+    fn read_message(&self, address: u32, size: usize) -> Result<Vec<u8>, RtMidiError>;
+    /// Returns the data of a reply to a read of `size` bytes from `address`, or [`None`] if the
+    /// message isn't the reply
+    fn parse_reply(&self, address: u32, size: usize, message: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Named parameters of a device, read and written with system exclusive messages
+///
+/// Gives editors a uniform interface across devices: the device's protocol is described by a
+/// [`ParameterProtocol`], and its parameters by [`Parameter`]s. Values are exchanged with the
+/// device through a [`SysExTransaction`].
+/// ```no_run
+/// use rtmidi::{Parameter, ParameterMap, RolandDevice, RtMidiIn, RtMidiOut, SysExTransaction};
+///
+/// let map = ParameterMap::new(RolandDevice::new(0x10, &[0x42], 3))
+///     .parameter(Parameter::new("Master Volume", 0x40_00_04, 0..=127))
+///     .parameter(Parameter::new("Master Key Shift", 0x40_00_05, 0x28..=0x58).offset(0x40));
+///
+/// # let input = RtMidiIn::new(Default::default()).unwrap();
+/// # let output = RtMidiOut::new(Default::default()).unwrap();
+/// let device = SysExTransaction::new(&input, &output);
+/// map.set(&device, "Master Key Shift", -12.0).unwrap();
+/// println!("Volume: {}", map.get(&device, "Master Volume").unwrap());
+/// ```
+pub struct ParameterMap<P> {
+    protocol: Arc<P>,
+    parameters: Vec<Parameter>,
+}
+
+impl<P: ParameterProtocol + Send + Sync + 'static> ParameterMap<P> {
+    pub fn new(protocol: P) -> Self {
+        ParameterMap {
+            protocol: Arc::new(protocol),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Add a parameter
+    pub fn parameter(mut self, parameter: Parameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    /// Returns the parameters, in the order they were added
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// Returns the parameter with a name
+    pub fn find(&self, name: &str) -> Option<&Parameter> {
+        self.parameters
+            .iter()
+            .find(|parameter| parameter.name == name)
+    }
+
+    /// Read the value of a parameter from the device
+    pub fn get(&self, device: &SysExTransaction, name: &str) -> Result<f64, RtMidiError> {
+        let parameter = self.lookup(name)?;
+        let (address, size) = (parameter.address, parameter.size);
+        let request = self.protocol.read_message(address, size)?;
+        let protocol = Arc::clone(&self.protocol);
+        let reply = device.request(&request, move |message| {
+            protocol.parse_reply(address, size, message).is_some()
+        })?;
+        let data = self
+            .protocol
+            .parse_reply(address, size, &reply)
+            .unwrap_or_default();
+        Ok(parameter.to_value(parameter.decode(&data)?))
+    }
+
+    /// Write the value of a parameter to the device
+    pub fn set(
+        &self,
+        device: &SysExTransaction,
+        name: &str,
+        value: f64,
+    ) -> Result<(), RtMidiError> {
+        let parameter = self.lookup(name)?;
+        let data = parameter.encode(parameter.to_raw(value)?);
+        device.send(&self.protocol.write_message(parameter.address, &data)?)
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Parameter, RtMidiError> {
+        self.find(name)
+            .ok_or_else(|| RtMidiError::Error(format!("Unknown parameter {}", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Parameter, ParameterEncoding, ParameterProtocol};
+    use crate::roland::RolandDevice;
+
+    #[test]
+    fn scaling() {
+        let parameter = Parameter::new("Fine Tune", 0, 0..=2000)
+            .offset(1000)
+            .scale(0.1);
+        assert_eq!(parameter.to_value(1000), 0.0);
+        assert_eq!(parameter.to_value(0), -100.0);
+        assert_eq!(parameter.to_raw(100.0).unwrap(), 2000);
+        assert_eq!(parameter.to_raw(-0.04).unwrap(), 1000);
+        assert!(parameter.to_raw(100.1).is_err());
+        assert!(parameter.to_raw(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn encoding() {
+        let parameter = Parameter::new("Tempo", 0, 0..=0xFFFF).size(4, ParameterEncoding::Nibbles);
+        assert_eq!(parameter.encode(0x1234), [0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(parameter.decode(&[0x01, 0x02, 0x03, 0x04]).unwrap(), 0x1234);
+        assert!(parameter.decode(&[0x10, 0x02, 0x03, 0x04]).is_err());
+        assert!(parameter.decode(&[0x01]).is_err());
+
+        let parameter = Parameter::new("Level", 0, 0..=16383).size(2, ParameterEncoding::SevenBit);
+        assert_eq!(parameter.encode(16383), [0x7F, 0x7F]);
+        assert_eq!(parameter.decode(&[0x01, 0x00]).unwrap(), 128);
+    }
+
+    #[test]
+    fn roland() {
+        let device = RolandDevice::new(0x10, &[0x42], 3);
+        let reply = device.data_set(0x400004, &[0x7F]).unwrap();
+        assert_eq!(device.parse_reply(0x400004, 1, &reply), Some(vec![0x7F]));
+        assert_eq!(device.parse_reply(0x400005, 1, &reply), None);
+        assert_eq!(device.parse_reply(0x400004, 2, &reply), None);
+        let request = device.read_message(0x400004, 1).unwrap();
+        assert_eq!(device.parse_reply(0x400004, 1, &request), None);
+    }
+}
@@ -0,0 +1,455 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::backend::{Backend, BackendCallback, InputConnection, OutputConnection};
+use crate::decoder::data_length;
+use crate::error::RtMidiError;
+use crate::threads;
+
+/// Size of the reads from a pipe
+const READ_SIZE: usize = 1024;
+/// Size of a timestamped frame's header: microseconds since the previous message (u32) and the
+/// message length (u16), both little-endian
+const FRAME_HEADER_SIZE: usize = 6;
+/// Size of a length-prefixed frame's header: the message length (u16, little-endian)
+const LENGTH_SIZE: usize = 2;
+
+type Reader = Box<dyn Read + Send>;
+type Writer = Box<dyn Write + Send>;
+
+/// How messages are written to a pipe's byte stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PipeFraming {
+    /// Raw MIDI bytes, as on a MIDI cable. Input may use running status, and real-time messages
+    /// may interrupt other messages.
+    #[default]
+    Raw,
+    /// Each message is preceded by the microseconds since the previous message (a little-endian
+    /// `u32`) and its length (a little-endian `u16`), so timing survives buffering
+    Timestamped,
+    /// Each message is preceded by its length (a little-endian `u16`)
+    LengthPrefixed,
+}
+
+/// [`Backend`] reading and writing MIDI byte streams
+///
+/// Provides one input port reading from a [`Read`], and one output port writing to a [`Write`],
+/// both named after the backend. With [`PipeBackend::stdio`], MIDI tools can be composed in
+/// shell pipelines (e.g. `midi-source | filter | midi-sink`), and processes can be tested by
+/// feeding them bytes.
+///
+/// A read can't be interrupted, so an input only notices it's been closed after its next read
+/// returns, and the port can only be opened again once it has.
+/// ```no_run
+/// use rtmidi::{MidiSystem, PipeBackend, RtMidiApi};
+///
+/// let system = MidiSystem::new(RtMidiApi::Unspecified, "filter")
+///     .unwrap()
+///     .backend(PipeBackend::stdio());
+/// system.open_input("stdio").unwrap();
+/// system.open_output("stdio").unwrap();
+/// system.route("stdio", "stdio").unwrap();
+/// ```
+pub struct PipeBackend {
+    name: String,
+    framing: PipeFraming,
+    // Whether there is an input, as its reader is taken while it's open
+    input: bool,
+    reader: Arc<Mutex<Option<Reader>>>,
+    writer: Option<Arc<Mutex<Writer>>>,
+}
+
+impl PipeBackend {
+    /// Create a backend whose ports have the given name, with an input if there is a reader and
+    /// an output if there is a writer
+    pub fn new(name: &str, reader: Option<Reader>, writer: Option<Writer>) -> Self {
+        PipeBackend {
+            name: name.to_string(),
+            framing: PipeFraming::default(),
+            input: reader.is_some(),
+            reader: Arc::new(Mutex::new(reader)),
+            writer: writer.map(|writer| Arc::new(Mutex::new(writer))),
+        }
+    }
+
+    /// Create a backend reading from stdin and writing to stdout, with ports named "stdio"
+    pub fn stdio() -> Self {
+        PipeBackend::new(
+            "stdio",
+            Some(Box::new(io::stdin())),
+            Some(Box::new(io::stdout())),
+        )
+    }
+
+    /// Set the framing of both streams
+    pub fn framing(mut self, framing: PipeFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    fn find(&self, port: &str, available: bool) -> Result<(), RtMidiError> {
+        if available && port == self.name {
+            Ok(())
+        } else {
+            Err(RtMidiError::PortNotFound(port.to_string()))
+        }
+    }
+}
+
+impl Backend for PipeBackend {
+    fn name(&self) -> &str {
+        "pipe"
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        Ok(Some(self.name.clone())
+            .filter(|_| self.input)
+            .into_iter()
+            .collect())
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        Ok(Some(self.name.clone())
+            .filter(|_| self.writer.is_some())
+            .into_iter()
+            .collect())
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        _client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        self.find(port, self.input)?;
+        let mut reader = lock(&self.reader)
+            .take()
+            .ok_or_else(|| RtMidiError::Error(format!("{} is already open", port)))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let slot = Arc::clone(&self.reader);
+        let framing = self.framing;
+        let thread_stop = Arc::clone(&stop);
+        // The thread is left to end by itself, as it may be blocked reading
+        threads::spawn(format!("rtmidi-in:{}", port), move || {
+            let _ = match framing {
+                PipeFraming::Raw => read_raw(&mut reader, &thread_stop, &callback),
+                framing => read_frames(&mut reader, framing, &thread_stop, &callback),
+            };
+            // Give the reader back so the port can be opened again
+            if thread_stop.load(Ordering::Relaxed) {
+                *lock(&slot) = Some(reader);
+            }
+        });
+        Ok(Box::new(PipeInput(stop)))
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        _client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        self.find(port, self.writer.is_some())?;
+        Ok(Box::new(PipeOutput {
+            writer: Arc::clone(self.writer.as_ref().unwrap()),
+            framing: self.framing,
+            last: Mutex::new(None),
+        }))
+    }
+}
+
+/// Input port opened by a [`PipeBackend`], whose thread stops after its next read when dropped
+struct PipeInput(Arc<AtomicBool>);
+
+impl InputConnection for PipeInput {}
+
+impl Drop for PipeInput {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Read raw bytes until the end of the stream or stopped, passing each message to the callback
+fn read_raw(reader: &mut Reader, stop: &AtomicBool, f: &BackendCallback) -> io::Result<()> {
+    let mut parser = StreamParser::default();
+    let mut buffer = [0; READ_SIZE];
+    let mut last: Option<Instant> = None;
+    loop {
+        let length = reader.read(&mut buffer)?;
+        if length == 0 || stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        for &byte in &buffer[..length] {
+            parser.push(byte, |message| {
+                let now = Instant::now();
+                let delta = last.map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+                last = Some(now);
+                f(delta, message);
+            });
+        }
+    }
+}
+
+/// Read timestamped or length-prefixed frames until the end of the stream or stopped, passing
+/// each message to the callback
+fn read_frames(
+    reader: &mut Reader,
+    framing: PipeFraming,
+    stop: &AtomicBool,
+    f: &BackendCallback,
+) -> io::Result<()> {
+    let timestamped = framing == PipeFraming::Timestamped;
+    let mut header = [0; FRAME_HEADER_SIZE];
+    let header = if timestamped {
+        &mut header[..]
+    } else {
+        &mut header[..LENGTH_SIZE]
+    };
+    let mut message = Vec::new();
+    let mut last: Option<Instant> = None;
+    loop {
+        if let Err(e) = reader.read_exact(header) {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(e),
+            };
+        }
+        let (delta, length) = header.split_at(header.len() - LENGTH_SIZE);
+        let length = u16::from_le_bytes([length[0], length[1]]);
+        message.resize(usize::from(length), 0);
+        reader.read_exact(&mut message)?;
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let delta = match *delta {
+            [a, b, c, d] => f64::from(u32::from_le_bytes([a, b, c, d])) / 1_000_000.0,
+            _ => last.map_or(0.0, |last| now.duration_since(last).as_secs_f64()),
+        };
+        last = Some(now);
+        f(delta, &message);
+    }
+}
+
+/// Output port opened by a [`PipeBackend`]
+struct PipeOutput {
+    writer: Arc<Mutex<Writer>>,
+    framing: PipeFraming,
+    // When the previous message was sent, for timestamped frames
+    last: Mutex<Option<Instant>>,
+}
+
+impl OutputConnection for PipeOutput {
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        let mut writer = lock(&self.writer);
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + message.len());
+        if self.framing != PipeFraming::Raw {
+            let length = u16::try_from(message.len())
+                .map_err(|_| RtMidiError::InvalidMessage("message too long".to_string()))?;
+            if self.framing == PipeFraming::Timestamped {
+                let now = Instant::now();
+                let mut last = lock(&self.last);
+                let delta = last.map_or(0, |last| {
+                    now.duration_since(last)
+                        .as_micros()
+                        .min(u128::from(u32::MAX)) as u32
+                });
+                *last = Some(now);
+                frame.extend_from_slice(&delta.to_le_bytes());
+            }
+            frame.extend_from_slice(&length.to_le_bytes());
+        }
+        frame.extend_from_slice(message);
+        writer
+            .write_all(&frame)
+            .and_then(|_| writer.flush())
+            .map_err(|e| RtMidiError::Error(e.to_string()))
+    }
+}
+
+/// Splits a raw byte stream into messages, keeping incomplete messages across reads
+#[derive(Debug)]
+struct StreamParser {
+    // Running status
+    status: Option<u8>,
+    buffer: Vec<u8>,
+    // Data bytes still to come, or None within a system exclusive message
+    remaining: Option<usize>,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        StreamParser {
+            status: None,
+            buffer: Vec::new(),
+            remaining: Some(0),
+        }
+    }
+}
+
+impl StreamParser {
+    fn push<F: FnMut(&[u8])>(&mut self, byte: u8, mut f: F) {
+        match byte {
+            // Real-time messages may be interleaved with any other message
+            0xF8..=0xFF => return f(&[byte]),
+            0xF7 if self.remaining.is_none() && !self.buffer.is_empty() => {
+                self.buffer.push(byte);
+                f(&self.buffer);
+                self.buffer.clear();
+                self.remaining = Some(0);
+                return;
+            }
+            0x80..=0xF7 => {
+                // A new status byte truncates any incomplete message
+                self.status = match byte {
+                    0x80..=0xEF => Some(byte),
+                    _ => None,
+                };
+                self.buffer.clear();
+                self.buffer.push(byte);
+                self.remaining = data_length(byte);
+            }
+            _ => match self.remaining {
+                None => self.buffer.push(byte),
+                Some(0) => match self.status {
+                    Some(status) => {
+                        self.buffer.clear();
+                        self.buffer.extend_from_slice(&[status, byte]);
+                        self.remaining = data_length(status).map(|length| length - 1);
+                    }
+                    // Orphan data bytes are dropped
+                    None => return,
+                },
+                Some(length) => {
+                    self.buffer.push(byte);
+                    self.remaining = Some(length - 1);
+                }
+            },
+        }
+        if self.remaining == Some(0) && !self.buffer.is_empty() {
+            f(&self.buffer);
+            self.buffer.clear();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Write};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{PipeBackend, PipeFraming, StreamParser};
+    use crate::{Backend, RtMidiError};
+
+    /// Writer whose output the test can read
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn receive(backend: &PipeBackend, count: usize) -> Vec<(f64, Vec<u8>)> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let _input = backend
+            .open_input(
+                "pipe",
+                "Test",
+                Box::new(move |delta, message| {
+                    sink.lock().unwrap().push((delta, message.to_vec()))
+                }),
+            )
+            .unwrap();
+        let start = Instant::now();
+        while received.lock().unwrap().len() < count && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let received = received.lock().unwrap().clone();
+        received
+    }
+
+    #[test]
+    fn parser() {
+        let mut parser = StreamParser::default();
+        let mut messages = Vec::new();
+        let stream = [
+            0x90, 60, 0xF8, 100, 62, 90, 0xF0, 0x7E, 0x7F, 0xF8, 0x06, 0xF7, 64, 0xC0, 5,
+        ];
+        for &byte in stream.iter() {
+            parser.push(byte, |message| messages.push(message.to_vec()));
+        }
+        assert_eq!(
+            messages,
+            [
+                vec![0xF8],
+                vec![0x90, 60, 100],
+                vec![0x90, 62, 90],
+                vec![0xF8],
+                vec![0xF0, 0x7E, 0x7F, 0x06, 0xF7],
+                vec![0xC0, 5],
+            ]
+        );
+    }
+
+    #[test]
+    fn raw() {
+        let output = Shared::default();
+        let backend = PipeBackend::new(
+            "pipe",
+            Some(Box::new(Cursor::new(vec![0x90, 60, 100, 0x80, 60, 0]))),
+            Some(Box::new(output.clone())),
+        );
+        assert_eq!(backend.input_ports().unwrap(), ["pipe"]);
+        assert_eq!(backend.output_ports().unwrap(), ["pipe"]);
+        let received = receive(&backend, 2);
+        assert_eq!(received[0].1, [0x90, 60, 100]);
+        assert_eq!(received[1].1, [0x80, 60, 0]);
+
+        let port = backend.open_output("pipe", "Test").unwrap();
+        port.send(&[0xB0, 7, 100]).unwrap();
+        assert_eq!(*output.0.lock().unwrap(), [0xB0, 7, 100]);
+        assert_eq!(
+            backend.open_output("other", "Test").err(),
+            Some(RtMidiError::PortNotFound("other".to_string()))
+        );
+    }
+
+    #[test]
+    fn timestamped() {
+        let output = Shared::default();
+        let backend = PipeBackend::new("pipe", None, Some(Box::new(output.clone())))
+            .framing(PipeFraming::Timestamped);
+        assert!(backend.input_ports().unwrap().is_empty());
+        let port = backend.open_output("pipe", "Test").unwrap();
+        port.send(&[0x90, 60, 100]).unwrap();
+        port.send(&[0xFA]).unwrap();
+        let written = output.0.lock().unwrap().clone();
+        assert_eq!(written[..9], [0, 0, 0, 0, 3, 0, 0x90, 60, 100]);
+        assert_eq!(written[13..], [1, 0, 0xFA]);
+
+        let mut stream = written;
+        // 0.25 seconds before the next message
+        stream.extend_from_slice(&[0x90, 0xD0, 0x03, 0x00, 2, 0, 0xC0, 5]);
+        let backend = PipeBackend::new("pipe", Some(Box::new(Cursor::new(stream))), None)
+            .framing(PipeFraming::Timestamped);
+        let received = receive(&backend, 3);
+        assert_eq!(received[0], (0.0, vec![0x90, 60, 100]));
+        assert_eq!(received[1].1, [0xFA]);
+        assert_eq!(received[2], (0.25, vec![0xC0, 5]));
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::api::RtMidiApi;
+
+/// Longest device name reported by the Windows MM API, which truncates longer names
+const WINDOWS_MM_NAME_LENGTH: usize = 31;
+
+/// Backend-specific part of a port name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PortSuffix {
+    /// ALSA sequencer address of the port
+    AlsaAddress { client: u8, port: u8 },
+    /// Number added by the Windows MM API to tell apart devices with the same name
+    Number(u32),
+}
+
+/// A port name split into its parts
+///
+/// Each API names ports differently: ALSA as "Device:Port client:port", JACK as "client:port",
+/// Windows MM as the (truncated) device name followed by a number, and CoreMIDI as the device and
+/// port names run together. Parsing them gives the same fields for each, so ports can be matched
+/// without knowing which API listed them.
+/// ```
+/// use rtmidi::{PortName, PortSuffix, RtMidiApi};
+///
+/// let name = PortName::parse(RtMidiApi::LinuxALSA, "Launchpad X:Launchpad X MIDI 1 28:0");
+/// assert_eq!(name.device, "Launchpad X");
+/// assert_eq!(name.port.as_deref(), Some("Launchpad X MIDI 1"));
+/// assert_eq!(name.suffix, Some(PortSuffix::AlsaAddress { client: 28, port: 0 }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PortName {
+    /// Name of the device (or ALSA and JACK client)
+    pub device: String,
+    /// Name of the port within the device, where the API gives it separately
+    pub port: Option<String>,
+    /// Backend-specific part of the name, such as the ALSA address
+    pub suffix: Option<PortSuffix>,
+    /// Whether the device name was cut short by the API
+    pub truncated: bool,
+}
+
+impl PortName {
+    /// Parse a port name listed by an API. For [`RtMidiApi::Unspecified`] (or the dummy API) the
+    /// form is guessed: ALSA names are recognised by their address, and anything else is taken as
+    /// a device name.
+    pub fn parse(api: RtMidiApi, name: &str) -> Self {
+        match api {
+            RtMidiApi::LinuxALSA => alsa(name),
+            RtMidiApi::UnixJack => split(name, None),
+            RtMidiApi::WindowsMM => windows_mm(name),
+            RtMidiApi::MacOSXCore => device(name),
+            RtMidiApi::Unspecified | RtMidiApi::RtMidiDummy => match alsa_address(name) {
+                Some(_) => alsa(name),
+                None => device(name),
+            },
+        }
+    }
+
+    /// Returns the most specific part of the name: the port name if there is one, otherwise the
+    /// device name
+    pub fn label(&self) -> &str {
+        self.port.as_deref().unwrap_or(&self.device)
+    }
+
+    /// Returns [`true`] if `pattern` appears in the device or port name, ignoring case
+    pub fn matches(&self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        self.device.to_lowercase().contains(&pattern)
+            || matches!(&self.port, Some(port) if port.to_lowercase().contains(&pattern))
+    }
+}
+
+impl fmt::Display for PortName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.port {
+            Some(port) => write!(f, "{}: {}", self.device, port),
+            None => write!(f, "{}", self.device),
+        }
+    }
+}
+
+fn device(name: &str) -> PortName {
+    PortName {
+        device: name.to_string(),
+        port: None,
+        suffix: None,
+        truncated: false,
+    }
+}
+
+/// Split "device:port" at the first colon
+fn split(name: &str, suffix: Option<PortSuffix>) -> PortName {
+    match name.split_once(':') {
+        Some((device, port)) => PortName {
+            device: device.to_string(),
+            port: Some(port.to_string()),
+            suffix,
+            truncated: false,
+        },
+        None => PortName {
+            suffix,
+            ..device(name)
+        },
+    }
+}
+
+fn alsa(name: &str) -> PortName {
+    match alsa_address(name) {
+        Some((rest, client, port)) => split(rest, Some(PortSuffix::AlsaAddress { client, port })),
+        None => split(name, None),
+    }
+}
+
+/// Split the trailing " client:port" address from an ALSA port name
+fn alsa_address(name: &str) -> Option<(&str, u8, u8)> {
+    let (rest, address) = name.rsplit_once(' ')?;
+    let (client, port) = address.split_once(':')?;
+    Some((rest, client.parse().ok()?, port.parse().ok()?))
+}
+
+fn windows_mm(name: &str) -> PortName {
+    let (device, number) = match name.rsplit_once(' ') {
+        Some((device, number)) => match number.parse() {
+            Ok(number) => (device, Some(PortSuffix::Number(number))),
+            Err(_) => (name, None),
+        },
+        None => (name, None),
+    };
+    PortName {
+        device: device.to_string(),
+        port: None,
+        suffix: number,
+        truncated: device.encode_utf16().count() >= WINDOWS_MM_NAME_LENGTH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PortName, PortSuffix};
+    use crate::api::RtMidiApi;
+
+    #[test]
+    fn parse() {
+        let name = PortName::parse(
+            RtMidiApi::UnixJack,
+            "a2j:Launchpad X [28] (capture): MIDI 1",
+        );
+        assert_eq!(name.device, "a2j");
+        assert_eq!(
+            name.port.as_deref(),
+            Some("Launchpad X [28] (capture): MIDI 1")
+        );
+        assert_eq!(name.suffix, None);
+
+        let name = PortName::parse(RtMidiApi::WindowsMM, "Focusrite USB MIDI 1");
+        assert_eq!(name.device, "Focusrite USB MIDI");
+        assert_eq!(name.suffix, Some(PortSuffix::Number(1)));
+        assert!(!name.truncated);
+        let name = PortName::parse(RtMidiApi::WindowsMM, "MIDIIN2 (Arturia KeyStep Pro Mk 0");
+        assert!(name.truncated);
+
+        let name = PortName::parse(RtMidiApi::MacOSXCore, "IAC Driver Bus 1");
+        assert_eq!(name.device, "IAC Driver Bus 1");
+        assert_eq!(name.label(), "IAC Driver Bus 1");
+
+        let name = PortName::parse(
+            RtMidiApi::Unspecified,
+            "Midi Through:Midi Through Port-0 14:0",
+        );
+        assert_eq!(name.label(), "Midi Through Port-0");
+        assert_eq!(
+            name.suffix,
+            Some(PortSuffix::AlsaAddress {
+                client: 14,
+                port: 0
+            })
+        );
+        assert!(name.matches("through port"));
+        assert_eq!(name.to_string(), "Midi Through: Midi Through Port-0");
+    }
+}
@@ -0,0 +1,353 @@
+use std::cmp::Ordering;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::alsa::AlsaSequencer;
+use crate::api::RtMidiApi;
+#[cfg(all(feature = "coremidi", target_os = "macos"))]
+use crate::coremidi;
+use crate::error::RtMidiError;
+use crate::midi_in::{RtMidiIn, RtMidiInArgs};
+use crate::midi_out::{RtMidiOut, RtMidiOutArgs};
+use crate::port_name::PortName;
+use crate::system::PortDirection;
+use crate::usb::{self, UsbId};
+use crate::RtMidiPort;
+
+/// Client and virtual port names of the instances open in this process
+static OWN_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// What kind of port a [`PortInfo`] is, as far as can be told
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PortKind {
+    /// A port of a physical device, such as a USB keyboard or a DIN interface
+    Hardware,
+    /// A port of a program, such as a virtual port or a software synthesizer
+    Software,
+    /// A loopback port, which passes on whatever is sent to it, such as ALSA's "Midi Through" or
+    /// the macOS IAC buses
+    Through,
+    /// The API gave nothing to go on
+    Unknown,
+}
+
+/// A port listed by [`RtMidiIn::ports`] or [`RtMidiOut::ports`]
+/// ```
+/// use rtmidi::RtMidiIn;
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// for port in input.ports().unwrap().iter().filter(|port| !port.is_own_client()) {
+///     println!("{}: {}", port.number, port.name);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PortInfo {
+    /// Port number, valid until devices are added or removed
+    pub number: RtMidiPort,
+    /// Name of the port as given by the API
+    pub name: String,
+    /// API the port was listed by
+    pub api: RtMidiApi,
+    /// Best guess at what kind of port this is, from what the API reports (the ALSA port type or
+    /// the CoreMIDI device) and failing that the name
+    pub kind: PortKind,
+    /// USB identity of the device behind the port, where the platform reports it (Linux ALSA and
+    /// JACK, and Windows MM with the `winmm` feature)
+    pub usb: Option<UsbId>,
+    own_client: bool,
+}
+
+impl PortInfo {
+    fn new(
+        api: RtMidiApi,
+        direction: PortDirection,
+        number: RtMidiPort,
+        name: &str,
+        hints: &Hints,
+    ) -> Self {
+        let own_client = is_own(api, name);
+        let kind = if own_client {
+            PortKind::Software
+        } else {
+            hints.kind(api, name)
+        };
+        PortInfo {
+            number,
+            name: name.to_string(),
+            api,
+            kind,
+            usb: usb::lookup(api, direction, number, name),
+            own_client,
+        }
+    }
+
+    /// Returns [`true`] if the port belongs to an [`RtMidiIn`] or [`RtMidiOut`] in this process,
+    /// such as a virtual port it opened, so that an application doesn't connect to itself.
+    ///
+    /// Ports are recognised by their client name (ALSA and JACK) or virtual port name (CoreMIDI),
+    /// so another program using the same name is taken for this one. Give instances a distinctive
+    /// client name to avoid this.
+    pub fn is_own_client(&self) -> bool {
+        self.own_client
+    }
+
+    /// Returns the port name split into its parts
+    pub fn port_name(&self) -> PortName {
+        PortName::parse(self.api, &self.name)
+    }
+
+    fn sort_key(&self) -> (&str, RtMidiApi, RtMidiPort, PortKind, &Option<UsbId>, bool) {
+        (
+            &self.name,
+            self.api,
+            self.number,
+            self.kind,
+            &self.usb,
+            self.own_client,
+        )
+    }
+}
+
+/// Returns the names of the available MIDI input ports, in port number order.
+///
+/// A temporary client is created to enumerate the ports and closed again before returning, so a
+/// device list can be shown without keeping an [`RtMidiIn`] around. Port numbers can change as
+/// devices come and go, so open ports by name where possible.
+/// ```
+/// use rtmidi::RtMidiApi;
+///
+/// for (port, name) in rtmidi::input_ports(RtMidiApi::Unspecified).unwrap().iter().enumerate() {
+///     println!("{}: {}", port, name);
+/// }
+/// ```
+pub fn input_ports(api: RtMidiApi) -> Result<Vec<String>, RtMidiError> {
+    let input = RtMidiIn::new(RtMidiInArgs {
+        api,
+        ..Default::default()
+    })?;
+    port_names(input.port_count()?, |port| input.port_name(port))
+}
+
+/// Returns the names of the available MIDI output ports, in port number order.
+///
+/// See [`input_ports`].
+pub fn output_ports(api: RtMidiApi) -> Result<Vec<String>, RtMidiError> {
+    let output = RtMidiOut::new(RtMidiOutArgs {
+        api,
+        ..Default::default()
+    })?;
+    port_names(output.port_count()?, |port| output.port_name(port))
+}
+
+/// Returns the names of `count` ports, in port number order
+pub(crate) fn port_names<'a, F>(count: RtMidiPort, name: F) -> Result<Vec<String>, RtMidiError>
+where
+    F: Fn(RtMidiPort) -> Result<&'a str, RtMidiError>,
+{
+    (0..count)
+        .map(|port| name(port).map(String::from))
+        .collect()
+}
+
+impl Ord for PortInfo {
+    /// Ports sort by name, so they can be listed alphabetically
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for PortInfo {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returns the `count` ports listed by `api`, in port number order
+pub(crate) fn port_info<'a, F>(
+    api: RtMidiApi,
+    direction: PortDirection,
+    count: RtMidiPort,
+    name: F,
+) -> Result<Vec<PortInfo>, RtMidiError>
+where
+    F: Fn(RtMidiPort) -> Result<&'a str, RtMidiError>,
+{
+    let hints = Hints::new(api);
+    (0..count)
+        .map(|port| name(port).map(|name| PortInfo::new(api, direction, port, name, &hints)))
+        .collect()
+}
+
+/// Port kinds reported by the backend, looked up once per enumeration
+#[derive(Default)]
+struct Hints {
+    kinds: Vec<(String, PortKind)>,
+    // Kind of the ports the backend doesn't report on
+    unlisted: Option<PortKind>,
+}
+
+impl Hints {
+    fn new(api: RtMidiApi) -> Self {
+        match api {
+            #[cfg(all(feature = "alsa", target_os = "linux"))]
+            RtMidiApi::LinuxALSA => Hints {
+                kinds: AlsaSequencer::new()
+                    .and_then(|sequencer| sequencer.port_kinds())
+                    .unwrap_or_default(),
+                unlisted: None,
+            },
+            // Only virtual endpoints are missing from the device tree
+            #[cfg(all(feature = "coremidi", target_os = "macos"))]
+            RtMidiApi::MacOSXCore => match coremidi::port_kinds() {
+                Ok(kinds) => Hints {
+                    kinds,
+                    unlisted: Some(PortKind::Software),
+                },
+                Err(_) => Hints::default(),
+            },
+            _ => Hints::default(),
+        }
+    }
+
+    fn kind(&self, api: RtMidiApi, name: &str) -> PortKind {
+        match self.kinds.iter().find(|(port, _)| port == name) {
+            Some(&(_, kind)) => kind,
+            None => self.unlisted.unwrap_or_else(|| kind_from_name(api, name)),
+        }
+    }
+}
+
+/// Guess the kind of a port from the names of well-known ports
+fn kind_from_name(api: RtMidiApi, name: &str) -> PortKind {
+    let name = PortName::parse(api, name);
+    match (api, name.device.as_str()) {
+        (RtMidiApi::UnixJack, "system") => PortKind::Hardware,
+        (_, "Midi Through") | (_, "IAC Driver") => PortKind::Through,
+        (_, device) if device.starts_with("IAC Driver ") || device.starts_with("loopMIDI") => {
+            PortKind::Through
+        }
+        (RtMidiApi::WindowsMM, "Microsoft GS Wavetable Synth") => PortKind::Software,
+        // The a2j bridge names its ports after the ALSA port they stand for
+        (RtMidiApi::UnixJack, "a2j") if name.label().starts_with("Midi Through") => {
+            PortKind::Through
+        }
+        _ => PortKind::Unknown,
+    }
+}
+
+/// A client or virtual port name registered as belonging to this process while it's held
+pub(crate) struct OwnName(String);
+
+impl OwnName {
+    pub fn new(name: &str) -> Self {
+        lock().push(name.to_string());
+        OwnName(name.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for OwnName {
+    fn drop(&mut self) {
+        let mut names = lock();
+        if let Some(index) = names.iter().position(|name| *name == self.0) {
+            names.swap_remove(index);
+        }
+    }
+}
+
+fn lock() -> MutexGuard<'static, Vec<String>> {
+    OWN_NAMES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns [`true`] if the device (ALSA or JACK client) of a port listed by `api` has a name
+/// registered by this process. CoreMIDI lists virtual ports by their own name, which is the
+/// device name when parsed.
+fn is_own(api: RtMidiApi, name: &str) -> bool {
+    let name = PortName::parse(api, name);
+    lock().contains(&name.device)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{input_ports, is_own, kind_from_name, output_ports, OwnName, PortInfo, PortKind};
+    use crate::RtMidiApi;
+
+    #[test]
+    fn ports() {
+        assert!(input_ports(RtMidiApi::Unspecified).is_ok());
+        assert!(output_ports(RtMidiApi::Unspecified).is_ok());
+    }
+
+    #[test]
+    fn own_client() {
+        let port = "rtmidi own_client test:Virtual Port 130:0";
+        assert!(!is_own(RtMidiApi::LinuxALSA, port));
+        let client = OwnName::new("rtmidi own_client test");
+        let other = OwnName::new("rtmidi own_client test");
+        assert!(is_own(RtMidiApi::LinuxALSA, port));
+        drop(client);
+        assert!(is_own(RtMidiApi::LinuxALSA, port));
+        drop(other);
+        assert!(!is_own(RtMidiApi::LinuxALSA, port));
+
+        let _port = OwnName::new("rtmidi own_client test port");
+        assert!(is_own(RtMidiApi::MacOSXCore, "rtmidi own_client test port"));
+    }
+
+    #[test]
+    fn kinds() {
+        let kind = |api, name| kind_from_name(api, name);
+        assert_eq!(
+            kind(
+                RtMidiApi::LinuxALSA,
+                "Midi Through:Midi Through Port-0 14:0"
+            ),
+            PortKind::Through
+        );
+        assert_eq!(
+            kind(RtMidiApi::UnixJack, "system:midi_capture_1"),
+            PortKind::Hardware
+        );
+        assert_eq!(
+            kind(
+                RtMidiApi::UnixJack,
+                "a2j:Midi Through [14] (capture): Midi Through Port-0"
+            ),
+            PortKind::Through
+        );
+        assert_eq!(
+            kind(RtMidiApi::MacOSXCore, "IAC Driver Bus 1"),
+            PortKind::Through
+        );
+        assert_eq!(
+            kind(RtMidiApi::WindowsMM, "Microsoft GS Wavetable Synth 0"),
+            PortKind::Software
+        );
+        assert_eq!(
+            kind(RtMidiApi::WindowsMM, "Focusrite USB MIDI 1"),
+            PortKind::Unknown
+        );
+    }
+
+    #[test]
+    fn sort() {
+        let port = |number, name: &str| PortInfo {
+            number,
+            name: name.to_string(),
+            api: RtMidiApi::LinuxALSA,
+            kind: PortKind::Hardware,
+            usb: None,
+            own_client: false,
+        };
+        let mut ports = vec![port(0, "Midi Through"), port(1, "Keystation")];
+        ports.sort();
+        assert_eq!(ports[0].name, "Keystation");
+        let unique: HashSet<_> = ports.iter().chain(&ports).collect();
+        assert_eq!(unique.len(), 2);
+    }
+}
@@ -0,0 +1,367 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::RtMidiError;
+use crate::roland::ROLAND_ID;
+use crate::usb::UsbId;
+
+/// Roland's USB vendor ID. Roland devices need a pause of at least 20 ms between system
+/// exclusive messages.
+const ROLAND_VENDOR_ID: u16 = 0x0582;
+const ROLAND_SYSEX_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Quirks registered with [`register_quirks`], looked up along with the built-in ones
+static REGISTERED: Mutex<Vec<(DeviceMatch, Quirks)>> = Mutex::new(Vec::new());
+
+/// Ways a device deviates from the MIDI specification, which [`crate::RtMidiOut`] works around
+///
+/// The quirks of the device behind a port are looked up when the port is opened (see
+/// [`register_quirks`]) and applied to everything sent through it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Quirks {
+    /// Shortest pause between system exclusive packets
+    pub sysex_interval: Duration,
+    /// Largest system exclusive packet the device accepts. Longer messages are split, with
+    /// [`Quirks::sysex_interval`] between the packets.
+    pub max_sysex_packet: Option<usize>,
+    /// The device doesn't understand running status, so messages sent without a status byte have
+    /// the previous one added back
+    pub no_running_status: bool,
+}
+
+impl Quirks {
+    /// Returns [`true`] if the device has none of the quirks
+    pub fn is_empty(&self) -> bool {
+        *self == Quirks::default()
+    }
+
+    /// Combine the quirks of two matches, keeping the stricter of each
+    fn merge(self, other: Quirks) -> Quirks {
+        Quirks {
+            sysex_interval: self.sysex_interval.max(other.sysex_interval),
+            max_sysex_packet: match (self.max_sysex_packet, other.max_sysex_packet) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            no_running_status: self.no_running_status || other.no_running_status,
+        }
+    }
+}
+
+/// Identifies the devices a set of [`Quirks`] applies to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceMatch {
+    /// USB vendor ID, and product ID unless the quirks apply to every product of the vendor
+    Usb {
+        vendor_id: u16,
+        product_id: Option<u16>,
+    },
+    /// Text contained in the port name, ignoring case
+    Name(String),
+    /// Manufacturer ID (one or three bytes) from an identity reply, and family and model codes
+    /// unless the quirks apply more widely
+    Inquiry {
+        manufacturer: Vec<u8>,
+        family: Option<u16>,
+        model: Option<u16>,
+    },
+}
+
+impl DeviceMatch {
+    fn matches(&self, device: &Device) -> bool {
+        match (self, device) {
+            (
+                DeviceMatch::Usb {
+                    vendor_id,
+                    product_id,
+                },
+                Device::Port { usb: Some(usb), .. },
+            ) => {
+                *vendor_id == usb.vendor_id
+                    && product_id.unwrap_or(usb.product_id) == usb.product_id
+            }
+            (DeviceMatch::Name(pattern), Device::Port { name, .. }) => {
+                name.to_lowercase().contains(&pattern.to_lowercase())
+            }
+            (
+                DeviceMatch::Inquiry {
+                    manufacturer,
+                    family,
+                    model,
+                },
+                Device::Identity(identity),
+            ) => {
+                *manufacturer == identity.manufacturer
+                    && family.unwrap_or(identity.family) == identity.family
+                    && model.unwrap_or(identity.model) == identity.model
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What is known of a device when looking up its quirks
+enum Device<'a> {
+    Port {
+        name: &'a str,
+        usb: Option<&'a UsbId>,
+    },
+    Identity(&'a DeviceIdentity),
+}
+
+/// A device's reply to an identity request (`F0 7E <device> 06 01 F7`)
+/// ```
+/// use rtmidi::DeviceIdentity;
+///
+/// let reply = [0xF0, 0x7E, 0x10, 0x06, 0x02, 0x41, 0x42, 0x04, 0x02, 0x00, 0, 1, 0, 0, 0xF7];
+/// let identity = DeviceIdentity::parse(&reply).unwrap();
+/// assert_eq!(identity.manufacturer, [0x41]);
+/// assert_eq!(identity.family, 0x0442);
+/// assert_eq!(identity.model, 0x0002);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceIdentity {
+    /// Device ID the reply came from
+    pub device_id: u8,
+    /// Manufacturer ID: one byte, or three starting with `0x00`
+    pub manufacturer: Vec<u8>,
+    pub family: u16,
+    pub model: u16,
+    /// Software revision, in a format of the manufacturer's choosing
+    pub version: [u8; 4],
+}
+
+impl DeviceIdentity {
+    /// Parse an identity reply message
+    pub fn parse(reply: &[u8]) -> Result<Self, RtMidiError> {
+        let invalid = || RtMidiError::InvalidMessage("not an identity reply".to_string());
+        let body = match reply {
+            [0xF0, 0x7E, _, 0x06, 0x02, .., 0xF7] => &reply[5..reply.len() - 1],
+            _ => return Err(invalid()),
+        };
+        let manufacturer_length = if body.first() == Some(&0x00) { 3 } else { 1 };
+        if body.len() != manufacturer_length + 8 {
+            return Err(invalid());
+        }
+        let (manufacturer, rest) = body.split_at(manufacturer_length);
+        let word = |lsb: u8, msb: u8| u16::from(msb) << 8 | u16::from(lsb);
+        Ok(DeviceIdentity {
+            device_id: reply[2],
+            manufacturer: manufacturer.to_vec(),
+            family: word(rest[0], rest[1]),
+            model: word(rest[2], rest[3]),
+            version: [rest[4], rest[5], rest[6], rest[7]],
+        })
+    }
+
+    /// Returns the quirks of the device, from those registered with [`register_quirks`] and the
+    /// built-in ones
+    pub fn quirks(&self) -> Quirks {
+        lookup(&Device::Identity(self))
+    }
+}
+
+/// Add to the quirks known for the devices matching `device`. Ports opened afterwards by
+/// [`crate::RtMidiOut`] have the quirks of every match applied, keeping the stricter setting
+/// where they differ.
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::{register_quirks, DeviceMatch, Quirks};
+///
+/// register_quirks(
+///     DeviceMatch::Name("Old Synth".to_string()),
+///     Quirks {
+///         sysex_interval: Duration::from_millis(50),
+///         max_sysex_packet: Some(128),
+///         ..Default::default()
+///     },
+/// );
+/// ```
+pub fn register_quirks(device: DeviceMatch, quirks: Quirks) {
+    lock().push((device, quirks));
+}
+
+/// Returns the quirks of the device behind a port, from its name and USB identity
+pub(crate) fn port_quirks(name: &str, usb: Option<&UsbId>) -> Quirks {
+    lookup(&Device::Port { name, usb })
+}
+
+fn lookup(device: &Device) -> Quirks {
+    lock()
+        .iter()
+        .chain(builtin().iter())
+        .filter(|(matcher, _)| matcher.matches(device))
+        .fold(Quirks::default(), |quirks, (_, other)| quirks.merge(*other))
+}
+
+fn builtin() -> Vec<(DeviceMatch, Quirks)> {
+    let roland = Quirks {
+        sysex_interval: ROLAND_SYSEX_INTERVAL,
+        ..Default::default()
+    };
+    vec![
+        (
+            DeviceMatch::Usb {
+                vendor_id: ROLAND_VENDOR_ID,
+                product_id: None,
+            },
+            roland,
+        ),
+        (
+            DeviceMatch::Inquiry {
+                manufacturer: vec![ROLAND_ID],
+                family: None,
+                model: None,
+            },
+            roland,
+        ),
+    ]
+}
+
+fn lock() -> MutexGuard<'static, Vec<(DeviceMatch, Quirks)>> {
+    REGISTERED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Applies quirks to the messages sent to a port
+#[derive(Debug, Default)]
+pub(crate) struct QuirkState {
+    pub quirks: Quirks,
+    // Status byte a message without one continues
+    running_status: Option<u8>,
+    // Whether a system exclusive message is being sent in parts
+    in_sysex: bool,
+    last_sysex: Option<Instant>,
+}
+
+impl QuirkState {
+    pub fn new(quirks: Quirks) -> Self {
+        QuirkState {
+            quirks,
+            ..Default::default()
+        }
+    }
+
+    /// Send a message with `send`, adjusted for the quirks
+    pub fn send<F>(&mut self, message: &[u8], mut send: F) -> Result<(), RtMidiError>
+    where
+        F: FnMut(&[u8]) -> Result<(), RtMidiError>,
+    {
+        let first = match message.first() {
+            Some(&first) => first,
+            None => return send(message),
+        };
+        let sysex = first == 0xF0 || (self.in_sysex && first < 0x80);
+        if sysex {
+            self.running_status = None;
+            self.in_sysex = message.last() != Some(&0xF7);
+            let size = self.quirks.max_sysex_packet.unwrap_or(message.len()).max(1);
+            for packet in message.chunks(size) {
+                if let Some(last) = self.last_sysex {
+                    let next = last + self.quirks.sysex_interval;
+                    let now = Instant::now();
+                    if next > now {
+                        thread::sleep(next - now);
+                    }
+                }
+                send(packet)?;
+                self.last_sysex = Some(Instant::now());
+            }
+            return Ok(());
+        }
+        match first {
+            0x80..=0xEF => self.running_status = Some(first),
+            // System common messages cancel running status, real-time messages leave it
+            0xF0..=0xF7 => self.running_status = None,
+            0x00..=0x7F if self.quirks.no_running_status => {
+                if let Some(status) = self.running_status {
+                    let mut full = Vec::with_capacity(message.len() + 1);
+                    full.push(status);
+                    full.extend_from_slice(message);
+                    return send(&full);
+                }
+            }
+            _ => {}
+        }
+        send(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{port_quirks, register_quirks, DeviceIdentity, DeviceMatch, QuirkState, Quirks};
+    use crate::usb::UsbId;
+
+    #[test]
+    fn lookup() {
+        let roland = UsbId {
+            vendor_id: 0x0582,
+            product_id: 0x012A,
+            serial: None,
+        };
+        assert_eq!(
+            port_quirks("UM-ONE", Some(&roland)).sysex_interval,
+            Duration::from_millis(20)
+        );
+        assert!(port_quirks("Quirks Test Synth", None).is_empty());
+        register_quirks(
+            DeviceMatch::Name("quirks test".to_string()),
+            Quirks {
+                max_sysex_packet: Some(64),
+                ..Default::default()
+            },
+        );
+        register_quirks(
+            DeviceMatch::Name("Test Synth".to_string()),
+            Quirks {
+                max_sysex_packet: Some(32),
+                no_running_status: true,
+                ..Default::default()
+            },
+        );
+        let quirks = port_quirks("Quirks Test Synth", None);
+        assert_eq!(quirks.max_sysex_packet, Some(32));
+        assert!(quirks.no_running_status);
+
+        let reply = [
+            0xF0, 0x7E, 0x10, 0x06, 0x02, 0x41, 0x42, 0x04, 0x02, 0x00, 0, 1, 0, 0, 0xF7,
+        ];
+        let identity = DeviceIdentity::parse(&reply).unwrap();
+        assert_eq!(identity.quirks().sysex_interval, Duration::from_millis(20));
+        assert!(DeviceIdentity::parse(&reply[..10]).is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let mut state = QuirkState::new(Quirks {
+            sysex_interval: Duration::from_millis(10),
+            max_sysex_packet: Some(4),
+            no_running_status: true,
+        });
+        let mut sent = Vec::new();
+        let start = Instant::now();
+        let mut send = |message: &[u8]| {
+            sent.push(message.to_vec());
+            Ok(())
+        };
+        state.send(&[0x90, 60, 100], &mut send).unwrap();
+        state.send(&[62, 100], &mut send).unwrap();
+        state.send(&[0xF0, 1, 2, 3, 4, 5], &mut send).unwrap();
+        state.send(&[6, 0xF7], &mut send).unwrap();
+        state.send(&[64, 100], &mut send).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(
+            sent,
+            [
+                vec![0x90, 60, 100],
+                vec![0x90, 62, 100],
+                vec![0xF0, 1, 2, 3],
+                vec![4, 5],
+                vec![6, 0xF7],
+                vec![64, 100],
+            ]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::RtMidiError;
+use crate::parameter::ParameterProtocol;
+use crate::sysex::{Checksum, SysExBuilder};
+
+/// Roland manufacturer ID
+pub const ROLAND_ID: u8 = 0x41;
+
+/// Data request (RQ1) command ID
+const RQ1: u8 = 0x11;
+/// Data set (DT1) command ID
+const DT1: u8 = 0x12;
+
+/// Address based parameter access for Roland devices
+///
+/// Builds data set (DT1) and data request (RQ1) system exclusive messages, and parses the
+/// messages a device sends in reply. Addresses are written as in Roland's documentation, with
+/// one 7-bit value per byte (e.g. `0x40007F` for "40 00 7F").
+/// ```
+/// use rtmidi::{RolandDevice, RolandMessage};
+///
+/// // Sound Canvas (model 42), device ID 17, with 3 byte addresses
+/// let device = RolandDevice::new(0x10, &[0x42], 3);
+/// let reset = device.data_set(0x40007F, &[0x00]).unwrap();
+/// assert_eq!(reset, [0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]);
+///
+/// let request = device.data_request(0x400004, 1).unwrap();
+/// assert_eq!(
+///     request,
+///     [0xF0, 0x41, 0x10, 0x42, 0x11, 0x40, 0x00, 0x04, 0x00, 0x00, 0x01, 0x3B, 0xF7]
+/// );
+///
+/// // Master volume reply
+/// let reply = [0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x7F, 0x3D, 0xF7];
+/// assert_eq!(
+///     device.parse(&reply).unwrap(),
+///     RolandMessage::DataSet { address: 0x400004, data: vec![0x7F] }
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolandDevice {
+    device_id: u8,
+    model_id: Vec<u8>,
+    address_size: usize,
+}
+
+/// Roland system exclusive message parsed by [`RolandDevice::parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RolandMessage {
+    /// Data set (DT1): `data` is written to, or was read from, `address`
+    DataSet { address: u32, data: Vec<u8> },
+    /// Data request (RQ1): `size` bytes are requested from `address`
+    DataRequest { address: u32, size: u32 },
+}
+
+impl RolandDevice {
+    /// Create a device with a device ID (`0x10` to `0x1F` for devices 17 to 32), model ID and
+    /// address size (usually 3 or 4 bytes). Sizes in data requests use the same number of bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address_size` is not between 1 and 4.
+    pub fn new(device_id: u8, model_id: &[u8], address_size: usize) -> Self {
+        assert!(
+            (1..=4).contains(&address_size),
+            "Invalid address size {}",
+            address_size
+        );
+        RolandDevice {
+            device_id,
+            model_id: model_id.to_vec(),
+            address_size,
+        }
+    }
+
+    /// Returns a data set (DT1) message writing `data` to `address`
+    pub fn data_set(&self, address: u32, data: &[u8]) -> Result<Vec<u8>, RtMidiError> {
+        self.message(DT1, address)?.bytes(data).build()
+    }
+
+    /// Returns a data request (RQ1) message reading `size` bytes from `address`
+    pub fn data_request(&self, address: u32, size: u32) -> Result<Vec<u8>, RtMidiError> {
+        let size = encode_size(size, self.address_size)?;
+        self.message(RQ1, address)?.bytes(&size).build()
+    }
+
+    /// Parse a message from the device, validating its header and checksum
+    pub fn parse(&self, message: &[u8]) -> Result<RolandMessage, RtMidiError> {
+        let header = [&[0xF0, ROLAND_ID, self.device_id][..], &self.model_id].concat();
+        let body = message
+            .strip_prefix(&header[..])
+            .and_then(|body| body.strip_suffix(&[0xF7]))
+            .ok_or_else(|| invalid("not a message from this device"))?;
+        let (&command, body) = body
+            .split_first()
+            .ok_or_else(|| invalid("missing command ID"))?;
+        if body.len() <= self.address_size || !Checksum::Roland.verify(body) {
+            return Err(invalid("invalid checksum"));
+        }
+        let (address, data) = body[..body.len() - 1].split_at(self.address_size);
+        let address = decode_address(address)?;
+        match command {
+            DT1 => Ok(RolandMessage::DataSet {
+                address,
+                data: data.to_vec(),
+            }),
+            RQ1 if data.len() == self.address_size => Ok(RolandMessage::DataRequest {
+                address,
+                size: decode_size(data)?,
+            }),
+            RQ1 => Err(invalid("invalid data request size")),
+            command => Err(invalid(&format!("unknown command ID 0x{:02x}", command))),
+        }
+    }
+
+    fn message(&self, command: u8, address: u32) -> Result<SysExBuilder, RtMidiError> {
+        Ok(SysExBuilder::new()
+            .byte(ROLAND_ID)
+            .byte(self.device_id)
+            .bytes(&self.model_id)
+            .byte(command)
+            .checksum(Checksum::Roland)
+            .bytes(&encode_address(address, self.address_size)?))
+    }
+}
+
+impl ParameterProtocol for RolandDevice {
+    fn write_message(&self, address: u32, data: &[u8]) -> Result<Vec<u8>, RtMidiError> {
+        self.data_set(address, data)
+    }
+
+    fn read_message(&self, address: u32, size: usize) -> Result<Vec<u8>, RtMidiError> {
+        self.data_request(address, size as u32)
+    }
+
+    fn parse_reply(&self, address: u32, size: usize, message: &[u8]) -> Option<Vec<u8>> {
+        match self.parse(message) {
+            Ok(RolandMessage::DataSet { address: a, data })
+                if a == address && data.len() == size =>
+            {
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn invalid(message: &str) -> RtMidiError {
+    RtMidiError::InvalidMessage(format!("Roland system exclusive: {}", message))
+}
+
+fn encode_address(address: u32, size: usize) -> Result<Vec<u8>, RtMidiError> {
+    let bytes = address.to_be_bytes();
+    let (high, low) = bytes.split_at(4 - size);
+    if high.iter().any(|&byte| byte != 0) || low.iter().any(|&byte| byte >= 0x80) {
+        return Err(invalid(&format!("invalid address 0x{:x}", address)));
+    }
+    Ok(low.to_vec())
+}
+
+fn decode_address(bytes: &[u8]) -> Result<u32, RtMidiError> {
+    if bytes.iter().any(|&byte| byte >= 0x80) {
+        return Err(invalid("invalid address"));
+    }
+    Ok(bytes
+        .iter()
+        .fold(0, |address, &byte| address << 8 | u32::from(byte)))
+}
+
+fn encode_size(size: u32, length: usize) -> Result<Vec<u8>, RtMidiError> {
+    if u64::from(size) >= 1 << (7 * length) {
+        return Err(invalid(&format!("invalid size {}", size)));
+    }
+    Ok((0..length)
+        .rev()
+        .map(|index| (size >> (7 * index)) as u8 & 0x7F)
+        .collect())
+}
+
+fn decode_size(bytes: &[u8]) -> Result<u32, RtMidiError> {
+    if bytes.iter().any(|&byte| byte >= 0x80) {
+        return Err(invalid("invalid size"));
+    }
+    Ok(bytes
+        .iter()
+        .fold(0, |size, &byte| size << 7 | u32::from(byte)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RolandDevice, RolandMessage};
+
+    #[test]
+    fn data_set() {
+        let device = RolandDevice::new(0x10, &[0x00, 0x00, 0x3B], 4);
+        let message = device.data_set(0x1800_0000, &[0x01, 0x02]).unwrap();
+        assert_eq!(
+            message,
+            [
+                0xF0, 0x41, 0x10, 0x00, 0x00, 0x3B, 0x12, 0x18, 0x00, 0x00, 0x00, 0x01, 0x02, 0x65,
+                0xF7
+            ]
+        );
+        assert_eq!(
+            device.parse(&message).unwrap(),
+            RolandMessage::DataSet {
+                address: 0x1800_0000,
+                data: vec![0x01, 0x02]
+            }
+        );
+        assert!(device.data_set(0x0000_0080, &[]).is_err());
+        assert!(device.data_set(0, &[0x80]).is_err());
+        assert!(RolandDevice::new(0x10, &[0x42], 3)
+            .data_set(0x0100_0000, &[])
+            .is_err());
+    }
+
+    #[test]
+    fn data_request() {
+        let device = RolandDevice::new(0x10, &[0x42], 3);
+        let message = device.data_request(0x100000, 200).unwrap();
+        assert_eq!(
+            message,
+            [0xF0, 0x41, 0x10, 0x42, 0x11, 0x10, 0x00, 0x00, 0x00, 0x01, 0x48, 0x27, 0xF7]
+        );
+        assert_eq!(
+            device.parse(&message).unwrap(),
+            RolandMessage::DataRequest {
+                address: 0x100000,
+                size: 200
+            }
+        );
+        assert!(device.data_request(0, 1 << 21).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let device = RolandDevice::new(0x10, &[0x42], 3);
+        let mut message = device.data_set(0x40007F, &[0x00]).unwrap();
+        // Another device
+        assert!(RolandDevice::new(0x11, &[0x42], 3).parse(&message).is_err());
+        // Bad checksum
+        message[9] = 0x40;
+        assert!(device.parse(&message).is_err());
+        // Truncated
+        assert!(device.parse(&message[..6]).is_err());
+        assert!(device.parse(&[0x90, 60, 100]).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::error::RtMidiError;
+
+/// Universal Non-Real Time and Real Time sub-IDs
+const NON_REAL_TIME: u8 = 0x7E;
+const REAL_TIME: u8 = 0x7F;
+/// MIDI Tuning Standard sub-ID#1, and the bulk dump reply and single note change sub-ID#2s
+const MIDI_TUNING: u8 = 0x08;
+const BULK_DUMP: u8 = 0x01;
+const SINGLE_NOTE: u8 = 0x02;
+/// Length of the tuning name in a bulk dump
+const NAME_LENGTH: usize = 16;
+/// Most key changes a single note tuning change message can hold
+const MAX_CHANGES: usize = 127;
+/// MIDI Tuning Standard frequency data meaning "no change", used for unmapped keys
+const NO_CHANGE: [u8; 3] = [0x7F, 0x7F, 0x7F];
+
+/// Scala scale
+///
+/// Parsed from a `.scl` file. Pitches are in cents above the first degree, which isn't listed;
+/// the last pitch is the period the scale repeats at (usually the 2/1 octave). Map it to keys with
+/// a [`KeyboardMapping`] to get a [`Tuning`]. Requires the `scala` feature.
+/// ```
+/// use rtmidi::{KeyboardMapping, Scale};
+///
+/// let scale = Scale::parse("! just.scl\nJust major\n 7\n9/8\n5/4\n4/3\n3/2\n5/3\n15/8\n2/1\n")
+///     .unwrap();
+/// assert_eq!(scale.pitches.len(), 7);
+/// let tuning = scale.tuning(&KeyboardMapping::default());
+/// let message = tuning.bulk_dump(0x7F, 0, "Just major");
+/// assert_eq!(message.len(), 408);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    pub pitches: Vec<f64>,
+}
+
+impl Scale {
+    /// Parse the text of a `.scl` file
+    pub fn parse(text: &str) -> Result<Self, RtMidiError> {
+        let mut lines = lines(text);
+        let description = lines
+            .next()
+            .ok_or_else(|| invalid("missing description"))?
+            .trim()
+            .to_string();
+        let count = number(lines.next(), "note count")?;
+        let pitches = lines
+            .take(count)
+            .map(pitch)
+            .collect::<Result<Vec<_>, _>>()?;
+        if pitches.len() != count {
+            return Err(invalid("fewer pitches than the note count"));
+        }
+        Ok(Scale {
+            description,
+            pitches,
+        })
+    }
+
+    /// Returns a degree's pitch in cents above degree 0, counting periods for degrees past the
+    /// end of the scale (or below 0)
+    pub fn cents(&self, degree: i32) -> f64 {
+        let (period, step) = match self.pitches.last() {
+            Some(&period) => (period, self.pitches.len() as i32),
+            None => return 0.0,
+        };
+        let (periods, index) = (degree.div_euclid(step), degree.rem_euclid(step));
+        let offset = match index {
+            0 => 0.0,
+            index => self.pitches[index as usize - 1],
+        };
+        f64::from(periods) * period + offset
+    }
+
+    /// Returns the tuning of every key with a keyboard mapping
+    pub fn tuning(&self, mapping: &KeyboardMapping) -> Tuning {
+        let reference = mapping
+            .degree(mapping.reference_note)
+            .map_or(0.0, |degree| self.cents(degree));
+        let mut frequencies = [None; 128];
+        for key in mapping.first_note..=mapping.last_note.min(127) {
+            frequencies[usize::from(key)] = mapping.degree(key).map(|degree| {
+                let cents = self.cents(degree) - reference;
+                mapping.reference_frequency * 2f64.powf(cents / 1200.0)
+            });
+        }
+        Tuning { frequencies }
+    }
+}
+
+/// Scala keyboard mapping
+///
+/// Parsed from a `.kbm` file, it maps keys to scale degrees. The default is the linear mapping
+/// Scala uses without one: middle C (key 60) is degree 0, and A (key 69) is 440 Hz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_note: u8,
+    pub last_note: u8,
+    /// Key that degree 0 is mapped to
+    pub middle_note: u8,
+    /// Key whose frequency is given
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    /// Degree that the mapping repeats at, ignored without a mapping
+    pub octave_degree: i32,
+    /// Degree of each key in the repeating pattern starting from the middle note ([`None`] for
+    /// unmapped keys). Empty for a linear mapping, where each key is the next degree.
+    pub mapping: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        KeyboardMapping {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    /// Parse the text of a `.kbm` file
+    pub fn parse(text: &str) -> Result<Self, RtMidiError> {
+        let mut lines = lines(text);
+        let size = number(lines.next(), "map size")?;
+        let first_note = key(lines.next(), "first note")?;
+        let last_note = key(lines.next(), "last note")?;
+        let middle_note = key(lines.next(), "middle note")?;
+        let reference_note = key(lines.next(), "reference note")?;
+        let reference_frequency = number::<f64>(lines.next(), "reference frequency")?;
+        if reference_frequency.is_nan() || reference_frequency <= 0.0 {
+            return Err(invalid("reference frequency must be positive"));
+        }
+        let octave_degree = number(lines.next(), "octave degree")?;
+        let mut mapping = lines
+            .take(size)
+            .map(|line| match first_word(line) {
+                "x" | "X" => Ok(None),
+                degree => degree
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| invalid("invalid mapping degree")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Scala allows the end of the mapping to be left out, for unmapped keys
+        mapping.resize(size, None);
+        let mapping = KeyboardMapping {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        };
+        if mapping.degree(reference_note).is_none() {
+            return Err(invalid("reference note is not mapped"));
+        }
+        Ok(mapping)
+    }
+
+    /// Returns the scale degree a key is mapped to, or [`None`] if it isn't mapped
+    pub fn degree(&self, key: u8) -> Option<i32> {
+        let offset = i32::from(key) - i32::from(self.middle_note);
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let size = self.mapping.len() as i32;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        Some(offset.div_euclid(size) * self.octave_degree + degree)
+    }
+}
+
+/// Frequencies of the 128 keys, for sending with the MIDI Tuning Standard
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    /// Frequency in Hz of each key, [`None`] for keys that are unmapped
+    pub frequencies: [Option<f64>; 128],
+}
+
+impl Tuning {
+    /// Returns a Bulk Tuning Dump message, retuning every key of a tuning program (0-127). Devices
+    /// usually need the program selecting (with the Tuning Program RPN) before it's heard.
+    /// Unmapped keys are left unchanged. The name is truncated or padded with spaces to 16
+    /// ASCII characters.
+    pub fn bulk_dump(&self, device_id: u8, program: u8, name: &str) -> Vec<u8> {
+        let mut message = vec![
+            0xF0,
+            NON_REAL_TIME,
+            device_id & 0x7F,
+            MIDI_TUNING,
+            BULK_DUMP,
+            program & 0x7F,
+        ];
+        let name = name
+            .bytes()
+            .map(|byte| if byte.is_ascii() { byte } else { b'?' });
+        message.extend(name.chain(std::iter::repeat(b' ')).take(NAME_LENGTH));
+        for frequency in self.frequencies.iter() {
+            message.extend_from_slice(&frequency.map_or(NO_CHANGE, frequency_data));
+        }
+        // The checksum is the XOR of everything after F0
+        let checksum = message[1..]
+            .iter()
+            .fold(0, |checksum, byte| checksum ^ byte);
+        message.push(checksum & 0x7F);
+        message.push(0xF7);
+        message
+    }
+
+    /// Returns Single Note Tuning Change messages, retuning the mapped keys of a tuning program
+    /// (0-127) in real time, so that sounding notes change pitch
+    pub fn single_note_changes(&self, device_id: u8, program: u8) -> Vec<Vec<u8>> {
+        let changes = self
+            .frequencies
+            .iter()
+            .enumerate()
+            .filter_map(|(key, frequency)| Some((key as u8, frequency_data((*frequency)?))))
+            .collect::<Vec<_>>();
+        changes
+            .chunks(MAX_CHANGES)
+            .map(|changes| {
+                let mut message = vec![
+                    0xF0,
+                    REAL_TIME,
+                    device_id & 0x7F,
+                    MIDI_TUNING,
+                    SINGLE_NOTE,
+                    program & 0x7F,
+                    changes.len() as u8,
+                ];
+                for (key, data) in changes {
+                    message.push(*key);
+                    message.extend_from_slice(data);
+                }
+                message.push(0xF7);
+                message
+            })
+            .collect()
+    }
+}
+
+/// Returns the MIDI Tuning Standard frequency data for a frequency: the equal tempered key at or
+/// below it, and the 14-bit fraction of a semitone above that key
+fn frequency_data(frequency: f64) -> [u8; 3] {
+    let semitones = 69.0 + 12.0 * (frequency / 440.0).log2();
+    if semitones.is_nan() || semitones < 0.0 {
+        return [0, 0, 0];
+    }
+    let mut key = semitones.floor();
+    let mut fraction = ((semitones - key) * 16384.0).round();
+    if fraction >= 16384.0 {
+        key += 1.0;
+        fraction = 0.0;
+    }
+    let fraction = fraction as u16;
+    let data = [
+        key.min(127.0) as u8,
+        (fraction >> 7) as u8,
+        (fraction & 0x7F) as u8,
+    ];
+    if key > 127.0 || data == NO_CHANGE {
+        // The highest frequency, as 7F 7F 7F is reserved
+        return [0x7F, 0x7F, 0x7E];
+    }
+    data
+}
+
+/// Returns the lines of a Scala file that aren't comments
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().filter(|line| !line.starts_with('!'))
+}
+
+/// Returns the first word of a line, as anything after it is ignored
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+fn key(line: Option<&str>, what: &str) -> Result<u8, RtMidiError> {
+    number::<u8>(line, what).map(|key| key.min(127))
+}
+
+fn number<T: std::str::FromStr>(line: Option<&str>, what: &str) -> Result<T, RtMidiError> {
+    let line = line.ok_or_else(|| invalid(&format!("missing {}", what)))?;
+    first_word(line)
+        .parse()
+        .map_err(|_| invalid(&format!("invalid {}", what)))
+}
+
+/// Parses a pitch in cents (containing a period) or as a ratio
+fn pitch(line: &str) -> Result<f64, RtMidiError> {
+    let word = first_word(line);
+    if word.contains('.') {
+        return word.parse().map_err(|_| invalid("invalid pitch in cents"));
+    }
+    let (numerator, denominator) = word.split_once('/').unwrap_or((word, "1"));
+    let ratio = |value: &str| value.parse::<u64>().ok().filter(|&value| value > 0);
+    match (ratio(numerator), ratio(denominator)) {
+        (Some(numerator), Some(denominator)) => {
+            Ok(1200.0 * (numerator as f64 / denominator as f64).log2())
+        }
+        _ => Err(invalid("invalid pitch ratio")),
+    }
+}
+
+fn invalid(reason: &str) -> RtMidiError {
+    RtMidiError::InvalidFile(format!("Scala: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frequency_data, KeyboardMapping, Scale};
+
+    const EQUAL: &str = "! 12edo.scl\n!\n12-tone equal temperament\n 12\n!\n100.0\n200.\n300.0\n\
+        400.0\n500.0\n600.0\n700.0\n800.0\n900.0\n1000.0\n1100.0\n2/1 octave\n";
+
+    #[test]
+    fn parse() {
+        let scale = Scale::parse(EQUAL).unwrap();
+        assert_eq!(scale.description, "12-tone equal temperament");
+        assert_eq!(scale.pitches.len(), 12);
+        assert_eq!(scale.pitches[1], 200.0);
+        assert!((scale.pitches[11] - 1200.0).abs() < 1e-9);
+        assert!((scale.cents(-1) + 100.0).abs() < 1e-9);
+        assert!((scale.cents(25) - 2500.0).abs() < 1e-9);
+
+        assert!(Scale::parse("Missing pitches\n3\n100.0\n").is_err());
+        assert!(Scale::parse("Bad ratio\n1\n3/0\n").is_err());
+        assert!(Scale::parse("Bad count\nmany\n").is_err());
+    }
+
+    #[test]
+    fn keyboard_mapping() {
+        // Only the white keys, mapped to a 7 note scale
+        let mapping = KeyboardMapping::parse(
+            "! white.kbm\n12\n0\n127\n60\n69\n440.0\n7\n0\n x\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n",
+        )
+        .unwrap();
+        assert_eq!(mapping.mapping.len(), 12);
+        assert_eq!(mapping.degree(60), Some(0));
+        assert_eq!(mapping.degree(61), None);
+        assert_eq!(mapping.degree(69), Some(5));
+        assert_eq!(mapping.degree(72), Some(7));
+        assert_eq!(mapping.degree(59), Some(-1));
+        assert!(KeyboardMapping::parse("1\n0\n127\n60\n69\n440.0\n1\nx\n").is_err());
+    }
+
+    #[test]
+    fn tuning() {
+        let scale = Scale::parse(EQUAL).unwrap();
+        let tuning = scale.tuning(&KeyboardMapping::default());
+        assert!((tuning.frequencies[69].unwrap() - 440.0).abs() < 1e-9);
+        assert!((tuning.frequencies[81].unwrap() - 880.0).abs() < 1e-9);
+        assert_eq!(frequency_data(tuning.frequencies[60].unwrap()), [60, 0, 0]);
+
+        let message = tuning.bulk_dump(0x7F, 3, "12-TET");
+        assert_eq!(message.len(), 408);
+        assert_eq!(message[..6], [0xF0, 0x7E, 0x7F, 0x08, 0x01, 0x03]);
+        assert_eq!(&message[6..22], b"12-TET          ");
+        assert_eq!(message[22..25], [0, 0, 0]);
+        assert_eq!(message[407], 0xF7);
+        let checksum = message[1..406]
+            .iter()
+            .fold(0, |checksum, byte| checksum ^ byte);
+        assert_eq!(message[406], checksum & 0x7F);
+
+        let messages = tuning.single_note_changes(0, 0);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0][..7], [0xF0, 0x7F, 0x00, 0x08, 0x02, 0x00, 127]);
+        assert_eq!(
+            messages[1][..11],
+            [0xF0, 0x7F, 0x00, 0x08, 0x02, 0x00, 1, 127, 127, 0, 0]
+        );
+    }
+
+    #[test]
+    fn frequency() {
+        // A quarter tone above A440, and out of range frequencies
+        assert_eq!(frequency_data(440.0 * 2f64.powf(0.5 / 12.0)), [69, 0x40, 0]);
+        assert_eq!(frequency_data(1.0), [0, 0, 0]);
+        assert_eq!(frequency_data(20000.0), [0x7F, 0x7F, 0x7E]);
+    }
+}
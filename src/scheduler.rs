@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::clock::{self, Clock};
+use crate::error::RtMidiError;
+use crate::tempo::{self, TempoMap};
+use crate::worker::Handle;
+
+/// What happens to messages still scheduled when an output's port is closed or the output is
+/// dropped, see [`crate::RtMidiOut::set_pending_policy`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PendingPolicy {
+    /// Send them all at once, in order
+    Flush,
+    /// Send them as they become due, for up to the given time, then send the rest at once
+    WaitUntilDue(Duration),
+    /// Discard them, apart from the note-offs of [`crate::RtMidiOut::send_note`], which are sent
+    /// at once
+    #[default]
+    Discard,
+}
+
+/// Timing quantization settings
+///
+/// Note-on events are moved towards the nearest point on a grid of `subdivision` steps per beat.
+/// `strength` (0.0 to 1.0) sets how far they are moved, with 1.0 snapping exactly to the grid.
+/// `swing` (0.0 to 1.0) delays every second grid step: it gives the position of the off-beat
+/// step within each pair of steps, relative to the straight position, so 0.0 is straight and
+/// 1/3 gives a triplet feel. Matching note-offs are moved by the same amount as their note-on so
+/// note lengths are preserved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantize {
+    pub subdivision: u32,
+    pub strength: f64,
+    pub swing: f64,
+}
+
+impl Default for Quantize {
+    /// Full strength sixteenth notes without swing
+    fn default() -> Self {
+        Quantize {
+            subdivision: 4,
+            strength: 1.0,
+            swing: 0.0,
+        }
+    }
+}
+
+impl Quantize {
+    /// Returns the quantized position of a beat
+    pub fn apply(&self, beat: f64) -> f64 {
+        let step = 1.0 / self.subdivision.max(1) as f64;
+        let pair = (beat / (2.0 * step)).floor();
+        let start = pair * 2.0 * step;
+        // Candidate grid points around the beat: this pair's two steps and the next downbeat
+        let candidates = [
+            start,
+            start + step * (1.0 + self.swing.clamp(0.0, 1.0)),
+            start + 2.0 * step,
+        ];
+        let target = candidates
+            .iter()
+            .copied()
+            .fold(start, |nearest, candidate| {
+                if (candidate - beat).abs() < (nearest - beat).abs() {
+                    candidate
+                } else {
+                    nearest
+                }
+            });
+        beat + (target - beat) * self.strength.clamp(0.0, 1.0)
+    }
+}
+
+/// Humanization settings
+///
+/// Scheduled note-on events are moved by a random offset of up to `timing` seconds either way,
+/// and their velocity changed by up to `velocity` either way (staying between 1 and 127), for
+/// less mechanical output. Matching note-offs are moved by the same amount as their note-on, as
+/// with quantization, which is applied first. Offsets come from a pseudo-random generator
+/// started from `seed`, so the same seed and messages give the same result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Humanize {
+    pub timing: f64,
+    pub velocity: u8,
+    pub seed: u64,
+}
+
+impl Default for Humanize {
+    /// Up to 5ms and 5 velocity steps either way
+    fn default() -> Self {
+        Humanize {
+            timing: 0.005,
+            velocity: 5,
+            seed: 0,
+        }
+    }
+}
+
+/// Seedable pseudo-random generator (SplitMix64)
+#[derive(Default)]
+struct Rng(u64);
+
+impl Rng {
+    /// Returns a number between -1.0 and 1.0
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+/// Default number of beats in a bar
+pub const DEFAULT_BEATS_PER_BAR: u32 = 4;
+
+/// A number of bars, for scheduling in musical time (see [`Scheduler::schedule_at_bar`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bars(pub u32);
+
+/// A number of beats (quarter notes), for scheduling in musical time (see
+/// [`Scheduler::schedule_at_bar`])
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Beats(pub f64);
+
+impl Beats {
+    /// Returns the number of beats in `ticks` at a resolution of `ppq` ticks (pulses) per
+    /// quarter note
+    pub fn from_ticks(ticks: u64, ppq: u32) -> Self {
+        Beats(ticks as f64 / f64::from(ppq.max(1)))
+    }
+}
+
+/// Output scheduler
+///
+/// Sends messages at a future time from an output's internal timing thread. Created with
+/// [`crate::RtMidiOut::scheduler`]; handles are cheap to clone and may be used from any thread.
+/// Scheduled messages pass through the output's transforms (see
+/// [`crate::RtMidiOut::add_transform`]) when they are due.
+///
+/// The scheduler keeps a musical [`Clock`] used for timing options such as quantization, which
+/// may follow a [`TempoMap`] rather than a fixed tempo (see [`Scheduler::set_tempo_map`]). Known
+/// latency after the output (e.g. of a USB interface or a slow synth) can be compensated with
+/// [`Scheduler::set_latency_compensation`], so several outputs stay aligned.
+///
+/// Timing and velocity can be humanized with random offsets (see [`Scheduler::set_humanize`]).
+///
+/// Messages can also be scheduled in musical time, at positions in bars and beats counted from
+/// beat zero of the clock, which are converted to time with the clock or tempo map.
+///
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::{Bars, Beats, RtMidiOut, RtMidiError};
+///
+/// fn arpeggio(output: &RtMidiOut) -> Result<(), RtMidiError> {
+///     let scheduler = output.scheduler()?;
+///     for (step, note) in [60, 64, 67].iter().enumerate() {
+///         let at = Duration::from_millis(250 * step as u64);
+///         scheduler.schedule_in(at, &[144, *note, 90])?;
+///         scheduler.schedule_in(at + Duration::from_millis(200), &[128, *note, 0])?;
+///     }
+///     Ok(())
+/// }
+///
+/// fn bar_five(output: &RtMidiOut) -> Result<(), RtMidiError> {
+///     let scheduler = output.scheduler()?;
+///     // Bars and beats count from zero, so this is the second beat of the fifth bar
+///     scheduler.schedule_at_bar(Bars(4), Beats(1.0), &[144, 60, 90])?;
+///     scheduler.schedule_at_bar(Bars(4), Beats(1.5), &[128, 60, 0])
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Scheduler {
+    handle: Handle,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    clock: Clock,
+    beats_per_bar: u32,
+    tempo_map: Option<TempoMap>,
+    quantize: Option<Quantize>,
+    humanize: Option<Humanize>,
+    rng: Rng,
+    // Offsets (in seconds) applied to sounding notes, keyed by channel and note number
+    offsets: HashMap<(u8, u8), f64>,
+    // Seconds by which messages are sent early (or late, if negative)
+    latency: f64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            clock: Default::default(),
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            tempo_map: None,
+            quantize: None,
+            humanize: None,
+            rng: Default::default(),
+            offsets: HashMap::new(),
+            latency: 0.0,
+        }
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn new(handle: Handle) -> Self {
+        Scheduler {
+            handle,
+            state: Default::default(),
+        }
+    }
+
+    /// Schedule a message to be sent at the given instant. Messages scheduled in the past are
+    /// sent immediately.
+    pub fn schedule_at(&self, at: Instant, message: &[u8]) -> Result<(), RtMidiError> {
+        let (at, message) = {
+            let mut state = self.lock();
+            (state.due(at, message), state.humanize_velocity(message))
+        };
+        self.handle.schedule(at, message)
+    }
+
+    /// Schedule a message to be sent after a delay
+    pub fn schedule_in(&self, delay: Duration, message: &[u8]) -> Result<(), RtMidiError> {
+        self.schedule_at(Instant::now() + delay, message)
+    }
+
+    /// Schedule a message to be sent at a position in beats, counted from beat zero of the clock
+    pub fn schedule_at_beat(&self, beat: f64, message: &[u8]) -> Result<(), RtMidiError> {
+        let (at, message) = {
+            let mut state = self.lock();
+            let at = state.instant_at(beat);
+            (state.due(at, message), state.humanize_velocity(message))
+        };
+        self.handle.schedule(at, message)
+    }
+
+    /// Schedule a message to be sent at a position in bars and beats, counted from zero at beat
+    /// zero of the clock (see [`Scheduler::set_beats_per_bar`])
+    pub fn schedule_at_bar(
+        &self,
+        bar: Bars,
+        beat: Beats,
+        message: &[u8],
+    ) -> Result<(), RtMidiError> {
+        let beats_per_bar = self.lock().beats_per_bar;
+        self.schedule_at_beat(
+            f64::from(bar.0) * f64::from(beats_per_bar) + beat.0,
+            message,
+        )
+    }
+
+    /// Set the number of beats in a bar (default [`DEFAULT_BEATS_PER_BAR`])
+    pub fn set_beats_per_bar(&self, beats_per_bar: u32) {
+        self.lock().beats_per_bar = beats_per_bar;
+    }
+
+    /// Returns the number of beats in a bar
+    pub fn beats_per_bar(&self) -> u32 {
+        self.lock().beats_per_bar
+    }
+
+    /// Returns the scheduler's musical clock. While a tempo map is set, only the origin of the
+    /// clock is used.
+    pub fn clock(&self) -> Clock {
+        self.lock().clock
+    }
+
+    /// Replace the scheduler's musical clock, removing any tempo map
+    pub fn set_clock(&self, clock: Clock) {
+        let mut state = self.lock();
+        state.clock = clock;
+        state.tempo_map = None;
+    }
+
+    /// Set the tempo in beats per minute, keeping the current beat position and removing any
+    /// tempo map
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tempo` is not a finite number greater than zero.
+    pub fn set_tempo(&self, tempo: f64) {
+        tempo::check(tempo);
+        let mut state = self.lock();
+        let now = Instant::now();
+        let beat = state.beat_at(now);
+        state.clock = Clock::new(clock::offset(now, -beat * 60.0 / tempo), tempo);
+        state.tempo_map = None;
+    }
+
+    /// Follow a tempo map, with beat zero of the map at the origin of the clock. Replaced by the
+    /// fixed tempo of the clock with [`None`].
+    pub fn set_tempo_map(&self, tempo_map: Option<TempoMap>) {
+        self.lock().tempo_map = tempo_map;
+    }
+
+    /// Returns the tempo map, if one is set
+    pub fn tempo_map(&self) -> Option<TempoMap> {
+        self.lock().tempo_map.clone()
+    }
+
+    /// Returns the position in beats at an instant, following the tempo map if one is set
+    pub fn beat_at(&self, at: Instant) -> f64 {
+        self.lock().beat_at(at)
+    }
+
+    /// Returns the instant of a position in beats, following the tempo map if one is set
+    pub fn instant_at(&self, beat: f64) -> Instant {
+        self.lock().instant_at(beat)
+    }
+
+    /// Enable (or disable, with [`None`]) quantization of scheduled note-on events to the clock
+    pub fn set_quantize(&self, quantize: Option<Quantize>) {
+        self.lock().quantize = quantize;
+    }
+
+    /// Enable (or disable, with [`None`]) random offsets to the timing and velocity of scheduled
+    /// note-on events. The pseudo-random generator is restarted from the seed.
+    pub fn set_humanize(&self, humanize: Option<Humanize>) {
+        let mut state = self.lock();
+        state.humanize = humanize;
+        state.rng = Rng(humanize.map_or(0, |humanize| humanize.seed));
+    }
+
+    /// Compensate for latency after the output, in seconds: every scheduled message is sent this
+    /// much earlier than its scheduled time (or later, if negative). Quantization and the clock
+    /// still apply to the scheduled time. Defaults to 0.0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `latency` is not finite.
+    pub fn set_latency_compensation(&self, latency: f64) {
+        assert!(latency.is_finite(), "Invalid latency {}", latency);
+        self.lock().latency = latency;
+    }
+
+    /// Returns the latency compensation in seconds
+    pub fn latency_compensation(&self) -> f64 {
+        self.lock().latency
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    fn beat_at(&self, at: Instant) -> f64 {
+        match &self.tempo_map {
+            Some(map) => map.beat_at(clock::seconds_since(self.clock.origin(), at)),
+            None => self.clock.beat_at(at),
+        }
+    }
+
+    fn instant_at(&self, beat: f64) -> Instant {
+        match &self.tempo_map {
+            Some(map) => clock::offset(self.clock.origin(), map.seconds_at(beat)),
+            None => self.clock.instant_at(beat),
+        }
+    }
+
+    /// Returns the instant a message scheduled at `at` is sent
+    fn due(&mut self, at: Instant, message: &[u8]) -> Instant {
+        clock::offset(self.adjust(at, message), -self.latency)
+    }
+
+    /// Apply timing options to a message scheduled at `at`
+    fn adjust(&mut self, at: Instant, message: &[u8]) -> Instant {
+        let (status, note, velocity) = match *message {
+            [status, note, velocity] if status & 0xE0 == 0x80 => (status, note, velocity),
+            _ => return at,
+        };
+        let key = (status & 0x0F, note);
+        if status & 0xF0 == 0x90 && velocity > 0 {
+            if self.quantize.is_none() && self.humanize.is_none() {
+                return at;
+            }
+            let mut target = match self.quantize {
+                Some(quantize) => self.instant_at(quantize.apply(self.beat_at(at))),
+                None => at,
+            };
+            if let Some(humanize) = self.humanize {
+                target = clock::offset(target, humanize.timing * self.rng.next());
+            }
+            self.offsets.insert(key, clock::seconds_since(at, target));
+            target
+        } else {
+            match self.offsets.remove(&key) {
+                Some(seconds) => clock::offset(at, seconds),
+                None => at,
+            }
+        }
+    }
+
+    /// Returns a message with the velocity of a note-on humanized
+    fn humanize_velocity(&mut self, message: &[u8]) -> Vec<u8> {
+        let mut message = message.to_vec();
+        if let (Some(humanize), [status, _, velocity]) = (self.humanize, &mut message[..]) {
+            if *status & 0xF0 == 0x90 && *velocity > 0 {
+                let offset = (f64::from(humanize.velocity) * self.rng.next()).round();
+                *velocity = (f64::from(*velocity) + offset).clamp(1.0, 127.0) as u8;
+            }
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Beats, Humanize, Quantize, Rng, State};
+    use crate::clock::{self, Clock};
+    use crate::tempo::TempoMap;
+
+    #[test]
+    fn quantize() {
+        let quantize = Quantize::default();
+        assert_eq!(quantize.apply(0.2), 0.25);
+        assert_eq!(quantize.apply(0.1), 0.0);
+        assert_eq!(quantize.apply(0.45), 0.5);
+        assert_eq!(quantize.apply(-0.2), -0.25);
+    }
+
+    #[test]
+    fn quantize_strength() {
+        let quantize = Quantize {
+            strength: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(quantize.apply(0.2), 0.225);
+    }
+
+    #[test]
+    fn quantize_swing() {
+        let quantize = Quantize {
+            subdivision: 2,
+            swing: 1.0 / 3.0,
+            ..Default::default()
+        };
+        assert!((quantize.apply(0.6) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(quantize.apply(1.1), 1.0);
+    }
+
+    #[test]
+    fn latency_compensation() {
+        let mut state = State {
+            latency: 0.01,
+            quantize: Some(Default::default()),
+            ..Default::default()
+        };
+        let beat = state.clock.instant_at(1.0);
+        // Quantized to the beat, then sent 10ms early
+        assert_eq!(
+            state.due(beat + Duration::from_millis(5), &[0x90, 60, 100]),
+            beat - Duration::from_millis(10)
+        );
+        state.latency = -0.01;
+        let at = Instant::now();
+        assert_eq!(state.due(at, &[0xF8]), at + Duration::from_millis(10));
+    }
+
+    #[test]
+    fn tempo_map() {
+        let origin = Instant::now();
+        let mut state = State {
+            clock: Clock::new(origin, 120.0),
+            tempo_map: Some(TempoMap::new(120.0).change(4.0, 60.0)),
+            quantize: Some(Quantize {
+                subdivision: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(state.beat_at(origin + Duration::from_secs(3)), 5.0);
+        assert_eq!(state.instant_at(6.0), origin + Duration::from_secs(4));
+        // Quantized to beat 5, at 3 seconds
+        assert_eq!(
+            state.due(origin + Duration::from_millis(3200), &[0x90, 60, 100]),
+            origin + Duration::from_secs(3)
+        );
+    }
+
+    #[test]
+    fn humanize() {
+        let humanize = Humanize {
+            timing: 0.01,
+            velocity: 10,
+            seed: 42,
+        };
+        let origin = Instant::now();
+        let play = || {
+            let mut state = State {
+                clock: Clock::new(origin, 120.0),
+                humanize: Some(humanize),
+                rng: Rng(humanize.seed),
+                ..Default::default()
+            };
+            let at = state.clock.instant_at(1.0);
+            let on = state.due(at, &[0x90, 60, 100]);
+            let velocity = state.humanize_velocity(&[0x90, 60, 100])[2];
+            let off = state.due(at + Duration::from_millis(100), &[0x80, 60, 0]);
+            assert_eq!(state.humanize_velocity(&[0x80, 60, 0]), [0x80, 60, 0]);
+            (
+                clock::seconds_since(at, on),
+                clock::seconds_since(on, off),
+                velocity,
+            )
+        };
+        let (offset, length, velocity) = play();
+        assert!(offset.abs() <= 0.01);
+        assert!((length - 0.1).abs() < 1e-6);
+        assert!((90..=110).contains(&velocity));
+        // The same seed gives the same offsets
+        assert_eq!(play(), (offset, length, velocity));
+    }
+
+    #[test]
+    fn beats() {
+        assert_eq!(Beats::from_ticks(720, 480), Beats(1.5));
+        assert_eq!(Beats::from_ticks(1, 0), Beats(1.0));
+    }
+}
@@ -0,0 +1,559 @@
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::os::raw::{c_int, c_long, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backend::{Backend, BackendCallback, InputConnection, OutputConnection};
+use crate::error::RtMidiError;
+use crate::threads;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        length: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: c_long,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, length: usize) -> c_int;
+}
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const MAP_SHARED: c_int = 1;
+
+/// Written last when a transport is created, so a peer never sees it half initialised
+const MAGIC: u32 = u32::from_le_bytes(*b"RMSH");
+/// Size of the transport's header (magic and ring capacity), and of each ring's header (write
+/// position, then read position and reader heartbeat on their own cache line)
+const HEADER_SIZE: usize = 64;
+const RING_HEADER_SIZE: usize = 128;
+const WRITE_OFFSET: usize = 0;
+const READ_OFFSET: usize = 64;
+const HEARTBEAT_OFFSET: usize = 72;
+/// Size of a message's header: when it was sent (u64 microseconds since the Unix epoch) and its
+/// length (u32)
+const MESSAGE_HEADER_SIZE: usize = 12;
+/// Default size of each ring, in bytes
+pub const DEFAULT_SHM_CAPACITY: usize = 64 * 1024;
+/// How long a reader can go without a heartbeat before it's considered dead
+const READER_TIMEOUT: Duration = Duration::from_secs(1);
+/// Polls an idle reader spins for before it starts sleeping between polls
+const SPIN_POLLS: u32 = 1000;
+const POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+/// [`Backend`] exchanging MIDI with another process through shared memory
+///
+/// The transport is a file mapped by both processes (ideally on a RAM-backed file system such
+/// as `/dev/shm`), holding a lock-free ring buffer for each direction. One process creates it
+/// with [`SharedMemoryBackend::create`] and the other opens it with
+/// [`SharedMemoryBackend::open`]; each then has one input and one output port named "shm".
+/// Messages keep the time they were sent at, so the receiver's timestamps don't include polling
+/// latency, which suits plugin and host bridges.
+///
+/// Each ring has a single reader and writer, so only one process may open each side. The reader
+/// polls its ring, spinning briefly before sleeping between polls, and leaves a heartbeat the
+/// writer checks: sends fail with [`RtMidiError::Disconnected`] once the peer's input has
+/// stopped without closing, e.g. because its process died, and with [`RtMidiError::WouldBlock`]
+/// while the ring is full.
+/// ```no_run
+/// use rtmidi::{MidiSystem, RtMidiApi, SharedMemoryBackend, DEFAULT_SHM_CAPACITY};
+///
+/// let backend = SharedMemoryBackend::create("/dev/shm/bridge", DEFAULT_SHM_CAPACITY).unwrap();
+/// let system = MidiSystem::new(RtMidiApi::Unspecified, "host")
+///     .unwrap()
+///     .backend(backend);
+/// let output = system.open_output("shm").unwrap();
+/// ```
+pub struct SharedMemoryBackend {
+    mapping: Arc<Mapping>,
+    // Ring this process writes to, the other being the one it reads
+    ring: usize,
+    input_open: Arc<AtomicBool>,
+    // Serialises this process's writers, as a ring has a single writer
+    writer: Arc<Mutex<()>>,
+}
+
+impl SharedMemoryBackend {
+    /// Create the transport's file with rings of the given size (in bytes), replacing it if it
+    /// already exists
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self, RtMidiError> {
+        // Keep the second ring's header aligned
+        let capacity = (capacity.max(MESSAGE_HEADER_SIZE + 1) + 63) & !63;
+        let capacity_field = u32::try_from(capacity)
+            .map_err(|_| RtMidiError::Error("shared memory capacity is too large".to_string()))?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(error)?;
+        file.set_len(size(capacity) as u64).map_err(error)?;
+        let mapping = Mapping::new(&file, size(capacity))?;
+        unsafe { (mapping.ptr.add(4) as *mut u32).write(capacity_field) };
+        mapping.atomic_u32(0).store(MAGIC, Ordering::Release);
+        Ok(SharedMemoryBackend::new(mapping, 0))
+    }
+
+    /// Open a transport created by another process
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RtMidiError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(error)?;
+        let length = file.metadata().map_err(error)?.len() as usize;
+        let invalid = || RtMidiError::Error("not a shared memory MIDI transport".to_string());
+        if length < HEADER_SIZE {
+            return Err(invalid());
+        }
+        let header = Mapping::new(&file, HEADER_SIZE)?;
+        if header.atomic_u32(0).load(Ordering::Acquire) != MAGIC {
+            return Err(invalid());
+        }
+        let capacity = unsafe { (header.ptr.add(4) as *const u32).read() } as usize;
+        // As created, since the other process controls the file
+        if capacity <= MESSAGE_HEADER_SIZE || capacity & 63 != 0 || length != size(capacity) {
+            return Err(invalid());
+        }
+        Ok(SharedMemoryBackend::new(Mapping::new(&file, length)?, 1))
+    }
+
+    fn new(mapping: Mapping, ring: usize) -> Self {
+        SharedMemoryBackend {
+            mapping: Arc::new(mapping),
+            ring,
+            input_open: Arc::new(AtomicBool::new(false)),
+            writer: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Returns whether the other process has its input open, and so is receiving what's sent
+    pub fn peer_reading(&self) -> bool {
+        Ring::new(&self.mapping, self.ring).reader_alive()
+    }
+
+    fn find(&self, port: &str) -> Result<(), RtMidiError> {
+        if port == "shm" {
+            Ok(())
+        } else {
+            Err(RtMidiError::PortNotFound(port.to_string()))
+        }
+    }
+}
+
+impl Backend for SharedMemoryBackend {
+    fn name(&self) -> &str {
+        "shm"
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        Ok(vec!["shm".to_string()])
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        Ok(vec!["shm".to_string()])
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        _client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        self.find(port)?;
+        if self.input_open.swap(true, Ordering::AcqRel) {
+            return Err(RtMidiError::Error(format!("{} is already open", port)));
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let mapping = Arc::clone(&self.mapping);
+        let ring = 1 - self.ring;
+        let input_open = Arc::clone(&self.input_open);
+        let thread_stop = Arc::clone(&stop);
+        let thread = threads::spawn(format!("rtmidi-in:{}", port), move || {
+            read(&Ring::new(&mapping, ring), &thread_stop, &callback);
+            input_open.store(false, Ordering::Release);
+        });
+        Ok(Box::new(SharedMemoryInput {
+            stop,
+            thread: Some(thread),
+        }))
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        _client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        self.find(port)?;
+        Ok(Box::new(SharedMemoryOutput {
+            mapping: Arc::clone(&self.mapping),
+            ring: self.ring,
+            writer: Arc::clone(&self.writer),
+        }))
+    }
+}
+
+/// Input port opened by a [`SharedMemoryBackend`], whose thread is stopped when dropped
+struct SharedMemoryInput {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputConnection for SharedMemoryInput {}
+
+impl Drop for SharedMemoryInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Output port opened by a [`SharedMemoryBackend`]
+struct SharedMemoryOutput {
+    mapping: Arc<Mapping>,
+    ring: usize,
+    writer: Arc<Mutex<()>>,
+}
+
+impl OutputConnection for SharedMemoryOutput {
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        let _writer = lock(&self.writer);
+        Ring::new(&self.mapping, self.ring).write(now(), message)
+    }
+}
+
+/// Poll a ring until stopped, passing each message to the callback with the time since the
+/// previous one was sent. Stops early if the ring is corrupt, as the other process controls it.
+fn read(ring: &Ring, stop: &AtomicBool, f: &BackendCallback) {
+    let mut position = ring.read.load(Ordering::Acquire);
+    let mut last = None;
+    let mut idle = 0;
+    let mut message = Vec::new();
+    while !stop.load(Ordering::Acquire) {
+        ring.heartbeat.store(now().max(1), Ordering::Release);
+        let available = ring.write.load(Ordering::Acquire).wrapping_sub(position);
+        if available == 0 {
+            idle += 1;
+            if idle < SPIN_POLLS {
+                thread::yield_now();
+            } else {
+                thread::sleep(POLL_INTERVAL);
+            }
+            continue;
+        }
+        idle = 0;
+        if available > ring.capacity as u64 || available < MESSAGE_HEADER_SIZE as u64 {
+            break;
+        }
+        let mut header = [0; MESSAGE_HEADER_SIZE];
+        ring.copy_out(position, &mut header);
+        let mut time = [0; 8];
+        time.copy_from_slice(&header[..8]);
+        let time = u64::from_le_bytes(time);
+        let length = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize;
+        if length > ring.capacity - MESSAGE_HEADER_SIZE
+            || (MESSAGE_HEADER_SIZE + length) as u64 > available
+        {
+            break;
+        }
+        message.resize(length, 0);
+        ring.copy_out(position + MESSAGE_HEADER_SIZE as u64, &mut message);
+        position += (MESSAGE_HEADER_SIZE + length) as u64;
+        ring.read.store(position, Ordering::Release);
+        let delta = last.map_or(0, |last| time.saturating_sub(last));
+        last = Some(time);
+        f(delta as f64 / 1_000_000.0, &message);
+    }
+    // Closed rather than dead
+    ring.heartbeat.store(0, Ordering::Release);
+}
+
+/// A file mapped into memory, shared with other processes mapping it
+struct Mapping {
+    ptr: *mut u8,
+    length: usize,
+}
+
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, length: usize) -> Result<Self, RtMidiError> {
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                length,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(error(std::io::Error::last_os_error()));
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            length,
+        })
+    }
+
+    fn atomic_u32(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU32) }
+    }
+
+    fn atomic_u64(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { munmap(self.ptr as *mut c_void, self.length) };
+    }
+}
+
+/// One direction of a transport: a single-reader, single-writer byte ring. The positions only
+/// increase, so the ring is empty when they're equal.
+struct Ring<'a> {
+    write: &'a AtomicU64,
+    read: &'a AtomicU64,
+    // When the reader last polled (microseconds since the Unix epoch), or 0 if it's closed
+    heartbeat: &'a AtomicU64,
+    data: *mut u8,
+    capacity: usize,
+}
+
+impl<'a> Ring<'a> {
+    fn new(mapping: &'a Mapping, index: usize) -> Self {
+        let capacity = (mapping.length - HEADER_SIZE) / 2 - RING_HEADER_SIZE;
+        let offset = HEADER_SIZE + index * (RING_HEADER_SIZE + capacity);
+        Ring {
+            write: mapping.atomic_u64(offset + WRITE_OFFSET),
+            read: mapping.atomic_u64(offset + READ_OFFSET),
+            heartbeat: mapping.atomic_u64(offset + HEARTBEAT_OFFSET),
+            data: unsafe { mapping.ptr.add(offset + RING_HEADER_SIZE) },
+            capacity,
+        }
+    }
+
+    fn reader_alive(&self) -> bool {
+        let heartbeat = self.heartbeat.load(Ordering::Acquire);
+        heartbeat != 0 && now().saturating_sub(heartbeat) < READER_TIMEOUT.as_micros() as u64
+    }
+
+    fn write(&self, time: u64, message: &[u8]) -> Result<(), RtMidiError> {
+        let length = MESSAGE_HEADER_SIZE + message.len();
+        if length > self.capacity {
+            return Err(RtMidiError::InvalidMessage("message too long".to_string()));
+        }
+        let heartbeat = self.heartbeat.load(Ordering::Acquire);
+        if heartbeat != 0 && !self.reader_alive() {
+            return Err(RtMidiError::Disconnected);
+        }
+        let position = self.write.load(Ordering::Acquire);
+        let used = position.wrapping_sub(self.read.load(Ordering::Acquire));
+        if used > self.capacity as u64 {
+            return Err(RtMidiError::Error(
+                "shared memory ring is corrupt".to_string(),
+            ));
+        }
+        if self.capacity - (used as usize) < length {
+            return Err(RtMidiError::WouldBlock);
+        }
+        let mut header = [0; MESSAGE_HEADER_SIZE];
+        header[..8].copy_from_slice(&time.to_le_bytes());
+        header[8..].copy_from_slice(&(message.len() as u32).to_le_bytes());
+        self.copy_in(position, &header);
+        self.copy_in(position + MESSAGE_HEADER_SIZE as u64, message);
+        self.write
+            .store(position + length as u64, Ordering::Release);
+        Ok(())
+    }
+
+    fn copy_in(&self, position: u64, data: &[u8]) {
+        let offset = (position % self.capacity as u64) as usize;
+        let first = data.len().min(self.capacity - offset);
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.data.add(offset), first);
+            ptr::copy_nonoverlapping(data[first..].as_ptr(), self.data, data.len() - first);
+        }
+    }
+
+    fn copy_out(&self, position: u64, data: &mut [u8]) {
+        let offset = (position % self.capacity as u64) as usize;
+        let first = data.len().min(self.capacity - offset);
+        let rest = data.len() - first;
+        unsafe {
+            ptr::copy_nonoverlapping(self.data.add(offset), data.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(self.data, data[first..].as_mut_ptr(), rest);
+        }
+    }
+}
+
+/// Size of a transport whose rings have the given capacity
+fn size(capacity: usize) -> usize {
+    HEADER_SIZE + 2 * (RING_HEADER_SIZE + capacity)
+}
+
+/// Microseconds since the Unix epoch, which both processes share
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_micros() as u64)
+}
+
+fn error(e: std::io::Error) -> RtMidiError {
+    RtMidiError::Error(e.to_string())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{Ring, SharedMemoryBackend, MESSAGE_HEADER_SIZE};
+    use crate::{Backend, RtMidiError};
+
+    fn path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("rtmidi-test-{}-{}", name, process::id()))
+    }
+
+    #[test]
+    fn exchange() {
+        let path = path("shm");
+        let host = SharedMemoryBackend::create(&path, 256).unwrap();
+        let plugin = SharedMemoryBackend::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!host.peer_reading());
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let input = plugin
+            .open_input(
+                "shm",
+                "Test",
+                Box::new(move |delta, message| {
+                    sink.lock().unwrap().push((delta, message.to_vec()))
+                }),
+            )
+            .unwrap();
+        assert!(plugin
+            .open_input("shm", "Test", Box::new(|_, _| {}))
+            .is_err());
+        let output = host.open_output("shm", "Test").unwrap();
+        // Enough messages to wrap around the ring
+        for i in 0..40 {
+            loop {
+                match output.send(&[0x90, i, 100]) {
+                    Err(RtMidiError::WouldBlock) => thread::yield_now(),
+                    result => break result.unwrap(),
+                }
+            }
+        }
+        let start = Instant::now();
+        while received.lock().unwrap().len() < 40 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 40);
+        assert_eq!(received[39].1, [0x90, 39, 100]);
+        assert!(host.peer_reading());
+        drop(input);
+        assert!(!host.peer_reading());
+        assert!(plugin
+            .open_input("shm", "Test", Box::new(|_, _| {}))
+            .is_ok());
+    }
+
+    #[test]
+    fn reader_death() {
+        let path = path("shm-death");
+        let host = SharedMemoryBackend::create(&path, 64).unwrap();
+        fs::remove_file(&path).unwrap();
+        let ring = Ring::new(&host.mapping, 0);
+        assert_eq!(ring.capacity, 64);
+        // Fills the ring with no reader
+        for _ in 0..(64 / (MESSAGE_HEADER_SIZE + 3)) {
+            ring.write(1, &[0x90, 60, 100]).unwrap();
+        }
+        assert_eq!(
+            ring.write(1, &[0x90, 60, 100]),
+            Err(RtMidiError::WouldBlock)
+        );
+        assert_eq!(
+            ring.write(1, &[0; 60]),
+            Err(RtMidiError::InvalidMessage("message too long".to_string()))
+        );
+        // A reader that stopped polling long ago
+        ring.heartbeat.store(1, Ordering::Release);
+        assert_eq!(ring.write(1, &[0xF8]), Err(RtMidiError::Disconnected));
+        assert!(SharedMemoryBackend::open(env::temp_dir()).is_err());
+    }
+
+    #[test]
+    fn corrupt() {
+        let path = path("shm-corrupt");
+        let host = SharedMemoryBackend::create(&path, 64).unwrap();
+        let plugin = SharedMemoryBackend::open(&path).unwrap();
+        // Capacities too small to hold a message, or that misalign the second ring
+        let capacity = unsafe { host.mapping.ptr.add(4) as *mut u32 };
+        unsafe { capacity.write(0) };
+        assert!(SharedMemoryBackend::open(&path).is_err());
+        unsafe { capacity.write(65) };
+        assert!(SharedMemoryBackend::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        let ring = Ring::new(&host.mapping, 0);
+        ring.read.store(10, Ordering::Release);
+        assert_eq!(
+            ring.write(1, &[0xF8]),
+            Err(RtMidiError::Error(
+                "shared memory ring is corrupt".to_string()
+            ))
+        );
+        ring.read.store(0, Ordering::Release);
+
+        // A message longer than the ring stops the reader rather than being read
+        let received = Arc::new(Mutex::new(0));
+        let sink = Arc::clone(&received);
+        let _input = plugin
+            .open_input(
+                "shm",
+                "Test",
+                Box::new(move |_, _| *sink.lock().unwrap() += 1),
+            )
+            .unwrap();
+        let mut header = [0; MESSAGE_HEADER_SIZE];
+        header[8..].copy_from_slice(&1000u32.to_le_bytes());
+        ring.copy_in(0, &header);
+        ring.write.store(64, Ordering::Release);
+        let start = Instant::now();
+        while plugin.input_open.load(Ordering::Acquire) && start.elapsed() < Duration::from_secs(5)
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!plugin.input_open.load(Ordering::Acquire));
+        assert_eq!(*received.lock().unwrap(), 0);
+    }
+}
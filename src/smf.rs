@@ -0,0 +1,436 @@
+use std::time::Duration;
+
+use crate::decoder;
+use crate::error::RtMidiError;
+use crate::tempo::TempoMap;
+
+/// Default tempo of a Standard MIDI File in microseconds per quarter note (120 BPM)
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// Set Tempo meta event type
+const META_TEMPO: u8 = 0x51;
+
+/// Standard MIDI File
+///
+/// A parsed Standard MIDI File (format 0, 1 or 2). [`Smf::messages`] flattens the tracks into a
+/// single list of timed messages ready to be sent to an output.
+/// ```
+/// use rtmidi::Smf;
+///
+/// let data = [
+///     b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+///     b'M', b'T', b'r', b'k', 0, 0, 0, 12,
+///     0x00, 0x90, 60, 100,
+///     0x60, 0x80, 60, 0,
+///     0x00, 0xFF, 0x2F, 0x00,
+/// ];
+/// let smf = Smf::parse(&data).unwrap();
+/// for (offset, message) in smf.messages() {
+///     println!("{:?}: {:?}", offset, message);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Smf {
+    pub format: u16,
+    pub division: Division,
+    pub tracks: Vec<Track>,
+}
+
+/// Time division of a Standard MIDI File
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Division {
+    /// Delta times are in ticks per quarter note (metrical time)
+    TicksPerBeat(u16),
+    /// Delta times are in subdivisions of a SMPTE frame. A frame rate of 29 is 29.97 (drop
+    /// frame).
+    Timecode { fps: u8, ticks_per_frame: u8 },
+}
+
+/// A track of a Standard MIDI File
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Track {
+    pub events: Vec<TrackEvent>,
+}
+
+/// An event in a track, with the number of ticks since the previous event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackEvent {
+    pub delta: u32,
+    pub event: SmfEvent,
+}
+
+/// Standard MIDI File event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmfEvent {
+    /// A channel message, with running status resolved
+    Midi(Vec<u8>),
+    /// A complete System Exclusive message, including the leading `F0`
+    SysEx(Vec<u8>),
+    /// An escaped (`F7`) event, sent as the raw bytes it contains
+    Escape(Vec<u8>),
+    /// A meta event of the given type
+    Meta { kind: u8, data: Vec<u8> },
+}
+
+impl SmfEvent {
+    /// Returns the bytes to send to an output for this event, or [`None`] for meta events
+    pub fn message(&self) -> Option<&[u8]> {
+        match self {
+            SmfEvent::Midi(data) | SmfEvent::SysEx(data) | SmfEvent::Escape(data) => Some(data),
+            SmfEvent::Meta { .. } => None,
+        }
+    }
+}
+
+impl Smf {
+    /// Parse the contents of a Standard MIDI File
+    pub fn parse(data: &[u8]) -> Result<Self, RtMidiError> {
+        let mut reader = Reader { data, position: 0 };
+        let (id, header) = reader.chunk()?;
+        if id != b"MThd" || header.len() < 6 {
+            return Err(invalid("missing MThd header"));
+        }
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let count = u16::from_be_bytes([header[2], header[3]]);
+        let division = match [header[4], header[5]] {
+            [fps, ticks_per_frame] if fps & 0x80 != 0 => Division::Timecode {
+                fps: (fps as i8).unsigned_abs(),
+                ticks_per_frame,
+            },
+            division => Division::TicksPerBeat(u16::from_be_bytes(division)),
+        };
+        if division == Division::TicksPerBeat(0) {
+            return Err(invalid("zero ticks per beat"));
+        }
+        let mut tracks = Vec::with_capacity(count as usize);
+        while tracks.len() < count as usize && !reader.is_empty() {
+            let (id, track) = reader.chunk()?;
+            // Unknown chunk types must be ignored
+            if id == b"MTrk" {
+                tracks.push(Track::parse(track)?);
+            }
+        }
+        Ok(Smf {
+            format,
+            division,
+            tracks,
+        })
+    }
+
+    /// Returns every message that can be sent to an output, with its offset from the start of
+    /// the file.
+    ///
+    /// Tracks are merged in time order (events at the same time keep their track order) and
+    /// tempo changes in any track are applied to all of them. Format 2 files, which contain
+    /// independent sequences, are merged in the same way.
+    pub fn messages(&self) -> Vec<(Duration, Vec<u8>)> {
+        let mut events: Vec<(u64, &SmfEvent)> = Vec::new();
+        for track in &self.tracks {
+            let mut tick = 0u64;
+            for event in &track.events {
+                tick += u64::from(event.delta);
+                events.push((tick, &event.event));
+            }
+        }
+        // Stable, so simultaneous events stay in track order
+        events.sort_by_key(|&(tick, _)| tick);
+
+        let mut messages = Vec::new();
+        let mut tempo = DEFAULT_TEMPO;
+        let (mut last, mut seconds) = (0u64, 0.0);
+        for (tick, event) in events {
+            seconds += (tick - last) as f64 * self.tick_duration(tempo);
+            last = tick;
+            match event {
+                SmfEvent::Meta { kind, data } if *kind == META_TEMPO && data.len() == 3 => {
+                    tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                }
+                event => {
+                    if let Some(message) = event.message() {
+                        messages.push((Duration::from_secs_f64(seconds), message.to_vec()));
+                    }
+                }
+            }
+        }
+        messages
+    }
+
+    /// Returns the tempo changes in the file (from any track), for a [`crate::Scheduler`] to
+    /// follow. Returns [`None`] for files in timecode, which don't have a tempo.
+    pub fn tempo_map(&self) -> Option<TempoMap> {
+        let ticks = match self.division {
+            Division::TicksPerBeat(ticks) => f64::from(ticks),
+            Division::Timecode { .. } => return None,
+        };
+        let mut map = TempoMap::new(60_000_000.0 / f64::from(DEFAULT_TEMPO));
+        for track in &self.tracks {
+            let mut tick = 0u64;
+            for event in &track.events {
+                tick += u64::from(event.delta);
+                if let SmfEvent::Meta { kind, data } = &event.event {
+                    if *kind == META_TEMPO && data.len() == 3 {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        if tempo > 0 {
+                            map = map.change(tick as f64 / ticks, 60_000_000.0 / f64::from(tempo));
+                        }
+                    }
+                }
+            }
+        }
+        Some(map)
+    }
+
+    /// Duration of a tick in seconds at a tempo in microseconds per quarter note
+    fn tick_duration(&self, tempo: u32) -> f64 {
+        match self.division {
+            Division::TicksPerBeat(ticks) => f64::from(tempo) / 1_000_000.0 / f64::from(ticks),
+            Division::Timecode {
+                fps,
+                ticks_per_frame,
+            } => {
+                let fps = if fps == 29 { 29.97 } else { f64::from(fps) };
+                1.0 / (fps * f64::from(ticks_per_frame.max(1)))
+            }
+        }
+    }
+}
+
+impl Track {
+    fn parse(data: &[u8]) -> Result<Self, RtMidiError> {
+        let mut reader = Reader { data, position: 0 };
+        let mut events = Vec::new();
+        let mut running = None;
+        while !reader.is_empty() {
+            let delta = reader.variable()?;
+            let event = match reader.byte()? {
+                0xFF => {
+                    running = None;
+                    let kind = reader.byte()?;
+                    let length = reader.variable()? as usize;
+                    let data = reader.bytes(length)?.to_vec();
+                    let end = kind == 0x2F;
+                    events.push(TrackEvent {
+                        delta,
+                        event: SmfEvent::Meta { kind, data },
+                    });
+                    if end {
+                        break;
+                    }
+                    continue;
+                }
+                0xF0 => {
+                    running = None;
+                    let length = reader.variable()? as usize;
+                    let mut message = vec![0xF0];
+                    message.extend_from_slice(reader.bytes(length)?);
+                    SmfEvent::SysEx(message)
+                }
+                0xF7 => {
+                    running = None;
+                    let length = reader.variable()? as usize;
+                    SmfEvent::Escape(reader.bytes(length)?.to_vec())
+                }
+                status @ 0x80..=0xEF => {
+                    running = Some(status);
+                    let length = decoder::data_length(status).unwrap_or(0);
+                    let mut message = vec![status];
+                    message.extend_from_slice(reader.bytes(length)?);
+                    SmfEvent::Midi(message)
+                }
+                data @ 0x00..=0x7F => {
+                    let status = running.ok_or_else(|| invalid("data byte without status"))?;
+                    let length = decoder::data_length(status).unwrap_or(0);
+                    let mut message = vec![status, data];
+                    message.extend_from_slice(reader.bytes(length - 1)?);
+                    SmfEvent::Midi(message)
+                }
+                status => {
+                    return Err(invalid(&format!(
+                        "unexpected status byte {:02X} in track",
+                        status
+                    )))
+                }
+            };
+            events.push(TrackEvent { delta, event });
+        }
+        Ok(Track { events })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn byte(&mut self) -> Result<u8, RtMidiError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], RtMidiError> {
+        let end = self.position.saturating_add(length);
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Read a variable-length quantity (at most four bytes)
+    fn variable(&mut self) -> Result<u32, RtMidiError> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | u32::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("variable-length quantity too long"))
+    }
+
+    fn chunk(&mut self) -> Result<(&'a [u8], &'a [u8]), RtMidiError> {
+        let id = self.bytes(4)?;
+        let length = self.bytes(4)?;
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]);
+        Ok((id, self.bytes(length as usize)?))
+    }
+}
+
+fn invalid(reason: &str) -> RtMidiError {
+    RtMidiError::InvalidFile(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Division, Smf, SmfEvent};
+    use crate::error::RtMidiError;
+
+    fn file(division: [u8; 2], tracks: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"MThd\0\0\0\x06\0\x01".to_vec();
+        data.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        data.extend_from_slice(&division);
+        for track in tracks {
+            data.extend_from_slice(b"MTrk");
+            data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            data.extend_from_slice(track);
+        }
+        data
+    }
+
+    fn millis(messages: Vec<(Duration, Vec<u8>)>) -> Vec<(u64, Vec<u8>)> {
+        messages
+            .into_iter()
+            .map(|(offset, message)| ((offset.as_secs_f64() * 1000.0).round() as u64, message))
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let data = file(
+            [0, 96],
+            &[&[
+                0x00, 0x90, 60, 100, // Note on
+                0x81, 0x40, 62, 100, // Running status, delta 192
+                0x00, 0xF0, 0x03, 0x7E, 0x09, 0xF7, // SysEx
+                0x00, 0xFF, 0x2F, 0x00, // End of track
+            ]],
+        );
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(smf.format, 1);
+        assert_eq!(smf.division, Division::TicksPerBeat(96));
+        let events: Vec<_> = smf.tracks[0]
+            .events
+            .iter()
+            .map(|event| (event.delta, event.event.clone()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (0, SmfEvent::Midi(vec![0x90, 60, 100])),
+                (192, SmfEvent::Midi(vec![0x90, 62, 100])),
+                (0, SmfEvent::SysEx(vec![0xF0, 0x7E, 0x09, 0xF7])),
+                (
+                    0,
+                    SmfEvent::Meta {
+                        kind: 0x2F,
+                        data: vec![]
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(matches!(
+            Smf::parse(b"RIFF"),
+            Err(RtMidiError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            Smf::parse(&file([0, 96], &[&[0x00, 60, 100]])),
+            Err(RtMidiError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            Smf::parse(&file([0, 96], &[&[0x00, 0x90, 60]])),
+            Err(RtMidiError::InvalidFile(_))
+        ));
+    }
+
+    #[test]
+    fn messages() {
+        let data = file(
+            [0, 96],
+            &[
+                // Tempo track: 60 BPM after one beat
+                &[0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40],
+                &[0x00, 0x90, 60, 100, 0x81, 0x40, 0x80, 60, 0],
+            ],
+        );
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(
+            millis(smf.messages()),
+            vec![
+                (0, vec![0x90, 60, 100]),
+                // One beat at 120 BPM, then one at 60 BPM
+                (1500, vec![0x80, 60, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn tempo_map() {
+        let data = file([0, 96], &[&[0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40]]);
+        let map = Smf::parse(&data).unwrap().tempo_map().unwrap();
+        assert_eq!(map.changes(), &[(0.0, 120.0), (1.0, 60.0)]);
+        assert_eq!(
+            Smf::parse(&file([0xE7, 40], &[])).unwrap().tempo_map(),
+            None
+        );
+    }
+
+    #[test]
+    fn messages_timecode() {
+        let data = file(
+            [0xE7, 40],
+            &[&[0x00, 0x90, 60, 100, 0x83, 0x60, 0x80, 60, 0]],
+        );
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(
+            smf.division,
+            Division::Timecode {
+                fps: 25,
+                ticks_per_frame: 40
+            }
+        );
+        // 480 ticks at 1000 ticks per second
+        assert_eq!(millis(smf.messages())[1].0, 480);
+    }
+}
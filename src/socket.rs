@@ -0,0 +1,143 @@
+use std::fs;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+use crate::backend::{Backend, BackendCallback, InputConnection, OutputConnection};
+use crate::error::RtMidiError;
+use crate::pipe::{PipeBackend, PipeFraming};
+
+/// [`Backend`] exchanging MIDI with another local process over a Unix socket
+///
+/// Provides one input and one output port, both named after the backend, carrying
+/// length-prefixed messages ([`PipeFraming::LengthPrefixed`]) in each direction. One process
+/// waits for the other with [`SocketBackend::accept`], and the other connects with
+/// [`SocketBackend::connect`], so they can exchange MIDI without the OS MIDI system, e.g. in
+/// containers or tests.
+///
+/// Windows named pipes aren't supported, as a synchronous pipe handle can't be read and written
+/// at the same time.
+/// ```no_run
+/// use rtmidi::{MidiSystem, RtMidiApi, SocketBackend};
+///
+/// let system = MidiSystem::new(RtMidiApi::Unspecified, "synth")
+///     .unwrap()
+///     .backend(SocketBackend::accept("/tmp/synth.sock").unwrap());
+/// let input = system.open_input("socket").unwrap();
+/// ```
+pub struct SocketBackend(PipeBackend);
+
+impl SocketBackend {
+    /// Connect to a process waiting on a socket
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, RtMidiError> {
+        SocketBackend::new(UnixStream::connect(path).map_err(error)?)
+    }
+
+    /// Create a socket and wait for a process to connect to it. The socket file is removed
+    /// once it has, and replaced if it already exists.
+    pub fn accept<P: AsRef<Path>>(path: P) -> Result<Self, RtMidiError> {
+        let path = path.as_ref();
+        let _ = fs::remove_file(path);
+        let listener = UnixListener::bind(path).map_err(error)?;
+        let accepted = listener.accept();
+        let _ = fs::remove_file(path);
+        SocketBackend::new(accepted.map_err(error)?.0)
+    }
+
+    fn new(stream: UnixStream) -> Result<Self, RtMidiError> {
+        let reader = stream.try_clone().map_err(error)?;
+        let backend = PipeBackend::new("socket", Some(Box::new(reader)), Some(Box::new(stream)))
+            .framing(PipeFraming::LengthPrefixed);
+        Ok(SocketBackend(backend))
+    }
+}
+
+impl Backend for SocketBackend {
+    fn name(&self) -> &str {
+        "socket"
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        self.0.input_ports()
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        self.0.output_ports()
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        self.0.open_input(port, client_name, callback)
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        self.0.open_output(port, client_name)
+    }
+}
+
+fn error(e: std::io::Error) -> RtMidiError {
+    RtMidiError::Error(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::SocketBackend;
+    use crate::Backend;
+
+    #[test]
+    fn exchange() {
+        let path = env::temp_dir().join(format!("rtmidi-test-{}.sock", process::id()));
+        let accept_path = path.clone();
+        let server = thread::spawn(move || SocketBackend::accept(accept_path).unwrap());
+        let start = Instant::now();
+        let client = loop {
+            match SocketBackend::connect(&path) {
+                Ok(client) => break client,
+                Err(_) if start.elapsed() < Duration::from_secs(5) => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                Err(e) => panic!("{:?}", e),
+            }
+        };
+        let server = server.join().unwrap();
+        assert!(!path.exists());
+        assert_eq!(server.input_ports().unwrap(), ["socket"]);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let _input = server
+            .open_input(
+                "socket",
+                "Test",
+                Box::new(move |_, message| sink.lock().unwrap().push(message.to_vec())),
+            )
+            .unwrap();
+        let output = client.open_output("socket", "Test").unwrap();
+        output.send(&[0x90, 60, 100]).unwrap();
+        output.send(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        let start = Instant::now();
+        while received.lock().unwrap().len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                vec![0x90, 60, 100],
+                vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]
+            ]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::smf::Smf;
+use crate::threads;
+
+/// Fake input that plays a Standard MIDI File
+///
+/// Delivers the messages of a file to a callback with the same arguments as
+/// [`crate::RtMidiIn::set_callback`] (the delta-time in seconds since the previous message, and
+/// the message), at real or accelerated speed, without opening any ports. This allows timing
+/// sensitive application logic to be tested with realistic input.
+/// ```
+/// use rtmidi::{Smf, SmfSource};
+///
+/// # let data = [
+/// #     b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+/// #     b'M', b'T', b'r', b'k', 0, 0, 0, 4, 0x00, 0xFF, 0x2F, 0x00,
+/// # ];
+/// let smf = Smf::parse(&data).unwrap();
+/// // Play ten times faster than real time
+/// SmfSource::new(&smf).speed(10.0).play(|timestamp, message| {
+///     println!("{}: {:02x?}", timestamp, message);
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct SmfSource {
+    messages: Vec<(Duration, Vec<u8>)>,
+    speed: f64,
+}
+
+impl SmfSource {
+    pub fn new(smf: &Smf) -> Self {
+        SmfSource {
+            messages: smf.messages(),
+            speed: 1.0,
+        }
+    }
+
+    /// Set the playback speed (e.g. 2.0 plays twice as fast). [`f64::INFINITY`] delivers every
+    /// message immediately, with the original delta-times.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is not greater than zero.
+    pub fn speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "Invalid playback speed {}", speed);
+        self.speed = speed;
+        self
+    }
+
+    /// Deliver every message to a callback on the current thread, returning once the file has
+    /// been played.
+    ///
+    /// Messages are delivered at their time in the file relative to the start of playback, so
+    /// timing errors don't accumulate. Delta-times passed to the callback are scaled by the
+    /// speed, as if the file had been played to an input at that speed.
+    pub fn play<F: FnMut(f64, &[u8])>(&self, mut callback: F) {
+        let start = Instant::now();
+        let mut last = Duration::from_secs(0);
+        for (time, message) in &self.messages {
+            let delta = (*time - last).as_secs_f64();
+            last = *time;
+            if self.speed.is_finite() {
+                let due = start + time.div_f64(self.speed);
+                let now = Instant::now();
+                if due > now {
+                    thread::sleep(due - now);
+                }
+                callback(delta / self.speed, message);
+            } else {
+                callback(delta, message);
+            }
+        }
+    }
+
+    /// Play the file to a callback on a new thread
+    pub fn spawn<F: FnMut(f64, &[u8]) + Send + 'static>(self, callback: F) -> JoinHandle<()> {
+        threads::spawn("rtmidi-smf".to_string(), move || self.play(callback))
+    }
+
+    /// Play the file on a new thread into a queue, which is closed at the end of the file
+    pub fn queue(self) -> Receiver<(f64, Vec<u8>)> {
+        let (sender, receiver) = mpsc::channel();
+        self.spawn(move |timestamp, message| {
+            // The receiver may have been dropped, in which case playback continues unheard
+            let _ = sender.send((timestamp, message.to_vec()));
+        });
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::SmfSource;
+    use crate::smf::Smf;
+
+    fn smf() -> Smf {
+        let data = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, // Header
+            b'M', b'T', b'r', b'k', 0, 0, 0, 12, // Track
+            0x00, 0x90, 60, 100, // Note on
+            0x60, 0x80, 60, 0, // Note off after a beat (500ms)
+            0x00, 0xFF, 0x2F, 0x00, // End of track
+        ];
+        Smf::parse(&data).unwrap()
+    }
+
+    #[test]
+    fn play() {
+        let mut messages = Vec::new();
+        let start = Instant::now();
+        SmfSource::new(&smf())
+            .speed(10.0)
+            .play(|timestamp, message| messages.push(((timestamp * 1000.0).round(), message[0])));
+        assert!(start.elapsed().as_millis() >= 50);
+        assert_eq!(messages, vec![(0.0, 0x90), (50.0, 0x80)]);
+    }
+
+    #[test]
+    fn queue() {
+        let queue = SmfSource::new(&smf()).speed(f64::INFINITY).queue();
+        let messages: Vec<_> = queue.iter().collect();
+        assert_eq!(
+            messages,
+            vec![(0.0, vec![0x90, 60, 100]), (0.5, vec![0x80, 60, 0])]
+        );
+    }
+}
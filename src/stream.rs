@@ -0,0 +1,110 @@
+/// Part of a system exclusive message delivered by [`crate::RtMidiIn::stream_sysex`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysExChunk<'a> {
+    /// A message started (`0xF0` was received)
+    Begin,
+    /// Data bytes of the message, excluding the `0xF0` and `0xF7` framing
+    Data(&'a [u8]),
+    /// The message was terminated by `0xF7`
+    End,
+    /// The message was interrupted by another status byte before it was terminated
+    Aborted,
+}
+
+/// Splits system exclusive messages into chunks, following messages that arrive in pieces
+pub struct SysExStream<F> {
+    chunk_size: usize,
+    callback: F,
+    active: bool,
+}
+
+impl<F: FnMut(SysExChunk)> SysExStream<F> {
+    pub fn new(chunk_size: usize, callback: F) -> Self {
+        SysExStream {
+            chunk_size: chunk_size.max(1),
+            callback,
+            active: false,
+        }
+    }
+
+    /// Handle a message, returning [`true`] if it was (part of) a system exclusive message
+    pub fn process(&mut self, message: &[u8]) -> bool {
+        let data = match message.split_first() {
+            Some((0xF0, data)) => {
+                if self.active {
+                    (self.callback)(SysExChunk::Aborted);
+                }
+                self.active = true;
+                (self.callback)(SysExChunk::Begin);
+                data
+            }
+            // A continuation of the current message
+            Some((&byte, _)) if self.active && (byte < 0x80 || byte == 0xF7) => message,
+            // System real-time messages may be interleaved with system exclusive data
+            Some((&byte, _)) if byte >= 0xF8 => return false,
+            Some(_) if self.active => {
+                self.active = false;
+                (self.callback)(SysExChunk::Aborted);
+                return false;
+            }
+            _ => return false,
+        };
+        let end = data.iter().position(|&byte| byte >= 0x80);
+        for chunk in data[..end.unwrap_or(data.len())].chunks(self.chunk_size) {
+            (self.callback)(SysExChunk::Data(chunk));
+        }
+        match end.map(|index| data[index]) {
+            Some(0xF7) => {
+                self.active = false;
+                (self.callback)(SysExChunk::End);
+            }
+            Some(_) => {
+                self.active = false;
+                (self.callback)(SysExChunk::Aborted);
+            }
+            None => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SysExStream;
+
+    fn chunks(messages: &[&[u8]]) -> (Vec<String>, Vec<bool>) {
+        let mut chunks = Vec::new();
+        let handled = {
+            let mut stream = SysExStream::new(2, |chunk| chunks.push(format!("{:?}", chunk)));
+            messages
+                .iter()
+                .map(|message| stream.process(message))
+                .collect()
+        };
+        (chunks, handled)
+    }
+
+    #[test]
+    fn complete() {
+        let (chunks, handled) = chunks(&[&[0xF0, 1, 2, 3, 0xF7], &[0x90, 60, 100]]);
+        assert_eq!(chunks, ["Begin", "Data([1, 2])", "Data([3])", "End"]);
+        assert_eq!(handled, [true, false]);
+    }
+
+    #[test]
+    fn fragments() {
+        let (chunks, handled) = chunks(&[&[0xF0, 1], &[0xF8], &[2, 3], &[4, 0xF7]]);
+        assert_eq!(
+            chunks,
+            ["Begin", "Data([1])", "Data([2, 3])", "Data([4])", "End"]
+        );
+        assert_eq!(handled, [true, false, true, true]);
+    }
+
+    #[test]
+    fn aborted() {
+        let (chunks, handled) = chunks(&[&[0xF0, 1], &[0x90, 60, 100]]);
+        assert_eq!(chunks, ["Begin", "Data([1])", "Aborted"]);
+        assert_eq!(handled, [true, false]);
+    }
+}
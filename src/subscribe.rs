@@ -0,0 +1,127 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Returns [`true`] if the message was consumed
+type Callback = Box<dyn Fn(f64, &[u8]) -> bool + Send>;
+
+#[derive(Default)]
+struct List {
+    next_id: u64,
+    // Ordered by descending priority, then by subscription order
+    callbacks: Vec<(u64, i32, Callback)>,
+}
+
+/// Shared list of input subscribers, invoked in priority order with every incoming message
+#[derive(Clone, Default)]
+pub struct Subscribers(Arc<Mutex<List>>);
+
+impl Subscribers {
+    pub fn add(&self, priority: i32, callback: Callback) -> Subscription {
+        let mut list = lock(&self.0);
+        let id = list.next_id;
+        list.next_id += 1;
+        let index = list
+            .callbacks
+            .iter()
+            .position(|(_, other, _)| *other < priority)
+            .unwrap_or(list.callbacks.len());
+        list.callbacks.insert(index, (id, priority, callback));
+        let list = Arc::downgrade(&self.0);
+        Subscription::new(move || {
+            if let Some(list) = list.upgrade() {
+                lock(&list).callbacks.retain(|(other, _, _)| *other != id);
+            }
+        })
+    }
+
+    /// Invoke the subscribers until one consumes the message, returning [`true`] if it was
+    /// consumed
+    pub fn emit(&self, timestamp: f64, message: &[u8]) -> bool {
+        lock(&self.0)
+            .callbacks
+            .iter()
+            .any(|(_, _, callback)| callback(timestamp, message))
+    }
+}
+
+/// Subscription to the messages of an input
+///
+/// Returned by [`crate::RtMidiIn::subscribe`] and [`crate::RtMidiIn::subscribe_with_priority`]
+/// (and for other notifications, such as [`crate::Transport::subscribe`]). The subscriber is
+/// removed when this is dropped (or [`Subscription::unsubscribe`] is called); use
+/// [`std::mem::forget`] to keep it for the lifetime of the input.
+#[must_use = "the subscriber is removed when the subscription is dropped"]
+pub struct Subscription {
+    remove: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl Subscription {
+    /// Create a subscription that calls `remove` to remove its subscriber
+    pub(crate) fn new<F: FnOnce() + Send + Sync + 'static>(remove: F) -> Self {
+        Subscription {
+            remove: Some(Box::new(remove)),
+        }
+    }
+
+    /// Remove the subscriber
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            remove();
+        }
+    }
+}
+
+fn lock(list: &Mutex<List>) -> MutexGuard<'_, List> {
+    list.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Subscribers;
+
+    #[test]
+    fn emit() {
+        let subscribers = Subscribers::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let subscription = {
+            let received = Arc::clone(&received);
+            subscribers.add(
+                0,
+                Box::new(move |_timestamp, message: &[u8]| {
+                    received.lock().unwrap().push(message.to_vec());
+                    false
+                }),
+            )
+        };
+        assert!(!subscribers.emit(0.0, &[0x90, 60, 100]));
+        subscription.unsubscribe();
+        subscribers.emit(0.0, &[0x80, 60, 0]);
+        assert_eq!(*received.lock().unwrap(), vec![vec![0x90, 60, 100]]);
+    }
+
+    #[test]
+    fn priority() {
+        let subscribers = Subscribers::default();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = |name: &'static str, consume: bool| {
+            let order = Arc::clone(&order);
+            Box::new(move |_timestamp, message: &[u8]| {
+                order.lock().unwrap().push(name);
+                consume && message[0] == 0xF8
+            })
+        };
+        let _recorder = subscribers.add(0, subscriber("recorder", false));
+        let _logger = subscribers.add(0, subscriber("logger", false));
+        let _filter = subscribers.add(10, subscriber("filter", true));
+        assert!(!subscribers.emit(0.0, &[0x90, 60, 100]));
+        assert_eq!(*order.lock().unwrap(), ["filter", "recorder", "logger"]);
+        order.lock().unwrap().clear();
+        assert!(subscribers.emit(0.0, &[0xF8]));
+        assert_eq!(*order.lock().unwrap(), ["filter"]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::RtMidiError;
+
+/// Token for cancelling a long running operation from another thread
+///
+/// Clones share the same state, so one clone can be kept by the operation and another by (e.g.)
+/// a cancel button.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    /// Returns [`true`] if cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// System exclusive transfer arguments
+///
+/// Defines how [`crate::RtMidiOut::send_sysex`] splits a message.
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::SysExArgs;
+///
+/// let args = SysExArgs {
+///     chunk_size: 128,
+///     chunk_interval: Duration::from_millis(50),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct SysExArgs {
+    /// Maximum number of bytes sent at once
+    pub chunk_size: usize,
+    /// Pause between chunks, giving slow devices time to process them
+    pub chunk_interval: Duration,
+    /// Stops the transfer when cancelled
+    pub cancel: Option<CancelToken>,
+}
+
+impl Default for SysExArgs {
+    fn default() -> Self {
+        SysExArgs {
+            chunk_size: 256,
+            chunk_interval: Duration::from_millis(20),
+            cancel: None,
+        }
+    }
+}
+
+impl SysExArgs {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        matches!(&self.cancel, Some(cancel) if cancel.is_cancelled())
+    }
+}
+
+/// Checksum algorithms used in system exclusive messages by common vendors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// Roland: the checksum makes the 7-bit sum of the address, data and checksum zero
+    Roland,
+    /// Yamaha: the two's complement of the 7-bit sum of the data (the same value as
+    /// [`Checksum::Roland`])
+    Yamaha,
+    /// Kawai (e.g. K1 and K4): the 7-bit sum of the data plus `0xA5`
+    Kawai,
+}
+
+impl Checksum {
+    /// Returns the checksum of the given bytes
+    pub fn compute(&self, data: &[u8]) -> u8 {
+        let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) & 0x7F;
+        match self {
+            Checksum::Roland | Checksum::Yamaha => (0x80 - sum) & 0x7F,
+            Checksum::Kawai => sum.wrapping_add(0xA5) & 0x7F,
+        }
+    }
+
+    /// Returns [`true`] if the last byte of `data` is the checksum of the bytes before it
+    pub fn verify(&self, data: &[u8]) -> bool {
+        match data.split_last() {
+            Some((&checksum, data)) => self.compute(data) == checksum,
+            None => false,
+        }
+    }
+}
+
+/// System exclusive message builder
+///
+/// Appends the `0xF0` and `0xF7` framing and, optionally, a vendor checksum over part of the
+/// message.
+/// ```
+/// use rtmidi::{Checksum, SysExBuilder};
+///
+/// // Roland DT1: set address 40 00 7F to 0
+/// let message = SysExBuilder::new()
+///     .bytes(&[0x41, 0x10, 0x42, 0x12])
+///     .checksum(Checksum::Roland)
+///     .bytes(&[0x40, 0x00, 0x7F, 0x00])
+///     .build()
+///     .unwrap();
+/// assert_eq!(message, [0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SysExBuilder {
+    bytes: Vec<u8>,
+    checksum: Option<(Checksum, usize)>,
+}
+
+impl Default for SysExBuilder {
+    fn default() -> Self {
+        SysExBuilder {
+            bytes: vec![0xF0],
+            checksum: None,
+        }
+    }
+}
+
+impl SysExBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Append a data byte
+    pub fn byte(mut self, byte: u8) -> Self {
+        self.bytes.push(byte);
+        self
+    }
+
+    /// Append data bytes
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    /// Add a checksum covering the bytes appended after this call, placed just before the `0xF7`
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = Some((checksum, self.bytes.len()));
+        self
+    }
+
+    /// Returns the message, or [`RtMidiError::InvalidMessage`] if a data byte isn't 7-bit
+    pub fn build(self) -> Result<Vec<u8>, RtMidiError> {
+        let mut bytes = self.bytes;
+        if let Some(&byte) = bytes[1..].iter().find(|&&byte| byte >= 0x80) {
+            return Err(RtMidiError::InvalidMessage(format!(
+                "invalid system exclusive data byte 0x{:02x}",
+                byte
+            )));
+        }
+        if let Some((checksum, start)) = self.checksum {
+            let value = checksum.compute(&bytes[start..]);
+            bytes.push(value);
+        }
+        bytes.push(0xF7);
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancelToken, Checksum, SysExBuilder};
+
+    #[test]
+    fn cancel() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn checksum() {
+        // Roland GS reset: F0 41 10 42 12 40 00 7F 00 41 F7
+        assert_eq!(Checksum::Roland.compute(&[0x40, 0x00, 0x7F, 0x00]), 0x41);
+        assert!(Checksum::Roland.verify(&[0x40, 0x00, 0x7F, 0x00, 0x41]));
+        assert!(!Checksum::Roland.verify(&[0x40, 0x00, 0x7F, 0x00, 0x40]));
+        assert!(!Checksum::Roland.verify(&[]));
+        assert_eq!(Checksum::Roland.compute(&[]), 0);
+        assert_eq!(Checksum::Yamaha.compute(&[0x7F, 0x7F]), 0x02);
+        assert_eq!(Checksum::Kawai.compute(&[0x01, 0x02]), 0x28);
+    }
+
+    #[test]
+    fn build() {
+        let message = SysExBuilder::new()
+            .byte(0x40)
+            .checksum(Checksum::Kawai)
+            .bytes(&[0x01, 0x02])
+            .build()
+            .unwrap();
+        assert_eq!(message, [0xF0, 0x40, 0x01, 0x02, 0x28, 0xF7]);
+        assert_eq!(SysExBuilder::new().build().unwrap(), [0xF0, 0xF7]);
+        assert!(SysExBuilder::new().byte(0x80).build().is_err());
+    }
+}
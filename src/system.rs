@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::api::RtMidiApi;
+use crate::backend::{Backend, InputConnection, OutputConnection, RtMidiBackend};
+use crate::error::RtMidiError;
+use crate::subscribe::{Subscribers, Subscription};
+use crate::threads;
+
+/// Direction of a MIDI port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortDirection {
+    Input,
+    Output,
+}
+
+/// Change to the ports available on the system, reported by [`MidiSystem::watch`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Added {
+        direction: PortDirection,
+        name: String,
+    },
+    Removed {
+        direction: PortDirection,
+        name: String,
+    },
+}
+
+/// Device manager
+///
+/// Owns the open inputs and outputs of an application, keyed by port name, and brings
+/// enumeration, opening by name, routing and device change notifications together in one place.
+/// Ports come from the native RtMidi API and any other [`Backend`]s added with
+/// [`MidiSystem::backend`], and are handed out as lightweight [`MidiInput`] and [`MidiOutput`]
+/// handles. Opening a port that is already open returns the existing handle.
+///
+/// Ports are opened by name: an exact match is preferred, otherwise the first port whose name
+/// contains the given text is used (e.g. "Launchpad" for "Launchpad X:Launchpad X MIDI 1 20:0").
+/// ```
+/// use rtmidi::{MidiSystem, RtMidiApi};
+///
+/// let system = MidiSystem::new(RtMidiApi::Unspecified, "My Application").unwrap();
+/// for name in system.input_ports().unwrap() {
+///     println!("{}", name);
+/// }
+/// // Play a keyboard through a synthesizer
+/// if system.route("Keyboard", "Synth").is_ok() {
+///     system.output("Synth").unwrap().message(&[0xC0, 5]).unwrap();
+/// }
+/// ```
+pub struct MidiSystem {
+    api: RtMidiApi,
+    client_name: String,
+    // The native backend first, then any others in the order they were added
+    backends: Vec<Arc<dyn Backend>>,
+    inputs: Mutex<HashMap<String, MidiInput>>,
+    outputs: Mutex<HashMap<String, MidiOutput>>,
+    // Input and output port names of each route
+    routes: Mutex<Vec<(String, String, Subscription)>>,
+}
+
+impl MidiSystem {
+    /// Create a device manager for an API, with a client name used for every port it opens
+    pub fn new(api: RtMidiApi, client_name: &str) -> Result<Self, RtMidiError> {
+        let native = RtMidiBackend::new(api, client_name)?;
+        Ok(MidiSystem {
+            api: native.api(),
+            client_name: client_name.to_string(),
+            backends: vec![Arc::new(native)],
+            inputs: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(HashMap::new()),
+            routes: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Add a backend, whose ports are listed after those of the backends already added
+    pub fn backend<B: Backend + 'static>(mut self, backend: B) -> Self {
+        self.backends.push(Arc::new(backend));
+        self
+    }
+
+    /// Returns the native API in use
+    pub fn api(&self) -> RtMidiApi {
+        self.api
+    }
+
+    /// Returns the names of the available input ports of every backend
+    pub fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        list(&self.backends, PortDirection::Input).map(names)
+    }
+
+    /// Returns the names of the available output ports of every backend
+    pub fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        list(&self.backends, PortDirection::Output).map(names)
+    }
+
+    /// Open the input port matching a name, or return it if it is already open
+    pub fn open_input(&self, name: &str) -> Result<MidiInput, RtMidiError> {
+        let (backend, port) = self.find(PortDirection::Input, name)?;
+        let mut inputs = lock(&self.inputs);
+        if let Some(input) = inputs.get(&port) {
+            return Ok(input.clone());
+        }
+        let subscribers = Subscribers::default();
+        let callback = {
+            let subscribers = subscribers.clone();
+            Box::new(move |timestamp, message: &[u8]| {
+                subscribers.emit(timestamp, message);
+            })
+        };
+        let connection = backend.open_input(&port, &self.client_name, callback)?;
+        let input = MidiInput(Arc::new(Input {
+            name: port.clone(),
+            subscribers,
+            _connection: connection,
+        }));
+        inputs.insert(port, input.clone());
+        Ok(input)
+    }
+
+    /// Open the output port matching a name, or return it if it is already open
+    pub fn open_output(&self, name: &str) -> Result<MidiOutput, RtMidiError> {
+        let (backend, port) = self.find(PortDirection::Output, name)?;
+        let mut outputs = lock(&self.outputs);
+        if let Some(output) = outputs.get(&port) {
+            return Ok(output.clone());
+        }
+        let connection = backend.open_output(&port, &self.client_name)?;
+        let output = MidiOutput(Arc::new(Output {
+            name: port.clone(),
+            connection,
+        }));
+        outputs.insert(port, output.clone());
+        Ok(output)
+    }
+
+    /// Returns the backend and name of the port matching a name
+    fn find(
+        &self,
+        direction: PortDirection,
+        name: &str,
+    ) -> Result<(Arc<dyn Backend>, String), RtMidiError> {
+        let ports = list(&self.backends, direction)?;
+        let names: Vec<&str> = ports.iter().map(|(_, port)| port.as_str()).collect();
+        let index = find(&names, name).ok_or_else(|| not_found(name))?;
+        let (backend, port) = &ports[index];
+        Ok((Arc::clone(&self.backends[*backend]), port.clone()))
+    }
+
+    /// Returns the open input matching a name
+    pub fn input(&self, name: &str) -> Option<MidiInput> {
+        let inputs = lock(&self.inputs);
+        open_port(&inputs, name).map(|port| inputs[&port].clone())
+    }
+
+    /// Returns the open output matching a name
+    pub fn output(&self, name: &str) -> Option<MidiOutput> {
+        let outputs = lock(&self.outputs);
+        open_port(&outputs, name).map(|port| outputs[&port].clone())
+    }
+
+    /// Close the open input matching a name, along with its routes, returning [`false`] if there
+    /// is none. The port stays open until every handle to it has been dropped.
+    pub fn close_input(&self, name: &str) -> bool {
+        let mut inputs = lock(&self.inputs);
+        match open_port(&inputs, name) {
+            Some(port) => {
+                lock(&self.routes).retain(|(input, _, _)| *input != port);
+                inputs.remove(&port);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close the open output matching a name, along with its routes, returning [`false`] if
+    /// there is none. The port stays open until every handle to it has been dropped.
+    pub fn close_output(&self, name: &str) -> bool {
+        let mut outputs = lock(&self.outputs);
+        match open_port(&outputs, name) {
+            Some(port) => {
+                lock(&self.routes).retain(|(_, output, _)| *output != port);
+                outputs.remove(&port);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forward every message received by an input to an output, opening either if necessary.
+    /// Errors sending to the output are ignored, and adding a route that exists has no effect.
+    pub fn route(&self, input: &str, output: &str) -> Result<(), RtMidiError> {
+        let source = self.open_input(input)?;
+        let destination = self.open_output(output)?;
+        let (input, output) = (source.name().to_string(), destination.name().to_string());
+        let mut routes = lock(&self.routes);
+        if !routes.iter().any(|(i, o, _)| *i == input && *o == output) {
+            let subscription = source.subscribe(move |_timestamp, message| {
+                let _ = destination.message(message);
+            });
+            routes.push((input, output, subscription));
+        }
+        Ok(())
+    }
+
+    /// Remove the route from an open input to an open output, returning [`false`] if there is
+    /// none
+    pub fn unroute(&self, input: &str, output: &str) -> bool {
+        let input = open_port(&lock(&self.inputs), input);
+        let output = open_port(&lock(&self.outputs), output);
+        let mut routes = lock(&self.routes);
+        let count = routes.len();
+        routes.retain(|(i, o, _)| Some(i) != input.as_ref() || Some(o) != output.as_ref());
+        routes.len() != count
+    }
+
+    /// Returns the input and output port names of each route
+    pub fn routes(&self) -> Vec<(String, String)> {
+        lock(&self.routes)
+            .iter()
+            .map(|(input, output, _)| (input.clone(), output.clone()))
+            .collect()
+    }
+
+    /// Watch for ports being added to or removed from the system.
+    ///
+    /// The ports are listed every `interval` on a separate thread, which calls `callback` with
+    /// each change. Watching stops when the returned [`Subscription`] is dropped. Open ports
+    /// aren't affected by their device being removed; use [`MidiSystem::close_input`] or
+    /// [`MidiSystem::close_output`] to close them.
+    pub fn watch<F>(&self, interval: Duration, callback: F) -> Result<Subscription, RtMidiError>
+    where
+        F: Fn(DeviceEvent) + Send + 'static,
+    {
+        let backends = self.backends.clone();
+        let ports = move || {
+            Ok::<_, RtMidiError>((
+                names(list(&backends, PortDirection::Input)?),
+                names(list(&backends, PortDirection::Output)?),
+            ))
+        };
+        let (mut inputs, mut outputs) = ports()?;
+        let (stop, stopped) = mpsc::sync_channel::<()>(0);
+        threads::spawn("rtmidi-watch".to_string(), move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                // Listing can fail while a device is being removed; try again next time
+                if let Ok((new_inputs, new_outputs)) = ports() {
+                    changes(PortDirection::Input, &inputs, &new_inputs, &callback);
+                    changes(PortDirection::Output, &outputs, &new_outputs, &callback);
+                    inputs = new_inputs;
+                    outputs = new_outputs;
+                }
+            }
+        });
+        Ok(Subscription::new(move || drop(stop)))
+    }
+}
+
+/// Handle to an input opened by a [`MidiSystem`]
+///
+/// Clones refer to the same input.
+#[derive(Clone)]
+pub struct MidiInput(Arc<Input>);
+
+struct Input {
+    name: String,
+    subscribers: Subscribers,
+    _connection: Box<dyn InputConnection>,
+}
+
+impl MidiInput {
+    /// Returns the port name
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Add a subscriber, which is invoked with every message received (possibly on another
+    /// thread) until the returned [`Subscription`] is dropped
+    pub fn subscribe<F>(&self, subscriber: F) -> Subscription
+    where
+        F: Fn(f64, &[u8]) + Send + 'static,
+    {
+        self.0.subscribers.add(
+            0,
+            Box::new(move |timestamp, message| {
+                subscriber(timestamp, message);
+                false
+            }),
+        )
+    }
+}
+
+/// Handle to an output opened by a [`MidiSystem`]
+///
+/// Clones refer to the same output.
+#[derive(Clone)]
+pub struct MidiOutput(Arc<Output>);
+
+struct Output {
+    name: String,
+    connection: Box<dyn OutputConnection>,
+}
+
+impl MidiOutput {
+    /// Returns the port name
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Send a single, complete message
+    pub fn message(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.0.connection.send(message)
+    }
+}
+
+/// Returns the index of the backend and the name of every port in a direction
+fn list(
+    backends: &[Arc<dyn Backend>],
+    direction: PortDirection,
+) -> Result<Vec<(usize, String)>, RtMidiError> {
+    let mut ports = Vec::new();
+    for (index, backend) in backends.iter().enumerate() {
+        let names = match direction {
+            PortDirection::Input => backend.input_ports()?,
+            PortDirection::Output => backend.output_ports()?,
+        };
+        ports.extend(names.into_iter().map(|name| (index, name)));
+    }
+    Ok(ports)
+}
+
+fn names(ports: Vec<(usize, String)>) -> Vec<String> {
+    ports.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Returns the index of the port matching a name: the port with that name if there is one,
+/// otherwise the first port whose name contains it
+fn find<T: AsRef<str>>(ports: &[T], name: &str) -> Option<usize> {
+    ports
+        .iter()
+        .position(|port| port.as_ref() == name)
+        .or_else(|| ports.iter().position(|port| port.as_ref().contains(name)))
+}
+
+/// Returns the port name of the open port matching a name
+fn open_port<T>(ports: &HashMap<String, T>, name: &str) -> Option<String> {
+    let mut names: Vec<&String> = ports.keys().collect();
+    names.sort();
+    find(&names, name).map(|index| names[index].clone())
+}
+
+/// Report the ports removed from and added to a list
+fn changes<F: Fn(DeviceEvent)>(direction: PortDirection, old: &[String], new: &[String], f: &F) {
+    for name in old.iter().filter(|name| !new.contains(name)) {
+        f(DeviceEvent::Removed {
+            direction,
+            name: name.clone(),
+        });
+    }
+    for name in new.iter().filter(|name| !old.contains(name)) {
+        f(DeviceEvent::Added {
+            direction,
+            name: name.clone(),
+        });
+    }
+}
+
+fn not_found(name: &str) -> RtMidiError {
+    RtMidiError::PortNotFound(name.to_string())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    use super::{changes, find, DeviceEvent, MidiSystem, PortDirection};
+    use crate::{
+        Backend, BackendCallback, InputConnection, OutputConnection, RtMidiApi, RtMidiError,
+    };
+
+    /// Backend with an input played by the test and an output that records what it is sent
+    #[derive(Clone, Default)]
+    struct Loopback {
+        keys: Arc<Mutex<Option<BackendCallback>>>,
+        sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    struct Keys;
+
+    impl InputConnection for Keys {}
+
+    struct Synth(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl OutputConnection for Synth {
+        fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+            self.0.lock().unwrap().push(message.to_vec());
+            Ok(())
+        }
+    }
+
+    impl Backend for Loopback {
+        fn name(&self) -> &str {
+            "loopback"
+        }
+
+        fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+            Ok(vec!["Loopback Keys".to_string()])
+        }
+
+        fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+            Ok(vec!["Loopback Synth".to_string()])
+        }
+
+        fn open_input(
+            &self,
+            _port: &str,
+            _client_name: &str,
+            callback: BackendCallback,
+        ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+            *self.keys.lock().unwrap() = Some(callback);
+            Ok(Box::new(Keys))
+        }
+
+        fn open_output(
+            &self,
+            _port: &str,
+            _client_name: &str,
+        ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+            Ok(Box::new(Synth(Arc::clone(&self.sent))))
+        }
+    }
+
+    #[test]
+    fn new() {
+        let system = MidiSystem::new(RtMidiApi::Unspecified, "Test").unwrap();
+        assert_ne!(system.api(), RtMidiApi::Unspecified);
+        assert!(system.input_ports().is_ok());
+        assert!(system.output_ports().is_ok());
+        assert_eq!(
+            system.open_input("No Such Port").err(),
+            Some(RtMidiError::PortNotFound("No Such Port".to_string()))
+        );
+        assert!(system.input("No Such Port").is_none());
+        assert!(!system.close_output("No Such Port"));
+        assert!(system.routes().is_empty());
+    }
+
+    #[test]
+    fn backend() {
+        let loopback = Loopback::default();
+        let system = MidiSystem::new(RtMidiApi::Unspecified, "Test")
+            .unwrap()
+            .backend(loopback.clone());
+        assert!(system
+            .input_ports()
+            .unwrap()
+            .contains(&"Loopback Keys".to_string()));
+        system.route("Loopback Keys", "Loopback Synth").unwrap();
+        assert_eq!(
+            system.routes(),
+            [("Loopback Keys".to_string(), "Loopback Synth".to_string())]
+        );
+        let play = |message: &[u8]| loopback.keys.lock().unwrap().as_ref().unwrap()(0.0, message);
+        play(&[0x90, 60, 100]);
+        assert!(system.unroute("Loopback Keys", "Loopback Synth"));
+        play(&[0x80, 60, 0]);
+        assert_eq!(*loopback.sent.lock().unwrap(), [vec![0x90, 60, 100]]);
+    }
+
+    #[test]
+    fn find_port() {
+        let ports = ["Synth Pro", "Synth", "Keyboard MIDI 1"];
+        assert_eq!(find(&ports, "Synth"), Some(1));
+        assert_eq!(find(&ports, "Keyboard"), Some(2));
+        assert_eq!(find(&ports, "Pro"), Some(0));
+        assert_eq!(find(&ports, "Drums"), None);
+    }
+
+    #[test]
+    fn device_events() {
+        let events = RefCell::new(Vec::new());
+        let old = ["A".to_string(), "B".to_string()];
+        let new = ["B".to_string(), "C".to_string()];
+        changes(PortDirection::Output, &old, &new, &|event| {
+            events.borrow_mut().push(event)
+        });
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                DeviceEvent::Removed {
+                    direction: PortDirection::Output,
+                    name: "A".to_string()
+                },
+                DeviceEvent::Added {
+                    direction: PortDirection::Output,
+                    name: "C".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+/// Tempo map
+///
+/// A sequence of tempo changes at positions in beats, from which the time of any beat can be
+/// found. Each tempo (in beats per minute) applies from its position until the next change;
+/// the first tempo, at beat zero, also applies before it. Used by a [`crate::Scheduler`] (see
+/// [`crate::Scheduler::set_tempo_map`]) to follow ritardandos and tempo automation.
+/// ```
+/// use rtmidi::TempoMap;
+///
+/// // Four beats at 120 BPM, then 60 BPM
+/// let map = TempoMap::new(120.0).change(4.0, 60.0);
+/// assert_eq!(map.seconds_at(4.0), 2.0);
+/// assert_eq!(map.seconds_at(6.0), 4.0);
+/// assert_eq!(map.beat_at(3.0), 5.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    // Sorted by beat, starting at beat zero
+    changes: Vec<(f64, f64)>,
+}
+
+impl TempoMap {
+    /// Create a map with a constant tempo
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tempo` is not greater than zero.
+    pub fn new(tempo: f64) -> Self {
+        check(tempo);
+        TempoMap {
+            changes: vec![(0.0, tempo)],
+        }
+    }
+
+    /// Add a tempo change at a position in beats, replacing any change at the same position
+    ///
+    /// # Panics
+    ///
+    /// Panics if `beat` is negative or `tempo` is not greater than zero.
+    pub fn change(mut self, beat: f64, tempo: f64) -> Self {
+        assert!(beat >= 0.0, "Invalid tempo change position {}", beat);
+        check(tempo);
+        match self
+            .changes
+            .binary_search_by(|(position, _)| position.partial_cmp(&beat).unwrap())
+        {
+            Ok(index) => self.changes[index].1 = tempo,
+            Err(index) => self.changes.insert(index, (beat, tempo)),
+        }
+        self
+    }
+
+    /// Returns the tempo changes, as positions in beats and tempos, in order
+    pub fn changes(&self) -> &[(f64, f64)] {
+        &self.changes
+    }
+
+    /// Returns the tempo at a position in beats
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        self.changes
+            .iter()
+            .rev()
+            .find(|(position, _)| *position <= beat)
+            .unwrap_or(&self.changes[0])
+            .1
+    }
+
+    /// Returns the time of a position in beats, in seconds from beat zero
+    pub fn seconds_at(&self, beat: f64) -> f64 {
+        let mut seconds = 0.0;
+        let (mut position, mut tempo) = (0.0, self.changes[0].1);
+        for &(change, next) in self.changes.iter().filter(|(change, _)| *change > 0.0) {
+            if change >= beat {
+                break;
+            }
+            seconds += (change - position) * 60.0 / tempo;
+            position = change;
+            tempo = next;
+        }
+        seconds + (beat - position) * 60.0 / tempo
+    }
+
+    /// Returns the position in beats at a time in seconds from beat zero
+    pub fn beat_at(&self, seconds: f64) -> f64 {
+        let mut elapsed = 0.0;
+        let (mut position, mut tempo) = (0.0, self.changes[0].1);
+        for &(change, next) in self.changes.iter().filter(|(change, _)| *change > 0.0) {
+            let end = elapsed + (change - position) * 60.0 / tempo;
+            if end >= seconds {
+                break;
+            }
+            elapsed = end;
+            position = change;
+            tempo = next;
+        }
+        position + (seconds - elapsed) * tempo / 60.0
+    }
+}
+
+pub(crate) fn check(tempo: f64) {
+    assert!(tempo > 0.0 && tempo.is_finite(), "Invalid tempo {}", tempo);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TempoMap;
+
+    #[test]
+    fn constant() {
+        let map = TempoMap::new(90.0);
+        assert_eq!(map.seconds_at(3.0), 2.0);
+        assert_eq!(map.beat_at(2.0), 3.0);
+        assert_eq!(map.seconds_at(-1.5), -1.0);
+        assert_eq!(map.beat_at(-1.0), -1.5);
+    }
+
+    #[test]
+    fn changes() {
+        let map = TempoMap::new(120.0)
+            .change(8.0, 240.0)
+            .change(4.0, 60.0)
+            .change(8.0, 30.0);
+        assert_eq!(map.changes(), &[(0.0, 120.0), (4.0, 60.0), (8.0, 30.0)]);
+        assert_eq!(map.tempo_at(-1.0), 120.0);
+        assert_eq!(map.tempo_at(4.0), 60.0);
+        assert_eq!(map.tempo_at(10.0), 30.0);
+        for &(beat, seconds) in &[(2.0, 1.0), (4.0, 2.0), (8.0, 6.0), (9.0, 8.0), (-2.0, -1.0)] {
+            assert_eq!(map.seconds_at(beat), seconds);
+            assert_eq!(map.beat_at(seconds), beat);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::thread::{self, JoinHandle};
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn pthread_self() -> std::os::raw::c_ulong;
+    fn pthread_setname_np(thread: std::os::raw::c_ulong, name: *const c_char) -> c_int;
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn pthread_setname_np(name: *const c_char) -> c_int;
+}
+
+/// Linux limits thread names to 15 bytes
+#[cfg(target_os = "linux")]
+const MAX_NAME_LENGTH: usize = 15;
+#[cfg(not(target_os = "linux"))]
+const MAX_NAME_LENGTH: usize = 63;
+
+/// Spawn a named thread, panicking if it can't be created as [`thread::spawn`] does
+pub fn spawn<F, T>(name: String, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::Builder::new()
+        .name(name)
+        .spawn(f)
+        .expect("failed to spawn thread")
+}
+
+/// Name the current thread as seen by debuggers and profilers, for threads created by RtMidi
+/// rather than the crate. Only Linux and macOS threads can be named this way.
+pub fn set_name(name: &str) {
+    let mut end = name.len().min(MAX_NAME_LENGTH);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    let name = match CString::new(&name[..end]) {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+    #[cfg(target_os = "linux")]
+    unsafe {
+        pthread_setname_np(pthread_self(), name.as_ptr());
+    }
+    #[cfg(target_os = "macos")]
+    unsafe {
+        pthread_setname_np(name.as_ptr());
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    drop(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{set_name, spawn};
+
+    #[test]
+    fn names() {
+        let name = spawn("rtmidi-test".to_string(), || {
+            thread::current().name().map(String::from)
+        });
+        assert_eq!(name.join().unwrap().as_deref(), Some("rtmidi-test"));
+        // Long and multi-byte names are truncated rather than rejected
+        thread::spawn(|| set_name("rtmidi-in:Ünïcödé MIDI Port 1"))
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+/// Bandwidth of a standard 5-pin DIN MIDI connection in bytes per second (31250 baud, 10 bits per
+/// byte)
+pub const DIN_MIDI_BYTES_PER_SECOND: u32 = 3125;
+
+/// Output rate limiter
+///
+/// Models the output as a serial wire of fixed bandwidth. Each message occupies the wire for the
+/// time it takes to transmit its bytes, and later messages must wait for the wire to become free.
+/// System real-time messages (`0xF8`-`0xFF`) may be interleaved with other traffic on a real MIDI
+/// connection, so they never wait, although they still use up bandwidth.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_second: u32,
+    busy_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u32) -> Self {
+        RateLimiter {
+            bytes_per_second: bytes_per_second.max(1),
+            busy_until: None,
+        }
+    }
+
+    /// Returns how long a (non real-time) message must wait before it can be sent
+    pub fn delay(&self, now: Instant) -> Duration {
+        match self.busy_until {
+            Some(busy_until) => busy_until.saturating_duration_since(now),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// Reserve bandwidth for a message, returning how long to wait before sending it
+    pub fn reserve(&mut self, message: &[u8], now: Instant) -> Duration {
+        let delay = if is_realtime(message) {
+            Duration::from_secs(0)
+        } else {
+            self.delay(now)
+        };
+        let start = match self.busy_until {
+            Some(busy_until) if busy_until > now => busy_until,
+            _ => now,
+        };
+        let duration = Duration::from_secs_f64(message.len() as f64 / self.bytes_per_second as f64);
+        self.busy_until = Some(start + duration);
+        delay
+    }
+}
+
+/// Returns [`true`] if the message is a single system real-time byte
+pub fn is_realtime(message: &[u8]) -> bool {
+    matches!(message, [status] if *status >= 0xF8)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{is_realtime, RateLimiter};
+
+    #[test]
+    fn reserve() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1000);
+        assert_eq!(limiter.reserve(&[144, 64, 90], now), Duration::from_secs(0));
+        assert_eq!(
+            limiter.reserve(&[128, 64, 0], now),
+            Duration::from_millis(3)
+        );
+        assert_eq!(limiter.delay(now), Duration::from_millis(6));
+        assert_eq!(
+            limiter.delay(now + Duration::from_millis(10)),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn reserve_realtime() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1000);
+        limiter.reserve(&[0xF0; 100], now);
+        assert_eq!(limiter.reserve(&[0xF8], now), Duration::from_secs(0));
+        assert_eq!(limiter.delay(now), Duration::from_millis(101));
+    }
+
+    #[test]
+    fn realtime() {
+        assert!(is_realtime(&[0xF8]));
+        assert!(is_realtime(&[0xFE]));
+        assert!(!is_realtime(&[0xF7]));
+        assert!(!is_realtime(&[0xF8, 0xF8]));
+        assert!(!is_realtime(&[]));
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How the output thread waits for scheduled messages
+///
+/// Set with [`crate::RtMidiOut::set_timer_strategy`]. Sleeps are only as accurate as the system
+/// timer, which on Windows defaults to a resolution of about 15ms. The other strategies trade CPU
+/// time for accuracy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerStrategy {
+    /// Sleep until each message is due
+    #[default]
+    Sleep,
+    /// Sleep, with the system timer resolution raised to 1ms (with `timeBeginPeriod`) on Windows
+    /// while the output thread is running. This is the same as [`TimerStrategy::Sleep`] on other
+    /// systems, where timers are already precise.
+    HighResolution,
+    /// Sleep as with [`TimerStrategy::HighResolution`] until `spin` before each message is due,
+    /// then busy-wait, for sub-millisecond accuracy at the cost of a CPU core while waiting
+    Hybrid { spin: Duration },
+    /// Hand scheduled messages to the MIDI system up to `lookahead` before they're due, with
+    /// their timestamps, so the driver sends them on time. Only CoreMIDI outputs (on macOS, with
+    /// the `coremidi` feature), ALSA outputs (on Linux, with the `alsa` feature, using a
+    /// sequencer queue) and JACK outputs (with the `jack` feature, placing each message at its
+    /// frame within the audio period) support this, and only without a rate limit or device
+    /// quirks; otherwise this is the same as [`TimerStrategy::HighResolution`]. See
+    /// [`crate::RtMidiOut::supports_native_scheduling`].
+    Native { lookahead: Duration },
+}
+
+impl TimerStrategy {
+    /// Wait for a value from `receiver` until `deadline` (or indefinitely with [`None`])
+    pub fn recv<T>(
+        &self,
+        receiver: &Receiver<T>,
+        deadline: Option<Instant>,
+    ) -> Result<T, RecvTimeoutError> {
+        let deadline = match deadline {
+            Some(deadline) => deadline,
+            None => return receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let spin = match *self {
+            TimerStrategy::Hybrid { spin } => spin,
+            _ => Duration::from_secs(0),
+        };
+        let now = Instant::now();
+        if deadline > now + spin {
+            match receiver.recv_timeout(deadline - now - spin) {
+                Err(RecvTimeoutError::Timeout) if spin.as_nanos() > 0 => {}
+                result => return result,
+            }
+        }
+        loop {
+            match receiver.try_recv() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) if Instant::now() >= deadline => {
+                    return Err(RecvTimeoutError::Timeout)
+                }
+                Err(TryRecvError::Empty) => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Returns how long before they're due scheduled messages may be handed to the MIDI system
+    pub fn lookahead(&self) -> Duration {
+        match *self {
+            TimerStrategy::Native { lookahead } => lookahead,
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// Raise the system timer resolution if required, until the returned guard is dropped
+    pub fn period(&self) -> TimerPeriod {
+        TimerPeriod::new(*self != TimerStrategy::Sleep)
+    }
+}
+
+/// Resolution of the system timer requested with `timeBeginPeriod`, in milliseconds
+#[cfg(windows)]
+const PERIOD: u32 = 1;
+
+#[cfg(windows)]
+#[link(name = "winmm")]
+extern "system" {
+    fn timeBeginPeriod(period: u32) -> u32;
+    fn timeEndPeriod(period: u32) -> u32;
+}
+
+/// Raised system timer resolution, restored when dropped
+pub struct TimerPeriod {
+    #[cfg_attr(not(windows), allow(dead_code))]
+    raised: bool,
+}
+
+impl TimerPeriod {
+    #[cfg(windows)]
+    fn new(raise: bool) -> Self {
+        // Returns TIMERR_NOERROR (0) on success
+        let raised = raise && unsafe { timeBeginPeriod(PERIOD) } == 0;
+        TimerPeriod { raised }
+    }
+
+    #[cfg(not(windows))]
+    fn new(_raise: bool) -> Self {
+        TimerPeriod { raised: false }
+    }
+}
+
+impl Drop for TimerPeriod {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        {
+            if self.raised {
+                unsafe { timeEndPeriod(PERIOD) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::{Duration, Instant};
+
+    use super::TimerStrategy;
+
+    #[test]
+    fn recv() {
+        let (sender, receiver) = mpsc::channel::<()>();
+        for strategy in [
+            TimerStrategy::Sleep,
+            TimerStrategy::HighResolution,
+            TimerStrategy::Hybrid {
+                spin: Duration::from_millis(2),
+            },
+            TimerStrategy::Native {
+                lookahead: Duration::from_millis(50),
+            },
+        ]
+        .iter()
+        {
+            let _period = strategy.period();
+            let deadline = Instant::now() + Duration::from_millis(5);
+            assert_eq!(
+                strategy.recv(&receiver, Some(deadline)),
+                Err(RecvTimeoutError::Timeout)
+            );
+            assert!(Instant::now() >= deadline);
+            sender.send(()).unwrap();
+            assert_eq!(strategy.recv(&receiver, None), Ok(()));
+        }
+    }
+}
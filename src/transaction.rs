@@ -0,0 +1,114 @@
+use std::sync::mpsc;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::error::RtMidiError;
+use crate::midi_in::RtMidiIn;
+use crate::midi_out::RtMidiOut;
+
+/// Default time to wait for a reply
+pub const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Request/reply exchanges of system exclusive messages with a device
+///
+/// Sends requests to a device through an output and waits for its replies on an input. Replies
+/// are taken from the input before its callback or any other subscribers see them. Incoming
+/// messages must be delivered as they arrive, i.e. the input needs a callback (see
+/// [`RtMidiIn::set_callback`]) and system exclusive messages must not be ignored (see
+/// [`RtMidiIn::ignore_types`]).
+/// ```no_run
+/// use rtmidi::{RtMidiIn, RtMidiOut, SysExTransaction};
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// let output = RtMidiOut::new(Default::default()).unwrap();
+/// input.open_port(0, "Editor").unwrap();
+/// input.set_callback(|_timestamp, _message| {}).unwrap();
+/// input.ignore_types(false, true, true).unwrap();
+/// output.open_port(0, "Editor").unwrap();
+///
+/// // Identity Request
+/// let transaction = SysExTransaction::new(&input, &output);
+/// let reply = transaction
+///     .request(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7], |message| {
+///         message.starts_with(&[0xF0, 0x7E]) && message.get(3..5) == Some(&[0x06, 0x02])
+///     })
+///     .unwrap();
+/// ```
+pub struct SysExTransaction<'a> {
+    input: &'a RtMidiIn,
+    output: &'a RtMidiOut,
+    timeout: Duration,
+}
+
+impl<'a> SysExTransaction<'a> {
+    pub fn new(input: &'a RtMidiIn, output: &'a RtMidiOut) -> Self {
+        SysExTransaction {
+            input,
+            output,
+            timeout: DEFAULT_REPLY_TIMEOUT,
+        }
+    }
+
+    /// Set the time to wait for a reply (default [`DEFAULT_REPLY_TIMEOUT`])
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send a message that has no reply
+    pub fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        self.output.message(message)
+    }
+
+    /// Send a request and return the first incoming message for which `is_reply` returns
+    /// [`true`], or [`RtMidiError::Timeout`] if none is received in time
+    pub fn request<F>(&self, request: &[u8], is_reply: F) -> Result<Vec<u8>, RtMidiError>
+    where
+        F: Fn(&[u8]) -> bool + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let sender = Mutex::new(Some(sender));
+        let _subscription =
+            self.input
+                .subscribe_with_priority(i32::MAX, move |_timestamp, message| {
+                    if !is_reply(message) {
+                        return false;
+                    }
+                    // Only the first reply is returned, later ones are passed on
+                    match lock(&sender).take() {
+                        Some(sender) => sender.send(message.to_vec()).is_ok(),
+                        None => false,
+                    }
+                });
+        self.output.message(request)?;
+        receiver
+            .recv_timeout(self.timeout)
+            .map_err(|_| RtMidiError::Timeout)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::SysExTransaction;
+    use crate::error::RtMidiError;
+    use crate::midi_in::RtMidiIn;
+    use crate::midi_out::RtMidiOut;
+
+    #[test]
+    fn timeout() {
+        let input = RtMidiIn::new(Default::default()).unwrap();
+        let output = RtMidiOut::new(Default::default()).unwrap();
+        let start = Instant::now();
+        let result = SysExTransaction::new(&input, &output)
+            .timeout(Duration::from_millis(10))
+            .request(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7], |_message| true);
+        assert_eq!(result, Err(RtMidiError::Timeout));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,132 @@
+//! Message transforms
+//!
+//! A [`Transform`] rewrites a stream of MIDI messages: it may pass messages through, change them,
+//! drop them or produce new ones, immediately or later in time. Transforms can be chained with a
+//! [`Pipeline`], and added to an output with [`crate::RtMidiOut::add_transform`], in which case
+//! they run on the output's internal timing thread.
+
+use std::time::Instant;
+
+mod chord;
+mod note_off;
+mod remap;
+mod scale;
+mod smooth;
+mod sustain;
+mod thin;
+
+pub use chord::{Chord, Voicing};
+pub use note_off::{NoteOffStyle, NoteOffs};
+pub use remap::{Curve, Destination, Mapping, Remap};
+pub use scale::{Scale, ScaleMode, ScaleQuantize};
+pub use smooth::Smoother;
+pub use sustain::Sustain;
+pub use thin::Thin;
+
+/// A message transform
+pub trait Transform: Send {
+    /// Process a message received at `now`, passing any resulting messages to `emit`
+    fn process(&mut self, now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8]));
+
+    /// Produce any messages due at `now`, returning when the transform next needs polling (or
+    /// [`None`] if it has nothing pending). Transforms that only act on incoming messages don't
+    /// need to implement this.
+    fn poll(&mut self, _now: Instant, _emit: &mut dyn FnMut(&[u8])) -> Option<Instant> {
+        None
+    }
+}
+
+/// A chain of transforms, each feeding the next
+///
+/// When not attached to an output the owner is responsible for calling [`Pipeline::poll`] at
+/// (or after) the returned time for transforms that produce delayed messages.
+#[derive(Default)]
+pub struct Pipeline {
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl Pipeline {
+    /// Append a transform to the end of the chain
+    pub fn push<T: Transform + 'static>(&mut self, transform: T) {
+        self.transforms.push(Box::new(transform))
+    }
+
+    /// Append a boxed transform to the end of the chain
+    pub fn push_boxed(&mut self, transform: Box<dyn Transform>) {
+        self.transforms.push(transform)
+    }
+
+    /// Remove all transforms
+    pub fn clear(&mut self) {
+        self.transforms.clear()
+    }
+
+    /// Returns [`true`] if there are no transforms, in which case messages pass straight through
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Pass a message through the chain
+    pub fn process(&mut self, now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        process(&mut self.transforms, now, message, emit)
+    }
+
+    /// Poll every transform in the chain, returning the earliest time any needs polling again
+    pub fn poll(&mut self, now: Instant, emit: &mut dyn FnMut(&[u8])) -> Option<Instant> {
+        let mut next: Option<Instant> = None;
+        for index in 0..self.transforms.len() {
+            let (head, rest) = self.transforms.split_at_mut(index + 1);
+            let at = head[index].poll(now, &mut |message| process(rest, now, message, emit));
+            next = match (next, at) {
+                (Some(next), Some(at)) => Some(next.min(at)),
+                (next, at) => next.or(at),
+            };
+        }
+        next
+    }
+}
+
+fn process(
+    transforms: &mut [Box<dyn Transform>],
+    now: Instant,
+    message: &[u8],
+    emit: &mut dyn FnMut(&[u8]),
+) {
+    match transforms.split_first_mut() {
+        Some((first, rest)) => first.process(now, message, &mut |message| {
+            process(rest, now, message, emit)
+        }),
+        None => emit(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Pipeline, Transform};
+
+    struct Transpose(u8);
+
+    impl Transform for Transpose {
+        fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+            emit(&[message[0], message[1] + self.0, message[2]])
+        }
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut pipeline = Pipeline::default();
+        let mut output = Vec::new();
+        pipeline.process(Instant::now(), &[144, 60, 90], &mut |m| {
+            output.push(m.to_vec())
+        });
+        pipeline.push(Transpose(12));
+        pipeline.push(Transpose(7));
+        pipeline.process(Instant::now(), &[144, 60, 90], &mut |m| {
+            output.push(m.to_vec())
+        });
+        assert_eq!(output, vec![vec![144, 60, 90], vec![144, 79, 90]]);
+        assert_eq!(pipeline.poll(Instant::now(), &mut |_| {}), None);
+    }
+}
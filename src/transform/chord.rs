@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::Transform;
+
+/// How the notes of a [`Chord`] are arranged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Voicing {
+    /// The intervals as given
+    #[default]
+    Close,
+    /// The second-highest note moved down an octave
+    Drop2,
+    /// Every second note from the bottom (the second, fourth, ...) moved up an octave
+    Spread,
+}
+
+/// Chord generator (harmonizer)
+///
+/// Expands each note into a chord of notes at intervals from it, each with its velocity scaled.
+/// Note-offs end every note of the chord the note-on started, even if the settings have changed
+/// since. A note shared by two sounding chords is only ended once both have ended. Notes that
+/// would fall outside the MIDI note range are left out, and other messages pass through.
+/// ```
+/// use rtmidi::transform::{Chord, Voicing};
+///
+/// // Major triads with a quieter fifth, spread over two octaves
+/// let chord = Chord::new(&[0, 4])
+///     .interval(7, 0.8)
+///     .voicing(Voicing::Spread);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chord {
+    // Semitones from the played note and velocity scale of each note
+    intervals: Vec<(i8, f64)>,
+    voicing: Voicing,
+    // Notes started by each played note, keyed by channel and note
+    chords: HashMap<(u8, u8), Vec<u8>>,
+    // Number of sounding chords containing each note, keyed by channel and note
+    sounding: HashMap<(u8, u8), usize>,
+}
+
+impl Chord {
+    /// Create a chord of notes at intervals in semitones from the played note (0 for the note
+    /// itself), at its velocity
+    pub fn new(intervals: &[i8]) -> Self {
+        Chord {
+            intervals: intervals.iter().map(|&interval| (interval, 1.0)).collect(),
+            voicing: Voicing::Close,
+            chords: HashMap::new(),
+            sounding: HashMap::new(),
+        }
+    }
+
+    /// Add a note at an interval, with the velocity of the played note scaled by `velocity`
+    pub fn interval(mut self, semitones: i8, velocity: f64) -> Self {
+        self.intervals.push((semitones, velocity));
+        self
+    }
+
+    /// Set how the notes are arranged (default [`Voicing::Close`])
+    pub fn voicing(mut self, voicing: Voicing) -> Self {
+        self.voicing = voicing;
+        self
+    }
+
+    /// Returns the notes and velocities of the chord for a played note, lowest first
+    fn notes(&self, note: u8, velocity: u8) -> Vec<(u8, u8)> {
+        let mut offsets = self.intervals.clone();
+        offsets.sort_by_key(|&(interval, _)| interval);
+        match self.voicing {
+            Voicing::Close => {}
+            Voicing::Drop2 => {
+                if offsets.len() >= 2 {
+                    let index = offsets.len() - 2;
+                    offsets[index].0 -= 12;
+                }
+            }
+            Voicing::Spread => {
+                for offset in offsets.iter_mut().skip(1).step_by(2) {
+                    offset.0 += 12;
+                }
+            }
+        }
+        offsets.sort_by_key(|&(interval, _)| interval);
+        let mut notes: Vec<(u8, u8)> = Vec::new();
+        for (interval, scale) in offsets {
+            let number = i16::from(note) + i16::from(interval);
+            if !(0..=127).contains(&number) || notes.iter().any(|&(n, _)| i16::from(n) == number) {
+                continue;
+            }
+            let velocity = (f64::from(velocity) * scale).round().clamp(1.0, 127.0) as u8;
+            notes.push((number as u8, velocity));
+        }
+        notes
+    }
+
+    /// End the chord started by a note, if it is sounding
+    fn release(
+        &mut self,
+        channel: u8,
+        note: u8,
+        status: u8,
+        velocity: u8,
+        emit: &mut dyn FnMut(&[u8]),
+    ) {
+        for number in self.chords.remove(&(channel, note)).unwrap_or_default() {
+            let key = (channel, number);
+            match self.sounding.get_mut(&key) {
+                Some(count) if *count > 1 => *count -= 1,
+                _ => {
+                    self.sounding.remove(&key);
+                    emit(&[status, number, velocity]);
+                }
+            }
+        }
+    }
+}
+
+impl Transform for Chord {
+    fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (status, note, velocity) = match *message {
+            [status, note, velocity] if status & 0xE0 == 0x80 => (status, note & 0x7F, velocity),
+            _ => return emit(message),
+        };
+        let channel = status & 0x0F;
+        if status & 0xF0 == 0x90 && velocity > 0 {
+            // A note struck again while sounding ends its previous chord first
+            self.release(channel, note, 0x80 | channel, 64, emit);
+            let notes = self.notes(note, velocity);
+            for &(number, velocity) in &notes {
+                *self.sounding.entry((channel, number)).or_default() += 1;
+                emit(&[status, number, velocity]);
+            }
+            self.chords
+                .insert((channel, note), notes.iter().map(|&(n, _)| n).collect());
+        } else {
+            self.release(channel, note, status, velocity, emit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Chord, Voicing};
+    use crate::transform::Transform;
+
+    fn run(chord: &mut Chord, input: &[&[u8]]) -> Vec<Vec<u8>> {
+        let mut output = Vec::new();
+        for message in input {
+            chord.process(Instant::now(), message, &mut |m| output.push(m.to_vec()));
+        }
+        output
+    }
+
+    #[test]
+    fn voicing() {
+        let chord = Chord::new(&[0, 4, 7, 10]);
+        let notes = |voicing| {
+            let chord = chord.clone().voicing(voicing);
+            chord
+                .notes(60, 100)
+                .into_iter()
+                .map(|(note, _)| note)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(notes(Voicing::Close), [60, 64, 67, 70]);
+        assert_eq!(notes(Voicing::Drop2), [55, 60, 64, 70]);
+        assert_eq!(notes(Voicing::Spread), [60, 67, 76, 82]);
+        // Out of range notes are left out, and velocities scaled
+        let chord = Chord::new(&[0]).interval(12, 0.5).interval(-12, 2.0);
+        assert_eq!(chord.notes(120, 100), [(108, 127), (120, 100)]);
+    }
+
+    #[test]
+    fn note_offs() {
+        let mut chord = Chord::new(&[0, 4, 7]);
+        let output = run(
+            &mut chord,
+            &[&[0x90, 60, 100], &[0x90, 64, 100], &[0xB0, 7, 100]],
+        );
+        assert_eq!(output.len(), 7);
+        // Changing the chord doesn't affect the notes already sounding
+        chord = chord.interval(12, 1.0);
+        let output = run(&mut chord, &[&[0x80, 60, 0], &[0x90, 64, 0]]);
+        assert_eq!(
+            output,
+            vec![
+                // E is still held by the second chord
+                vec![0x80, 60, 0],
+                vec![0x80, 67, 0],
+                vec![0x90, 64, 0],
+                vec![0x90, 68, 0],
+                vec![0x90, 71, 0],
+            ]
+        );
+    }
+}
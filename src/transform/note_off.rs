@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use super::Transform;
+
+/// How a [`NoteOffs`] transform represents the end of a note
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteOffStyle {
+    /// Note-off messages, with note-ons of zero velocity given a release velocity of 64
+    #[default]
+    NoteOff,
+    /// Note-ons with zero velocity, which devices using running status prefer as a run of notes
+    /// then shares one status byte. The release velocity is lost.
+    NoteOnZero,
+}
+
+/// Note-off normalization transform
+///
+/// Converts every note ending to a single representation (see [`NoteOffStyle`]), so that later
+/// transforms and devices only ever handle one. Other messages pass through.
+/// ```
+/// use rtmidi::transform::{NoteOffStyle, NoteOffs};
+///
+/// let note_offs = NoteOffs::new(NoteOffStyle::NoteOnZero);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoteOffs {
+    style: NoteOffStyle,
+}
+
+impl NoteOffs {
+    pub fn new(style: NoteOffStyle) -> Self {
+        NoteOffs { style }
+    }
+}
+
+impl Transform for NoteOffs {
+    fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        match *message {
+            [status, note, 0] if status & 0xF0 == 0x90 && self.style == NoteOffStyle::NoteOff => {
+                emit(&[0x80 | status & 0x0F, note, 64])
+            }
+            [status, note, _]
+                if status & 0xF0 == 0x80 && self.style == NoteOffStyle::NoteOnZero =>
+            {
+                emit(&[0x90 | status & 0x0F, note, 0])
+            }
+            _ => emit(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{NoteOffStyle, NoteOffs};
+    use crate::transform::Transform;
+
+    fn run(style: NoteOffStyle, input: &[&[u8]]) -> Vec<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut note_offs = NoteOffs::new(style);
+        for message in input {
+            note_offs.process(Instant::now(), message, &mut |m| output.push(m.to_vec()));
+        }
+        output
+    }
+
+    #[test]
+    fn note_offs() {
+        let input: &[&[u8]] = &[
+            &[0x92, 60, 0],
+            &[0x82, 61, 30],
+            &[0x92, 62, 1],
+            &[0xB2, 7, 0],
+        ];
+        assert_eq!(
+            run(NoteOffStyle::NoteOff, input),
+            vec![
+                vec![0x82, 60, 64],
+                vec![0x82, 61, 30],
+                vec![0x92, 62, 1],
+                vec![0xB2, 7, 0],
+            ]
+        );
+        assert_eq!(
+            run(NoteOffStyle::NoteOnZero, input),
+            vec![
+                vec![0x92, 60, 0],
+                vec![0x92, 61, 0],
+                vec![0x92, 62, 1],
+                vec![0xB2, 7, 0],
+            ]
+        );
+    }
+}
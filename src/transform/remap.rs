@@ -0,0 +1,189 @@
+use std::time::Instant;
+
+use super::Transform;
+use crate::value;
+
+/// Response curve of a controller [`Mapping`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// The value (from 0.0 to 1.0) raised to a power: above 1.0 gives finer control at the
+    /// bottom of the range, below 1.0 at the top
+    Power(f64),
+    /// Finer control at both ends of the range (smoothstep)
+    SCurve,
+}
+
+impl Curve {
+    fn apply(self, x: f64) -> f64 {
+        match self {
+            Curve::Linear => x,
+            Curve::Power(exponent) => x.powf(exponent),
+            Curve::SCurve => x * x * (3.0 - 2.0 * x),
+        }
+    }
+}
+
+/// Message a controller is mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    PitchBend,
+    ChannelPressure,
+    Controller(u8),
+}
+
+/// Mapping of a controller to a [`Destination`]
+///
+/// The controller's value is converted to a position from 0.0 to 1.0, shaped by the curve, then
+/// scaled to the output range (by default the whole range of the destination). Values are scaled
+/// about the centre, so a controller at its centre (64) gives a centred pitch bend (8192).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mapping {
+    controller: u8,
+    destination: Destination,
+    curve: Curve,
+    range: (f64, f64),
+}
+
+impl Mapping {
+    /// Map a controller number to a destination
+    pub fn new(controller: u8, destination: Destination) -> Self {
+        Mapping {
+            controller: controller & 0x7F,
+            destination,
+            curve: Curve::Linear,
+            range: (0.0, 1.0),
+        }
+    }
+
+    /// Set the response curve (default [`Curve::Linear`])
+    pub fn curve(mut self, curve: Curve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Set the output range, as fractions of the destination's range (default 0.0 to 1.0). A
+    /// minimum above the maximum inverts the controller.
+    pub fn range(mut self, minimum: f64, maximum: f64) -> Self {
+        self.range = (minimum.clamp(0.0, 1.0), maximum.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Returns the message for a value of the controller
+    fn message(&self, channel: u8, value: u8) -> Vec<u8> {
+        // Positions run from 0.0 to 1.0, with the centre at 0.5
+        let x = self
+            .curve
+            .apply((value::from_7bit_centred(value & 0x7F) + 1.0) / 2.0)
+            .clamp(0.0, 1.0);
+        let (minimum, maximum) = self.range;
+        let y = (minimum + (maximum - minimum) * x) * 2.0 - 1.0;
+        match self.destination {
+            Destination::PitchBend => {
+                let value = value::to_pitch_bend(y);
+                vec![0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8]
+            }
+            Destination::ChannelPressure => vec![0xD0 | channel, value::to_7bit_centred(y)],
+            Destination::Controller(controller) => {
+                vec![0xB0 | channel, controller & 0x7F, value::to_7bit_centred(y)]
+            }
+        }
+    }
+}
+
+/// Controller remapping transform
+///
+/// Converts Control Change messages for selected controllers into pitch bend, channel pressure
+/// or other controllers (see [`Mapping`]), so controllers with fixed assignments can drive any
+/// destination. A controller can be mapped to several destinations. Messages are sent on the
+/// channel they were received on, and other messages pass through.
+/// ```
+/// use rtmidi::transform::{Curve, Destination, Mapping, Remap};
+///
+/// // Pitch bend from the modulation wheel, and filter cutoff from an expression pedal
+/// let remap = Remap::new()
+///     .map(Mapping::new(1, Destination::PitchBend))
+///     .map(Mapping::new(11, Destination::Controller(74)).curve(Curve::Power(2.0)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Remap {
+    mappings: Vec<Mapping>,
+}
+
+impl Remap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a mapping
+    pub fn map(mut self, mapping: Mapping) -> Self {
+        self.mappings.push(mapping);
+        self
+    }
+}
+
+impl Transform for Remap {
+    fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (channel, controller, value) = match *message {
+            [status, controller, value] if status & 0xF0 == 0xB0 => {
+                (status & 0x0F, controller, value)
+            }
+            _ => return emit(message),
+        };
+        let mut mapped = false;
+        for mapping in &self.mappings {
+            if mapping.controller == controller {
+                emit(&mapping.message(channel, value));
+                mapped = true;
+            }
+        }
+        if !mapped {
+            emit(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Curve, Destination, Mapping, Remap};
+    use crate::transform::Transform;
+
+    #[test]
+    fn mapping() {
+        let bend = Mapping::new(1, Destination::PitchBend);
+        assert_eq!(bend.message(2, 0), [0xE2, 0, 0]);
+        assert_eq!(bend.message(2, 64), [0xE2, 0, 64]);
+        assert_eq!(bend.message(2, 127), [0xE2, 0x7F, 0x7F]);
+        let pressure = Mapping::new(1, Destination::ChannelPressure).range(1.0, 0.5);
+        assert_eq!(pressure.message(0, 0), [0xD0, 127]);
+        assert_eq!(pressure.message(0, 127), [0xD0, 64]);
+        let curved = Mapping::new(1, Destination::Controller(7)).curve(Curve::Power(2.0));
+        assert_eq!(curved.message(0, 64), [0xB0, 7, 32]);
+        assert_eq!(curved.message(0, 127), [0xB0, 7, 127]);
+        let s = Mapping::new(1, Destination::Controller(7)).curve(Curve::SCurve);
+        assert_eq!(s.message(0, 64), [0xB0, 7, 64]);
+    }
+
+    #[test]
+    fn remap() {
+        let mut remap = Remap::new()
+            .map(Mapping::new(1, Destination::PitchBend))
+            .map(Mapping::new(1, Destination::Controller(74)));
+        let mut output = Vec::new();
+        for message in &[&[0xB3, 1, 0][..], &[0xB3, 7, 100], &[0x93, 60, 100]] {
+            remap.process(Instant::now(), message, &mut |m| output.push(m.to_vec()));
+        }
+        assert_eq!(
+            output,
+            vec![
+                vec![0xE3, 0, 0],
+                vec![0xB3, 74, 0],
+                vec![0xB3, 7, 100],
+                vec![0x93, 60, 100],
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use super::Transform;
+
+/// A musical scale in a key
+///
+/// A set of pitch classes, given as a root (0 for C to 11 for B) and the degrees of the scale in
+/// semitones above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    // Bit n is set if pitch class n is in the scale
+    pitch_classes: u16,
+}
+
+impl Scale {
+    /// Create a scale from a root (0 for C to 11 for B) and degrees in semitones above it
+    pub fn new(root: u8, degrees: &[u8]) -> Self {
+        let pitch_classes = degrees.iter().fold(0, |classes, &degree| {
+            classes | 1 << ((u16::from(root) + u16::from(degree)) % 12)
+        });
+        Scale { pitch_classes }
+    }
+
+    /// Major (Ionian) scale
+    pub fn major(root: u8) -> Self {
+        Scale::new(root, &[0, 2, 4, 5, 7, 9, 11])
+    }
+
+    /// Natural minor (Aeolian) scale
+    pub fn minor(root: u8) -> Self {
+        Scale::new(root, &[0, 2, 3, 5, 7, 8, 10])
+    }
+
+    /// Major pentatonic scale
+    pub fn pentatonic(root: u8) -> Self {
+        Scale::new(root, &[0, 2, 4, 7, 9])
+    }
+
+    /// Returns [`true`] if a note is in the scale
+    pub fn contains(&self, note: u8) -> bool {
+        self.pitch_classes & 1 << (note % 12) != 0
+    }
+
+    /// Returns the note in the scale nearest a note, preferring the lower of two equally near
+    /// notes, or [`None`] if the scale is empty
+    pub fn nearest(&self, note: u8) -> Option<u8> {
+        (0..12u8).find_map(|distance| {
+            let below = note.checked_sub(distance).filter(|&n| self.contains(n));
+            let above =
+                Some(note.saturating_add(distance)).filter(|&n| n <= 127 && self.contains(n));
+            below.or(above)
+        })
+    }
+}
+
+/// How notes outside the scale are handled by a [`ScaleQuantize`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Moved to the nearest note in the scale
+    #[default]
+    Nearest,
+    /// Dropped
+    Block,
+}
+
+#[derive(Debug)]
+struct State {
+    scale: Scale,
+    mode: ScaleMode,
+    // Note played for each sounding note (or None if it was blocked), keyed by channel and note
+    notes: HashMap<(u8, u8), Option<u8>>,
+    // Number of sounding notes played as each note, keyed by channel and note
+    sounding: HashMap<(u8, u8), usize>,
+}
+
+impl State {
+    fn quantize(&self, note: u8) -> Option<u8> {
+        match self.mode {
+            ScaleMode::Nearest => self.scale.nearest(note),
+            ScaleMode::Block => Some(note).filter(|&note| self.scale.contains(note)),
+        }
+    }
+}
+
+/// Scale quantization transform
+///
+/// Constrains notes to a scale, either moving notes outside it to the nearest note in the scale
+/// or dropping them (see [`ScaleMode`]). Note-offs and polyphonic key pressure follow the note
+/// their note-on was moved to, even if the scale has changed since, so notes are never left
+/// hanging. A note played for two sounding notes is only ended once both have ended.
+///
+/// Clones share the same settings and notes, so the scale can be changed (e.g. following a chord
+/// progression) after the transform has been added to an output.
+/// ```
+/// use rtmidi::transform::{Scale, ScaleMode, ScaleQuantize};
+/// use rtmidi::RtMidiOut;
+///
+/// let output = RtMidiOut::new(Default::default()).unwrap();
+/// let quantize = ScaleQuantize::new(Scale::major(0), ScaleMode::Nearest);
+/// output.add_transform(quantize.clone()).unwrap();
+/// // Later, modulate to A minor
+/// quantize.set_scale(Scale::minor(9));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScaleQuantize(Arc<Mutex<State>>);
+
+impl ScaleQuantize {
+    pub fn new(scale: Scale, mode: ScaleMode) -> Self {
+        ScaleQuantize(Arc::new(Mutex::new(State {
+            scale,
+            mode,
+            notes: HashMap::new(),
+            sounding: HashMap::new(),
+        })))
+    }
+
+    /// Change the scale applied to new notes
+    pub fn set_scale(&self, scale: Scale) {
+        self.lock().scale = scale;
+    }
+
+    pub fn scale(&self) -> Scale {
+        self.lock().scale
+    }
+
+    /// Change how new notes outside the scale are handled
+    pub fn set_mode(&self, mode: ScaleMode) {
+        self.lock().mode = mode;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Transform for ScaleQuantize {
+    fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (status, note, data) = match *message {
+            [status, note, data] if (0x80..0xB0).contains(&status) => (status, note & 0x7F, data),
+            _ => return emit(message),
+        };
+        let channel = status & 0x0F;
+        let mut state = self.lock();
+        let state = &mut *state;
+        match status & 0xF0 {
+            0x90 if data > 0 => {
+                // A note struck again while sounding is ended first
+                if let Some(Some(previous)) = state.notes.remove(&(channel, note)) {
+                    if release(&mut state.sounding, channel, previous) {
+                        emit(&[0x80 | channel, previous, 64]);
+                    }
+                }
+                let played = state.quantize(note);
+                state.notes.insert((channel, note), played);
+                if let Some(played) = played {
+                    *state.sounding.entry((channel, played)).or_default() += 1;
+                    emit(&[status, played, data]);
+                }
+            }
+            0xA0 => match state.notes.get(&(channel, note)) {
+                Some(Some(played)) => emit(&[status, *played, data]),
+                Some(None) => {}
+                None => emit(message),
+            },
+            // Note-off (or note-on with zero velocity)
+            _ => match state.notes.remove(&(channel, note)) {
+                Some(Some(played)) => {
+                    if release(&mut state.sounding, channel, played) {
+                        emit(&[status, played, data]);
+                    }
+                }
+                Some(None) => {}
+                None => emit(message),
+            },
+        }
+    }
+}
+
+/// Mark a played note as ended, returning [`true`] if no other sounding note is played as it
+fn release(sounding: &mut HashMap<(u8, u8), usize>, channel: u8, note: u8) -> bool {
+    match sounding.get_mut(&(channel, note)) {
+        Some(count) if *count > 1 => {
+            *count -= 1;
+            false
+        }
+        _ => {
+            sounding.remove(&(channel, note));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Scale, ScaleMode, ScaleQuantize};
+    use crate::transform::Transform;
+
+    #[test]
+    fn scale() {
+        let scale = Scale::major(2);
+        assert!(scale.contains(62));
+        assert!(scale.contains(66));
+        assert!(!scale.contains(65));
+        assert_eq!(scale.nearest(65), Some(64));
+        assert_eq!(Scale::pentatonic(0).nearest(65), Some(64));
+        assert_eq!(Scale::pentatonic(0).nearest(66), Some(67));
+        assert_eq!(Scale::new(0, &[11]).nearest(0), Some(11));
+        assert_eq!(Scale::new(0, &[]).nearest(60), None);
+    }
+
+    #[test]
+    fn note_offs() {
+        let mut quantize = ScaleQuantize::new(Scale::major(0), ScaleMode::Nearest);
+        let control = quantize.clone();
+        let mut output = Vec::new();
+        let mut play = |quantize: &mut ScaleQuantize, message: &[u8]| {
+            quantize.process(Instant::now(), message, &mut |m| output.push(m.to_vec()))
+        };
+        play(&mut quantize, &[0x90, 61, 100]);
+        play(&mut quantize, &[0x90, 60, 100]);
+        control.set_scale(Scale::major(1));
+        control.set_mode(ScaleMode::Block);
+        play(&mut quantize, &[0xA0, 61, 50]);
+        play(&mut quantize, &[0x80, 61, 0]);
+        play(&mut quantize, &[0x90, 62, 100]);
+        play(&mut quantize, &[0x80, 62, 0]);
+        play(&mut quantize, &[0x80, 60, 0]);
+        assert_eq!(
+            output,
+            vec![
+                vec![0x90, 60, 100],
+                vec![0x90, 60, 100],
+                vec![0xA0, 60, 50],
+                // Both notes were played as C, which ends with the second
+                vec![0x80, 60, 0],
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::Transform;
+
+/// Controller value smoothing transform
+///
+/// Slews abrupt jumps in control change and pitch bend values into a ramp of intermediate values,
+/// useful when mapping coarse hardware knobs to sensitive parameters. The rate is given as the
+/// time taken to sweep the full range of a controller, and intermediate values are emitted at a
+/// fixed interval (10ms by default) until the target is reached.
+///
+/// By default continuous controllers 0-63 and pitch bend are smoothed; all other messages pass
+/// straight through. The first value seen for each controller is passed through unchanged.
+pub struct Smoother {
+    full_scale: Duration,
+    interval: Duration,
+    controllers: [bool; 128],
+    pitch_bend: bool,
+    ramps: HashMap<(u8, u8), Ramp>,
+}
+
+struct Ramp {
+    current: f64,
+    target: f64,
+    maximum: f64,
+    updated: Instant,
+    sent: u16,
+}
+
+impl Smoother {
+    /// Create a smoother that takes `full_scale` to sweep a controller's entire range
+    pub fn new(full_scale: Duration) -> Self {
+        let mut controllers = [false; 128];
+        for controller in controllers.iter_mut().take(64) {
+            *controller = true;
+        }
+        Smoother {
+            full_scale,
+            interval: Duration::from_millis(10),
+            controllers,
+            pitch_bend: true,
+            ramps: HashMap::new(),
+        }
+    }
+
+    /// Set the interval between intermediate values
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Smooth only the given controller numbers
+    pub fn controllers<I: IntoIterator<Item = u8>>(mut self, controllers: I) -> Self {
+        self.controllers = [false; 128];
+        for controller in controllers {
+            self.controllers[(controller & 0x7F) as usize] = true;
+        }
+        self
+    }
+
+    /// Enable or disable pitch bend smoothing
+    pub fn pitch_bend(mut self, enabled: bool) -> Self {
+        self.pitch_bend = enabled;
+        self
+    }
+
+    fn advance(&self, ramp: &mut Ramp, now: Instant) {
+        let elapsed = now.saturating_duration_since(ramp.updated).as_secs_f64();
+        let full_scale = self.full_scale.as_secs_f64();
+        let step = if full_scale > 0.0 {
+            ramp.maximum * elapsed / full_scale
+        } else {
+            ramp.maximum
+        };
+        ramp.current = if ramp.current < ramp.target {
+            (ramp.current + step).min(ramp.target)
+        } else {
+            (ramp.current - step).max(ramp.target)
+        };
+        ramp.updated = now;
+    }
+}
+
+impl Transform for Smoother {
+    fn process(&mut self, now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (key, value, maximum) = match *message {
+            [status, controller, value]
+                if status & 0xF0 == 0xB0 && self.controllers[(controller & 0x7F) as usize] =>
+            {
+                ((status, controller), value as u16, 127.0)
+            }
+            [status, lsb, msb] if status & 0xF0 == 0xE0 && self.pitch_bend => {
+                ((status, 0), (msb as u16) << 7 | lsb as u16, 16383.0)
+            }
+            _ => return emit(message),
+        };
+        if let Some(mut ramp) = self.ramps.remove(&key) {
+            self.advance(&mut ramp, now);
+            ramp.target = value as f64;
+            self.ramps.insert(key, ramp);
+        } else {
+            self.ramps.insert(
+                key,
+                Ramp {
+                    current: value as f64,
+                    target: value as f64,
+                    maximum,
+                    updated: now,
+                    sent: value,
+                },
+            );
+            emit(message);
+        }
+    }
+
+    fn poll(&mut self, now: Instant, emit: &mut dyn FnMut(&[u8])) -> Option<Instant> {
+        let mut moving = false;
+        let mut ramps = std::mem::take(&mut self.ramps);
+        for (&(status, controller), ramp) in ramps.iter_mut() {
+            if ramp.current == ramp.target && ramp.sent == ramp.target as u16 {
+                continue;
+            }
+            self.advance(ramp, now);
+            let value = ramp.current.round() as u16;
+            if value != ramp.sent {
+                ramp.sent = value;
+                if status & 0xF0 == 0xE0 {
+                    emit(&[status, (value & 0x7F) as u8, (value >> 7) as u8]);
+                } else {
+                    emit(&[status, controller, value as u8]);
+                }
+            }
+            moving |= ramp.current != ramp.target;
+        }
+        self.ramps = ramps;
+        if moving {
+            Some(now + self.interval)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Smoother;
+    use crate::transform::Transform;
+
+    #[test]
+    fn smooth() {
+        let now = Instant::now();
+        let mut smoother = Smoother::new(Duration::from_millis(1270));
+        let mut output = Vec::new();
+        let mut emit = |message: &[u8]| output.push(message.to_vec());
+
+        smoother.process(now, &[0xB0, 7, 0], &mut emit);
+        smoother.process(now, &[0xB0, 7, 100], &mut emit);
+        smoother.process(now, &[0x90, 60, 90], &mut emit);
+        assert_eq!(
+            smoother.poll(now, &mut emit),
+            Some(now + Duration::from_millis(10))
+        );
+
+        let later = now + Duration::from_millis(100);
+        assert_eq!(
+            smoother.poll(later, &mut emit),
+            Some(later + Duration::from_millis(10))
+        );
+        let end = now + Duration::from_secs(2);
+        assert_eq!(smoother.poll(end, &mut emit), None);
+        assert_eq!(
+            output,
+            vec![
+                vec![0xB0, 7, 0],
+                vec![0x90, 60, 90],
+                vec![0xB0, 7, 10],
+                vec![0xB0, 7, 100]
+            ]
+        );
+    }
+
+    #[test]
+    fn passthrough() {
+        let now = Instant::now();
+        let mut smoother = Smoother::new(Duration::from_secs(1)).controllers(vec![1]);
+        let mut output = Vec::new();
+        smoother.process(now, &[0xB0, 7, 0], &mut |m| output.push(m.to_vec()));
+        smoother.process(now, &[0xB0, 7, 100], &mut |m| output.push(m.to_vec()));
+        assert_eq!(output, vec![vec![0xB0, 7, 0], vec![0xB0, 7, 100]]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+use super::Transform;
+
+/// Sustain pedal controller number
+const SUSTAIN: u8 = 64;
+
+/// Sustain pedal to note length conversion
+///
+/// Absorbs sustain pedal messages (CC64) and instead holds back note-offs while the pedal is
+/// down, sending them when it is released. This produces a pedal-free note stream for sound
+/// engines or hardware that ignore the sustain controller. A note struck again while being
+/// sustained is ended first so note-ons and note-offs stay paired.
+#[derive(Debug, Default)]
+pub struct Sustain {
+    channels: [Channel; 16],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    pedal: bool,
+    // Release velocity of each note whose note-off is being held back
+    held: [Option<u8>; 128],
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Channel {
+            pedal: false,
+            held: [None; 128],
+        }
+    }
+}
+
+impl Transform for Sustain {
+    fn process(&mut self, _now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (status, data1, data2) = match *message {
+            [status, data1, data2] if (0x80..0xF0).contains(&status) => (status, data1, data2),
+            _ => return emit(message),
+        };
+        let number = status & 0x0F;
+        let channel = &mut self.channels[number as usize];
+        let note = (data1 & 0x7F) as usize;
+        match status & 0xF0 {
+            0xB0 if data1 == SUSTAIN => {
+                channel.pedal = data2 >= 64;
+                if !channel.pedal {
+                    for (note, held) in channel.held.iter_mut().enumerate() {
+                        if let Some(velocity) = held.take() {
+                            emit(&[0x80 | number, note as u8, velocity]);
+                        }
+                    }
+                }
+            }
+            // Note-off (or note-on with zero velocity)
+            0x80 | 0x90 if status & 0xF0 == 0x80 || data2 == 0 => {
+                if channel.pedal {
+                    channel.held[note] = Some(if status & 0xF0 == 0x80 { data2 } else { 64 });
+                } else {
+                    emit(message);
+                }
+            }
+            0x90 => {
+                if let Some(velocity) = channel.held[note].take() {
+                    emit(&[0x80 | number, data1, velocity]);
+                }
+                emit(message);
+            }
+            _ => emit(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::Sustain;
+    use crate::transform::Transform;
+
+    #[test]
+    fn sustain() {
+        let mut sustain = Sustain::default();
+        let mut output = Vec::new();
+        let input: &[&[u8]] = &[
+            &[0x90, 60, 90],
+            &[0xB0, 64, 127],
+            &[0x80, 60, 40],
+            &[0x91, 62, 90],
+            &[0x91, 62, 0],
+            &[0x90, 64, 90],
+            &[0x90, 64, 0],
+            &[0x90, 64, 80],
+            &[0xB0, 64, 0],
+            &[0x80, 64, 0],
+        ];
+        for message in input {
+            sustain.process(Instant::now(), message, &mut |m| output.push(m.to_vec()));
+        }
+        assert_eq!(
+            output,
+            vec![
+                vec![0x90, 60, 90],
+                vec![0x91, 62, 90],
+                vec![0x91, 62, 0],
+                vec![0x90, 64, 90],
+                vec![0x80, 64, 64],
+                vec![0x90, 64, 80],
+                vec![0x80, 60, 40],
+                vec![0x80, 64, 0],
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::Transform;
+
+/// Controller data thinning transform
+///
+/// Tames high-rate sources such as 14-bit encoders and accelerometers by forwarding a control
+/// change, pitch bend or channel pressure message only once the value has changed by at least
+/// the threshold since the last value forwarded, or once the interval has passed since then.
+/// Values held back are never lost: the latest is forwarded when the interval has passed, so the
+/// final value always arrives.
+///
+/// The threshold is in 7-bit steps, with pitch bend scaled to match (128 pitch bend steps per
+/// step). Controllers 32 to 63, the fine (LSB) halves of 14-bit controllers, pass through
+/// unchanged as receivers combine them with the last coarse value. Other messages also pass
+/// through.
+/// ```
+/// use std::time::Duration;
+/// use rtmidi::transform::Thin;
+///
+/// // At most one message every 20ms, unless the value jumps by 8 or more
+/// let thin = Thin::new(Duration::from_millis(20)).threshold(8);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Thin {
+    interval: Duration,
+    // Threshold in 14-bit steps
+    threshold: Option<u32>,
+    controllers: HashMap<(u8, u8), Controller>,
+}
+
+#[derive(Debug, Clone)]
+struct Controller {
+    value: u32,
+    sent_at: Instant,
+    // Latest message held back
+    pending: Option<(u32, Vec<u8>)>,
+}
+
+impl Thin {
+    /// Create a transform forwarding at most one message per controller every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Thin {
+            interval,
+            threshold: None,
+            controllers: HashMap::new(),
+        }
+    }
+
+    /// Also forward a message as soon as the value has changed by at least `threshold`
+    pub fn threshold(mut self, threshold: u8) -> Self {
+        self.threshold = Some(u32::from(threshold) << 7);
+        self
+    }
+}
+
+impl Transform for Thin {
+    fn process(&mut self, now: Instant, message: &[u8], emit: &mut dyn FnMut(&[u8])) {
+        let (key, value) = match *message {
+            [status, controller, value]
+                if status & 0xF0 == 0xB0 && !(32..64).contains(&controller) =>
+            {
+                ((status, controller), u32::from(value) << 7)
+            }
+            [status, lsb, msb] if status & 0xF0 == 0xE0 => {
+                ((status, 0), u32::from(msb) << 7 | u32::from(lsb))
+            }
+            [status, value] if status & 0xF0 == 0xD0 => ((status, 0), u32::from(value) << 7),
+            _ => return emit(message),
+        };
+        let (interval, threshold) = (self.interval, self.threshold);
+        let controller = match self.controllers.get_mut(&key) {
+            Some(controller) => controller,
+            None => {
+                self.controllers.insert(
+                    key,
+                    Controller {
+                        value,
+                        sent_at: now,
+                        pending: None,
+                    },
+                );
+                return emit(message);
+            }
+        };
+        let changed = value.abs_diff(controller.value);
+        let due = now.saturating_duration_since(controller.sent_at) >= interval;
+        if due || matches!(threshold, Some(threshold) if changed >= threshold) {
+            controller.value = value;
+            controller.sent_at = now;
+            controller.pending = None;
+            emit(message);
+        } else {
+            controller.pending = Some((value, message.to_vec()));
+        }
+    }
+
+    fn poll(&mut self, now: Instant, emit: &mut dyn FnMut(&[u8])) -> Option<Instant> {
+        let mut next: Option<Instant> = None;
+        for controller in self.controllers.values_mut() {
+            let due = controller.sent_at + self.interval;
+            match controller.pending.take() {
+                Some((value, message)) if now >= due && value != controller.value => {
+                    controller.value = value;
+                    controller.sent_at = now;
+                    emit(&message);
+                }
+                // Back to the value already sent
+                Some(_) if now >= due => {}
+                Some(pending) => {
+                    controller.pending = Some(pending);
+                    next = Some(next.map_or(due, |next| next.min(due)));
+                }
+                None => {}
+            }
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Thin;
+    use crate::transform::Transform;
+
+    #[test]
+    fn thin() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let mut thin = Thin::new(ms(20)).threshold(10);
+        let mut output = Vec::new();
+        let mut emit = |message: &[u8]| output.push(message.to_vec());
+
+        for (at, message) in [
+            (0, &[0xB0, 1, 0][..]),
+            (1, &[0xB0, 1, 5]),
+            (2, &[0xB0, 1, 9]),
+            (3, &[0xB0, 33, 9]),
+            (4, &[0xB0, 1, 20]),
+            (5, &[0xB0, 1, 22]),
+            (6, &[0xE0, 0, 64]),
+            (7, &[0xE0, 0, 65]),
+        ] {
+            thin.process(now + ms(at), message, &mut emit);
+        }
+        assert_eq!(thin.poll(now + ms(10), &mut emit), Some(now + ms(24)));
+        assert_eq!(thin.poll(now + ms(25), &mut emit), Some(now + ms(26)));
+        assert_eq!(thin.poll(now + ms(30), &mut emit), None);
+        thin.process(now + ms(60), &[0xB0, 1, 23], &mut emit);
+        assert_eq!(
+            output,
+            vec![
+                vec![0xB0, 1, 0],
+                vec![0xB0, 33, 9],
+                vec![0xB0, 1, 20],
+                vec![0xE0, 0, 64],
+                // The final values, once the interval has passed
+                vec![0xB0, 1, 22],
+                vec![0xE0, 0, 65],
+                vec![0xB0, 1, 23],
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::midi_in::RtMidiIn;
+use crate::scheduler::DEFAULT_BEATS_PER_BAR;
+use crate::subscribe::Subscription;
+
+/// MIDI Timing Clock messages per beat (quarter note)
+const CLOCKS_PER_BEAT: u64 = 24;
+
+/// MIDI Timing Clock messages per sixteenth note, the unit of Song Position Pointer
+const CLOCKS_PER_SIXTEENTH: u64 = 6;
+
+/// Whether a [`Transport`] is playing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportState {
+    #[default]
+    Stopped,
+    Playing,
+}
+
+/// A change of a [`Transport`], passed to its listeners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportEvent {
+    /// Playback started from the beginning of the song (Start, `FA`)
+    Started,
+    /// Playback continued from the song position (Continue, `FB`)
+    Continued,
+    /// Playback stopped (Stop, `FC`)
+    Stopped,
+    /// The song position was set (Song Position Pointer, `F2`), in MIDI clocks
+    Located(u64),
+    /// A beat was reached while playing. Bars and beats are counted from zero, so the first beat
+    /// of each bar is beat 0.
+    Beat { bar: u32, beat: u32 },
+}
+
+type Listener = Box<dyn Fn(TransportEvent) + Send>;
+
+#[derive(Default)]
+struct Listeners {
+    next_id: u64,
+    listeners: Vec<(u64, Listener)>,
+}
+
+struct State {
+    state: TransportState,
+    // Song position of the next clock, in clocks
+    position: u64,
+    beats_per_bar: u32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            state: TransportState::Stopped,
+            position: 0,
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+        }
+    }
+}
+
+impl State {
+    /// Process a message, returning the resulting change
+    fn process(&mut self, message: &[u8]) -> Option<TransportEvent> {
+        let playing = self.state == TransportState::Playing;
+        match *message {
+            [0xF8, ..] if playing => {
+                let (beats, clocks) = (
+                    self.position / CLOCKS_PER_BEAT,
+                    self.position % CLOCKS_PER_BEAT,
+                );
+                self.position += 1;
+                if clocks == 0 {
+                    let (bar, beat) = self.bar_and_beat(beats);
+                    return Some(TransportEvent::Beat { bar, beat });
+                }
+            }
+            [0xFA, ..] => {
+                self.state = TransportState::Playing;
+                self.position = 0;
+                return Some(TransportEvent::Started);
+            }
+            [0xFB, ..] if !playing => {
+                self.state = TransportState::Playing;
+                return Some(TransportEvent::Continued);
+            }
+            [0xFC, ..] if playing => {
+                self.state = TransportState::Stopped;
+                return Some(TransportEvent::Stopped);
+            }
+            [0xF2, lsb, msb] => {
+                let sixteenths = u64::from(msb & 0x7F) << 7 | u64::from(lsb & 0x7F);
+                self.position = sixteenths * CLOCKS_PER_SIXTEENTH;
+                return Some(TransportEvent::Located(self.position));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn bar_and_beat(&self, beats: u64) -> (u32, u32) {
+        let beats_per_bar = u64::from(self.beats_per_bar);
+        (
+            (beats / beats_per_bar) as u32,
+            (beats % beats_per_bar) as u32,
+        )
+    }
+}
+
+/// Transport of an external MIDI clock master
+///
+/// Tracks whether the master is playing and its position in the song from Start (`FA`),
+/// Continue (`FB`), Stop (`FC`), Song Position Pointer (`F2`) and Timing Clock (`F8`) messages,
+/// counting bars and beats as the song plays. Listeners added with [`Transport::subscribe`] are
+/// notified of each change, e.g. to start a recording on the master's Start or to update a
+/// position display on every beat. Where [`crate::ClockFollower`] locks a scheduler to the
+/// master's tempo, this follows its transport controls.
+///
+/// Messages can be passed to [`Transport::process`] from an existing input callback, or the
+/// transport attached to an input with [`Transport::attach`]. Clones refer to the same transport.
+/// ```
+/// use rtmidi::{RtMidiIn, Transport, TransportEvent};
+///
+/// let input = RtMidiIn::new(Default::default()).unwrap();
+/// let transport = Transport::new();
+/// let _attached = transport.attach(&input);
+/// let _listener = transport.subscribe(|event| match event {
+///     TransportEvent::Beat { bar, beat } => println!("{}.{}", bar + 1, beat + 1),
+///     event => println!("{:?}", event),
+/// });
+/// input.set_callback(|_timestamp, _message| {}).unwrap();
+/// input.ignore_types(true, false, true).unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct Transport {
+    state: Arc<Mutex<State>>,
+    listeners: Arc<Mutex<Listeners>>,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the number of beats in a bar (default [`DEFAULT_BEATS_PER_BAR`])
+    ///
+    /// # Panics
+    ///
+    /// Panics if `beats_per_bar` is zero.
+    pub fn beats_per_bar(self, beats_per_bar: u32) -> Self {
+        assert!(beats_per_bar > 0, "Invalid number of beats per bar");
+        lock(&self.state).beats_per_bar = beats_per_bar;
+        self
+    }
+
+    /// Follow the messages received by an input until the returned [`Subscription`] is dropped.
+    ///
+    /// Timing messages are ignored by inputs by default, so must be enabled with
+    /// [`RtMidiIn::ignore_types`].
+    pub fn attach(&self, input: &RtMidiIn) -> Subscription {
+        let transport = self.clone();
+        input.subscribe(move |_timestamp, message| transport.process(message))
+    }
+
+    /// Process a message, notifying the listeners of any change. Messages other than system
+    /// real-time and song position messages are ignored.
+    pub fn process(&self, message: &[u8]) {
+        // The listeners are locked first, so they are notified in order, but can query the
+        // transport
+        let listeners = lock(&self.listeners);
+        let event = lock(&self.state).process(message);
+        if let Some(event) = event {
+            for (_, listener) in &listeners.listeners {
+                listener(event);
+            }
+        }
+    }
+
+    /// Add a listener notified of every change, until the returned [`Subscription`] is dropped.
+    /// Listeners are invoked from the thread processing messages (RtMidi's input thread, once
+    /// attached), and must not add other listeners.
+    pub fn subscribe<F>(&self, listener: F) -> Subscription
+    where
+        F: Fn(TransportEvent) + Send + 'static,
+    {
+        let mut listeners = lock(&self.listeners);
+        let id = listeners.next_id;
+        listeners.next_id += 1;
+        listeners.listeners.push((id, Box::new(listener)));
+        let listeners = Arc::downgrade(&self.listeners);
+        Subscription::new(move || {
+            if let Some(listeners) = listeners.upgrade() {
+                lock(&listeners).listeners.retain(|(other, _)| *other != id);
+            }
+        })
+    }
+
+    pub fn state(&self) -> TransportState {
+        lock(&self.state).state
+    }
+
+    /// Returns [`true`] if the master is playing (after Start or Continue, until Stop)
+    pub fn is_playing(&self) -> bool {
+        self.state() == TransportState::Playing
+    }
+
+    /// Returns the song position in MIDI clocks (24 per beat)
+    pub fn clocks(&self) -> u64 {
+        lock(&self.state).position
+    }
+
+    /// Returns the song position in beats
+    pub fn beats(&self) -> f64 {
+        self.clocks() as f64 / CLOCKS_PER_BEAT as f64
+    }
+
+    /// Returns the bar and the beat within it of the song position, counted from zero
+    pub fn bar_and_beat(&self) -> (u32, u32) {
+        let state = lock(&self.state);
+        state.bar_and_beat(state.position / CLOCKS_PER_BEAT)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Transport, TransportEvent, TransportState};
+
+    fn record(transport: &Transport) -> Arc<Mutex<Vec<TransportEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        std::mem::forget(transport.subscribe(move |event| recorded.lock().unwrap().push(event)));
+        events
+    }
+
+    #[test]
+    fn start_and_beats() {
+        let transport = Transport::new().beats_per_bar(3);
+        let events = record(&transport);
+        // Clock is ignored while stopped
+        transport.process(&[0xF8]);
+        transport.process(&[0xFA]);
+        assert!(transport.is_playing());
+        for _ in 0..24 * 4 {
+            transport.process(&[0xF8]);
+        }
+        assert_eq!(transport.clocks(), 96);
+        assert_eq!(transport.bar_and_beat(), (1, 1));
+        transport.process(&[0xFC]);
+        transport.process(&[0xFC]);
+        assert_eq!(transport.state(), TransportState::Stopped);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                TransportEvent::Started,
+                TransportEvent::Beat { bar: 0, beat: 0 },
+                TransportEvent::Beat { bar: 0, beat: 1 },
+                TransportEvent::Beat { bar: 0, beat: 2 },
+                TransportEvent::Beat { bar: 1, beat: 0 },
+                TransportEvent::Stopped,
+            ]
+        );
+    }
+
+    #[test]
+    fn song_position() {
+        let transport = Transport::new();
+        let events = record(&transport);
+        // Five sixteenth notes in, then continue to the next beat
+        transport.process(&[0xF2, 5, 0]);
+        assert_eq!(transport.beats(), 1.25);
+        transport.process(&[0xFB]);
+        transport.process(&[0xFB]);
+        for _ in 0..19 {
+            transport.process(&[0xF8]);
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                TransportEvent::Located(30),
+                TransportEvent::Continued,
+                TransportEvent::Beat { bar: 0, beat: 2 },
+            ]
+        );
+
+        // Listeners are removed with their subscription
+        let subscription = transport.subscribe(|_event| panic!("Listener wasn't removed"));
+        drop(subscription);
+        transport.process(&[0xFA]);
+    }
+}
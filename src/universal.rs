@@ -0,0 +1,226 @@
+use std::convert::TryFrom;
+
+use crate::error::RtMidiError;
+use crate::roland::{RolandDevice, ROLAND_ID};
+
+/// Device ID addressing every device ("all call") in universal system exclusive messages
+pub const ALL_DEVICES: u8 = 0x7F;
+
+/// Universal Non-Real Time sub-ID
+const NON_REAL_TIME: u8 = 0x7E;
+/// Universal Real Time sub-ID
+const REAL_TIME: u8 = 0x7F;
+/// Yamaha manufacturer ID
+const YAMAHA_ID: u8 = 0x43;
+
+/// Roland GS model ID and System Mode Set (GS reset) address
+const GS_MODEL: u8 = 0x42;
+const GS_RESET: u32 = 0x40007F;
+
+/// Common system exclusive messages
+///
+/// The General MIDI and Device Control messages of the Universal System Exclusive set, along
+/// with the Roland GS and Yamaha XG resets that are sent to the same devices in the same way.
+/// Device IDs address one device (usually `0x00` to `0x0F`, or `0x10` and up for Roland), or
+/// every device with [`ALL_DEVICES`] (not available for the GS and XG resets, which use their
+/// manufacturer's own device numbering).
+/// ```
+/// use rtmidi::{UniversalSysEx, ALL_DEVICES};
+///
+/// let message = UniversalSysEx::GmSystemOn { device_id: ALL_DEVICES };
+/// assert_eq!(message.to_bytes(), [0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]);
+///
+/// assert_eq!(
+///     UniversalSysEx::parse(&[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x64, 0xF7]).unwrap(),
+///     UniversalSysEx::MasterVolume { device_id: ALL_DEVICES, volume: 0x3200 }
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniversalSysEx {
+    /// General MIDI System On: resets the device to General MIDI
+    GmSystemOn { device_id: u8 },
+    /// General MIDI 2 System On
+    Gm2SystemOn { device_id: u8 },
+    /// General MIDI System Off: returns the device to its own (non-GM) mode
+    GmSystemOff { device_id: u8 },
+    /// Roland GS reset, for a Roland device ID (`0x10` for the default device 17)
+    GsReset { device_id: u8 },
+    /// Yamaha XG System On, for a Yamaha device number (`0x00` to `0x0F`)
+    XgSystemOn { device_number: u8 },
+    /// Master Volume, from 0 (silent) to `0x3FFF` (full volume)
+    MasterVolume { device_id: u8, volume: u16 },
+    /// Master Fine Tuning, from 0 (100 cents flat) through `0x2000` (A440) to `0x3FFF` (100
+    /// cents sharp)
+    MasterFineTune { device_id: u8, value: u16 },
+    /// Master Coarse Tuning in semitones, from -64 to 63
+    MasterCoarseTune { device_id: u8, semitones: i8 },
+}
+
+impl UniversalSysEx {
+    /// Parse a complete system exclusive message
+    pub fn parse(bytes: &[u8]) -> Result<Self, RtMidiError> {
+        let body = bytes
+            .strip_prefix(&[0xF0])
+            .and_then(|body| body.strip_suffix(&[0xF7]))
+            .ok_or_else(|| invalid("not a system exclusive message"))?;
+        if body.iter().any(|&byte| byte >= 0x80) {
+            return Err(invalid("status byte inside system exclusive message"));
+        }
+        let data14 = |lsb: u8, msb: u8| u16::from(msb) << 7 | u16::from(lsb);
+        Ok(match *body {
+            [NON_REAL_TIME, device_id, 0x09, 0x01] => UniversalSysEx::GmSystemOn { device_id },
+            [NON_REAL_TIME, device_id, 0x09, 0x02] => UniversalSysEx::GmSystemOff { device_id },
+            [NON_REAL_TIME, device_id, 0x09, 0x03] => UniversalSysEx::Gm2SystemOn { device_id },
+            [REAL_TIME, device_id, 0x04, 0x01, lsb, msb] => UniversalSysEx::MasterVolume {
+                device_id,
+                volume: data14(lsb, msb),
+            },
+            [REAL_TIME, device_id, 0x04, 0x03, lsb, msb] => UniversalSysEx::MasterFineTune {
+                device_id,
+                value: data14(lsb, msb),
+            },
+            [REAL_TIME, device_id, 0x04, 0x04, _, msb] => UniversalSysEx::MasterCoarseTune {
+                device_id,
+                semitones: msb as i8 - 0x40,
+            },
+            [ROLAND_ID, device_id, GS_MODEL, ..] if gs_reset(device_id) == bytes => {
+                UniversalSysEx::GsReset { device_id }
+            }
+            [YAMAHA_ID, device, 0x4C, 0x00, 0x00, 0x7E, 0x00] if device & 0x70 == 0x10 => {
+                UniversalSysEx::XgSystemOn {
+                    device_number: device & 0x0F,
+                }
+            }
+            _ => return Err(invalid("unrecognised message")),
+        })
+    }
+
+    /// Encode the message as bytes. Device IDs and values are masked to their valid range.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data14 = |value: u16| [(value & 0x7F) as u8, ((value >> 7) & 0x7F) as u8];
+        let body = match *self {
+            UniversalSysEx::GmSystemOn { device_id } => vec![NON_REAL_TIME, device_id, 0x09, 0x01],
+            UniversalSysEx::GmSystemOff { device_id } => {
+                vec![NON_REAL_TIME, device_id, 0x09, 0x02]
+            }
+            UniversalSysEx::Gm2SystemOn { device_id } => {
+                vec![NON_REAL_TIME, device_id, 0x09, 0x03]
+            }
+            UniversalSysEx::GsReset { device_id } => return gs_reset(device_id & 0x7F),
+            UniversalSysEx::XgSystemOn { device_number } => vec![
+                YAMAHA_ID,
+                0x10 | device_number & 0x0F,
+                0x4C,
+                0x00,
+                0x00,
+                0x7E,
+                0x00,
+            ],
+            UniversalSysEx::MasterVolume { device_id, volume } => {
+                let [lsb, msb] = data14(volume);
+                vec![REAL_TIME, device_id, 0x04, 0x01, lsb, msb]
+            }
+            UniversalSysEx::MasterFineTune { device_id, value } => {
+                let [lsb, msb] = data14(value);
+                vec![REAL_TIME, device_id, 0x04, 0x03, lsb, msb]
+            }
+            UniversalSysEx::MasterCoarseTune {
+                device_id,
+                semitones,
+            } => {
+                let msb = (semitones.clamp(-64, 63) + 0x40) as u8;
+                vec![REAL_TIME, device_id, 0x04, 0x04, 0x00, msb]
+            }
+        };
+        let mut bytes = Vec::with_capacity(body.len() + 2);
+        bytes.push(0xF0);
+        bytes.extend(body.iter().map(|&byte| byte & 0x7F));
+        bytes.push(0xF7);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for UniversalSysEx {
+    type Error = RtMidiError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        UniversalSysEx::parse(bytes)
+    }
+}
+
+impl From<&UniversalSysEx> for Vec<u8> {
+    fn from(message: &UniversalSysEx) -> Self {
+        message.to_bytes()
+    }
+}
+
+/// Returns the GS reset message for a Roland device ID
+fn gs_reset(device_id: u8) -> Vec<u8> {
+    RolandDevice::new(device_id, &[GS_MODEL], 3)
+        .data_set(GS_RESET, &[0x00])
+        .unwrap_or_default()
+}
+
+fn invalid(message: &str) -> RtMidiError {
+    RtMidiError::InvalidMessage(format!("Universal system exclusive: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UniversalSysEx, ALL_DEVICES};
+
+    #[test]
+    fn messages() {
+        let messages: &[(&[u8], UniversalSysEx)] = &[
+            (
+                &[0xF0, 0x7E, 0x7F, 0x09, 0x02, 0xF7],
+                UniversalSysEx::GmSystemOff {
+                    device_id: ALL_DEVICES,
+                },
+            ),
+            (
+                &[0xF0, 0x7E, 0x00, 0x09, 0x03, 0xF7],
+                UniversalSysEx::Gm2SystemOn { device_id: 0 },
+            ),
+            (
+                &[
+                    0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+                ],
+                UniversalSysEx::GsReset { device_id: 0x10 },
+            ),
+            (
+                &[0xF0, 0x43, 0x12, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7],
+                UniversalSysEx::XgSystemOn { device_number: 2 },
+            ),
+            (
+                &[0xF0, 0x7F, 0x7F, 0x04, 0x03, 0x00, 0x40, 0xF7],
+                UniversalSysEx::MasterFineTune {
+                    device_id: ALL_DEVICES,
+                    value: 0x2000,
+                },
+            ),
+            (
+                &[0xF0, 0x7F, 0x7F, 0x04, 0x04, 0x00, 0x3E, 0xF7],
+                UniversalSysEx::MasterCoarseTune {
+                    device_id: ALL_DEVICES,
+                    semitones: -2,
+                },
+            ),
+        ];
+        for (bytes, message) in messages {
+            assert_eq!(&UniversalSysEx::parse(bytes).unwrap(), message);
+            assert_eq!(&message.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        // GS reset with a bad checksum, an unknown message and an unterminated message
+        assert!(UniversalSysEx::parse(&[
+            0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x40, 0xF7
+        ])
+        .is_err());
+        assert!(UniversalSysEx::parse(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).is_err());
+        assert!(UniversalSysEx::parse(&[0xF0, 0x7E, 0x7F, 0x09, 0x01]).is_err());
+    }
+}
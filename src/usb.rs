@@ -0,0 +1,167 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+
+use crate::api::RtMidiApi;
+use crate::port_name::{PortName, PortSuffix};
+use crate::system::PortDirection;
+#[cfg(all(feature = "winmm", target_os = "windows"))]
+use crate::winmm;
+use crate::RtMidiPort;
+
+/// First ALSA sequencer client number given to sound cards, each of which has a block of them
+const ALSA_CARD_CLIENTS: u8 = 16;
+const ALSA_CLIENTS_PER_CARD: u8 = 4;
+/// First client number given to applications rather than sound cards
+const ALSA_DYNAMIC_CLIENTS: u8 = 128;
+
+/// USB identity of the device behind a port, see [`crate::PortInfo::usb`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UsbId {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Serial number, if the device reports one
+    pub serial: Option<String>,
+}
+
+/// Returns the USB identity of the device behind a port, where the platform tells which device
+/// that is: the sound card of ALSA ports (also when bridged to JACK by a2j) on Linux, and the
+/// device interface of Windows MM ports with the `winmm` feature.
+#[allow(unused_variables)]
+pub(crate) fn lookup(
+    api: RtMidiApi,
+    direction: PortDirection,
+    port: RtMidiPort,
+    name: &str,
+) -> Option<UsbId> {
+    match api {
+        #[cfg(target_os = "linux")]
+        RtMidiApi::LinuxALSA | RtMidiApi::UnixJack => {
+            let card = alsa_card(alsa_client(api, name)?)?;
+            // The card's device is a USB interface, whose parent is the USB device
+            let interface =
+                fs::canonicalize(format!("/sys/class/sound/card{}/device", card)).ok()?;
+            sysfs_usb_id(interface.parent()?)
+        }
+        #[cfg(all(feature = "winmm", target_os = "windows"))]
+        RtMidiApi::WindowsMM => winmm::device_interface(direction, port)
+            .as_deref()
+            .and_then(device_interface_usb_id),
+        _ => None,
+    }
+}
+
+/// Returns the ALSA client of a port, from its address or the client number the a2j bridge puts
+/// in the names of the ports it bridges ("a2j:Launchpad X [28] (capture): ...")
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn alsa_client(api: RtMidiApi, name: &str) -> Option<u8> {
+    let name = PortName::parse(api, name);
+    match name.suffix {
+        Some(PortSuffix::AlsaAddress { client, .. }) => Some(client),
+        _ if name.device == "a2j" => {
+            let port = name.port?;
+            let (_, rest) = port.split_once(" [")?;
+            let (client, _) = rest.split_once(']')?;
+            client.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Returns the sound card of a kernel ALSA sequencer client
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn alsa_card(client: u8) -> Option<u8> {
+    if (ALSA_CARD_CLIENTS..ALSA_DYNAMIC_CLIENTS).contains(&client) {
+        Some((client - ALSA_CARD_CLIENTS) / ALSA_CLIENTS_PER_CARD)
+    } else {
+        None
+    }
+}
+
+/// Reads the identity of a USB device from its sysfs directory
+#[cfg(target_os = "linux")]
+fn sysfs_usb_id(device: &std::path::Path) -> Option<UsbId> {
+    let read = |file| fs::read_to_string(device.join(file)).ok();
+    let hex = |file| u16::from_str_radix(read(file)?.trim(), 16).ok();
+    Some(UsbId {
+        vendor_id: hex("idVendor")?,
+        product_id: hex("idProduct")?,
+        serial: read("serial").map(|serial| serial.trim().to_string()),
+    })
+}
+
+/// Parses a Windows device interface path such as
+/// `\\?\usb#vid_1235&pid_0103&mi_00#7&2a1b3c&0&0000#{guid}`. Windows uses the serial number as
+/// the instance ID of devices that have one, unless the device is split into interfaces (`mi_`),
+/// otherwise it makes up an ID containing `&`.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn device_interface_usb_id(path: &str) -> Option<UsbId> {
+    let mut parts = path.split('#');
+    if !parts.next()?.to_lowercase().ends_with("usb") {
+        return None;
+    }
+    let ids = parts.next()?.to_lowercase();
+    let id = |prefix: &str| {
+        let (_, rest) = ids.split_once(prefix)?;
+        u16::from_str_radix(rest.get(..4)?, 16).ok()
+    };
+    let instance = parts.next().filter(|instance| !instance.contains('&'));
+    Some(UsbId {
+        vendor_id: id("vid_")?,
+        product_id: id("pid_")?,
+        serial: instance.filter(|_| !ids.contains("&mi_")).map(String::from),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alsa_card, alsa_client, device_interface_usb_id, UsbId};
+    use crate::api::RtMidiApi;
+
+    #[test]
+    fn alsa() {
+        let client = |api, name| alsa_client(api, name);
+        assert_eq!(
+            client(RtMidiApi::LinuxALSA, "Launchpad X:Launchpad X MIDI 1 28:0"),
+            Some(28)
+        );
+        assert_eq!(
+            client(
+                RtMidiApi::UnixJack,
+                "a2j:Launchpad X [28] (capture): Launchpad X MIDI 1"
+            ),
+            Some(28)
+        );
+        assert_eq!(client(RtMidiApi::UnixJack, "system:midi_capture_1"), None);
+        assert_eq!(alsa_card(28), Some(3));
+        assert_eq!(alsa_card(14), None);
+        assert_eq!(alsa_card(128), None);
+    }
+
+    #[test]
+    fn device_interface() {
+        assert_eq!(
+            device_interface_usb_id(
+                r"\\?\usb#vid_1235&pid_0103&mi_00#7&2a1b3c&0&0000#{6994ad04-93ef-11d0-a3cc-00a0c9223196}\global"
+            ),
+            Some(UsbId {
+                vendor_id: 0x1235,
+                product_id: 0x0103,
+                serial: None,
+            })
+        );
+        assert_eq!(
+            device_interface_usb_id(
+                r"\\?\USB#VID_0582&PID_012A#A1B2C3#{6994ad04-93ef-11d0-a3cc-00a0c9223196}\global"
+            ),
+            Some(UsbId {
+                vendor_id: 0x0582,
+                product_id: 0x012A,
+                serial: Some("A1B2C3".to_string()),
+            })
+        );
+        assert_eq!(
+            device_interface_usb_id(r"\\?\swd#mmdevapi#{0.0.0.00000000}.{guid}#{guid}"),
+            None
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Conversions between MIDI data values and normalized floats
+//!
+//! Unsigned values map 0 to 0.0 and the largest value to 1.0. Centred values (pitch bend, and
+//! controllers such as pan) map the centre exactly to 0.0, with the lowest value at -1.0 and the
+//! highest at 1.0. As the centre isn't halfway between the ends, the two sides are scaled
+//! separately. Floats are clamped to their range, and rounded to the nearest value.
+//! ```
+//! use rtmidi::value;
+//!
+//! assert_eq!(value::from_7bit(127), 1.0);
+//! assert_eq!(value::to_7bit(0.5), 64);
+//! assert_eq!(value::from_pitch_bend(8192), 0.0);
+//! assert_eq!(value::to_pitch_bend(-1.0), 0);
+//! assert_eq!(value::to_pitch_bend(1.0), 16383);
+//! ```
+
+/// Largest 7-bit and 14-bit values
+const MAX_7BIT: u16 = 0x7F;
+const MAX_14BIT: u16 = 0x3FFF;
+
+/// Centre of 7-bit and 14-bit values
+const CENTRE_7BIT: u16 = 0x40;
+const CENTRE_14BIT: u16 = 0x2000;
+
+/// Returns a 7-bit value (0 to 127) as 0.0 to 1.0
+pub fn from_7bit(value: u8) -> f64 {
+    from_unsigned(value.into(), MAX_7BIT)
+}
+
+/// Returns the 7-bit value nearest to a float from 0.0 to 1.0
+pub fn to_7bit(value: f64) -> u8 {
+    to_unsigned(value, MAX_7BIT) as u8
+}
+
+/// Returns a 14-bit value (0 to 16383) as 0.0 to 1.0
+pub fn from_14bit(value: u16) -> f64 {
+    from_unsigned(value, MAX_14BIT)
+}
+
+/// Returns the 14-bit value nearest to a float from 0.0 to 1.0
+pub fn to_14bit(value: f64) -> u16 {
+    to_unsigned(value, MAX_14BIT)
+}
+
+/// Returns a 7-bit value centred on 64, such as pan, as -1.0 to 1.0
+pub fn from_7bit_centred(value: u8) -> f64 {
+    from_centred(value.into(), CENTRE_7BIT, MAX_7BIT)
+}
+
+/// Returns the 7-bit value centred on 64 nearest to a float from -1.0 to 1.0
+pub fn to_7bit_centred(value: f64) -> u8 {
+    to_centred(value, CENTRE_7BIT, MAX_7BIT) as u8
+}
+
+/// Returns a pitch bend value (8192 is centre) as -1.0 to 1.0
+pub fn from_pitch_bend(value: u16) -> f64 {
+    from_centred(value, CENTRE_14BIT, MAX_14BIT)
+}
+
+/// Returns the pitch bend value nearest to a float from -1.0 to 1.0
+pub fn to_pitch_bend(value: f64) -> u16 {
+    to_centred(value, CENTRE_14BIT, MAX_14BIT)
+}
+
+fn from_unsigned(value: u16, max: u16) -> f64 {
+    f64::from(value.min(max)) / f64::from(max)
+}
+
+fn to_unsigned(value: f64, max: u16) -> u16 {
+    (value.clamp(0.0, 1.0) * f64::from(max)).round() as u16
+}
+
+fn from_centred(value: u16, centre: u16, max: u16) -> f64 {
+    let value = value.min(max);
+    if value < centre {
+        -f64::from(centre - value) / f64::from(centre)
+    } else {
+        f64::from(value - centre) / f64::from(max - centre)
+    }
+}
+
+fn to_centred(value: f64, centre: u16, max: u16) -> u16 {
+    let value = value.clamp(-1.0, 1.0);
+    let offset = if value < 0.0 {
+        value * f64::from(centre)
+    } else {
+        value * f64::from(max - centre)
+    };
+    (f64::from(centre) + offset).round() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        from_14bit, from_7bit, from_7bit_centred, from_pitch_bend, to_14bit, to_7bit,
+        to_7bit_centred, to_pitch_bend,
+    };
+
+    #[test]
+    fn round_trip() {
+        for value in 0..=127 {
+            assert_eq!(to_7bit(from_7bit(value)), value);
+            assert_eq!(to_7bit_centred(from_7bit_centred(value)), value);
+        }
+        for value in 0..=0x3FFF {
+            assert_eq!(to_14bit(from_14bit(value)), value);
+            assert_eq!(to_pitch_bend(from_pitch_bend(value)), value);
+        }
+    }
+
+    #[test]
+    fn centre() {
+        assert_eq!(from_7bit_centred(64), 0.0);
+        assert_eq!(from_7bit_centred(0), -1.0);
+        assert_eq!(from_7bit_centred(127), 1.0);
+        assert_eq!(to_7bit_centred(0.0), 64);
+        assert_eq!(from_pitch_bend(0), -1.0);
+        assert_eq!(from_pitch_bend(0x3FFF), 1.0);
+        assert_eq!(to_pitch_bend(0.0), 8192);
+        assert_eq!(to_pitch_bend(0.5), 12288);
+        assert_eq!(to_pitch_bend(-0.5), 4096);
+    }
+
+    #[test]
+    fn clamp() {
+        assert_eq!(to_7bit(2.0), 127);
+        assert_eq!(to_7bit(-1.0), 0);
+        assert_eq!(to_14bit(1.5), 0x3FFF);
+        assert_eq!(to_pitch_bend(-3.0), 0);
+        assert_eq!(from_7bit(200), 1.0);
+    }
+}
@@ -0,0 +1,152 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+
+use std::ffi::OsStr;
+use std::os::raw::c_void;
+use std::os::windows::ffi::OsStrExt;
+use std::slice;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::error::RtMidiError;
+
+type LPVM_MIDI_PORT = *mut c_void;
+type VmMidiDataCallback = extern "system" fn(LPVM_MIDI_PORT, *const u8, u32, usize);
+
+/// Port flags: parse received data into messages, and create only the half of the port that
+/// other applications send to (our input) or receive from (our output)
+const TE_VM_FLAGS_PARSE_RX: u32 = 1;
+const TE_VM_FLAGS_INSTANTIATE_RX_ONLY: u32 = 4;
+const TE_VM_FLAGS_INSTANTIATE_TX_ONLY: u32 = 8;
+/// Longest system exclusive message received
+const MAX_SYSEX_LENGTH: u32 = 65535;
+
+extern "system" {
+    fn virtualMIDICreatePortEx2(
+        name: *const u16,
+        callback: Option<VmMidiDataCallback>,
+        instance: usize,
+        max_sysex_length: u32,
+        flags: u32,
+    ) -> LPVM_MIDI_PORT;
+    fn virtualMIDIClosePort(port: LPVM_MIDI_PORT);
+    fn virtualMIDISendData(port: LPVM_MIDI_PORT, data: *const u8, length: u32) -> i32;
+    fn GetLastError() -> u32;
+}
+
+/// Receives the messages of a virtual input, with the time in seconds since the previous one
+pub type VirtualHandler = Arc<Mutex<dyn FnMut(f64, &[u8]) + Send>>;
+
+/// Handler of a virtual input, replaced when the input's callback is set
+#[derive(Clone, Default)]
+pub struct HandlerSlot(Arc<Mutex<Option<VirtualHandler>>>);
+
+impl HandlerSlot {
+    pub fn set(&self, handler: Option<VirtualHandler>) {
+        *lock(&self.0) = handler;
+    }
+}
+
+/// State passed to the driver's callback
+struct Receiver {
+    handler: HandlerSlot,
+    last: Mutex<Option<Instant>>,
+}
+
+/// Port created with the teVirtualMIDI driver (as used by loopMIDI), which Windows MM doesn't
+/// have virtual ports without
+pub struct VirtualPort {
+    port: LPVM_MIDI_PORT,
+    // Borrowed by the driver's callback until the port is closed
+    receiver: Option<Box<Receiver>>,
+}
+
+unsafe impl Send for VirtualPort {}
+
+impl VirtualPort {
+    /// Create a port that other applications receive from
+    pub fn output(name: &str) -> Result<Self, RtMidiError> {
+        create(name, None, TE_VM_FLAGS_INSTANTIATE_TX_ONLY)
+    }
+
+    /// Create a port that other applications send to, passing each message to the handler
+    pub fn input(name: &str, handler: HandlerSlot) -> Result<Self, RtMidiError> {
+        let receiver = Box::new(Receiver {
+            handler,
+            last: Mutex::new(None),
+        });
+        create(
+            name,
+            Some(receiver),
+            TE_VM_FLAGS_PARSE_RX | TE_VM_FLAGS_INSTANTIATE_RX_ONLY,
+        )
+    }
+
+    pub fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        if unsafe { virtualMIDISendData(self.port, message.as_ptr(), message.len() as u32) } == 0 {
+            return Err(error("virtualMIDISendData"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VirtualPort {
+    fn drop(&mut self) {
+        // Closing the port waits for the callback to return, so the receiver can then be freed
+        unsafe { virtualMIDIClosePort(self.port) };
+        self.receiver.take();
+    }
+}
+
+fn create(
+    name: &str,
+    receiver: Option<Box<Receiver>>,
+    flags: u32,
+) -> Result<VirtualPort, RtMidiError> {
+    let name: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+    let (callback, instance) = match &receiver {
+        Some(receiver) => (
+            Some(receive as VmMidiDataCallback),
+            &**receiver as *const Receiver as usize,
+        ),
+        None => (None, 0),
+    };
+    let port = unsafe {
+        virtualMIDICreatePortEx2(name.as_ptr(), callback, instance, MAX_SYSEX_LENGTH, flags)
+    };
+    if port.is_null() {
+        return Err(error("virtualMIDICreatePortEx2"));
+    }
+    Ok(VirtualPort { port, receiver })
+}
+
+extern "system" fn receive(_port: LPVM_MIDI_PORT, data: *const u8, length: u32, instance: usize) {
+    let receiver = unsafe { &*(instance as *const Receiver) };
+    if data.is_null() || length == 0 {
+        return;
+    }
+    let message = unsafe { slice::from_raw_parts(data, length as usize) };
+    let now = Instant::now();
+    let delta = {
+        let mut last = lock(&receiver.last);
+        let delta = last.map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+        *last = Some(now);
+        delta
+    };
+    let handler = lock(&receiver.handler.0).clone();
+    if let Some(handler) = handler {
+        (lock(&handler))(delta, message);
+    }
+}
+
+/// Returns an error for a failed driver call, e.g. because the driver isn't installed or a port
+/// of the same name already exists
+fn error(function: &str) -> RtMidiError {
+    RtMidiError::Error(format!("{} failed with error {}", function, unsafe {
+        GetLastError()
+    }))
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
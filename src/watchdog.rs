@@ -0,0 +1,146 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::event::{EventHandler, RtMidiEvent};
+use crate::threads;
+
+/// Active Sensing status byte
+const ACTIVE_SENSING: u8 = 0xFE;
+
+/// Recommended interval between Active Sensing messages sent to a device
+pub const ACTIVE_SENSING_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum silence allowed by the MIDI specification once Active Sensing has been received
+pub const ACTIVE_SENSING_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Active Sensing watchdog
+///
+/// Once an Active Sensing message has been received, the sender must transmit something at least
+/// every 300ms. The watchdog tracks incoming messages from a dedicated thread and raises
+/// [`RtMidiEvent::ConnectionLost`] if the input stays silent for longer than the timeout. It is
+/// then disarmed until the next Active Sensing message.
+#[derive(Default)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    timeout: Option<Duration>,
+    last: Option<Instant>,
+    stopped: bool,
+}
+
+impl Watchdog {
+    /// Start, restart or (with [`None`]) stop the watchdog
+    pub fn set_timeout(&self, timeout: Option<Duration>, events: &EventHandler) {
+        let mut thread = self.thread.lock().unwrap_or_else(PoisonError::into_inner);
+        {
+            let mut state = self.shared.lock();
+            state.stopped = true;
+            self.shared.condvar.notify_all();
+        }
+        if let Some(thread) = thread.take() {
+            let _ = thread.join();
+        }
+        let mut state = self.shared.lock();
+        *state = State {
+            timeout,
+            last: None,
+            stopped: false,
+        };
+        if timeout.is_some() {
+            let shared = Arc::clone(&self.shared);
+            let events = events.clone();
+            *thread = Some(threads::spawn("rtmidi-watchdog".to_string(), move || {
+                shared.run(&events)
+            }));
+        }
+    }
+
+    /// Record an incoming message
+    pub fn feed(&self, message: &[u8]) {
+        let mut state = self.shared.lock();
+        if state.timeout.is_none() {
+            return;
+        }
+        if message == [ACTIVE_SENSING] {
+            if state.last.replace(Instant::now()).is_none() {
+                self.shared.condvar.notify_all();
+            }
+        } else if state.last.is_some() {
+            state.last = Some(Instant::now());
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run(&self, events: &EventHandler) {
+        let mut state = self.lock();
+        while !state.stopped {
+            state = match (state.timeout, state.last) {
+                (Some(timeout), Some(last)) => {
+                    let elapsed = last.elapsed();
+                    if elapsed >= timeout {
+                        state.last = None;
+                        drop(state);
+                        events.emit(RtMidiEvent::ConnectionLost);
+                        self.lock()
+                    } else {
+                        self.condvar
+                            .wait_timeout(state, timeout - elapsed)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0
+                    }
+                }
+                _ => self
+                    .condvar
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use super::Watchdog;
+    use crate::event::{EventHandler, RtMidiEvent};
+
+    #[test]
+    fn connection_lost() {
+        let (sender, receiver) = mpsc::channel();
+        let events = EventHandler::default();
+        events.set(Some(Box::new(move |event| sender.send(event).unwrap())));
+        let watchdog = Watchdog::default();
+        watchdog.set_timeout(Some(Duration::from_millis(20)), &events);
+
+        // Not armed until Active Sensing is received
+        watchdog.feed(&[144, 64, 90]);
+        sleep(Duration::from_millis(40));
+        assert!(receiver.try_recv().is_err());
+
+        watchdog.feed(&[0xFE]);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(1)),
+            Ok(RtMidiEvent::ConnectionLost)
+        );
+        watchdog.set_timeout(None, &events);
+    }
+}
@@ -0,0 +1,497 @@
+#![allow(clippy::upper_case_acronyms)]
+#![allow(non_snake_case)]
+
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::{Backend, BackendCallback, InputConnection, OutputConnection};
+use crate::decoder::data_length;
+use crate::error::RtMidiError;
+use crate::system::PortDirection;
+use crate::RtMidiPort;
+
+type HMIDIIN = *mut c_void;
+type HMIDIOUT = *mut c_void;
+type MMRESULT = u32;
+
+const MMSYSERR_NOERROR: MMRESULT = 0;
+const MIDIERR_STILLPLAYING: MMRESULT = 65;
+
+/// `midiInOpen` flag for a callback function, and the messages it receives
+const CALLBACK_FUNCTION: u32 = 0x0003_0000;
+const MIM_DATA: u32 = 0x3C3;
+const MIM_LONGDATA: u32 = 0x3C4;
+
+/// Number and size of the buffers system exclusive messages are received into
+const SYSEX_BUFFERS: usize = 4;
+const SYSEX_BUFFER_SIZE: usize = 1024;
+
+/// Driver messages returning the size and contents of the device interface path of a device
+const DRV_QUERYDEVICEINTERFACE: u32 = 0x080C;
+const DRV_QUERYDEVICEINTERFACESIZE: u32 = 0x080D;
+
+/// Maximum length of a device name in the device capabilities
+const MAXPNAMELEN: usize = 32;
+
+// The SDK declares the multimedia structures with byte packing (mmsystem.h includes
+// pshpack1.h), which leaves the pointers of `MIDIHDR` unaligned on 64-bit Windows
+#[repr(C, packed)]
+struct MIDIINCAPSW {
+    wMid: u16,
+    wPid: u16,
+    vDriverVersion: u32,
+    szPname: [u16; MAXPNAMELEN],
+    dwSupport: u32,
+}
+
+#[repr(C, packed)]
+struct MIDIOUTCAPSW {
+    wMid: u16,
+    wPid: u16,
+    vDriverVersion: u32,
+    szPname: [u16; MAXPNAMELEN],
+    wTechnology: u16,
+    wVoices: u16,
+    wNotes: u16,
+    wChannelMask: u16,
+    dwSupport: u32,
+}
+
+#[repr(C, packed)]
+struct MIDIHDR {
+    lpData: *mut u8,
+    dwBufferLength: u32,
+    dwBytesRecorded: u32,
+    dwUser: usize,
+    dwFlags: u32,
+    lpNext: *mut MIDIHDR,
+    reserved: usize,
+    dwOffset: u32,
+    dwReserved: [usize; 8],
+}
+
+impl MIDIHDR {
+    fn new(data: &mut [u8]) -> Self {
+        MIDIHDR {
+            lpData: data.as_mut_ptr(),
+            dwBufferLength: data.len() as u32,
+            dwBytesRecorded: 0,
+            dwUser: 0,
+            dwFlags: 0,
+            lpNext: ptr::null_mut(),
+            reserved: 0,
+            dwOffset: 0,
+            dwReserved: [0; 8],
+        }
+    }
+}
+
+type MidiInProc = extern "system" fn(HMIDIIN, u32, usize, usize, usize);
+
+extern "system" {
+    fn midiInGetNumDevs() -> u32;
+    fn midiInGetDevCapsW(device: usize, caps: *mut MIDIINCAPSW, size: u32) -> MMRESULT;
+    fn midiInOpen(
+        handle: *mut HMIDIIN,
+        device: u32,
+        callback: usize,
+        instance: usize,
+        flags: u32,
+    ) -> MMRESULT;
+    fn midiInStart(handle: HMIDIIN) -> MMRESULT;
+    fn midiInStop(handle: HMIDIIN) -> MMRESULT;
+    fn midiInReset(handle: HMIDIIN) -> MMRESULT;
+    fn midiInClose(handle: HMIDIIN) -> MMRESULT;
+    fn midiInPrepareHeader(handle: HMIDIIN, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiInUnprepareHeader(handle: HMIDIIN, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiInAddBuffer(handle: HMIDIIN, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiInMessage(handle: HMIDIIN, message: u32, param1: usize, param2: usize) -> MMRESULT;
+    fn midiOutGetNumDevs() -> u32;
+    fn midiOutGetDevCapsW(device: usize, caps: *mut MIDIOUTCAPSW, size: u32) -> MMRESULT;
+    fn midiOutOpen(
+        handle: *mut HMIDIOUT,
+        device: u32,
+        callback: usize,
+        instance: usize,
+        flags: u32,
+    ) -> MMRESULT;
+    fn midiOutShortMsg(handle: HMIDIOUT, message: u32) -> MMRESULT;
+    fn midiOutLongMsg(handle: HMIDIOUT, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiOutPrepareHeader(handle: HMIDIOUT, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiOutUnprepareHeader(handle: HMIDIOUT, header: *mut MIDIHDR, size: u32) -> MMRESULT;
+    fn midiOutReset(handle: HMIDIOUT) -> MMRESULT;
+    fn midiOutClose(handle: HMIDIOUT) -> MMRESULT;
+    fn midiOutMessage(handle: HMIDIOUT, message: u32, param1: usize, param2: usize) -> MMRESULT;
+}
+
+/// Native Windows Multimedia (WinMM) [`Backend`]
+///
+/// Provides the MIDI devices of the Windows Multimedia API to a [`crate::MidiSystem`] directly
+/// rather than through RtMidi, with the same port names as RtMidi's Windows MM API (the device
+/// name followed by its number, e.g. "USB MIDI Interface 0"). Messages from an input are
+/// delivered on the system's callback thread.
+/// ```no_run
+/// use rtmidi::{MidiSystem, RtMidiApi, RtMidiError, WinMmBackend};
+///
+/// fn open() -> Result<MidiSystem, RtMidiError> {
+///     let system = MidiSystem::new(RtMidiApi::RtMidiDummy, "My Application")?
+///         .backend(WinMmBackend::new());
+///     system.route("USB MIDI Interface", "Microsoft GS Wavetable Synth")?;
+///     Ok(system)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WinMmBackend;
+
+impl WinMmBackend {
+    pub fn new() -> Self {
+        WinMmBackend
+    }
+
+    /// Open the input device with the port name `port`. Windows Multimedia has no virtual ports
+    /// (nor names for an application's own ports), so there must be one. System exclusive data
+    /// is passed on as it arrives, rather than as whole messages, while `sysex_streams` is above
+    /// zero.
+    pub(crate) fn connect_input(
+        &self,
+        port: Option<&str>,
+        _client_name: &str,
+        _own_port: &str,
+        callback: BackendCallback,
+        sysex_streams: Arc<AtomicUsize>,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        let device = find(&self.input_ports()?, port.ok_or_else(no_virtual_ports)?)?;
+        let mut buffers = vec![vec![0; SYSEX_BUFFER_SIZE]; SYSEX_BUFFERS];
+        let headers = buffers
+            .iter_mut()
+            .map(|buffer| MIDIHDR::new(buffer))
+            .collect();
+        let mut state = Box::new(InputState {
+            callback,
+            handle: ptr::null_mut(),
+            closing: AtomicBool::new(false),
+            last: Mutex::new(None),
+            sysex: Mutex::new(Vec::new()),
+            sysex_streams,
+            _buffers: buffers,
+            headers,
+        });
+        let instance = &*state as *const InputState as usize;
+        let proc: MidiInProc = receive;
+        check(unsafe {
+            midiInOpen(
+                &mut state.handle,
+                device,
+                proc as usize,
+                instance,
+                CALLBACK_FUNCTION,
+            )
+        })?;
+        // Closes the device again if any of the rest fails
+        let mut input = WinMmInput(state);
+        let state = &mut *input.0;
+        for header in &mut state.headers {
+            check(unsafe { midiInPrepareHeader(state.handle, header, HEADER_SIZE) })?;
+            check(unsafe { midiInAddBuffer(state.handle, header, HEADER_SIZE) })?;
+        }
+        check(unsafe { midiInStart(state.handle) })?;
+        Ok(Box::new(input))
+    }
+
+    /// Open the output device with the port name `port`, which there must be as for
+    /// [`WinMmBackend::connect_input`]
+    pub(crate) fn connect_output(
+        &self,
+        port: Option<&str>,
+        _client_name: &str,
+        _own_port: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        let device = find(&self.output_ports()?, port.ok_or_else(no_virtual_ports)?)?;
+        let mut handle = ptr::null_mut();
+        check(unsafe { midiOutOpen(&mut handle, device, 0, 0, 0) })?;
+        Ok(Box::new(WinMmOutput(Mutex::new(handle))))
+    }
+}
+
+impl Backend for WinMmBackend {
+    fn name(&self) -> &str {
+        "winmm"
+    }
+
+    fn input_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        (0..unsafe { midiInGetNumDevs() })
+            .map(|device| {
+                let mut caps: MIDIINCAPSW = unsafe { mem::zeroed() };
+                let size = mem::size_of::<MIDIINCAPSW>() as u32;
+                check(unsafe { midiInGetDevCapsW(device as usize, &mut caps, size) })?;
+                // Copied out, as fields of a packed structure can't be borrowed
+                let name = caps.szPname;
+                Ok(port_name(&name, device))
+            })
+            .collect()
+    }
+
+    fn output_ports(&self) -> Result<Vec<String>, RtMidiError> {
+        (0..unsafe { midiOutGetNumDevs() })
+            .map(|device| {
+                let mut caps: MIDIOUTCAPSW = unsafe { mem::zeroed() };
+                let size = mem::size_of::<MIDIOUTCAPSW>() as u32;
+                check(unsafe { midiOutGetDevCapsW(device as usize, &mut caps, size) })?;
+                // Copied out, as fields of a packed structure can't be borrowed
+                let name = caps.szPname;
+                Ok(port_name(&name, device))
+            })
+            .collect()
+    }
+
+    fn open_input(
+        &self,
+        port: &str,
+        client_name: &str,
+        callback: BackendCallback,
+    ) -> Result<Box<dyn InputConnection>, RtMidiError> {
+        self.connect_input(
+            Some(port),
+            client_name,
+            client_name,
+            callback,
+            Arc::default(),
+        )
+    }
+
+    fn open_output(
+        &self,
+        port: &str,
+        client_name: &str,
+    ) -> Result<Box<dyn OutputConnection>, RtMidiError> {
+        self.connect_output(Some(port), client_name, client_name)
+    }
+}
+
+const HEADER_SIZE: u32 = mem::size_of::<MIDIHDR>() as u32;
+
+/// State of an open input, shared with the system's callback thread
+struct InputState {
+    callback: BackendCallback,
+    handle: HMIDIIN,
+    closing: AtomicBool,
+    // Timestamp of the last message, in milliseconds since the input was started
+    last: Mutex<Option<usize>>,
+    sysex: Mutex<Vec<u8>>,
+    // Streams of system exclusive data, which is passed on as it arrives while there are any
+    sysex_streams: Arc<AtomicUsize>,
+    // Buffers the headers point into
+    _buffers: Vec<Vec<u8>>,
+    headers: Vec<MIDIHDR>,
+}
+
+/// Input port opened by a [`WinMmBackend`], which is closed when dropped
+struct WinMmInput(Box<InputState>);
+
+unsafe impl Send for WinMmInput {}
+unsafe impl Sync for WinMmInput {}
+
+impl InputConnection for WinMmInput {}
+
+impl Drop for WinMmInput {
+    fn drop(&mut self) {
+        let state = &mut *self.0;
+        // Buffers returned by the reset mustn't be added back
+        state.closing.store(true, Ordering::SeqCst);
+        unsafe {
+            midiInStop(state.handle);
+            midiInReset(state.handle);
+            for header in &mut state.headers {
+                midiInUnprepareHeader(state.handle, header, HEADER_SIZE);
+            }
+            midiInClose(state.handle);
+        }
+    }
+}
+
+/// Input callback, invoked by the system with each message received
+extern "system" fn receive(
+    _handle: HMIDIIN,
+    message: u32,
+    instance: usize,
+    param1: usize,
+    param2: usize,
+) {
+    let state = unsafe { &*(instance as *const InputState) };
+    if message != MIM_DATA && message != MIM_LONGDATA {
+        return;
+    }
+    let delta = {
+        let mut last = state.last.lock().unwrap_or_else(PoisonError::into_inner);
+        let delta = last.map_or(0, |last| param2.saturating_sub(last));
+        *last = Some(param2);
+        delta as f64 / 1000.0
+    };
+    if message == MIM_DATA {
+        let bytes = (param1 as u32).to_le_bytes();
+        let length = match bytes[0] {
+            0xF8..=0xFF => 1,
+            status => 1 + data_length(status).unwrap_or(0),
+        };
+        return (state.callback)(delta, &bytes[..length]);
+    }
+    let header = param1 as *mut MIDIHDR;
+    let data =
+        unsafe { std::slice::from_raw_parts((*header).lpData, (*header).dwBytesRecorded as usize) };
+    if state.closing.load(Ordering::SeqCst) {
+        return;
+    }
+    // Long system exclusive messages arrive in several buffers, and are assembled unless
+    // streamed
+    let message = {
+        let mut sysex = state.sysex.lock().unwrap_or_else(PoisonError::into_inner);
+        sysex.extend_from_slice(data);
+        let streamed = state.sysex_streams.load(Ordering::Relaxed) > 0;
+        if sysex.last() == Some(&0xF7) || (streamed && !sysex.is_empty()) {
+            Some(mem::take(&mut *sysex))
+        } else {
+            None
+        }
+    };
+    unsafe { midiInAddBuffer(state.handle, header, HEADER_SIZE) };
+    if let Some(message) = message {
+        (state.callback)(delta, &message);
+    }
+}
+
+/// Output port opened by a [`WinMmBackend`], which is closed when dropped
+struct WinMmOutput(Mutex<HMIDIOUT>);
+
+unsafe impl Send for WinMmOutput {}
+unsafe impl Sync for WinMmOutput {}
+
+impl OutputConnection for WinMmOutput {
+    fn send(&self, message: &[u8]) -> Result<(), RtMidiError> {
+        let handle = *self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match message {
+            [] => Ok(()),
+            [0xF0, ..] => {
+                let mut data = message.to_vec();
+                let mut header = MIDIHDR::new(&mut data);
+                check(unsafe { midiOutPrepareHeader(handle, &mut header, HEADER_SIZE) })?;
+                let result = check(unsafe { midiOutLongMsg(handle, &mut header, HEADER_SIZE) });
+                // The header can only be released once the message has been sent
+                while unsafe { midiOutUnprepareHeader(handle, &mut header, HEADER_SIZE) }
+                    == MIDIERR_STILLPLAYING
+                {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                result
+            }
+            _ if message.len() <= 3 => {
+                let mut bytes = [0; 4];
+                bytes[..message.len()].copy_from_slice(message);
+                check(unsafe { midiOutShortMsg(handle, u32::from_le_bytes(bytes)) })
+            }
+            _ => Err(RtMidiError::InvalidMessage(format!(
+                "message of {} bytes isn't system exclusive",
+                message.len()
+            ))),
+        }
+    }
+}
+
+impl Drop for WinMmOutput {
+    fn drop(&mut self) {
+        let handle = *self.0.get_mut().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            midiOutReset(handle);
+            midiOutClose(handle);
+        }
+    }
+}
+
+/// Returns the device interface path of a device, which identifies the hardware behind it
+pub(crate) fn device_interface(direction: PortDirection, device: RtMidiPort) -> Option<String> {
+    // Messages can be sent to a device ID in place of a handle
+    let message = |message, param1, param2| unsafe {
+        match direction {
+            PortDirection::Input => {
+                midiInMessage(device as usize as HMIDIIN, message, param1, param2)
+            }
+            PortDirection::Output => {
+                midiOutMessage(device as usize as HMIDIOUT, message, param1, param2)
+            }
+        }
+    };
+    let mut size: u32 = 0;
+    let result = message(
+        DRV_QUERYDEVICEINTERFACESIZE,
+        &mut size as *mut u32 as usize,
+        0,
+    );
+    if result != MMSYSERR_NOERROR || size == 0 {
+        return None;
+    }
+    // The size is in bytes, including the terminating null
+    let mut path = vec![0u16; size as usize / 2];
+    let result = message(
+        DRV_QUERYDEVICEINTERFACE,
+        path.as_mut_ptr() as usize,
+        size as usize,
+    );
+    if result != MMSYSERR_NOERROR {
+        return None;
+    }
+    let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+    Some(String::from_utf16_lossy(&path[..end]))
+}
+
+/// Returns the port name of a device, which RtMidi makes unique by adding its number
+fn port_name(name: &[u16], device: u32) -> String {
+    let length = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    format!("{} {}", String::from_utf16_lossy(&name[..length]), device)
+}
+
+/// Returns the device number of a port
+fn find(ports: &[String], port: &str) -> Result<u32, RtMidiError> {
+    ports
+        .iter()
+        .position(|name| name == port)
+        .map(|device| device as u32)
+        .ok_or_else(|| RtMidiError::PortNotFound(port.to_string()))
+}
+
+fn no_virtual_ports() -> RtMidiError {
+    RtMidiError::Unsupported("Windows Multimedia has no virtual ports".to_string())
+}
+
+fn check(result: MMRESULT) -> Result<(), RtMidiError> {
+    match result {
+        MMSYSERR_NOERROR => Ok(()),
+        result => Err(RtMidiError::Error(format!(
+            "Windows Multimedia error {}",
+            result
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::{MIDIHDR, MIDIINCAPSW, MIDIOUTCAPSW};
+
+    #[test]
+    fn struct_layout() {
+        // The sizes of the SDK's structures
+        assert_eq!(mem::size_of::<MIDIINCAPSW>(), 76);
+        assert_eq!(mem::size_of::<MIDIOUTCAPSW>(), 84);
+        let header = if cfg!(target_pointer_width = "64") {
+            112
+        } else {
+            64
+        };
+        assert_eq!(mem::size_of::<MIDIHDR>(), header);
+    }
+}
@@ -0,0 +1,526 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use crate::completion::Completion;
+use crate::error::RtMidiError;
+use crate::midi::Device;
+use crate::scheduler::PendingPolicy;
+use crate::threads;
+use crate::throttle::{self, RateLimiter};
+use crate::timer::TimerStrategy;
+use crate::transform::{Pipeline, Transform};
+
+/// Active Sensing status byte
+const ACTIVE_SENSING: u8 = 0xFE;
+
+/// Output worker thread
+///
+/// Owns a bounded queue of messages that are sent to the device from a dedicated thread, so
+/// callers never block on a slow backend. The same thread is used for timed output such as
+/// scheduled messages and Active Sensing keepalive messages. Commands are sent to the thread
+/// through a [`Handle`].
+pub struct Worker {
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn new(
+        device: Arc<Mutex<Device>>,
+        limiter: Arc<Mutex<Option<RateLimiter>>>,
+        capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let error = Arc::new(Mutex::new(None));
+        let state = State {
+            device,
+            limiter,
+            error: Arc::clone(&error),
+        };
+        let name = match lock(&state.device).port_label() {
+            Some(label) => format!("rtmidi-out:{}", label),
+            None => "rtmidi-out".to_string(),
+        };
+        let thread = threads::spawn(name, move || state.run(receiver));
+        Worker {
+            handle: Handle {
+                sender,
+                error,
+                next_note: Default::default(),
+            },
+            thread: Some(thread),
+        }
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Handles may outlive the worker, so the thread is told to stop explicitly. It sends any
+        // messages still queued (and pending note-offs) before exiting.
+        let _ = self.handle.sender.send(Command::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Cloneable handle for sending commands to a [`Worker`]
+///
+/// Errors raised by the backend on the worker thread are reported by the next call to
+/// [`Handle::send`] or [`Handle::try_send`]. Once the worker has stopped every command returns an
+/// error.
+#[derive(Clone)]
+pub struct Handle {
+    sender: SyncSender<Command>,
+    error: Arc<Mutex<Option<RtMidiError>>>,
+    next_note: Arc<AtomicU64>,
+}
+
+impl Handle {
+    /// Queue a message, returning [`RtMidiError::WouldBlock`] if the queue is full
+    pub fn try_send(&self, message: Vec<u8>) -> Result<(), RtMidiError> {
+        self.take_error()?;
+        match self.sender.try_send(Command::Send(message)) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(RtMidiError::WouldBlock),
+            Err(TrySendError::Disconnected(_)) => Err(disconnected()),
+        }
+    }
+
+    /// Queue a message, blocking until there is space in the queue
+    pub fn send(&self, message: Vec<u8>) -> Result<(), RtMidiError> {
+        self.take_error()?;
+        self.command(Command::Send(message))
+    }
+
+    /// Queue a message, blocking until there is space in the queue, and resolve `completion`
+    /// once it has been sent
+    #[cfg(feature = "async")]
+    pub fn send_with_completion(
+        &self,
+        message: Vec<u8>,
+        completion: Completion,
+    ) -> Result<(), RtMidiError> {
+        self.command(Command::SendWithCompletion(message, completion))
+    }
+
+    /// Schedule a message to be sent at a given time
+    pub fn schedule(&self, at: Instant, message: Vec<u8>) -> Result<(), RtMidiError> {
+        self.take_error()?;
+        self.command(Command::Schedule(at, message))
+    }
+
+    /// Schedule the note-off of a note, returning an identifier for [`Handle::release_note`].
+    /// Unlike other scheduled messages, note-offs are sent early rather than discarded when the
+    /// worker stops.
+    pub fn schedule_note_off(&self, at: Instant, message: Vec<u8>) -> Result<u64, RtMidiError> {
+        self.take_error()?;
+        let id = self.next_note.fetch_add(1, AtomicOrdering::Relaxed);
+        self.command(Command::ScheduleNoteOff(id, at, message))?;
+        Ok(id)
+    }
+
+    /// Remove a scheduled note-off, sending it now if `send` is [`true`]. Does nothing if it has
+    /// already been sent.
+    pub fn release_note(&self, id: u64, send: bool) -> Result<(), RtMidiError> {
+        self.command(Command::ReleaseNote(id, send))
+    }
+
+    /// Deal with the scheduled messages according to `policy`, and send any queued messages,
+    /// returning once nothing is left to send
+    pub fn finish(&self, policy: PendingPolicy) -> Result<(), RtMidiError> {
+        let (done, finished) = mpsc::sync_channel(1);
+        self.command(Command::Finish(policy, done))?;
+        finished.recv().map_err(|_| disconnected())
+    }
+
+    /// Start sending Active Sensing at the given interval, or stop with [`None`]
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> Result<(), RtMidiError> {
+        self.command(Command::Keepalive(interval))
+    }
+
+    /// Set how the worker waits for timed output
+    pub fn set_timer_strategy(&self, strategy: TimerStrategy) -> Result<(), RtMidiError> {
+        self.command(Command::TimerStrategy(strategy))
+    }
+
+    /// Append a transform to the output pipeline
+    pub fn add_transform(&self, transform: Box<dyn Transform>) -> Result<(), RtMidiError> {
+        self.command(Command::AddTransform(transform))
+    }
+
+    /// Remove all transforms from the output pipeline
+    pub fn clear_transforms(&self) -> Result<(), RtMidiError> {
+        self.command(Command::ClearTransforms)
+    }
+
+    fn command(&self, command: Command) -> Result<(), RtMidiError> {
+        self.sender.send(command).map_err(|_| disconnected())
+    }
+
+    fn take_error(&self) -> Result<(), RtMidiError> {
+        match lock(&self.error).take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+fn disconnected() -> RtMidiError {
+    RtMidiError::Error("Output worker thread has stopped".to_string())
+}
+
+enum Command {
+    Send(Vec<u8>),
+    #[cfg(feature = "async")]
+    SendWithCompletion(Vec<u8>, Completion),
+    Keepalive(Option<Duration>),
+    TimerStrategy(TimerStrategy),
+    Schedule(Instant, Vec<u8>),
+    ScheduleNoteOff(u64, Instant, Vec<u8>),
+    ReleaseNote(u64, bool),
+    Finish(PendingPolicy, SyncSender<()>),
+    AddTransform(Box<dyn Transform>),
+    ClearTransforms,
+    Stop,
+}
+
+/// A message scheduled for a point in time
+struct Timed {
+    at: Instant,
+    // Keeps messages scheduled for the same time in order
+    sequence: u64,
+    message: Vec<u8>,
+    // Identifies the note-off of a note sent with `RtMidiOut::send_note`
+    note_off: Option<u64>,
+}
+
+impl PartialEq for Timed {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timed {}
+
+impl PartialOrd for Timed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timed {
+    // Reversed so the earliest message is at the top of the (max-)heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.sequence).cmp(&(self.at, self.sequence))
+    }
+}
+
+/// An entry in the queue of messages held back by the rate limiter
+enum Pending {
+    Message(Vec<u8>),
+    /// Resolved once the messages ahead of it have been sent
+    #[cfg(feature = "async")]
+    Completion(Completion),
+}
+
+struct State {
+    device: Arc<Mutex<Device>>,
+    limiter: Arc<Mutex<Option<RateLimiter>>>,
+    error: Arc<Mutex<Option<RtMidiError>>>,
+}
+
+impl State {
+    fn run(&self, receiver: Receiver<Command>) {
+        // Messages held back by the rate limiter. Real-time messages skip this queue.
+        let mut pending: VecDeque<Pending> = VecDeque::new();
+        let mut keepalive: Option<(Duration, Instant)> = None;
+        let mut pipeline = Pipeline::default();
+        let mut poll_at: Option<Instant> = None;
+        let mut scheduled: BinaryHeap<Timed> = BinaryHeap::new();
+        let mut sequence = 0u64;
+        let mut strategy = TimerStrategy::default();
+        let mut _period = strategy.period();
+        // Set while waiting for scheduled messages to become due before finishing, with every
+        // caller waiting for it
+        let mut finishing: Option<(Instant, Vec<SyncSender<()>>)> = None;
+        loop {
+            let now = Instant::now();
+            if matches!(&finishing, Some((until, _)) if scheduled.is_empty() || now >= *until) {
+                if let Some((_, done)) = finishing.take() {
+                    for timed in take_scheduled(&mut scheduled) {
+                        pipeline.process(now, &timed.message, &mut |message| {
+                            self.output(&mut pending, message)
+                        });
+                    }
+                    self.drain(mem::take(&mut pending));
+                    for done in done {
+                        let _ = done.send(());
+                    }
+                    continue;
+                }
+            }
+            if matches!(scheduled.peek(), Some(timed) if timed.at <= now) {
+                if let Some(timed) = scheduled.pop() {
+                    pipeline.process(now, &timed.message, &mut |message| {
+                        self.output(&mut pending, message)
+                    });
+                    poll_at = Some(now);
+                }
+                continue;
+            }
+            // Scheduled note-offs stay here, as they may be released early
+            let lookahead = if self.native() {
+                strategy.lookahead()
+            } else {
+                Duration::from_secs(0)
+            };
+            if matches!(scheduled.peek(), Some(timed) if timed.at <= now + lookahead)
+                && matches!(scheduled.peek(), Some(timed) if timed.note_off.is_none())
+            {
+                if let Some(timed) = scheduled.pop() {
+                    pipeline.process(now, &timed.message, &mut |message| {
+                        self.send_at(timed.at, message)
+                    });
+                    poll_at = Some(now);
+                }
+                continue;
+            }
+            if let Some((interval, next)) = keepalive {
+                if now >= next {
+                    self.send(&[ACTIVE_SENSING]);
+                    keepalive = Some((interval, now + interval));
+                    continue;
+                }
+            }
+            if let Some(at) = poll_at {
+                if now >= at {
+                    poll_at = pipeline.poll(now, &mut |message| self.output(&mut pending, message));
+                    continue;
+                }
+            }
+            let mut deadline = earliest(keepalive.map(|(_, next)| next), poll_at);
+            deadline = earliest(deadline, finishing.as_ref().map(|(until, _)| *until));
+            deadline = earliest(
+                deadline,
+                scheduled.peek().map(|timed| match timed.note_off {
+                    None => timed.at.checked_sub(lookahead).unwrap_or(timed.at),
+                    Some(_) => timed.at,
+                }),
+            );
+            if !pending.is_empty() {
+                let delay = match lock(&self.limiter).as_ref() {
+                    Some(limiter) => limiter.delay(now),
+                    None => Duration::from_secs(0),
+                };
+                if delay.as_nanos() == 0 || !matches!(pending.front(), Some(Pending::Message(_))) {
+                    match pending.pop_front() {
+                        Some(Pending::Message(message)) => self.send(&message),
+                        #[cfg(feature = "async")]
+                        Some(Pending::Completion(completion)) => {
+                            completion.complete(self.take_error())
+                        }
+                        None => {}
+                    }
+                    continue;
+                }
+                deadline = earliest(deadline, Some(now + delay));
+            }
+            match strategy.recv(&receiver, deadline) {
+                Ok(Command::Schedule(at, message)) => {
+                    sequence += 1;
+                    scheduled.push(Timed {
+                        at,
+                        sequence,
+                        message,
+                        note_off: None,
+                    });
+                }
+                Ok(Command::ScheduleNoteOff(id, at, message)) => {
+                    sequence += 1;
+                    scheduled.push(Timed {
+                        at,
+                        sequence,
+                        message,
+                        note_off: Some(id),
+                    });
+                }
+                Ok(Command::ReleaseNote(id, send)) => {
+                    let released = take_note_offs(&mut scheduled, |note_off| note_off == id);
+                    if send {
+                        let now = Instant::now();
+                        for timed in released {
+                            pipeline.process(now, &timed.message, &mut |message| {
+                                self.output(&mut pending, message)
+                            });
+                        }
+                        poll_at = Some(now);
+                    }
+                }
+                Ok(Command::Finish(PendingPolicy::WaitUntilDue(timeout), done)) => {
+                    let until = Instant::now() + timeout;
+                    match finishing.as_mut() {
+                        // Finish when the earliest timeout runs out
+                        Some((finish_at, waiting)) => {
+                            *finish_at = (*finish_at).min(until);
+                            waiting.push(done);
+                        }
+                        None => finishing = Some((until, vec![done])),
+                    }
+                }
+                Ok(Command::Finish(policy, done)) => {
+                    let now = Instant::now();
+                    let send = match policy {
+                        PendingPolicy::Discard => take_note_offs(&mut scheduled, |_| true),
+                        _ => take_scheduled(&mut scheduled),
+                    };
+                    scheduled.clear();
+                    for timed in send {
+                        pipeline.process(now, &timed.message, &mut |message| {
+                            self.output(&mut pending, message)
+                        });
+                    }
+                    self.drain(mem::take(&mut pending));
+                    let _ = done.send(());
+                }
+                Ok(Command::Send(message)) if pipeline.is_empty() => {
+                    self.output(&mut pending, &message)
+                }
+                Ok(Command::Send(message)) => {
+                    let now = Instant::now();
+                    pipeline.process(now, &message, &mut |message| {
+                        self.output(&mut pending, message)
+                    });
+                    poll_at = Some(now);
+                }
+                #[cfg(feature = "async")]
+                Ok(Command::SendWithCompletion(message, completion)) => {
+                    let now = Instant::now();
+                    pipeline.process(now, &message, &mut |message| {
+                        self.output(&mut pending, message)
+                    });
+                    poll_at = Some(now);
+                    pending.push_back(Pending::Completion(completion));
+                }
+                Ok(Command::Keepalive(interval)) => {
+                    keepalive = interval.map(|interval| (interval, Instant::now() + interval))
+                }
+                Ok(Command::TimerStrategy(new)) => {
+                    strategy = new;
+                    _period = strategy.period();
+                }
+                Ok(Command::AddTransform(transform)) => pipeline.push_boxed(transform),
+                Ok(Command::ClearTransforms) => {
+                    pipeline.clear();
+                    poll_at = None;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        // Notes are never left hanging, but other scheduled messages are discarded
+        let now = Instant::now();
+        for timed in take_note_offs(&mut scheduled, |_| true) {
+            pipeline.process(now, &timed.message, &mut |message| {
+                self.output(&mut pending, message)
+            });
+        }
+        self.drain(pending);
+    }
+
+    /// Send every queued message now
+    fn drain(&self, pending: VecDeque<Pending>) {
+        for entry in pending {
+            match entry {
+                Pending::Message(message) => self.send(&message),
+                #[cfg(feature = "async")]
+                Pending::Completion(completion) => completion.complete(self.take_error()),
+            }
+        }
+    }
+
+    /// Send a real-time message immediately, or queue any other message behind the rate limiter
+    fn output(&self, pending: &mut VecDeque<Pending>, message: &[u8]) {
+        if throttle::is_realtime(message) {
+            self.send(message)
+        } else {
+            pending.push_back(Pending::Message(message.to_vec()))
+        }
+    }
+
+    /// Take the error raised by the most recent failed send, if any, so it is reported once
+    #[cfg(feature = "async")]
+    fn take_error(&self) -> Result<(), RtMidiError> {
+        match lock(&self.error).take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether scheduled messages can be handed to the MIDI system before they're due,
+    /// which the rate limiter can't hold back
+    fn native(&self) -> bool {
+        lock(&self.limiter).is_none() && lock(&self.device).can_send_at()
+    }
+
+    fn send_at(&self, at: Instant, message: &[u8]) {
+        if let Err(e) = lock(&self.device).send_at(at, message) {
+            *lock(&self.error) = Some(e);
+        }
+    }
+
+    fn send(&self, message: &[u8]) {
+        let delay = match lock(&self.limiter).as_mut() {
+            Some(limiter) => limiter.reserve(message, Instant::now()),
+            None => Duration::from_secs(0),
+        };
+        if delay.as_nanos() > 0 {
+            thread::sleep(delay);
+        }
+        if let Err(e) = lock(&self.device).send(message) {
+            *lock(&self.error) = Some(e);
+        }
+    }
+}
+
+/// Remove the matching note-offs from the scheduled messages, returning them in order
+fn take_note_offs<F: Fn(u64) -> bool>(scheduled: &mut BinaryHeap<Timed>, matches: F) -> Vec<Timed> {
+    let (mut note_offs, rest): (Vec<_>, Vec<_>) = mem::take(scheduled)
+        .into_vec()
+        .into_iter()
+        .partition(|timed| matches!(timed.note_off, Some(id) if matches(id)));
+    *scheduled = rest.into();
+    // Timed sorts in reverse
+    note_offs.sort_by(|a, b| b.cmp(a));
+    note_offs
+}
+
+/// Remove every scheduled message, returning them in order
+fn take_scheduled(scheduled: &mut BinaryHeap<Timed>) -> Vec<Timed> {
+    // Timed sorts in reverse
+    let mut timed = mem::take(scheduled).into_sorted_vec();
+    timed.reverse();
+    timed
+}
+
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}